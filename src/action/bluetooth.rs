@@ -0,0 +1,104 @@
+use crate::error::{DeckError, Result};
+use tracing::debug;
+
+/// Adapter used when an action or entity names none.
+const DEFAULT_ADAPTER: &str = "hci0";
+
+/// Check that `mac` is a Bluetooth address like `AA:BB:CC:DD:EE:FF`.
+///
+/// # Errors
+/// Returns a message if it is not.
+pub fn check_address(mac: &str) -> std::result::Result<(), String> {
+    let parts: Vec<&str> = mac.split(':').collect();
+    let valid = parts.len() == 6
+        && parts
+            .iter()
+            .all(|p| p.len() == 2 && p.chars().all(|c| c.is_ascii_hexdigit()));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid Bluetooth address '{mac}'"))
+    }
+}
+
+/// BlueZ's object path of the device `mac` on `adapter`.
+fn device_path(adapter: &str, mac: &str) -> String {
+    format!(
+        "/org/bluez/{adapter}/dev_{}",
+        mac.to_ascii_uppercase().replace(':', "_")
+    )
+}
+
+async fn device(adapter: Option<&str>, mac: &str) -> Result<zbus::Proxy<'static>> {
+    let error = |e: zbus::Error| DeckError::Action(format!("Bluetooth {mac}: {e}"));
+    let connection = zbus::Connection::system().await.map_err(error)?;
+    let path = device_path(adapter.unwrap_or(DEFAULT_ADAPTER), mac);
+    zbus::Proxy::new_owned(connection, "org.bluez", path, "org.bluez.Device1")
+        .await
+        .map_err(error)
+}
+
+/// Whether the paired device `mac` is connected.
+///
+/// # Errors
+/// Returns `DeckError::Action` if D-Bus or BlueZ is unavailable or the
+/// device is not paired.
+pub async fn connected(adapter: Option<&str>, mac: &str) -> Result<bool> {
+    device(adapter, mac)
+        .await?
+        .get_property("Connected")
+        .await
+        .map_err(|e| DeckError::Action(format!("Bluetooth {mac}: {e}")))
+}
+
+/// Connect (`Some(true)`), disconnect or toggle the paired device `mac`.
+///
+/// # Errors
+/// Returns the errors of [`connected`], or `DeckError::Action` if BlueZ
+/// fails to connect, e.g. because the device is off or out of range.
+pub async fn execute(adapter: Option<&str>, mac: &str, connect: Option<bool>) -> Result<()> {
+    let proxy = device(adapter, mac).await?;
+    let error = |e: zbus::Error| DeckError::Action(format!("Bluetooth {mac}: {e}"));
+    let connect = match connect {
+        Some(connect) => connect,
+        None => !proxy
+            .get_property::<bool>("Connected")
+            .await
+            .map_err(error)?,
+    };
+    let method = if connect { "Connect" } else { "Disconnect" };
+    proxy.call_method(method, &()).await.map_err(error)?;
+    debug!("Bluetooth {mac}: {method}");
+    Ok(())
+}
+
+/// The state of a `bluetooth:[<adapter>/]<mac>` entity: `on` while the
+/// device is connected, else `off`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let (adapter, mac) = match address.split_once('/') {
+        Some((adapter, mac)) => (Some(adapter), mac),
+        None => (None, address),
+    };
+    let on = connected(adapter, mac)
+        .await
+        .map_err(|e| e.user_message())?;
+    Ok(if on { "on" } else { "off" }.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_paths() {
+        assert!(check_address("00:1b:66:aa:bb:cc").is_ok());
+        assert!(check_address("00:1b:66:aa:bb").is_err());
+        assert_eq!(
+            device_path("hci0", "00:1b:66:aa:bb:cc"),
+            "/org/bluez/hci0/dev_00_1B_66_AA_BB_CC"
+        );
+    }
+}
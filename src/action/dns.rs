@@ -0,0 +1,165 @@
+//! Name resolution of outgoing HTTP requests (`[deckd.dns]`): static host
+//! overrides, and a cache of lookups that also keeps serving the last known
+//! addresses of a host while its lookups fail, so a flaky resolver (mDNS
+//! especially) does not break every state fetch.
+
+use crate::config::schema::DnsConfig;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// The DNS settings of the current config.
+static CONFIG: LazyLock<RwLock<DnsConfig>> = LazyLock::new(RwLock::default);
+
+/// Lookups by lowercase host name.
+static CACHE: LazyLock<Mutex<HashMap<String, Entry>>> = LazyLock::new(Mutex::default);
+
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Addresses of the last successful lookup (empty if none yet).
+    addrs: Vec<SocketAddr>,
+    /// Until when to answer from the cache instead of looking the host up.
+    until: Instant,
+}
+
+/// Resolve with the settings of a (re)loaded config, starting a new cache.
+pub fn configure(config: &DnsConfig) {
+    let mut config = config.clone();
+    config.hosts = config
+        .hosts
+        .into_iter()
+        .map(|(host, ip)| (host.to_ascii_lowercase(), ip))
+        .collect();
+    *CONFIG.write().unwrap() = config;
+    CACHE.lock().unwrap().clear();
+}
+
+/// The resolver of the clients of [`super::http::build_client`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Resolver;
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        Box::pin(async move {
+            let addrs = lookup(&host).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Addresses of `host`: its override, cached ones, or a fresh lookup. A
+/// failed lookup falls back to the addresses of the last one that worked.
+async fn lookup(host: &str) -> std::io::Result<Vec<SocketAddr>> {
+    lookup_with(host, |host| async move {
+        Ok(tokio::net::lookup_host((host, 0)).await?.collect())
+    })
+    .await
+}
+
+/// [`lookup`], asking `resolve` when the cache cannot answer.
+async fn lookup_with<F, R>(host: &str, resolve: F) -> std::io::Result<Vec<SocketAddr>>
+where
+    F: FnOnce(String) -> R,
+    R: Future<Output = std::io::Result<Vec<SocketAddr>>>,
+{
+    let (cache, negative) = {
+        let config = CONFIG.read().unwrap();
+        if let Some(&ip) = config.hosts.get(host) {
+            return Ok(vec![SocketAddr::new(ip, 0)]);
+        }
+        (
+            Duration::from_secs(config.cache_secs),
+            Duration::from_secs(config.negative_secs),
+        )
+    };
+
+    let cached = CACHE.lock().unwrap().get(host).cloned();
+    if let Some(ref entry) = cached {
+        if Instant::now() < entry.until {
+            return answer(host, entry.addrs.clone());
+        }
+    }
+
+    match resolve(host.to_string()).await {
+        Ok(addrs) => {
+            debug!("resolved {host} to {addrs:?}");
+            store(host, addrs.clone(), cache);
+            Ok(addrs)
+        }
+        Err(e) => {
+            let last = cached.map(|entry| entry.addrs).unwrap_or_default();
+            if last.is_empty() {
+                debug!("failed to resolve {host}: {e}");
+            } else {
+                warn!("failed to resolve {host} ({e}), using its last addresses");
+            }
+            store(host, last.clone(), negative);
+            if last.is_empty() {
+                Err(e)
+            } else {
+                Ok(last)
+            }
+        }
+    }
+}
+
+fn store(host: &str, addrs: Vec<SocketAddr>, ttl: Duration) {
+    let until = Instant::now() + ttl;
+    CACHE
+        .lock()
+        .unwrap()
+        .insert(host.to_string(), Entry { addrs, until });
+}
+
+/// Cached addresses, or the remembered failure if there are none.
+fn answer(host: &str, addrs: Vec<SocketAddr>) -> std::io::Result<Vec<SocketAddr>> {
+    if addrs.is_empty() {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{host}: lookup failed recently"),
+        ))
+    } else {
+        Ok(addrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn overrides_hosts_and_remembers_failures() {
+        let config: DnsConfig = toml::from_str(
+            r#"
+hosts = { "HomeAssistant.local" = "192.168.1.10", "nas.lan" = "fd00::2" }
+"#,
+        )
+        .unwrap();
+        configure(&config);
+        assert_eq!(
+            lookup("homeassistant.local").await.unwrap(),
+            ["192.168.1.10:0".parse().unwrap()]
+        );
+        assert_eq!(
+            lookup("nas.lan").await.unwrap(),
+            ["[fd00::2]:0".parse().unwrap()]
+        );
+
+        // A host known to have resolved keeps its addresses when it fails.
+        let failing = |_| async { Err(std::io::ErrorKind::NotFound.into()) };
+        let unused = |host| async move { panic!("{host} looked up again") };
+        let last: SocketAddr = "10.0.0.7:0".parse().unwrap();
+        store("flaky.lan", vec![last], Duration::ZERO);
+        assert_eq!(lookup_with("flaky.lan", failing).await.unwrap(), [last]);
+        assert_eq!(lookup_with("flaky.lan", unused).await.unwrap(), [last]);
+
+        // Failures are remembered too.
+        assert!(lookup_with("gone.lan", failing).await.is_err());
+        assert!(lookup_with("gone.lan", unused).await.is_err());
+    }
+}
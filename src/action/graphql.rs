@@ -0,0 +1,84 @@
+use super::http::{self, Options};
+use super::ActionContext;
+use crate::error::{DeckError, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Post a GraphQL request and fail if the response reports `errors`.
+/// String values in `variables` are templated.
+///
+/// # Errors
+/// Returns `DeckError::Http` on network errors, `DeckError::Action` on a
+/// non-success status, a non-JSON response or GraphQL errors, or the errors
+/// of [`http::build_client`].
+#[allow(clippy::implicit_hasher)]
+pub async fn execute(
+    url: &str,
+    query: &str,
+    variables: Option<&Value>,
+    headers: &HashMap<String, String>,
+    options: Options<'_>,
+    ctx: &ActionContext,
+) -> Result<()> {
+    let client = http::build_client(options.insecure_tls, options.ca_cert.map(Path::new), None)?;
+    let variables = variables.map_or(Ok(Value::Null), |v| http::expand_json(v, ctx))?;
+    let payload = json!({ "query": query, "variables": variables });
+
+    let builder = http::apply_credentials(client.post(url), headers, options.auth)?;
+    let resp = builder.json(&payload).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(DeckError::Action(format!("GraphQL {url} → {status}")));
+    }
+
+    let body: Value = resp
+        .json()
+        .await
+        .map_err(|e| DeckError::Action(format!("GraphQL {url}: invalid response: {e}")))?;
+    if let Some(message) = response_error(&body) {
+        return Err(DeckError::Action(format!("GraphQL {url}: {message}")));
+    }
+    debug!("GraphQL {url} → {status}");
+    Ok(())
+}
+
+/// The first error message of a GraphQL response, with a count if there
+/// are more.
+fn response_error(body: &Value) -> Option<String> {
+    let errors = body.get("errors")?.as_array().filter(|e| !e.is_empty())?;
+    let first = errors[0]
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error");
+    Some(if errors.len() > 1 {
+        format!("{first} (+{} more)", errors.len() - 1)
+    } else {
+        first.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_graphql_errors() {
+        let ok = json!({ "data": { "repository": { "id": 1 } } });
+        assert_eq!(response_error(&ok), None);
+        assert_eq!(response_error(&json!({ "data": null, "errors": [] })), None);
+
+        let failed = json!({
+            "data": null,
+            "errors": [
+                { "message": "Field 'repo' doesn't exist" },
+                { "message": "second" },
+            ],
+        });
+        assert_eq!(
+            response_error(&failed).as_deref(),
+            Some("Field 'repo' doesn't exist (+1 more)")
+        );
+    }
+}
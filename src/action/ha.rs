@@ -0,0 +1,159 @@
+use super::http;
+use super::ActionContext;
+use crate::error::{DeckError, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::debug;
+
+/// Timeout of HA action requests; Assist may hand the sentence to an LLM.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fire the Home Assistant event `event` with `data`, whose string values
+/// are templated. Automations triggered by the event run in HA.
+///
+/// # Errors
+/// Returns `DeckError::Config` if no HA token is configured,
+/// `DeckError::Http` on network errors, or `DeckError::Action` if HA
+/// rejects the request.
+pub async fn fire_event(event: &str, data: Option<&Value>, ctx: &ActionContext) -> Result<()> {
+    let data = data.map_or_else(
+        || Ok(Value::Object(serde_json::Map::new())),
+        |d| http::expand_json(d, ctx),
+    )?;
+    post(&format!("/api/events/{event}"), &data).await?;
+    Ok(())
+}
+
+/// Call the Home Assistant service `service` (`<domain>.<service>`) with
+/// `data`, whose string values are templated.
+///
+/// # Errors
+/// Returns the errors of [`fire_event`], or `DeckError::Config` if
+/// `service` has no domain.
+pub async fn call_service(service: &str, data: Option<&Value>, ctx: &ActionContext) -> Result<()> {
+    let data = data.map_or_else(|| Ok(json!({})), |d| http::expand_json(d, ctx))?;
+    call_service_raw(service, &data).await
+}
+
+/// Call `service` with `data` as is, e.g. to restore a snapshot.
+///
+/// # Errors
+/// Returns the errors of [`call_service`].
+pub async fn call_service_raw(service: &str, data: &Value) -> Result<()> {
+    let (domain, name) = service.split_once('.').ok_or_else(|| {
+        DeckError::Config(format!(
+            "HA service '{service}': expected <domain>.<service>"
+        ))
+    })?;
+    post(&format!("/api/services/{domain}/{name}"), data).await?;
+    Ok(())
+}
+
+/// Send `text` to Home Assistant's conversation API and return the spoken
+/// reply.
+///
+/// # Errors
+/// Returns the errors of [`fire_event`], or `DeckError::Action` if HA could
+/// not handle the sentence (with its reply as the message).
+pub async fn assist(text: &str, language: Option<&str>, agent_id: Option<&str>) -> Result<String> {
+    let mut body = json!({ "text": text });
+    if let Some(language) = language {
+        body["language"] = json!(language);
+    }
+    if let Some(agent_id) = agent_id {
+        body["agent_id"] = json!(agent_id);
+    }
+    let resp: Value = post("/api/conversation/process", &body)
+        .await?
+        .json()
+        .await
+        .map_err(|e| DeckError::Action(format!("HA Assist: invalid response: {e}")))?;
+    let (failed, speech) = assist_reply(&resp);
+    if failed {
+        return Err(DeckError::Action(format!("HA Assist: {speech}")));
+    }
+    Ok(speech)
+}
+
+/// Whether a conversation response is an error, and its spoken reply.
+fn assist_reply(resp: &Value) -> (bool, String) {
+    let response = &resp["response"];
+    let speech = response["speech"]["plain"]["speech"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    (response["response_type"] == "error", speech)
+}
+
+/// The full state object of `entity_id`, with its attributes.
+///
+/// # Errors
+/// Returns the errors of [`fire_event`].
+pub async fn entity(entity_id: &str) -> Result<Value> {
+    let path = format!("/api/states/{entity_id}");
+    request(reqwest::Method::GET, &path, None)
+        .await?
+        .json()
+        .await
+        .map_err(|e| DeckError::Action(format!("HA {path}: invalid response: {e}")))
+}
+
+/// POST `body` to the Home Assistant API at `path` with the configured token.
+async fn post(path: &str, body: &Value) -> Result<reqwest::Response> {
+    request(reqwest::Method::POST, path, Some(body)).await
+}
+
+async fn request(
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&Value>,
+) -> Result<reqwest::Response> {
+    let token = crate::state::ha_token().map_err(|e| DeckError::Config(format!("HA: {e}")))?;
+    let client = crate::state::ha_client(TIMEOUT).map_err(DeckError::Action)?;
+    let (_, resp) = crate::state::send_to_ha(|ha_url| {
+        let builder = client
+            .request(method.clone(), format!("{ha_url}{path}"))
+            .bearer_auth(&token);
+        match body {
+            Some(body) => builder.json(body),
+            None => builder,
+        }
+    })
+    .await?;
+    let status = resp.status();
+    match status.as_u16() {
+        200..=299 => {
+            debug!("HA {method} {path} → {status}");
+            Ok(resp)
+        }
+        401 | 403 => Err(DeckError::Action(format!(
+            "HA {path}: token rejected ({status})"
+        ))),
+        _ => Err(DeckError::Action(format!("HA {path} → {status}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_assist_reply() {
+        let done = json!({
+            "response": {
+                "response_type": "action_done",
+                "speech": { "plain": { "speech": "Turned off the lights" } }
+            }
+        });
+        assert_eq!(assist_reply(&done), (false, "Turned off the lights".into()));
+        let error = json!({
+            "response": {
+                "response_type": "error",
+                "speech": { "plain": { "speech": "Sorry, I couldn't understand that" } },
+                "data": { "code": "no_intent_match" }
+            }
+        });
+        assert!(assist_reply(&error).0);
+        assert_eq!(assist_reply(&json!({})), (false, String::new()));
+    }
+}
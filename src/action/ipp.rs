@@ -0,0 +1,264 @@
+use crate::error::{DeckError, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::debug;
+
+/// Timeout of an IPP request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Port CUPS listens on.
+const PORT: u16 = 631;
+
+const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+const CANCEL_JOBS: u16 = 0x0038;
+
+const OPERATION_ATTRIBUTES: u8 = 0x01;
+const END_OF_ATTRIBUTES: u8 = 0x03;
+const INTEGER: u8 = 0x21;
+const ENUM: u8 = 0x23;
+const KEYWORD: u8 = 0x44;
+const URI: u8 = 0x45;
+const CHARSET: u8 = 0x47;
+const NATURAL_LANGUAGE: u8 = 0x48;
+const NAME: u8 = 0x42;
+
+/// Printer attributes a state read asks for.
+const REQUESTED: [&str; 4] = [
+    "printer-state",
+    "printer-state-reasons",
+    "queued-job-count",
+    "marker-levels",
+];
+
+/// A value of an IPP attribute; only the types deckd reads are decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum IppValue {
+    Integer(i32),
+    Text(String),
+    Other,
+}
+
+/// Attributes of a response by name, each with all of its values.
+type Attributes = HashMap<String, Vec<IppValue>>;
+
+/// An IPP request to `uri`; each attribute may have several values.
+fn encode(operation: u16, uri: &str, attributes: &[(u8, &str, &[&str])]) -> Vec<u8> {
+    let mut body = vec![2, 0];
+    body.extend(operation.to_be_bytes());
+    body.extend(1u32.to_be_bytes());
+    body.push(OPERATION_ATTRIBUTES);
+    let user = crate::privileges::var("USER").unwrap_or_else(|| "deckd".into());
+    let standard: [(u8, &str, &[&str]); 4] = [
+        (CHARSET, "attributes-charset", &["utf-8"]),
+        (NATURAL_LANGUAGE, "attributes-natural-language", &["en"]),
+        (URI, "printer-uri", &[uri]),
+        (NAME, "requesting-user-name", &[&user]),
+    ];
+    for (tag, name, values) in standard.iter().chain(attributes) {
+        for (i, value) in values.iter().enumerate() {
+            // Further values of an attribute go without its name.
+            let name = if i == 0 { *name } else { "" };
+            body.push(*tag);
+            body.extend((name.len() as u16).to_be_bytes());
+            body.extend(name.as_bytes());
+            body.extend((value.len() as u16).to_be_bytes());
+            body.extend(value.as_bytes());
+        }
+    }
+    body.push(END_OF_ATTRIBUTES);
+    body
+}
+
+/// The next `len` bytes of `rest`.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    let (head, tail) = (rest.get(..len)?, rest.get(len..)?);
+    *rest = tail;
+    Some(head)
+}
+
+/// The status code and attributes of an IPP response.
+fn decode(data: &[u8]) -> Option<(u16, Attributes)> {
+    let status = u16::from_be_bytes([*data.get(2)?, *data.get(3)?]);
+    let mut attributes = Attributes::new();
+    let mut rest = data.get(8..)?;
+    let mut last = String::new();
+    loop {
+        let tag = take(&mut rest, 1)?[0];
+        if tag == END_OF_ATTRIBUTES {
+            return Some((status, attributes));
+        }
+        if tag < 0x10 {
+            continue;
+        }
+        let len = take(&mut rest, 2)?;
+        let name = take(&mut rest, usize::from(u16::from_be_bytes([len[0], len[1]])))?;
+        let len = take(&mut rest, 2)?;
+        let value = take(&mut rest, usize::from(u16::from_be_bytes([len[0], len[1]])))?;
+        if !name.is_empty() {
+            last = String::from_utf8_lossy(name).into_owned();
+        }
+        let value = match (tag, <[u8; 4]>::try_from(value)) {
+            (INTEGER | ENUM, Ok(bytes)) => IppValue::Integer(i32::from_be_bytes(bytes)),
+            (0x40..=0x4F, _) => IppValue::Text(String::from_utf8_lossy(value).into_owned()),
+            _ => IppValue::Other,
+        };
+        attributes.entry(last.clone()).or_default().push(value);
+    }
+}
+
+/// Send `operation` for `printer` on the CUPS server `host` and return the
+/// response attributes.
+async fn request(
+    host: &str,
+    printer: &str,
+    operation: u16,
+    attributes: &[(u8, &str, &[&str])],
+) -> Result<Attributes> {
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:{PORT}")
+    };
+    let uri = format!("ipp://{host}/printers/{printer}");
+    let client = super::http::build_client(false, None, Some(TIMEOUT))?;
+    let resp = client
+        .post(format!("http://{host}/printers/{printer}"))
+        .header(reqwest::header::CONTENT_TYPE, "application/ipp")
+        .body(encode(operation, &uri, attributes))
+        .send()
+        .await?
+        .error_for_status()?;
+    let data = resp.bytes().await?;
+    let (status, attributes) = decode(&data)
+        .ok_or_else(|| DeckError::Action(format!("IPP {printer}: malformed response")))?;
+    // 0x0000-0x00FF are successful, possibly with ignored attributes.
+    if status > 0x00FF {
+        return Err(DeckError::Action(format!(
+            "IPP {printer}: status 0x{status:04x}"
+        )));
+    }
+    Ok(attributes)
+}
+
+/// Cancel every job queued on `printer`. CUPS's default policy only lets
+/// the jobs' owners and admins do that.
+///
+/// # Errors
+/// Returns `DeckError::Http` if CUPS cannot be reached, or
+/// `DeckError::Action` if it refuses.
+pub async fn cancel_jobs(host: &str, printer: &str) -> Result<()> {
+    request(host, printer, CANCEL_JOBS, &[]).await?;
+    debug!("IPP {printer}: cancelled all jobs");
+    Ok(())
+}
+
+/// The value of `field` in printer `attributes`.
+fn field(attributes: &Attributes, field: &str) -> std::result::Result<String, String> {
+    let values = |name: &str| attributes.get(name).map(Vec::as_slice).unwrap_or_default();
+    let integers = |name: &str| {
+        values(name).iter().filter_map(|v| match v {
+            IppValue::Integer(i) => Some(*i),
+            _ => None,
+        })
+    };
+    match field {
+        "state" => match integers("printer-state").next() {
+            Some(3) => Ok("idle".into()),
+            Some(4) => Ok("printing".into()),
+            Some(5) => Ok("stopped".into()),
+            _ => Err("no printer-state".into()),
+        },
+        "jobs" => Ok(integers("queued-job-count").next().unwrap_or(0).to_string()),
+        "reason" => Ok(values("printer-state-reasons")
+            .iter()
+            .find_map(|v| match v {
+                IppValue::Text(reason) if reason != "none" => Some(reason.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| "none".into())),
+        // Negative levels mean unknown.
+        "ink" => integers("marker-levels")
+            .filter(|&level| level >= 0)
+            .min()
+            .map(|level| level.to_string())
+            .ok_or_else(|| "no marker levels".into()),
+        _ => Err(format!(
+            "unknown field '{field}' (state, jobs, reason, ink)"
+        )),
+    }
+}
+
+/// The state of a `cups:<host>/<printer>/<field>` entity: `state` is
+/// `idle`, `printing` or `stopped`, `jobs` the queue length, `reason` the
+/// first state reason (e.g. `media-empty-error`, else `none`) and `ink` the
+/// lowest marker level in percent.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let mut parts = address.splitn(3, '/');
+    let (Some(host), Some(printer), Some(name)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(format!(
+            "expected <host>/<printer>/<field>, got '{address}'"
+        ));
+    };
+    let attributes = request(
+        host,
+        printer,
+        GET_PRINTER_ATTRIBUTES,
+        &[(KEYWORD, "requested-attributes", &REQUESTED)],
+    )
+    .await
+    .map_err(|e| e.user_message())?;
+    field(&attributes, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response with the given attributes, encoded like a request.
+    fn response(attributes: &[(u8, &str, &[u8])]) -> Vec<u8> {
+        let mut data = vec![2, 0, 0, 0, 0, 0, 0, 1, 0x04];
+        for (tag, name, value) in attributes {
+            data.push(*tag);
+            data.extend((name.len() as u16).to_be_bytes());
+            data.extend(name.as_bytes());
+            data.extend((value.len() as u16).to_be_bytes());
+            data.extend(*value);
+        }
+        data.push(END_OF_ATTRIBUTES);
+        data
+    }
+
+    #[test]
+    fn reads_printer_attributes() {
+        let data = response(&[
+            (ENUM, "printer-state", &5i32.to_be_bytes()),
+            (KEYWORD, "printer-state-reasons", b"media-empty-error"),
+            (INTEGER, "queued-job-count", &2i32.to_be_bytes()),
+            (INTEGER, "marker-levels", &64i32.to_be_bytes()),
+            (INTEGER, "", &12i32.to_be_bytes()),
+            (INTEGER, "", &(-1i32).to_be_bytes()),
+        ]);
+        let (status, attributes) = decode(&data).unwrap();
+        assert_eq!(status, 0);
+        assert_eq!(field(&attributes, "state"), Ok("stopped".into()));
+        assert_eq!(field(&attributes, "jobs"), Ok("2".into()));
+        assert_eq!(field(&attributes, "reason"), Ok("media-empty-error".into()));
+        assert_eq!(field(&attributes, "ink"), Ok("12".into()));
+        assert!(decode(&data[..data.len() - 3]).is_none());
+    }
+
+    #[test]
+    fn encodes_multi_valued_attributes() {
+        let body = encode(
+            GET_PRINTER_ATTRIBUTES,
+            "ipp://cups:631/printers/laser",
+            &[(KEYWORD, "requested-attributes", &["a", "bc"])],
+        );
+        assert_eq!(&body[..4], &[2, 0, 0, 0x0B]);
+        let tail = [KEYWORD, 0, 0, 0, 2, b'b', b'c', END_OF_ATTRIBUTES];
+        assert!(body.ends_with(&tail));
+    }
+}
@@ -0,0 +1,418 @@
+use crate::error::{DeckError, Result};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// Timeout of a Kubernetes API request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where a pod finds its service account.
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// The parts of a kubeconfig file deckd uses.
+#[derive(Debug, Default, Deserialize)]
+struct Kubeconfig {
+    #[serde(rename = "current-context")]
+    current_context: Option<String>,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+    #[serde(default)]
+    clusters: Vec<NamedCluster>,
+    #[serde(default)]
+    users: Vec<NamedUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedContext {
+    name: String,
+    context: Context,
+}
+
+#[derive(Debug, Deserialize)]
+struct Context {
+    cluster: String,
+    user: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedCluster {
+    name: String,
+    cluster: ClusterEntry,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ClusterEntry {
+    server: String,
+    certificate_authority: Option<PathBuf>,
+    certificate_authority_data: Option<String>,
+    #[serde(default)]
+    insecure_skip_tls_verify: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedUser {
+    name: String,
+    #[serde(default)]
+    user: User,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct User {
+    token: Option<String>,
+    #[serde(rename = "tokenFile")]
+    token_file: Option<PathBuf>,
+    client_certificate: Option<PathBuf>,
+    client_certificate_data: Option<String>,
+    client_key: Option<PathBuf>,
+    client_key_data: Option<String>,
+}
+
+/// How to reach the API server: its URL, a client trusting its CA (and
+/// carrying a client certificate if the user has one), and a bearer token.
+struct Cluster {
+    server: String,
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+/// Inline base64 `data`, else the contents of `path`.
+fn pem(data: Option<&str>, path: Option<&Path>, what: &str) -> Result<Option<Vec<u8>>> {
+    if let Some(data) = data {
+        let pem = base64::engine::general_purpose::STANDARD
+            .decode(data.trim())
+            .map_err(|e| DeckError::Config(format!("kubeconfig {what}: {e}")))?;
+        return Ok(Some(pem));
+    }
+    path.map(|path| {
+        std::fs::read(path)
+            .map_err(|e| DeckError::Config(format!("kubeconfig {what} {}: {e}", path.display())))
+    })
+    .transpose()
+}
+
+impl Kubeconfig {
+    /// The cluster and user of the current context.
+    fn current(&self) -> Result<(&ClusterEntry, &User)> {
+        let name = self
+            .current_context
+            .as_deref()
+            .ok_or_else(|| DeckError::Config("kubeconfig has no current-context".into()))?;
+        let context = self
+            .contexts
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| &c.context)
+            .ok_or_else(|| DeckError::Config(format!("kubeconfig: no context '{name}'")))?;
+        let cluster = self
+            .clusters
+            .iter()
+            .find(|c| c.name == context.cluster)
+            .map(|c| &c.cluster)
+            .ok_or_else(|| {
+                DeckError::Config(format!("kubeconfig: no cluster '{}'", context.cluster))
+            })?;
+        let user = self
+            .users
+            .iter()
+            .find(|u| u.name == context.user)
+            .map(|u| &u.user)
+            .ok_or_else(|| DeckError::Config(format!("kubeconfig: no user '{}'", context.user)))?;
+        Ok((cluster, user))
+    }
+}
+
+impl Cluster {
+    /// The cluster of `$KUBECONFIG` (its first file) or `~/.kube/config`,
+    /// else the service account of the pod deckd runs in.
+    fn load() -> Result<Self> {
+        let kubeconfig = std::env::var_os("KUBECONFIG")
+            .and_then(|paths| std::env::split_paths(&paths).next())
+            .or_else(|| {
+                let path = Path::new(&crate::privileges::var("HOME")?).join(".kube/config");
+                path.exists().then_some(path)
+            });
+        if let Some(path) = kubeconfig {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| DeckError::Config(format!("kubeconfig {}: {e}", path.display())))?;
+            let config: Kubeconfig = serde_yaml::from_str(&text)
+                .map_err(|e| DeckError::Config(format!("kubeconfig {}: {e}", path.display())))?;
+            return Self::from_kubeconfig(&config);
+        }
+        let (Ok(host), Ok(port)) = (
+            std::env::var("KUBERNETES_SERVICE_HOST"),
+            std::env::var("KUBERNETES_SERVICE_PORT"),
+        ) else {
+            return Err(DeckError::Config(
+                "no kubeconfig found and not running in a cluster".into(),
+            ));
+        };
+        let dir = Path::new(SERVICE_ACCOUNT);
+        let host = if host.contains(':') {
+            format!("[{host}]")
+        } else {
+            host
+        };
+        Self::build(
+            format!("https://{host}:{port}"),
+            false,
+            pem(None, Some(&dir.join("ca.crt")), "ca.crt")?,
+            None,
+            Some(crate::secret::read_file(&dir.join("token"))?),
+        )
+    }
+
+    fn from_kubeconfig(config: &Kubeconfig) -> Result<Self> {
+        let (cluster, user) = config.current()?;
+        let ca = pem(
+            cluster.certificate_authority_data.as_deref(),
+            cluster.certificate_authority.as_deref(),
+            "certificate-authority",
+        )?;
+        let certificate = pem(
+            user.client_certificate_data.as_deref(),
+            user.client_certificate.as_deref(),
+            "client-certificate",
+        )?;
+        let key = pem(
+            user.client_key_data.as_deref(),
+            user.client_key.as_deref(),
+            "client-key",
+        )?;
+        let identity = certificate.zip(key).map(|(mut pem, key)| {
+            pem.push(b'\n');
+            pem.extend(key);
+            pem
+        });
+        let token = match (&user.token, &user.token_file) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(path)) => Some(crate::secret::read_file(path)?),
+            (None, None) => None,
+        };
+        Self::build(
+            cluster.server.clone(),
+            cluster.insecure_skip_tls_verify,
+            ca,
+            identity,
+            token,
+        )
+    }
+
+    fn build(
+        server: String,
+        insecure: bool,
+        ca: Option<Vec<u8>>,
+        identity: Option<Vec<u8>>,
+        token: Option<String>,
+    ) -> Result<Self> {
+        let mut builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(insecure)
+            .timeout(TIMEOUT);
+        if let Some(ca) = ca {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&ca)?);
+        }
+        if let Some(identity) = identity {
+            builder = builder.identity(reqwest::Identity::from_pem(&identity)?);
+        }
+        Ok(Self {
+            server: server.trim_end_matches('/').to_string(),
+            client: super::http::with_network(builder)?.build()?,
+            token,
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}{path}", self.server));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<Value> {
+        let resp = self.request(reqwest::Method::GET, path).send().await?;
+        check(resp, path).await?.json().await.map_err(Into::into)
+    }
+
+    async fn patch(&self, path: &str, content_type: &str, body: &Value) -> Result<()> {
+        let resp = self
+            .request(reqwest::Method::PATCH, path)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body.to_string())
+            .send()
+            .await?;
+        check(resp, path).await?;
+        debug!("Kubernetes PATCH {path}");
+        Ok(())
+    }
+}
+
+/// `resp`, or an error carrying the API server's message.
+async fn check(resp: reqwest::Response, path: &str) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body: Value = resp.json().await.unwrap_or_default();
+    let message = body["message"].as_str().unwrap_or_else(|| status.as_str());
+    Err(DeckError::Action(format!("Kubernetes {path}: {message}")))
+}
+
+/// Split a `<namespace>/<name>` reference.
+///
+/// # Errors
+/// Returns a message if either part is missing.
+pub fn object(reference: &str) -> std::result::Result<(&str, &str), String> {
+    match reference.split_once('/') {
+        Some((namespace, name))
+            if !namespace.is_empty() && !name.is_empty() && !name.contains('/') =>
+        {
+            Ok((namespace, name))
+        }
+        _ => Err(format!(
+            "invalid Kubernetes object '{reference}': expected <namespace>/<name>"
+        )),
+    }
+}
+
+fn deployment_path(namespace: &str, name: &str) -> String {
+    format!("/apis/apps/v1/namespaces/{namespace}/deployments/{name}")
+}
+
+/// Ready and desired replicas of a deployment object.
+fn replicas(deployment: &Value) -> (u64, u64) {
+    (
+        deployment["status"]["readyReplicas"].as_u64().unwrap_or(0),
+        deployment["spec"]["replicas"].as_u64().unwrap_or(1),
+    )
+}
+
+/// The health of a deployment with `ready` of `desired` replicas.
+fn health(ready: u64, desired: u64) -> &'static str {
+    match (ready, desired) {
+        (_, 0) => "scaled_down",
+        (0, _) => "down",
+        (ready, desired) if ready < desired => "degraded",
+        _ => "ready",
+    }
+}
+
+/// Restart the deployment `reference` (`<namespace>/<name>`) like
+/// `kubectl rollout restart`, or scale it to `scale` replicas.
+///
+/// # Errors
+/// Returns `DeckError::Config` if no cluster is configured,
+/// `DeckError::Http` if it cannot be reached, or `DeckError::Action` if the
+/// API server rejects the change.
+pub async fn execute(reference: &str, restart: bool, scale: Option<u32>) -> Result<()> {
+    let (namespace, name) = object(reference).map_err(DeckError::Config)?;
+    let cluster = Cluster::load()?;
+    let path = deployment_path(namespace, name);
+    if let Some(replicas) = scale {
+        let body = json!({ "spec": { "replicas": replicas } });
+        cluster
+            .patch(
+                &format!("{path}/scale"),
+                "application/merge-patch+json",
+                &body,
+            )
+            .await?;
+    }
+    if restart {
+        let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let annotations = json!({ "kubectl.kubernetes.io/restartedAt": now });
+        let body =
+            json!({ "spec": { "template": { "metadata": { "annotations": annotations } } } });
+        cluster
+            .patch(&path, "application/strategic-merge-patch+json", &body)
+            .await?;
+    }
+    Ok(())
+}
+
+/// The state of a `k8s:<kind>/<namespace>/<name>` entity:
+/// `deployment` is `ready`, `degraded`, `down` or `scaled_down`, `replicas`
+/// is `<ready>/<desired>`, and `pod` is the pod's phase (`Running`, …).
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let (kind, reference) = address
+        .split_once('/')
+        .ok_or_else(|| format!("expected <kind>/<namespace>/<name>, got '{address}'"))?;
+    let (namespace, name) = object(reference)?;
+    let cluster = Cluster::load().map_err(|e| e.user_message())?;
+    let get = |path: String| async move { cluster.get(&path).await.map_err(|e| e.user_message()) };
+    match kind {
+        "deployment" | "replicas" => {
+            let (ready, desired) = replicas(&get(deployment_path(namespace, name)).await?);
+            Ok(if kind == "replicas" {
+                format!("{ready}/{desired}")
+            } else {
+                health(ready, desired).to_string()
+            })
+        }
+        "pod" => {
+            let pod = get(format!("/api/v1/namespaces/{namespace}/pods/{name}")).await?;
+            Ok(pod["status"]["phase"]
+                .as_str()
+                .unwrap_or("Unknown")
+                .to_string())
+        }
+        _ => Err(format!("unknown kind '{kind}' (deployment, replicas, pod)")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_current_context_of_kubeconfig() {
+        let config: Kubeconfig = serde_yaml::from_str(
+            r"
+apiVersion: v1
+kind: Config
+current-context: homelab
+contexts:
+- name: other
+  context: { cluster: other, user: other }
+- name: homelab
+  context: { cluster: k3s, user: deck }
+clusters:
+- name: k3s
+  cluster:
+    server: https://10.0.0.2:6443
+    insecure-skip-tls-verify: true
+users:
+- name: deck
+  user:
+    token: abc123
+",
+        )
+        .unwrap();
+        let (cluster, user) = config.current().unwrap();
+        assert_eq!(cluster.server, "https://10.0.0.2:6443");
+        assert!(cluster.insecure_skip_tls_verify);
+        assert_eq!(user.token.as_deref(), Some("abc123"));
+        assert!(Kubeconfig::default().current().is_err());
+    }
+
+    #[test]
+    fn deployment_health() {
+        let deployment = json!({ "spec": { "replicas": 3 }, "status": { "readyReplicas": 2 } });
+        assert_eq!(replicas(&deployment), (2, 3));
+        assert_eq!(health(2, 3), "degraded");
+        assert_eq!(health(3, 3), "ready");
+        assert_eq!(health(0, 1), "down");
+        assert_eq!(health(0, 0), "scaled_down");
+        assert_eq!(object("media/jellyfin"), Ok(("media", "jellyfin")));
+        assert!(object("jellyfin").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::error::{DeckError, Result};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+/// KNXnet/IP routing multicast group, where KNX IP routers listen.
+pub const ROUTING_ADDR: &str = "224.0.23.12:3671";
+
+/// Individual address the deck sends from (15.15.250).
+const SOURCE_ADDRESS: u16 = 0xfffa;
+
+/// A value written to a group address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum KnxValue {
+    /// DPT 1 (switch, up/down, ...).
+    Bool(bool),
+    /// A one-byte value, e.g. DPT 5 (0-255 scaling) or DPT 17 (scene).
+    Byte(u8),
+}
+
+/// Parse a three-level (`main/middle/sub`) or two-level (`main/sub`) group
+/// address.
+///
+/// # Errors
+/// Returns a message if the address is malformed or out of range.
+pub fn group_address(text: &str) -> std::result::Result<u16, String> {
+    let parts: Vec<u16> = text
+        .split('/')
+        .map(str::parse)
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| format!("invalid KNX group address '{text}'"))?;
+    match parts[..] {
+        [main, middle, sub] if main < 32 && middle < 8 && sub < 256 => {
+            Ok(main << 11 | middle << 8 | sub)
+        }
+        [main, sub] if main < 32 && sub < 2048 => Ok(main << 11 | sub),
+        _ => Err(format!("invalid KNX group address '{text}'")),
+    }
+}
+
+/// A KNXnet/IP routing indication carrying a GroupValueWrite of `value`.
+fn routing_indication(group: u16, value: KnxValue) -> Vec<u8> {
+    // TPCI/APCI: GroupValueWrite; a bool fits in the APCI's low bits.
+    let apdu: Vec<u8> = match value {
+        KnxValue::Bool(on) => vec![0x00, 0x80 | u8::from(on)],
+        KnxValue::Byte(byte) => vec![0x00, 0x80, byte],
+    };
+    let mut cemi = vec![
+        0x29, // L_Data.ind
+        0x00, // no additional info
+        0xbc, // standard frame, no repeat, broadcast, low priority
+        0xe0, // group address, hop count 6
+    ];
+    cemi.extend_from_slice(&SOURCE_ADDRESS.to_be_bytes());
+    cemi.extend_from_slice(&group.to_be_bytes());
+    // The length counts the APDU after its first byte.
+    cemi.push(u8::try_from(apdu.len() - 1).unwrap_or(u8::MAX));
+    cemi.extend(apdu);
+
+    let length = u16::try_from(6 + cemi.len()).unwrap_or(u16::MAX);
+    let mut frame = vec![0x06, 0x10, 0x05, 0x30];
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend(cemi);
+    frame
+}
+
+/// Write `value` to the group address `group` through KNXnet/IP routing,
+/// to `router` or the routing multicast group.
+///
+/// # Errors
+/// Returns `DeckError::Config` for a malformed address, or `DeckError::Io`
+/// if the datagram cannot be sent.
+pub async fn write(group: &str, value: KnxValue, router: Option<&str>) -> Result<()> {
+    let address = group_address(group).map_err(DeckError::Config)?;
+    let target = router.unwrap_or(ROUTING_ADDR);
+    let target: SocketAddr = tokio::net::lookup_host(target)
+        .await?
+        .next()
+        .ok_or_else(|| DeckError::Action(format!("KNX router '{target}' not found")))?;
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).await?;
+    socket
+        .send_to(&routing_indication(address, value), target)
+        .await?;
+    debug!("KNX {group} = {value:?}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_group_writes() {
+        assert_eq!(group_address("1/2/3"), Ok(0x0a03));
+        assert_eq!(group_address("4/1000"), Ok(0x23e8));
+        assert!(group_address("1/8/0").is_err());
+        assert!(group_address("light").is_err());
+
+        let frame = routing_indication(0x0a03, KnxValue::Bool(true));
+        assert_eq!(
+            frame,
+            [
+                0x06, 0x10, 0x05, 0x30, 0x00, 0x11, 0x29, 0x00, 0xbc, 0xe0, 0xff, 0xfa, 0x0a, 0x03,
+                0x01, 0x00, 0x81
+            ]
+        );
+        let frame = routing_indication(0x0a03, KnxValue::Byte(128));
+        assert_eq!(frame[5], 0x12);
+        assert_eq!(frame[14..], [0x02, 0x00, 0x80, 0x80]);
+    }
+}
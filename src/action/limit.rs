@@ -0,0 +1,279 @@
+//! Limits on actions running at once, by action type (`[deckd.actions]`).
+
+use crate::config::schema::{ActionLimit, ActionsConfig, LimitPolicy};
+use crate::error::{DeckError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Limits of types not configured: shell commands and HTTP requests queue
+/// behind a few running ones.
+const DEFAULT_LIMITS: [(&str, ActionLimit); 2] = [
+    (
+        "shell",
+        ActionLimit {
+            max_concurrent: 4,
+            policy: LimitPolicy::Queue,
+            queue: 16,
+        },
+    ),
+    (
+        "http",
+        ActionLimit {
+            max_concurrent: 8,
+            policy: LimitPolicy::Queue,
+            queue: 32,
+        },
+    ),
+];
+
+/// The limiter all actions run through.
+static LIMITER: LazyLock<Limiter> = LazyLock::new(Limiter::default);
+
+/// Use the limits of a (re)loaded config. Actions already running or
+/// waiting keep their place.
+pub fn configure(config: &ActionsConfig) {
+    LIMITER
+        .state
+        .lock()
+        .unwrap()
+        .limits
+        .clone_from(&config.limits);
+    LIMITER.changed.notify_waiters();
+}
+
+/// Run `action` (of type `kind`) within the limits.
+///
+/// # Errors
+/// Returns `DeckError::Action` if the action is dropped, else the action's
+/// own result. An action replaced by a newer one ends with `Ok`.
+pub async fn run(kind: &str, action: impl Future<Output = Result<()>>) -> Result<()> {
+    LIMITER.run(kind, action).await
+}
+
+/// Actions of one type running and waiting.
+#[derive(Debug, Default)]
+struct Slots {
+    /// Running actions, oldest first, with what stops them.
+    running: VecDeque<(u64, CancellationToken)>,
+    waiting: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    limits: HashMap<String, ActionLimit>,
+    slots: HashMap<String, Slots>,
+    next_id: u64,
+}
+
+impl State {
+    fn limit(&self, kind: &str) -> Option<ActionLimit> {
+        self.limits
+            .get(kind)
+            .copied()
+            .or_else(|| {
+                DEFAULT_LIMITS
+                    .iter()
+                    .find(|(name, _)| *name == kind)
+                    .map(|(_, limit)| *limit)
+            })
+            .filter(|limit| limit.max_concurrent > 0)
+    }
+
+    /// Start an action of `kind`, returning its id and what stops it.
+    fn start(&mut self, kind: &str) -> (u64, CancellationToken) {
+        self.next_id += 1;
+        let cancel = CancellationToken::new();
+        self.slots
+            .entry(kind.to_string())
+            .or_default()
+            .running
+            .push_back((self.next_id, cancel.clone()));
+        (self.next_id, cancel)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Limiter {
+    state: Mutex<State>,
+    /// Signalled when an action finishes or the limits change.
+    changed: Notify,
+}
+
+/// A running action's place, given up on drop.
+struct Running<'a> {
+    limiter: &'a Limiter,
+    kind: &'a str,
+    id: u64,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        if let Some(slots) = state.slots.get_mut(self.kind) {
+            slots.running.retain(|(id, _)| *id != self.id);
+        }
+        drop(state);
+        self.limiter.changed.notify_waiters();
+    }
+}
+
+/// A queued action's place in the queue, given up on drop.
+struct Waiting<'a> {
+    limiter: &'a Limiter,
+    kind: &'a str,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        if let Some(slots) = state.slots.get_mut(self.kind) {
+            slots.waiting = slots.waiting.saturating_sub(1);
+        }
+    }
+}
+
+/// What an action may do now.
+enum Admission {
+    Unlimited,
+    Start(u64, CancellationToken),
+    Wait,
+}
+
+impl Limiter {
+    /// Start an action of `kind` if its limit allows, else queue it (once)
+    /// or drop it.
+    fn admit<'a>(&'a self, kind: &'a str, waiting: &mut Option<Waiting<'a>>) -> Result<Admission> {
+        let mut state = self.state.lock().unwrap();
+        let Some(limit) = state.limit(kind) else {
+            return Ok(Admission::Unlimited);
+        };
+        let slots = state.slots.entry(kind.to_string()).or_default();
+        if slots.running.len() < limit.max_concurrent {
+            let (id, cancel) = state.start(kind);
+            return Ok(Admission::Start(id, cancel));
+        }
+        match limit.policy {
+            LimitPolicy::Drop => Err(DeckError::Action(format!(
+                "{} {kind} action(s) already running, dropped",
+                slots.running.len()
+            ))),
+            LimitPolicy::Replace => {
+                if let Some((_, oldest)) = slots.running.pop_front() {
+                    oldest.cancel();
+                }
+                let (id, cancel) = state.start(kind);
+                Ok(Admission::Start(id, cancel))
+            }
+            LimitPolicy::Queue if waiting.is_none() => {
+                if slots.waiting >= limit.queue {
+                    return Err(DeckError::Action(format!(
+                        "{} {kind} action(s) already waiting, dropped",
+                        slots.waiting
+                    )));
+                }
+                slots.waiting += 1;
+                *waiting = Some(Waiting {
+                    limiter: self,
+                    kind,
+                });
+                Ok(Admission::Wait)
+            }
+            LimitPolicy::Queue => Ok(Admission::Wait),
+        }
+    }
+
+    async fn run(&self, kind: &str, action: impl Future<Output = Result<()>>) -> Result<()> {
+        let mut waiting = None;
+        let (id, cancel) = loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            // Registered before checking, so a finish in between is not missed.
+            changed.as_mut().enable();
+            match self.admit(kind, &mut waiting)? {
+                Admission::Unlimited => return action.await,
+                Admission::Start(id, cancel) => break (id, cancel),
+                Admission::Wait => changed.await,
+            }
+        };
+        drop(waiting);
+        let _running = Running {
+            limiter: self,
+            kind,
+            id,
+        };
+        tokio::select! {
+            result = action => result,
+            () = cancel.cancelled() => {
+                info!("{kind} action replaced by a newer one");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter(max_concurrent: usize, policy: LimitPolicy, queue: usize) -> Limiter {
+        let limiter = Limiter::default();
+        let limit = ActionLimit {
+            max_concurrent,
+            policy,
+            queue,
+        };
+        limiter
+            .state
+            .lock()
+            .unwrap()
+            .limits
+            .insert("shell".into(), limit);
+        limiter
+    }
+
+    /// An action taking `ms`.
+    async fn action(ms: u64) -> Result<()> {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drops_queues_and_replaces() {
+        let dropping = limiter(1, LimitPolicy::Drop, 0);
+        let (first, second) = tokio::join!(
+            dropping.run("shell", action(100)),
+            dropping.run("shell", action(100)),
+        );
+        assert!(first.is_ok());
+        assert!(second.unwrap_err().to_string().contains("dropped"));
+        // Other types are not limited.
+        assert!(dropping.run("navigate", action(1)).await.is_ok());
+
+        let queueing = limiter(1, LimitPolicy::Queue, 1);
+        let start = std::time::Instant::now();
+        let (first, second, third) = tokio::join!(
+            queueing.run("shell", action(50)),
+            queueing.run("shell", action(50)),
+            queueing.run("shell", action(50)),
+        );
+        assert!(first.is_ok() && second.is_ok() && third.is_err());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let replacing = limiter(1, LimitPolicy::Replace, 0);
+        let start = std::time::Instant::now();
+        let (first, second) = tokio::join!(replacing.run("shell", action(10_000)), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            replacing.run("shell", action(10)).await
+        },);
+        assert!(first.is_ok() && second.is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(replacing.state.lock().unwrap().slots["shell"]
+            .running
+            .is_empty());
+    }
+}
@@ -1,37 +1,359 @@
+#[cfg(feature = "bluetooth")]
+pub mod bluetooth;
+pub mod dns;
+pub mod graphql;
+pub mod ha;
 pub mod http;
+pub mod ipp;
+#[cfg(feature = "kubernetes")]
+pub mod k8s;
+#[cfg(feature = "industrial")]
+pub mod knx;
+pub mod limit;
+#[cfg(feature = "industrial")]
+pub mod modbus;
 pub mod navigate;
+pub mod policy;
+pub mod proxmox;
+pub mod remote;
 pub mod shell;
+pub mod snapcast;
+pub mod vpn;
 
 use crate::config::schema::ActionConfig;
-use crate::error::Result;
+use crate::error::{DeckError, ErrorContext, Result};
 use crate::event::DeckEvent;
+use crate::ids::KeyIndex;
+use crate::overrides::OverrideSource;
+use crate::render::locale::Locale;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
-use tracing::info;
+use tracing::{info, warn};
 
-/// Execute an action based on its config.
+/// Values available to templated action fields: HTTP URLs, headers and
+/// bodies, shell commands, and toast texts.
+#[derive(Debug, Clone)]
+pub struct ActionContext {
+    /// Raw state of the pressed button's `state_entity` before the press.
+    pub state: Option<String>,
+    /// Locale for the clock placeholders.
+    pub locale: &'static Locale,
+    /// Extra placeholders by name, e.g. `hook.name` for `{hook.name}`.
+    pub vars: HashMap<String, String>,
+    /// The key whose press runs the action, if a key does.
+    pub key: Option<KeyIndex>,
+}
+
+impl ActionContext {
+    #[must_use]
+    pub fn new(locale: &'static Locale) -> Self {
+        Self {
+            state: None,
+            locale,
+            vars: HashMap::new(),
+            key: None,
+        }
+    }
+
+    /// Substitute `vars`, `{state}` (the raw state, empty if unknown) and the
+    /// clock placeholders, or render `text` if it is a template (see
+    /// [`crate::template`]).
+    ///
+    /// # Errors
+    /// Returns `DeckError::Action` if the template fails.
+    pub fn expand<'a>(&self, text: &'a str) -> Result<Cow<'a, str>> {
+        if !text.contains('{') {
+            return Ok(Cow::Borrowed(text));
+        }
+        if crate::template::is_template(text) {
+            return crate::template::render(text, &self.template_context())
+                .map(Cow::Owned)
+                .map_err(DeckError::Action);
+        }
+
+        let mut text = text.to_string();
+        for (name, value) in &self.vars {
+            text = text.replace(&format!("{{{name}}}"), value);
+        }
+        let text = text.replace(
+            crate::render::format::STATE_PLACEHOLDER,
+            self.state.as_deref().unwrap_or_default(),
+        );
+        let now = chrono::Local::now().naive_local();
+        Ok(Cow::Owned(crate::render::format::expand_clock(
+            &text,
+            self.locale,
+            now,
+        )))
+    }
+
+    /// Render a shell command if it is a template, quoting every value as a
+    /// single word. Other commands run as written.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Action` if the template fails.
+    pub fn expand_shell<'a>(&self, command: &'a str) -> Result<Cow<'a, str>> {
+        if !crate::template::is_template(command) {
+            return Ok(Cow::Borrowed(command));
+        }
+        crate::template::render_shell(command, &self.template_context())
+            .map(Cow::Owned)
+            .map_err(DeckError::Action)
+    }
+
+    fn template_context(&self) -> crate::template::Context<'_> {
+        crate::template::Context {
+            state: self.state.as_deref(),
+            vars: Some(&self.vars),
+            ..crate::template::Context::new(self.locale)
+        }
+    }
+}
+
+/// Pause before the one retry of an action that failed retryably.
+pub const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Execute an action based on its config, within the limits of its type
+/// (see [`limit`]), and announce the outcome as
+/// `DeckEvent::ActionCompleted`. Errors carry the action type as context.
 ///
 /// # Errors
-/// Returns `DeckError` if the action fails (HTTP error, shell failure, etc.).
-pub async fn execute(action: &ActionConfig, tx: &broadcast::Sender<DeckEvent>) -> Result<()> {
+/// Returns `DeckError` if the action fails (HTTP error, shell failure, etc.),
+/// is forbidden by the policy (see [`policy`]) or is dropped by its limit.
+pub async fn execute(
+    action: &ActionConfig,
+    tx: &broadcast::Sender<DeckEvent>,
+    ctx: &ActionContext,
+) -> Result<()> {
+    let started = Instant::now();
+    let result = match policy::check(action, ctx) {
+        Ok(()) => limit::run(action.kind(), run(action, tx, ctx)).await,
+        Err(e) => Err(e),
+    }
+    .map_err(|e| e.with_context(ErrorContext::default().action(action.kind())));
+    let _ = tx.send(DeckEvent::ActionCompleted {
+        key: ctx.key,
+        action_kind: action.kind(),
+        result: result.as_ref().map(|_| ()).map_err(ToString::to_string),
+        duration: started.elapsed(),
+    });
+    result
+}
+
+/// Execute an action, trying once more after [`RETRY_DELAY`] if it failed
+/// in a way that may pass (see `DeckError::is_retryable`).
+///
+/// # Errors
+/// Returns the error of the last attempt.
+pub async fn execute_retrying(
+    action: &ActionConfig,
+    tx: &broadcast::Sender<DeckEvent>,
+    ctx: &ActionContext,
+) -> Result<()> {
+    match execute(action, tx, ctx).await {
+        Err(e) if e.is_retryable() => {
+            warn!("{e}, retrying in {}s", RETRY_DELAY.as_secs());
+            tokio::time::sleep(RETRY_DELAY).await;
+            execute(action, tx, ctx).await
+        }
+        result => result,
+    }
+}
+
+async fn run(
+    action: &ActionConfig,
+    tx: &broadcast::Sender<DeckEvent>,
+    ctx: &ActionContext,
+) -> Result<()> {
     match action {
         ActionConfig::Http {
             method,
             url,
             headers,
             body,
+            json,
+            form,
+            auth,
+            insecure_tls,
+            ca_cert,
         } => {
             info!("executing HTTP {method} {url}");
-            http::execute(method, url, headers, body.as_deref()).await
+            let body = match (body, json, form) {
+                (_, Some(json), _) => http::Body::Json(json),
+                (_, _, Some(form)) => http::Body::Form(form),
+                (Some(raw), _, _) => http::Body::Raw(raw),
+                (None, None, None) => http::Body::Empty,
+            };
+            let options = http::Options {
+                auth: auth.as_ref(),
+                insecure_tls: *insecure_tls,
+                ca_cert: ca_cert.as_deref(),
+            };
+            http::execute(method, url, headers, body, options, ctx).await
+        }
+        ActionConfig::Graphql {
+            url,
+            query,
+            variables,
+            headers,
+            auth,
+            insecure_tls,
+            ca_cert,
+        } => {
+            info!("executing GraphQL request to {url}");
+            let options = http::Options {
+                auth: auth.as_ref(),
+                insecure_tls: *insecure_tls,
+                ca_cert: ca_cert.as_deref(),
+            };
+            graphql::execute(url, query, variables.as_ref(), headers, options, ctx).await
         }
-        ActionConfig::Shell { command } => {
+        ActionConfig::Shell { command, sandbox } => {
+            let command = ctx.expand_shell(command)?;
             info!("executing shell: {command}");
-            shell::execute(command).await
+            shell::execute(&command, sandbox.as_ref()).await
         }
-        ActionConfig::Navigate { page } => {
+        ActionConfig::Navigate {
+            picker: Some(entity),
+            ..
+        } => {
+            info!("opening color picker: {entity}");
+            let _ = tx.send(DeckEvent::OpenPicker(entity.clone()));
+            Ok(())
+        }
+        ActionConfig::Navigate { page, .. } => {
             info!("navigating to page: {page}");
             let _ = tx.send(DeckEvent::NavigateTo(page.clone()));
             Ok(())
         }
+        #[cfg(feature = "industrial")]
+        ActionConfig::KnxWrite {
+            group,
+            value,
+            router,
+        } => {
+            info!("KNX write {group}");
+            knx::write(group, *value, router.as_deref()).await
+        }
+        #[cfg(feature = "industrial")]
+        ActionConfig::ModbusWrite {
+            host,
+            unit,
+            register,
+            value,
+        } => {
+            info!("Modbus write {host} register {register}");
+            modbus::write_register(host, *unit, *register, *value).await
+        }
+        ActionConfig::PrintCancel { host, printer } => {
+            info!("cancel print jobs on {printer}");
+            ipp::cancel_jobs(host, printer).await
+        }
+        ActionConfig::Proxmox { vm, command, name } => {
+            info!("proxmox {vm}: {command:?}");
+            proxmox::execute(*vm, *command, name.as_deref()).await
+        }
+        #[cfg(feature = "kubernetes")]
+        ActionConfig::K8s {
+            deployment,
+            restart,
+            scale,
+        } => {
+            info!("k8s {deployment}: restart={restart} scale={scale:?}");
+            k8s::execute(deployment, *restart, *scale).await
+        }
+        #[cfg(feature = "bluetooth")]
+        ActionConfig::Bluetooth {
+            device,
+            connect,
+            adapter,
+        } => {
+            info!("bluetooth {device}: connect={connect:?}");
+            bluetooth::execute(adapter.as_deref(), device, *connect).await
+        }
+        ActionConfig::Vpn {
+            kind,
+            interface,
+            up,
+            exit_node,
+        } => {
+            info!("vpn {kind:?}: up={up:?} exit_node={exit_node:?}");
+            vpn::execute(*kind, interface.as_deref(), *up, exit_node.as_deref()).await
+        }
+        ActionConfig::Snapcast {
+            server,
+            client,
+            volume,
+            mute,
+            group,
+        } => {
+            let command = snapcast::Command::from_fields(*volume, *mute, group.as_deref())
+                .map_err(DeckError::Config)?;
+            info!("snapcast {client}: {command:?}");
+            snapcast::execute(server, client, &command).await
+        }
+        ActionConfig::SceneSave { scene, entities } => {
+            info!("saving scene {scene}: {} entities", entities.len());
+            let states = crate::scene::snapshot(entities).await?;
+            let _ = tx.send(DeckEvent::SceneSaved(scene.clone(), states));
+            Ok(())
+        }
+        ActionConfig::SceneRecall { scene } => {
+            info!("recalling scene {scene}");
+            let _ = tx.send(DeckEvent::RecallScene(scene.clone()));
+            Ok(())
+        }
+        ActionConfig::Remote { kind, host, key } => {
+            info!("remote {host}: {key}");
+            remote::press(*kind, host, *key).await
+        }
+        ActionConfig::HaEvent { event, data } => {
+            info!("firing HA event: {event}");
+            ha::fire_event(event, data.as_ref(), ctx).await
+        }
+        ActionConfig::HaService { service, data } => {
+            info!("calling HA service: {service}");
+            ha::call_service(service, data.as_ref(), ctx).await
+        }
+        ActionConfig::HaAssist {
+            text,
+            language,
+            agent_id,
+            reply,
+        } => {
+            let text = ctx.expand(text)?;
+            info!("sending to HA Assist: {text}");
+            let speech = ha::assist(&text, language.as_deref(), agent_id.as_deref()).await?;
+            if *reply && !speech.is_empty() {
+                let _ = tx.send(DeckEvent::Toast(speech));
+            }
+            Ok(())
+        }
+        ActionConfig::SetKey { key, content } => {
+            info!("setting key {key}");
+            let mut content = content.clone();
+            if let Some(label) = content.label.take() {
+                content.label = Some(ctx.expand(&label)?.into_owned());
+            }
+            let _ = tx.send(DeckEvent::SetKey(
+                *key,
+                OverrideSource::Action,
+                Some(content),
+            ));
+            Ok(())
+        }
+        ActionConfig::ClearKey { key } => {
+            info!("clearing key {key}");
+            let _ = tx.send(DeckEvent::SetKey(*key, OverrideSource::Action, None));
+            Ok(())
+        }
+        ActionConfig::Flash { pattern, key } => {
+            info!("flashing pattern '{pattern}'");
+            let _ = tx.send(DeckEvent::Flash(pattern.clone(), *key));
+            Ok(())
+        }
         ActionConfig::Back => {
             info!("navigating back");
             let _ = tx.send(DeckEvent::NavigateBack);
@@ -42,5 +364,63 @@ pub async fn execute(action: &ActionConfig, tx: &broadcast::Sender<DeckEvent>) -
             let _ = tx.send(DeckEvent::NavigateHome);
             Ok(())
         }
+        ActionConfig::DisplaySleep => {
+            info!("display sleep");
+            let _ = tx.send(DeckEvent::DisplaySleep);
+            Ok(())
+        }
+        ActionConfig::Guest { on } => {
+            let _ = tx.send(DeckEvent::SetGuest(*on));
+            Ok(())
+        }
+        ActionConfig::DisplayWake => {
+            info!("display wake");
+            let _ = tx.send(DeckEvent::DisplayWake);
+            Ok(())
+        }
+        ActionConfig::Brightness {
+            level: Some(level), ..
+        } => {
+            info!("setting brightness to {level}");
+            let _ = tx.send(DeckEvent::SetBrightness(Some(*level)));
+            Ok(())
+        }
+        ActionConfig::Brightness { level: None, step } => {
+            let detents = ctx
+                .vars
+                .get("dial.delta")
+                .and_then(|delta| delta.parse::<i32>().ok())
+                .unwrap_or(1);
+            let _ = tx.send(DeckEvent::StepBrightness(i32::from(*step) * detents));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn announces_completed_actions() {
+        let (tx, mut rx) = broadcast::channel(8);
+        let mut ctx = ActionContext::new(crate::render::locale::resolve("en-US"));
+        ctx.key = KeyIndex::new(3, 15);
+        execute(&ActionConfig::Back, &tx, &ctx).await.unwrap();
+
+        assert!(matches!(rx.try_recv(), Ok(DeckEvent::NavigateBack)));
+        match rx.try_recv() {
+            Ok(DeckEvent::ActionCompleted {
+                key,
+                action_kind,
+                result,
+                ..
+            }) => {
+                assert_eq!(key, ctx.key);
+                assert_eq!(action_kind, "back");
+                assert_eq!(result, Ok(()));
+            }
+            other => panic!("expected ActionCompleted, got {other:?}"),
+        }
     }
 }
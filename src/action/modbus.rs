@@ -0,0 +1,124 @@
+use crate::error::{DeckError, Result};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+/// Port of Modbus TCP.
+const PORT: u16 = 502;
+
+/// Timeout of a whole request, from connecting to the response.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+
+/// A request frame: the MBAP header and `pdu`.
+fn frame(unit: u8, pdu: &[u8]) -> Vec<u8> {
+    // Length counts the unit id and the PDU.
+    let length = u16::try_from(pdu.len() + 1).unwrap_or(u16::MAX);
+    let mut frame = vec![0, 1, 0, 0];
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.push(unit);
+    frame.extend_from_slice(pdu);
+    frame
+}
+
+/// The PDU of a response to `function`, or the exception it reports.
+fn check_response(function: u8, pdu: &[u8]) -> Result<&[u8]> {
+    match pdu {
+        [code, exception, ..] if *code == function | 0x80 => Err(DeckError::Action(format!(
+            "Modbus exception {exception:#04x}"
+        ))),
+        [code, rest @ ..] if *code == function => Ok(rest),
+        _ => Err(DeckError::Action("Modbus: unexpected response".into())),
+    }
+}
+
+/// Send `pdu` to `unit` at `host` and return the response PDU's data.
+async fn request(host: &str, unit: u8, pdu: &[u8]) -> Result<Vec<u8>> {
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:{PORT}")
+    };
+    let exchange = async {
+        let mut stream = TcpStream::connect(&addr).await?;
+        stream.write_all(&frame(unit, pdu)).await?;
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).await?;
+        let length = usize::from(u16::from_be_bytes([header[4], header[5]]));
+        let mut response = vec![0u8; length.saturating_sub(1)];
+        stream.read_exact(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let response = tokio::time::timeout(TIMEOUT, exchange)
+        .await
+        .map_err(|_| DeckError::Action(format!("Modbus {addr}: timeout")))??;
+    Ok(check_response(pdu[0], &response)?.to_vec())
+}
+
+/// Write `value` to the holding register `register` of `unit` at `host`.
+///
+/// # Errors
+/// Returns `DeckError::Io` if the device cannot be reached, or
+/// `DeckError::Action` if it answers with an exception.
+pub async fn write_register(host: &str, unit: u8, register: u16, value: u16) -> Result<()> {
+    let [reg_hi, reg_lo] = register.to_be_bytes();
+    let [val_hi, val_lo] = value.to_be_bytes();
+    request(
+        host,
+        unit,
+        &[WRITE_SINGLE_REGISTER, reg_hi, reg_lo, val_hi, val_lo],
+    )
+    .await?;
+    debug!("Modbus {host} unit {unit}: register {register} = {value}");
+    Ok(())
+}
+
+/// Read the holding register `register` of `unit` at `host`.
+///
+/// # Errors
+/// Returns the errors of [`write_register`].
+pub async fn read_register(host: &str, unit: u8, register: u16) -> Result<u16> {
+    let [reg_hi, reg_lo] = register.to_be_bytes();
+    let data = request(host, unit, &[READ_HOLDING_REGISTERS, reg_hi, reg_lo, 0, 1]).await?;
+    match data[..] {
+        [2, hi, lo] => Ok(u16::from_be_bytes([hi, lo])),
+        _ => Err(DeckError::Action("Modbus: unexpected response".into())),
+    }
+}
+
+/// The state of a `modbus:<host>[:port]/<unit>/<register>` entity: the
+/// register's value in decimal.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let parse = || {
+        let (rest, register) = address.rsplit_once('/')?;
+        let (host, unit) = rest.rsplit_once('/')?;
+        Some((host, unit.parse().ok()?, register.parse().ok()?))
+    };
+    let (host, unit, register) = parse().ok_or("expected <host>/<unit>/<register>")?;
+    read_register(host, unit, register)
+        .await
+        .map(|value| value.to_string())
+        .map_err(|e| e.user_message())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_and_responses() {
+        assert_eq!(
+            frame(1, &[WRITE_SINGLE_REGISTER, 0, 40, 0x01, 0x2c]),
+            [0, 1, 0, 0, 0, 6, 1, 6, 0, 40, 1, 44]
+        );
+        assert_eq!(check_response(3, &[3, 2, 0, 7]).unwrap(), [2, 0, 7]);
+        let exception = check_response(6, &[0x86, 0x02]).unwrap_err();
+        assert_eq!(exception.to_string(), "action error: Modbus exception 0x02");
+    }
+}
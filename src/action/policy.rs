@@ -0,0 +1,146 @@
+//! Which actions may run at all (`[deckd.policy]`), checked before every
+//! action so pages merged from a shared pack cannot run what the owner has
+//! ruled out.
+
+use super::ActionContext;
+use crate::config::schema::{ActionConfig, ActionPolicy};
+use crate::error::{DeckError, Result};
+use std::sync::{LazyLock, RwLock};
+
+/// The policy of the loaded config.
+static POLICY: LazyLock<RwLock<ActionPolicy>> = LazyLock::new(RwLock::default);
+
+/// Use the policy of a (re)loaded config.
+pub fn configure(policy: &ActionPolicy) {
+    POLICY.write().unwrap().clone_from(policy);
+}
+
+/// Check `action` against the policy, with its URL expanded as it would be
+/// requested.
+///
+/// # Errors
+/// Returns `DeckError::Action` if the policy forbids the action.
+pub fn check(action: &ActionConfig, ctx: &ActionContext) -> Result<()> {
+    permits(&POLICY.read().unwrap(), action, ctx)
+}
+
+fn permits(policy: &ActionPolicy, action: &ActionConfig, ctx: &ActionContext) -> Result<()> {
+    let kind = action.kind();
+    let allowed = policy
+        .allow
+        .as_ref()
+        .is_none_or(|allow| allow.iter().any(|k| k == kind));
+    if !allowed || policy.deny.iter().any(|k| k == kind) {
+        return Err(DeckError::Action(format!(
+            "{kind} actions are not allowed by deckd.policy"
+        )));
+    }
+
+    let Some(ref hosts) = policy.http_hosts else {
+        return Ok(());
+    };
+    let Some(target) = target(action, ctx)? else {
+        return Ok(());
+    };
+    let host = host_of(&target);
+    if hosts.iter().any(|pattern| host_matches(pattern, &host)) {
+        Ok(())
+    } else {
+        Err(DeckError::Action(format!(
+            "host '{host}' is not in deckd.policy.http_hosts"
+        )))
+    }
+}
+
+/// The URL or `host[:port]` that `action` reaches over the network, if it
+/// names one; an `http` or `graphql` URL as it would be requested.
+fn target(action: &ActionConfig, ctx: &ActionContext) -> Result<Option<String>> {
+    Ok(match action {
+        ActionConfig::Http { url, .. } | ActionConfig::Graphql { url, .. } => {
+            Some(ctx.expand(url)?.into_owned())
+        }
+        ActionConfig::Remote { host, .. } | ActionConfig::PrintCancel { host, .. } => {
+            Some(host.clone())
+        }
+        ActionConfig::Snapcast { server, .. } => Some(server.clone()),
+        ActionConfig::Proxmox { .. } => Some(std::env::var("PROXMOX_URL").unwrap_or_default()),
+        #[cfg(feature = "industrial")]
+        ActionConfig::ModbusWrite { host, .. } => Some(host.clone()),
+        #[cfg(feature = "industrial")]
+        ActionConfig::KnxWrite { router, .. } => router.clone(),
+        _ => None,
+    })
+}
+
+/// The lowercase host of a URL or `host[:port]` (IPv6 without brackets),
+/// empty if there is none.
+fn host_of(target: &str) -> String {
+    let url = if target.contains("://") {
+        reqwest::Url::parse(target)
+    } else {
+        reqwest::Url::parse(&format!("tcp://{target}"))
+    };
+    url.ok()
+        .and_then(|url| {
+            url.host_str()
+                .map(|host| host.trim_matches(['[', ']']).to_ascii_lowercase())
+        })
+        .unwrap_or_default()
+}
+
+/// Whether `host` is `pattern`, or a subdomain of it for `*.example.com`.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+        None => host == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denies_kinds_and_foreign_hosts() {
+        let policy: ActionPolicy = toml::from_str(
+            r#"
+deny = ["shell"]
+http_hosts = ["homeassistant.local", "*.lan"]
+"#,
+        )
+        .unwrap();
+        let ctx = ActionContext::new(crate::render::locale::resolve("en-US"));
+        let check = |toml: &str| {
+            let action: ActionConfig = toml::from_str(toml).unwrap();
+            permits(&policy, &action, &ctx).is_ok()
+        };
+
+        assert!(!check("action = \"shell\"\ncommand = \"true\""));
+        assert!(check("action = \"home\""));
+        assert!(check(
+            "action = \"http\"\nmethod = \"POST\"\nurl = \"http://HomeAssistant.local:8123/api\""
+        ));
+        assert!(check(
+            "action = \"http\"\nmethod = \"GET\"\nurl = \"https://nas.lan/\""
+        ));
+        assert!(!check(
+            "action = \"http\"\nmethod = \"GET\"\nurl = \"https://evil.example/lan\""
+        ));
+        assert!(!check(
+            "action = \"graphql\"\nurl = \"https://notlan/\"\nquery = \"{ x }\""
+        ));
+        assert!(check(
+            "action = \"print_cancel\"\nhost = \"printer.lan:631\"\nprinter = \"office\""
+        ));
+        assert!(!check(
+            "action = \"print_cancel\"\nhost = \"10.0.0.9\"\nprinter = \"office\""
+        ));
+        assert!(!check(
+            "action = \"snapcast\"\nserver = \"http://snap.example:1780\"\nclient = \"kitchen\"\nmute = true"
+        ));
+        assert_eq!(host_of("[FD00::2]:5555"), "fd00::2");
+    }
+}
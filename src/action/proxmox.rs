@@ -0,0 +1,190 @@
+use crate::error::{DeckError, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::debug;
+
+/// Timeout of a Proxmox API request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a `proxmox` action does to a VM or container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxmoxCommand {
+    Start,
+    /// Ask the guest OS to shut down.
+    Shutdown,
+    /// Power off at once.
+    Stop,
+    Reboot,
+    /// Start when stopped, else shut down.
+    Toggle,
+    Snapshot,
+}
+
+/// A VM or container as listed by `/cluster/resources`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Guest {
+    node: String,
+    /// `qemu` or `lxc`.
+    kind: String,
+    status: String,
+}
+
+/// Check a snapshot name: a letter, then letters, digits, `-` and `_`.
+///
+/// # Errors
+/// Returns a message if Proxmox would reject it.
+pub fn check_snapshot_name(name: &str) -> std::result::Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && name.len() <= 40;
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid Proxmox snapshot name '{name}'"))
+    }
+}
+
+/// The API token, `PROXMOX_TOKEN` (`user@realm!id=secret`) or the file
+/// named by `PROXMOX_TOKEN_FILE`.
+fn token() -> Result<String> {
+    if let Ok(token) = std::env::var("PROXMOX_TOKEN") {
+        return Ok(token);
+    }
+    match std::env::var_os("PROXMOX_TOKEN_FILE") {
+        Some(path) => crate::secret::read_file(&PathBuf::from(path)),
+        None => Err(DeckError::Config("PROXMOX_TOKEN not set".into())),
+    }
+}
+
+/// GET or POST `path` below `PROXMOX_URL`'s `/api2/json` and return the
+/// response's `data`. `PROXMOX_INSECURE_TLS=1` accepts the self-signed
+/// certificate Proxmox starts with; `PROXMOX_CA_CERT` trusts its CA instead.
+async fn api(post: Option<&[(&str, &str)]>, path: &str) -> Result<Value> {
+    let url = std::env::var("PROXMOX_URL")
+        .map_err(|_| DeckError::Config("PROXMOX_URL not set".into()))?;
+    let insecure = std::env::var("PROXMOX_INSECURE_TLS")
+        .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    let ca_cert = std::env::var_os("PROXMOX_CA_CERT").map(PathBuf::from);
+    let client = super::http::build_client(insecure, ca_cert.as_deref(), Some(TIMEOUT))?;
+    let url = format!("{}/api2/json{path}", url.trim_end_matches('/'));
+    let request = match post {
+        Some(form) => client.post(&url).form(form),
+        None => client.get(&url),
+    };
+    let resp = request
+        .header("Authorization", format!("PVEAPIToken={}", token()?))
+        .send()
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        // Parameter errors come as `errors: {name: message}`.
+        let body: Value = resp.json().await.unwrap_or_default();
+        let errors: Vec<String> = body["errors"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(name, message)| format!("{name}: {}", message.as_str().unwrap_or_default()))
+            .collect();
+        let reason = if errors.is_empty() {
+            status.to_string()
+        } else {
+            errors.join(", ")
+        };
+        return Err(DeckError::Action(format!("Proxmox {path}: {reason}")));
+    }
+    let body: Value = resp.json().await?;
+    Ok(body["data"].clone())
+}
+
+/// The guest `vmid` in a `/cluster/resources` listing.
+fn find(resources: &Value, vmid: u32) -> Option<Guest> {
+    let guest = resources
+        .as_array()?
+        .iter()
+        .find(|r| r["vmid"].as_u64() == Some(u64::from(vmid)))?;
+    Some(Guest {
+        node: guest["node"].as_str()?.to_string(),
+        kind: guest["type"].as_str()?.to_string(),
+        status: guest["status"].as_str().unwrap_or("unknown").to_string(),
+    })
+}
+
+async fn guest(vmid: u32) -> Result<Guest> {
+    let resources = api(None, "/cluster/resources?type=vm").await?;
+    find(&resources, vmid).ok_or_else(|| DeckError::Action(format!("Proxmox: no guest {vmid}")))
+}
+
+/// Run `command` on the VM or container `vmid`, wherever in the cluster it
+/// is. Snapshots are named `name`, else `deckd_<date>_<time>`.
+///
+/// # Errors
+/// Returns `DeckError::Config` if `PROXMOX_URL` or the token is not set,
+/// `DeckError::Http` if Proxmox cannot be reached, or `DeckError::Action`
+/// if the guest does not exist or the request is refused.
+pub async fn execute(vmid: u32, command: ProxmoxCommand, name: Option<&str>) -> Result<()> {
+    let guest = guest(vmid).await?;
+    let base = format!("/nodes/{}/{}/{vmid}", guest.node, guest.kind);
+    let status = match command {
+        ProxmoxCommand::Start => "start",
+        ProxmoxCommand::Shutdown => "shutdown",
+        ProxmoxCommand::Stop => "stop",
+        ProxmoxCommand::Reboot => "reboot",
+        ProxmoxCommand::Toggle if guest.status == "stopped" => "start",
+        ProxmoxCommand::Toggle => "shutdown",
+        ProxmoxCommand::Snapshot => {
+            let name = name.map_or_else(
+                || {
+                    chrono::Local::now()
+                        .format("deckd_%Y%m%d_%H%M%S")
+                        .to_string()
+                },
+                str::to_string,
+            );
+            api(Some(&[("snapname", &name)]), &format!("{base}/snapshot")).await?;
+            debug!("Proxmox {vmid}: snapshot {name}");
+            return Ok(());
+        }
+    };
+    api(Some(&[]), &format!("{base}/status/{status}")).await?;
+    debug!("Proxmox {vmid}: {status}");
+    Ok(())
+}
+
+/// The state of a `proxmox:<vmid>` entity: the guest's status, e.g.
+/// `running` or `stopped`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let vmid = address
+        .parse()
+        .map_err(|_| format!("expected a VM id, got '{address}'"))?;
+    guest(vmid)
+        .await
+        .map(|guest| guest.status)
+        .map_err(|e| e.user_message())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_guests_across_nodes() {
+        let resources = json!([
+            { "vmid": 100, "node": "pve1", "type": "qemu", "status": "running" },
+            { "vmid": 201, "node": "pve2", "type": "lxc", "status": "stopped" }
+        ]);
+        let guest = find(&resources, 201).unwrap();
+        assert_eq!((guest.node.as_str(), guest.kind.as_str()), ("pve2", "lxc"));
+        assert_eq!(guest.status, "stopped");
+        assert!(find(&resources, 300).is_none());
+        assert!(check_snapshot_name("before-upgrade").is_ok());
+        assert!(check_snapshot_name("1st").is_err());
+    }
+}
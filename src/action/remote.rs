@@ -0,0 +1,169 @@
+use crate::error::{DeckError, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+use tracing::debug;
+
+/// Timeout of a Roku keypress request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Port of Roku's External Control Protocol.
+const ROKU_PORT: u16 = 8060;
+
+/// Port of ADB over the network.
+const ADB_PORT: u16 = 5555;
+
+/// How a TV or streaming box is controlled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteKind {
+    /// Roku External Control Protocol over HTTP.
+    Roku,
+    /// `adb shell input keyevent` on Android TV / Fire TV, via the `adb` tool.
+    Adb,
+}
+
+/// A remote control button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteKey {
+    Power,
+    Home,
+    Back,
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    PlayPause,
+    Rewind,
+    Forward,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+}
+
+impl RemoteKey {
+    /// The key name in Roku's ECP.
+    #[must_use]
+    pub const fn roku(self) -> &'static str {
+        match self {
+            Self::Power => "Power",
+            Self::Home => "Home",
+            Self::Back => "Back",
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Select => "Select",
+            Self::PlayPause => "Play",
+            Self::Rewind => "Rev",
+            Self::Forward => "Fwd",
+            Self::VolumeUp => "VolumeUp",
+            Self::VolumeDown => "VolumeDown",
+            Self::Mute => "VolumeMute",
+        }
+    }
+
+    /// The Android key code.
+    #[must_use]
+    pub const fn android(self) -> &'static str {
+        match self {
+            Self::Power => "KEYCODE_POWER",
+            Self::Home => "KEYCODE_HOME",
+            Self::Back => "KEYCODE_BACK",
+            Self::Up => "KEYCODE_DPAD_UP",
+            Self::Down => "KEYCODE_DPAD_DOWN",
+            Self::Left => "KEYCODE_DPAD_LEFT",
+            Self::Right => "KEYCODE_DPAD_RIGHT",
+            Self::Select => "KEYCODE_DPAD_CENTER",
+            Self::PlayPause => "KEYCODE_MEDIA_PLAY_PAUSE",
+            Self::Rewind => "KEYCODE_MEDIA_REWIND",
+            Self::Forward => "KEYCODE_MEDIA_FAST_FORWARD",
+            Self::VolumeUp => "KEYCODE_VOLUME_UP",
+            Self::VolumeDown => "KEYCODE_VOLUME_DOWN",
+            Self::Mute => "KEYCODE_VOLUME_MUTE",
+        }
+    }
+}
+
+impl fmt::Display for RemoteKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.roku())
+    }
+}
+
+/// `host` with `port` appended unless it names one.
+fn with_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// Press `key` on the device at `host`.
+///
+/// # Errors
+/// Returns `DeckError::Http` or `DeckError::Action` if a Roku cannot be
+/// reached or rejects the key, and `DeckError::Io` or `DeckError::Shell` if
+/// `adb` cannot be run or fails.
+pub async fn press(kind: RemoteKind, host: &str, key: RemoteKey) -> Result<()> {
+    match kind {
+        RemoteKind::Roku => {
+            let url = format!(
+                "http://{}/keypress/{}",
+                with_port(host, ROKU_PORT),
+                key.roku()
+            );
+            let client = super::http::build_client(false, None, Some(TIMEOUT))?;
+            let resp = client.post(&url).send().await?;
+            let status = resp.status();
+            if !status.is_success() {
+                return Err(DeckError::Action(format!("Roku {host}: {key} → {status}")));
+            }
+            debug!("Roku {host}: {key}");
+            Ok(())
+        }
+        RemoteKind::Adb => {
+            let serial = with_port(host, ADB_PORT);
+            // Connecting is a no-op once connected, and reconnects after the
+            // device slept or the ADB server restarted.
+            adb(&["connect", &serial]).await?;
+            adb(&["-s", &serial, "shell", "input", "keyevent", key.android()]).await
+        }
+    }
+}
+
+async fn adb(args: &[&str]) -> Result<()> {
+    let output = tokio::process::Command::new("adb")
+        .args(args)
+        .envs(crate::privileges::env())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    // `adb connect` reports failures on stdout with a zero exit status.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && !stdout.starts_with("failed") && !stdout.contains("unable") {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(DeckError::Shell {
+        command: format!("adb {}", args.join(" ")),
+        message: format!("{}{}", stdout.trim(), stderr.trim()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_map_to_both_protocols() {
+        let key: RemoteKey = serde_json::from_str("\"play_pause\"").unwrap();
+        assert_eq!(key.roku(), "Play");
+        assert_eq!(key.android(), "KEYCODE_MEDIA_PLAY_PAUSE");
+        assert_eq!(with_port("tv.lan", ROKU_PORT), "tv.lan:8060");
+        assert_eq!(with_port("10.0.0.5:5556", ADB_PORT), "10.0.0.5:5556");
+    }
+}
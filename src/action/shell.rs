@@ -1,17 +1,98 @@
+use crate::config::schema::ShellSandbox;
 use crate::error::{DeckError, Result};
+use std::process::Stdio;
+use std::sync::{LazyLock, RwLock};
+use std::time::Duration;
 use tracing::{debug, warn};
 
-/// Execute a shell command via `/bin/sh -c`.
+/// The sandbox of commands without their own (`deckd.shell_sandbox`).
+static DEFAULT_SANDBOX: LazyLock<RwLock<Option<ShellSandbox>>> = LazyLock::new(RwLock::default);
+
+/// Use the default sandbox of a (re)loaded config.
+pub fn configure(sandbox: Option<&ShellSandbox>) {
+    DEFAULT_SANDBOX
+        .write()
+        .unwrap()
+        .clone_from(&sandbox.cloned());
+}
+
+/// Execute a shell command via `/bin/sh -c`, within the default sandbox
+/// tightened by `sandbox`, with the environment it allows. The shell runs in
+/// a process group of its own, which a timeout or dropping the future (e.g.
+/// when a tracked action is cancelled) kills along with everything in it.
 ///
 /// # Errors
 /// Returns `DeckError::Io` if the command cannot be spawned,
-/// or `DeckError::Shell` if it exits with a non-zero status.
-pub async fn execute(command: &str) -> Result<()> {
-    let output = tokio::process::Command::new("/bin/sh")
-        .arg("-c")
-        .arg(command)
-        .output()
-        .await?;
+/// or `DeckError::Shell` if it exits with a non-zero status or times out.
+pub async fn execute(command: &str, sandbox: Option<&ShellSandbox>) -> Result<()> {
+    let default = DEFAULT_SANDBOX.read().unwrap().clone().unwrap_or_default();
+    let sandbox = match sandbox {
+        Some(sandbox) => tighten(&default, sandbox),
+        None => default,
+    };
+    run(command, &sandbox).await
+}
+
+/// `floor` with the settings of `own` where they are stricter: the lower of
+/// each limit, only variables both allow, and `no_new_privileges` if either
+/// sets it. A command's own sandbox can never loosen the default one.
+fn tighten(floor: &ShellSandbox, own: &ShellSandbox) -> ShellSandbox {
+    fn lower(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            _ => a.or(b),
+        }
+    }
+    ShellSandbox {
+        timeout_secs: lower(floor.timeout_secs, own.timeout_secs),
+        memory_mb: lower(floor.memory_mb, own.memory_mb),
+        cpu_secs: lower(floor.cpu_secs, own.cpu_secs),
+        max_processes: lower(floor.max_processes, own.max_processes),
+        no_new_privileges: floor.no_new_privileges || own.no_new_privileges,
+        env: match (&floor.env, &own.env) {
+            (Some(floor), Some(own)) => {
+                Some(own.iter().filter(|v| floor.contains(v)).cloned().collect())
+            }
+            (floor, own) => floor.clone().or_else(|| own.clone()),
+        },
+    }
+}
+
+async fn run(command: &str, sandbox: &ShellSandbox) -> Result<()> {
+    let mut cmd = tokio::process::Command::new("/bin/sh");
+    cmd.arg("-c").arg(command).kill_on_drop(true);
+    let user_env = crate::privileges::env().map(|(name, value)| (name.into(), value.into()));
+    match sandbox.env {
+        Some(ref keep) => {
+            cmd.env_clear().envs(
+                std::env::vars_os()
+                    .chain(user_env)
+                    .filter(|(name, _)| name == "PATH" || keep.iter().any(|k| name == k.as_str())),
+            );
+        }
+        None => {
+            cmd.envs(user_env);
+        }
+    }
+    confine(&mut cmd, sandbox);
+
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut group = ProcessGroup(child.id());
+    let output = match sandbox.timeout_secs {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs), child.wait_with_output())
+            .await
+            .map_err(|_| DeckError::Shell {
+                command: command.to_string(),
+                message: format!("timed out after {secs}s"),
+            })??,
+        None => child.wait_with_output().await?,
+    };
+    // Finished on its own: whatever it left running in the background stays.
+    group.0 = None;
 
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -28,3 +109,145 @@ pub async fn execute(command: &str) -> Result<()> {
         })
     }
 }
+
+/// The process group of a running shell, killed when dropped.
+struct ProcessGroup(Option<u32>);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0.and_then(|id| libc::pid_t::try_from(id).ok()) {
+            // SAFETY: plain syscall; the group is the shell's own (see `confine`).
+            unsafe { libc::killpg(pgid, libc::SIGKILL) };
+        }
+    }
+}
+
+/// Start the shell in a session and process group of its own, with the
+/// resource limits and `no_new_privileges` of `sandbox`.
+fn confine(cmd: &mut tokio::process::Command, sandbox: &ShellSandbox) {
+    let limits: Vec<_> = [
+        (
+            libc::RLIMIT_AS,
+            sandbox.memory_mb.map(|mb| mb * 1024 * 1024),
+        ),
+        (libc::RLIMIT_CPU, sandbox.cpu_secs),
+        (libc::RLIMIT_NPROC, sandbox.max_processes),
+    ]
+    .into_iter()
+    .filter_map(|(resource, limit)| Some((resource, limit?)))
+    .collect();
+    let no_new_privileges = sandbox.no_new_privileges;
+    // SAFETY: between fork and exec only async-signal-safe calls are made
+    // (setsid, setrlimit, prctl), on data prepared before the fork.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for &(resource, limit) in &limits {
+                let rlimit = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+                if libc::setrlimit(resource, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if no_new_privileges && libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sandbox_limits_commands() {
+        let sandbox = ShellSandbox {
+            timeout_secs: Some(1),
+            ..ShellSandbox::default()
+        };
+        let err = execute("sleep 5", Some(&sandbox)).await.unwrap_err();
+        assert!(err.to_string().contains("command failed"), "{err}");
+
+        let sandbox = ShellSandbox {
+            cpu_secs: Some(7),
+            no_new_privileges: true,
+            ..ShellSandbox::default()
+        };
+        let check = "test \"$(ulimit -t)\" = 7 && grep -q 'NoNewPrivs:\\s*1' /proc/self/status";
+        assert!(execute(check, Some(&sandbox)).await.is_ok());
+        assert!(execute("exit 3", None).await.is_err());
+
+        let sandbox = ShellSandbox {
+            env: Some(vec!["LANG".into()]),
+            ..ShellSandbox::default()
+        };
+        let check = "test -n \"$PATH\" && test -z \"$HOME\" && test -z \"$CARGO\"";
+        assert!(execute(check, Some(&sandbox)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_what_the_command_started() {
+        let pidfile = std::env::temp_dir().join(format!("deckd-shell-{}.pid", std::process::id()));
+        let sandbox = ShellSandbox {
+            timeout_secs: Some(1),
+            ..ShellSandbox::default()
+        };
+        let command = format!("sleep 30 & echo $! > {}; wait", pidfile.display());
+        assert!(execute(&command, Some(&sandbox)).await.is_err());
+
+        let pid = std::fs::read_to_string(&pidfile).unwrap();
+        std::fs::remove_file(&pidfile).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        // Gone, or a zombie waiting for whoever adopted it.
+        let dead = || {
+            std::fs::read_to_string(&stat).map_or(true, |s| {
+                s.rsplit(')').next().unwrap().trim_start().starts_with('Z')
+            })
+        };
+        for _ in 0..20 {
+            if dead() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("sleep {} outlived the timeout", pid.trim());
+    }
+
+    #[tokio::test]
+    async fn own_sandbox_cannot_loosen_the_default() {
+        let default = ShellSandbox {
+            timeout_secs: Some(60),
+            memory_mb: Some(256),
+            no_new_privileges: true,
+            env: Some(vec!["HOME".into(), "LANG".into()]),
+            ..ShellSandbox::default()
+        };
+        let own = ShellSandbox {
+            timeout_secs: Some(5),
+            memory_mb: Some(1024),
+            ..ShellSandbox::default()
+        };
+        let sandbox = tighten(&default, &own);
+        assert_eq!(sandbox.timeout_secs, Some(5));
+        assert_eq!(sandbox.memory_mb, Some(256));
+        assert!(sandbox.no_new_privileges);
+        assert_eq!(sandbox.env, default.env);
+        let widen = ShellSandbox {
+            env: Some(vec!["LANG".into(), "HA_TOKEN".into()]),
+            ..ShellSandbox::default()
+        };
+        assert_eq!(tighten(&default, &widen).env, Some(vec!["LANG".into()]));
+
+        // A variable cargo sets for the tests stands in for a secret, so the
+        // process environment is left alone.
+        assert!(std::env::var_os("CARGO_MANIFEST_DIR").is_some());
+        let check = "test -z \"$CARGO_MANIFEST_DIR\"";
+        assert!(run(check, &sandbox).await.is_ok());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::error::{DeckError, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::debug;
+
+/// Timeout of a Snapcast JSON-RPC request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A Snapcast client, shown as a room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub id: String,
+    /// The client's configured name, else its host name.
+    pub name: String,
+    /// Id of the group the client plays in.
+    pub group: String,
+    /// Name of that group, may be empty.
+    pub group_name: String,
+    pub volume: u8,
+    pub muted: bool,
+    pub connected: bool,
+}
+
+/// What a `snapcast` action does to a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Change the volume by this many percent points.
+    Volume(i32),
+    ToggleMute,
+    /// Move the client into the group with this id or name, or the one
+    /// holding the client of this name.
+    Join(String),
+}
+
+impl Command {
+    /// The command of a `snapcast` action's fields, of which exactly one
+    /// must be set.
+    ///
+    /// # Errors
+    /// Returns a message if none or several are set.
+    pub fn from_fields(
+        volume: i32,
+        mute: bool,
+        group: Option<&str>,
+    ) -> std::result::Result<Self, String> {
+        match (volume, mute, group) {
+            (0, false, None) => Err("snapcast needs one of volume, mute and group".into()),
+            (change, false, None) => Ok(Self::Volume(change)),
+            (0, true, None) => Ok(Self::ToggleMute),
+            (0, false, Some(group)) => Ok(Self::Join(group.to_string())),
+            _ => Err("use only one of volume, mute and group".into()),
+        }
+    }
+}
+
+/// Every client of the server `status` (a `Server.GetStatus` result), in
+/// group order.
+#[must_use]
+pub fn rooms(status: &Value) -> Vec<Room> {
+    let groups = status["server"]["groups"].as_array().into_iter().flatten();
+    groups
+        .flat_map(|group| {
+            let clients = group["clients"].as_array().into_iter().flatten();
+            clients.map(move |client| {
+                let config = &client["config"];
+                let name = match config["name"].as_str() {
+                    Some(name) if !name.is_empty() => name,
+                    _ => client["host"]["name"].as_str().unwrap_or_default(),
+                };
+                let volume = config["volume"]["percent"].as_u64().unwrap_or(0).min(100);
+                Room {
+                    id: client["id"].as_str().unwrap_or_default().to_string(),
+                    name: name.to_string(),
+                    group: group["id"].as_str().unwrap_or_default().to_string(),
+                    group_name: group["name"].as_str().unwrap_or_default().to_string(),
+                    volume: u8::try_from(volume).unwrap_or(100),
+                    muted: config["volume"]["muted"].as_bool().unwrap_or(false),
+                    connected: client["connected"].as_bool().unwrap_or(false),
+                }
+            })
+        })
+        .collect()
+}
+
+/// The room with the client id or name `client`.
+fn find<'a>(rooms: &'a [Room], client: &str) -> Result<&'a Room> {
+    rooms
+        .iter()
+        .find(|room| room.id == client || room.name == client)
+        .ok_or_else(|| DeckError::Action(format!("Snapcast: no client '{client}'")))
+}
+
+/// The clients of the server at `server`, e.g. `http://snapserver:1780`.
+///
+/// # Errors
+/// Returns `DeckError::Http` if the server cannot be reached, or
+/// `DeckError::Action` if it answers with an error.
+pub async fn status(server: &str) -> Result<Vec<Room>> {
+    let status = rpc(server, "Server.GetStatus", json!({})).await?;
+    Ok(rooms(&status))
+}
+
+/// Run `command` on `client` (an id or name).
+///
+/// # Errors
+/// Returns the errors of [`status`], or `DeckError::Action` if the client
+/// or group does not exist.
+pub async fn execute(server: &str, client: &str, command: &Command) -> Result<()> {
+    let rooms = status(server).await?;
+    let room = find(&rooms, client)?;
+    match command {
+        Command::Volume(change) => {
+            let percent = (i32::from(room.volume) + change).clamp(0, 100);
+            set_volume(server, room, percent, room.muted).await
+        }
+        Command::ToggleMute => set_volume(server, room, i32::from(room.volume), !room.muted).await,
+        Command::Join(target) => {
+            let group = rooms
+                .iter()
+                .find(|r| r.group == *target || r.group_name == *target)
+                .or_else(|| find(&rooms, target).ok())
+                .map(|r| r.group.clone())
+                .ok_or_else(|| DeckError::Action(format!("Snapcast: no group '{target}'")))?;
+            if group == room.group {
+                return Ok(());
+            }
+            let clients: Vec<&str> = rooms
+                .iter()
+                .filter(|r| r.group == group || r.id == room.id)
+                .map(|r| r.id.as_str())
+                .collect();
+            rpc(
+                server,
+                "Group.SetClients",
+                json!({ "id": group, "clients": clients }),
+            )
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+async fn set_volume(server: &str, room: &Room, percent: i32, muted: bool) -> Result<()> {
+    let volume = json!({ "percent": percent, "muted": muted });
+    rpc(
+        server,
+        "Client.SetVolume",
+        json!({ "id": room.id, "volume": volume }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Call `method` on the server's HTTP JSON-RPC endpoint and return its result.
+async fn rpc(server: &str, method: &str, params: Value) -> Result<Value> {
+    let url = format!("{}/jsonrpc", server.trim_end_matches('/'));
+    let client = super::http::build_client(false, None, Some(TIMEOUT))?;
+    let request = json!({ "id": 1, "jsonrpc": "2.0", "method": method, "params": params });
+    let resp: Value = client
+        .post(&url)
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(error) = resp.get("error") {
+        let message = error["message"].as_str().unwrap_or("unknown error");
+        return Err(DeckError::Action(format!("Snapcast {method}: {message}")));
+    }
+    debug!("Snapcast {method}");
+    Ok(resp["result"].clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rooms_from_status() {
+        let status = json!({
+            "server": {
+                "groups": [{
+                    "id": "g1",
+                    "name": "",
+                    "clients": [{
+                        "id": "b8:27:eb:01",
+                        "connected": true,
+                        "config": { "name": "Kitchen", "volume": { "percent": 40, "muted": false } },
+                        "host": { "name": "pi-kitchen" }
+                    }, {
+                        "id": "b8:27:eb:02",
+                        "connected": false,
+                        "config": { "name": "", "volume": { "percent": 75, "muted": true } },
+                        "host": { "name": "pi-bath" }
+                    }]
+                }]
+            }
+        });
+        let rooms = rooms(&status);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0].name, "Kitchen");
+        assert_eq!(rooms[0].volume, 40);
+        assert_eq!(rooms[1].name, "pi-bath");
+        assert!(rooms[1].muted && !rooms[1].connected);
+        assert_eq!(find(&rooms, "b8:27:eb:02").unwrap().name, "pi-bath");
+        assert!(find(&rooms, "Garage").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::error::{DeckError, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use tracing::debug;
+
+/// Which VPN a `vpn` action or state entity controls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VpnKind {
+    Tailscale,
+    /// A `wg-quick` interface.
+    Wireguard,
+}
+
+/// Run `program` with `args` and return its standard output.
+async fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .envs(crate::privileges::env())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(DeckError::Shell {
+            command: format!("{program} {}", args.join(" ")),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether `tailscale status --json` says the node is connected, and the
+/// host name of the exit node it uses.
+fn tailscale_state(status: &Value) -> (bool, Option<&str>) {
+    let running = status["BackendState"] == "Running";
+    let exit_node = status["Peer"]
+        .as_object()
+        .into_iter()
+        .flat_map(|peers| peers.values())
+        .find(|peer| peer["ExitNode"] == true)
+        .and_then(|peer| peer["HostName"].as_str());
+    (running, exit_node)
+}
+
+async fn tailscale_status() -> Result<Value> {
+    let json = run("tailscale", &["status", "--json"]).await?;
+    serde_json::from_str(&json)
+        .map_err(|e| DeckError::Action(format!("tailscale status: invalid output: {e}")))
+}
+
+/// Whether the WireGuard interface `interface` is up.
+fn wireguard_up(interface: &str) -> bool {
+    Path::new("/sys/class/net").join(interface).exists()
+}
+
+/// The state of a `tailscale:<status|exit_node>` or `wireguard:<interface>`
+/// entity: `on` or `off`, or for `exit_node` the exit node's host name,
+/// `direct` without one, or `off`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(kind: VpnKind, address: &str) -> std::result::Result<String, String> {
+    let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+    match (kind, address) {
+        (VpnKind::Wireguard, interface) => Ok(on_off(wireguard_up(interface))),
+        (VpnKind::Tailscale, "status" | "exit_node") => {
+            let status = tailscale_status().await.map_err(|e| e.user_message())?;
+            let (running, exit_node) = tailscale_state(&status);
+            Ok(match (address, running, exit_node) {
+                ("status", running, _) => on_off(running),
+                (_, false, _) => "off".into(),
+                (_, true, Some(node)) => node.to_string(),
+                (_, true, None) => "direct".into(),
+            })
+        }
+        (VpnKind::Tailscale, _) => Err("expected tailscale:status or tailscale:exit_node".into()),
+    }
+}
+
+/// Bring the VPN up (`Some(true)`), down, or toggle it, and for Tailscale
+/// switch to `exit_node` (`""` to stop using one).
+///
+/// # Errors
+/// Returns `DeckError::Io` if the tool cannot be run, or `DeckError::Shell`
+/// if it fails (e.g. `wg-quick` without root).
+pub async fn execute(
+    kind: VpnKind,
+    interface: Option<&str>,
+    up: Option<bool>,
+    exit_node: Option<&str>,
+) -> Result<()> {
+    match kind {
+        VpnKind::Tailscale => {
+            if let Some(node) = exit_node {
+                run("tailscale", &["set", &format!("--exit-node={node}")]).await?;
+            }
+            let up = match up {
+                Some(up) => up,
+                None if exit_node.is_some() => return Ok(()),
+                None => !tailscale_state(&tailscale_status().await?).0,
+            };
+            run("tailscale", &[if up { "up" } else { "down" }]).await?;
+            debug!("tailscale {}", if up { "up" } else { "down" });
+        }
+        VpnKind::Wireguard => {
+            let interface = interface.ok_or_else(|| {
+                DeckError::Config("wireguard vpn action needs an interface".into())
+            })?;
+            let up = up.unwrap_or_else(|| !wireguard_up(interface));
+            run("wg-quick", &[if up { "up" } else { "down" }, interface]).await?;
+            debug!("wireguard {interface} {}", if up { "up" } else { "down" });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_tailscale_exit_node() {
+        let status = json!({
+            "BackendState": "Running",
+            "Peer": {
+                "nodekey:1": { "HostName": "nas", "ExitNode": false },
+                "nodekey:2": { "HostName": "vps-ams", "ExitNode": true }
+            }
+        });
+        assert_eq!(tailscale_state(&status), (true, Some("vps-ams")));
+        let stopped = json!({ "BackendState": "Stopped", "Peer": {} });
+        assert_eq!(tailscale_state(&stopped), (false, None));
+    }
+}
@@ -0,0 +1,44 @@
+//! Running as a Home Assistant add-on. The Supervisor hands the add-on a
+//! token for HA's API behind its proxy and forwards ingress requests from
+//! HA's frontend, so no `HA_URL`, `HA_TOKEN` or API setup is needed.
+
+use crate::config::schema::ApiConfig;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+/// HA's API as proxied by the Supervisor.
+pub const CORE_URL: &str = "http://supervisor/core";
+
+/// Config file in HA's config directory, mapped into the add-on.
+pub const CONFIG_FILE: &str = "/config/deckd/config.toml";
+
+/// Where the control API listens for ingress (`ingress_port` of the
+/// add-on's `config.yaml`).
+pub const INGRESS_LISTEN: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8099);
+
+/// The Supervisor's ingress proxy, the only peer the ingress API serves.
+/// HA has authenticated the user before forwarding.
+pub const INGRESS_GATEWAY: IpAddr = IpAddr::V4(Ipv4Addr::new(172, 30, 32, 2));
+
+/// The token the Supervisor passes to add-ons with `homeassistant_api`.
+#[must_use]
+pub fn supervisor_token() -> Option<String> {
+    std::env::var("SUPERVISOR_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+/// Whether deckd runs as an add-on.
+#[must_use]
+pub fn active() -> bool {
+    supervisor_token().is_some()
+}
+
+/// The control API served over ingress when the config has none.
+#[must_use]
+pub fn ingress_api() -> ApiConfig {
+    ApiConfig {
+        listen: INGRESS_LISTEN,
+        token: None,
+        announce: false,
+    }
+}
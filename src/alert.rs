@@ -0,0 +1,106 @@
+//! Whole-deck alerts (`[alerts]`): while an entity is in an alerting state,
+//! the deck shows the alert's page and/or blinks its flash pattern.
+
+use crate::config::schema::{AlertRule, AppConfig};
+use crate::event::DeckEvent;
+use crate::state::fetch_states;
+use arc_swap::ArcSwap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// How often alert entities are polled, and a pattern repeats while its
+/// alert holds.
+const ALERT_POLL: Duration = Duration::from_secs(5);
+
+/// The tripped alert with the highest priority (the first by name on a
+/// tie), if any.
+#[must_use]
+pub fn active<'a>(
+    alerts: &'a HashMap<String, AlertRule>,
+    states: &HashMap<String, String>,
+) -> Option<&'a str> {
+    alerts
+        .iter()
+        .filter(|(_, rule)| states.get(&rule.entity) == Some(&rule.state))
+        .max_by(|(a, x), (b, y)| x.priority.cmp(&y.priority).then_with(|| b.cmp(a)))
+        .map(|(name, _)| name.as_str())
+}
+
+/// Poll the alert entities and emit `Alert` when the active alert changes,
+/// and its `Flash` on every poll while it holds.
+pub async fn watch(
+    config: Arc<ArcSwap<AppConfig>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(ALERT_POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut current: Option<String> = None;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let cfg = config.load_full();
+        let alerts = &cfg.alerts;
+        let tripped = if alerts.is_empty() {
+            None
+        } else {
+            let entities: BTreeSet<&String> = alerts.values().map(|rule| &rule.entity).collect();
+            let entities: Vec<String> = entities.into_iter().cloned().collect();
+            active(alerts, &fetch_states(&entities).await).map(str::to_string)
+        };
+
+        if tripped != current {
+            match tripped {
+                Some(ref name) => info!("alert '{name}' tripped"),
+                None => info!("alert '{}' cleared", current.as_deref().unwrap_or_default()),
+            }
+            let _ = tx.send(DeckEvent::Alert(tripped.clone()));
+            current = tripped;
+        }
+        if let Some(pattern) = current
+            .as_ref()
+            .and_then(|name| alerts.get(name)?.pattern.clone())
+        {
+            let _ = tx.send(DeckEvent::Flash(pattern, None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highest_priority_tripped_alert_wins() {
+        let alerts: HashMap<String, AlertRule> = toml::from_str(
+            r#"
+smoke = { entity = "binary_sensor.smoke", state = "on", pattern = "red", priority = 10 }
+leak = { entity = "binary_sensor.leak", state = "on", page = "leak" }
+door = { entity = "binary_sensor.door", state = "on", page = "door" }
+"#,
+        )
+        .unwrap();
+        let states = |on: &[&str]| -> HashMap<String, String> {
+            ["smoke", "leak", "door"]
+                .iter()
+                .map(|e| {
+                    let state = if on.contains(e) { "on" } else { "off" };
+                    (format!("binary_sensor.{e}"), state.to_string())
+                })
+                .collect()
+        };
+        assert_eq!(active(&alerts, &states(&[])), None);
+        assert_eq!(active(&alerts, &states(&["leak"])), Some("leak"));
+        assert_eq!(active(&alerts, &states(&["leak", "smoke"])), Some("smoke"));
+        assert_eq!(active(&alerts, &states(&["leak", "door"])), Some("door"));
+        assert_eq!(active(&alerts, &HashMap::new()), None);
+    }
+}
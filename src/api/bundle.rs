@@ -0,0 +1,33 @@
+use super::{action_error, error, ApiState};
+use crate::bundle::Bundle;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Largest bundle accepted, icons included.
+pub(super) const MAX_SIZE: usize = 32 * 1024 * 1024;
+
+/// `PUT /config`: install a config bundle (a tar archive from `deckd push`)
+/// in place of the config. Only with a token, as it can change every action.
+pub(super) async fn install(State(state): State<ApiState>, body: Bytes) -> Response {
+    if state.token.is_none() {
+        return error(
+            StatusCode::FORBIDDEN,
+            "pushing a config needs a token in [deckd.api]".into(),
+        );
+    }
+    let bundle = match Bundle::from_tar(&body) {
+        Ok(bundle) => bundle,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let config_path = state.config_path.clone();
+    match tokio::task::spawn_blocking(move || bundle.install(&config_path)).await {
+        Ok(Ok(())) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) if e.kind() == crate::error::ErrorKind::Config => {
+            error(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        }
+        Ok(Err(e)) => action_error(&e),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
@@ -0,0 +1,21 @@
+use super::ApiState;
+use crate::event::DeckEvent;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct DiagnosticsRequest {
+    enabled: bool,
+}
+
+/// `PUT /diagnostics`: show each key's index, page and binding instead of the
+/// page. Key presses are logged but run no actions while enabled.
+pub(super) async fn set(
+    State(state): State<ApiState>,
+    Json(request): Json<DiagnosticsRequest>,
+) -> StatusCode {
+    let _ = state.tx.send(DeckEvent::Diagnostics(request.enabled));
+    StatusCode::NO_CONTENT
+}
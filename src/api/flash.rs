@@ -0,0 +1,44 @@
+use super::{error, ApiState};
+use crate::config::KEY_COUNT;
+use crate::event::DeckEvent;
+use crate::ids::KeyIndex;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct FlashRequest {
+    pattern: String,
+    /// The key to blink; the whole deck if unset.
+    #[serde(default)]
+    key: Option<u8>,
+}
+
+/// `POST /flash`: blink a key or the whole deck in a `[patterns]` pattern.
+pub(super) async fn start(
+    State(state): State<ApiState>,
+    Json(request): Json<FlashRequest>,
+) -> Response {
+    if !state.config.load().patterns.contains_key(&request.pattern) {
+        return error(
+            StatusCode::NOT_FOUND,
+            format!("unknown pattern '{}'", request.pattern),
+        );
+    }
+    let key = match request.key {
+        None => None,
+        Some(key) => match KeyIndex::new(key, KEY_COUNT) {
+            Some(key) => Some(key),
+            None => {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    format!("key {key} out of range (0-31)"),
+                )
+            }
+        },
+    };
+    let _ = state.tx.send(DeckEvent::Flash(request.pattern, key));
+    StatusCode::ACCEPTED.into_response()
+}
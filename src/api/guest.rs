@@ -0,0 +1,23 @@
+use super::ApiState;
+use crate::event::DeckEvent;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct GuestRequest {
+    enabled: bool,
+}
+
+/// `PUT /guest`: turn guest mode on or off.
+pub(super) async fn set(
+    State(state): State<ApiState>,
+    Json(request): Json<GuestRequest>,
+) -> StatusCode {
+    if state.config.load().deckd.guest.is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+    let _ = state.tx.send(DeckEvent::SetGuest(Some(request.enabled)));
+    StatusCode::NO_CONTENT
+}
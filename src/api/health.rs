@@ -0,0 +1,18 @@
+use super::ApiState;
+use crate::health::Report;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+
+/// `GET /healthz`: the health of the daemon's parts, with 503 if it is not
+/// healthy, for uptime probes.
+pub(super) async fn check(State(state): State<ApiState>) -> (StatusCode, Json<Report>) {
+    let connected = state.device.lock().unwrap().info.is_some();
+    let report = crate::health::report(connected);
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
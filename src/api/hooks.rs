@@ -0,0 +1,92 @@
+use super::{action_error, error, ApiState};
+use crate::action::ActionContext;
+use crate::event::DeckEvent;
+use crate::status::{Issue, IssueSource};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// `POST /hook/<name>`: run the action configured as `[hooks.<name>]`.
+///
+/// Top-level scalar fields of a JSON body are available to templated action
+/// fields as `{hook.<field>}`.
+pub(super) async fn trigger(
+    State(state): State<ApiState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Response {
+    let config = state.config.load_full();
+    let Some(action) = config.hooks.get(&name) else {
+        return error(StatusCode::NOT_FOUND, format!("unknown hook '{name}'"));
+    };
+    let vars = match payload_vars(&body) {
+        Ok(vars) => vars,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+
+    info!("webhook '{name}' triggered");
+    let ctx = ActionContext {
+        vars,
+        ..ActionContext::new(crate::render::locale::resolve(&config.deckd.locale))
+    };
+    match crate::action::execute(action, &state.tx, &ctx).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            warn!("webhook '{name}' failed: {e}");
+            let issue = Issue::new(
+                IssueSource::Action,
+                format!("hook {name}: {}", e.user_message()),
+            );
+            let _ = state.tx.send(DeckEvent::Issue(Issue {
+                kind: Some(e.kind()),
+                ..issue
+            }));
+            action_error(&e)
+        }
+    }
+}
+
+/// Placeholders from a JSON object body; an empty body has none.
+fn payload_vars(body: &[u8]) -> Result<HashMap<String, String>, String> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(HashMap::new());
+    }
+    let value: Value =
+        serde_json::from_slice(body).map_err(|e| format!("invalid JSON body: {e}"))?;
+    let Value::Object(fields) = value else {
+        return Err("body must be a JSON object".into());
+    };
+    Ok(fields
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => return None,
+            };
+            Some((format!("hook.{key}"), value))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_fields_become_placeholders() {
+        let vars =
+            payload_vars(br#"{"name": "Front door", "count": 2, "nested": {"x": 1}}"#).unwrap();
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars["hook.name"], "Front door");
+        assert_eq!(vars["hook.count"], "2");
+
+        assert!(payload_vars(b"").unwrap().is_empty());
+        assert!(payload_vars(b"[1, 2]").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use super::{error, ApiState};
+use crate::config::schema::KeyContent;
+use crate::config::KEY_COUNT;
+use crate::event::DeckEvent;
+use crate::ids::KeyIndex;
+use crate::overrides::OverrideSource;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use tracing::info;
+
+/// `PUT /keys/<key>`: show temporary content on a key until its TTL runs
+/// out or it is cleared.
+pub(super) async fn set(
+    State(state): State<ApiState>,
+    Path(key): Path<u8>,
+    Json(content): Json<KeyContent>,
+) -> Response {
+    let Some(key) = KeyIndex::new(key, KEY_COUNT) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("key {key} out of range (0-31)"),
+        );
+    };
+    info!("key {key} set via API");
+    let _ = state
+        .tx
+        .send(DeckEvent::SetKey(key, OverrideSource::Api, Some(content)));
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// `DELETE /keys/<key>`: remove the content set through the API, returning
+/// the key to any other override or its configured button.
+pub(super) async fn clear(State(state): State<ApiState>, Path(key): Path<u8>) -> Response {
+    let Some(key) = KeyIndex::new(key, KEY_COUNT) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("key {key} out of range (0-31)"),
+        );
+    };
+    info!("key {key} cleared via API");
+    let _ = state
+        .tx
+        .send(DeckEvent::SetKey(key, OverrideSource::Api, None));
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,254 @@
+mod bundle;
+mod diagnostics;
+mod flash;
+mod guest;
+mod health;
+mod hooks;
+mod keys;
+mod stats;
+mod toast;
+
+use crate::config::schema::AppConfig;
+use crate::device::{DeviceStatus, DeviceStatusHandle};
+use crate::error::{DeckError, ErrorKind, Result};
+use crate::event::DeckEvent;
+use crate::ids::PageId;
+use crate::usage::UsageStats;
+use arc_swap::ArcSwap;
+use axum::extract::{ConnectInfo, DefaultBodyLimit, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Handles the control API needs into the running daemon.
+#[derive(Clone)]
+pub struct ApiState {
+    pub device: DeviceStatusHandle,
+    /// Page currently shown, published by the event loop.
+    pub current_page: Arc<ArcSwap<PageId>>,
+    pub started: Instant,
+    pub config: Arc<ArcSwap<AppConfig>>,
+    pub usage: Arc<Mutex<UsageStats>>,
+    pub tx: broadcast::Sender<DeckEvent>,
+    /// Bearer token required on every request, if set.
+    pub token: Option<Arc<str>>,
+    /// The only address requests are taken from, if set (the add-on's
+    /// ingress proxy).
+    pub peer: Option<IpAddr>,
+    /// The config file a pushed bundle replaces.
+    pub config_path: PathBuf,
+}
+
+/// Serve the control API on `listen` until cancelled.
+///
+/// # Errors
+/// Returns `DeckError::Io` if the address cannot be bound.
+pub async fn serve(listen: SocketAddr, state: ApiState, cancel: CancellationToken) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("control API listening on {listen}");
+    let app = router(state).into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { cancel.cancelled().await })
+        .await?;
+    Ok(())
+}
+
+fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/status", get(status))
+        .route("/hook/{name}", post(hooks::trigger))
+        .route("/keys/{key}", put(keys::set).delete(keys::clear))
+        .route("/toast", post(toast::show))
+        .route("/flash", post(flash::start))
+        .route("/stats", get(stats::usage))
+        .route("/diagnostics", put(diagnostics::set))
+        .route("/guest", put(guest::set))
+        .route(
+            "/config",
+            put(bundle::install).layer(DefaultBodyLimit::max(bundle::MAX_SIZE)),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        // Open to probes without the token.
+        .route("/healthz", get(health::check))
+        .with_state(state)
+}
+
+/// Reject requests without the configured bearer token, or from other
+/// addresses than the configured peer.
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Some(peer) = state.peer {
+        let from = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip());
+        if from != Some(peer) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+    if let Some(ref token) = state.token {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !presented.is_some_and(|p| crate::secret::tokens_match(p, token)) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// A JSON `{"error": ...}` response.
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// The response for a failed action: the status follows the error kind,
+/// and the body says whether the caller may retry as is.
+fn action_error(e: &DeckError) -> Response {
+    let status = match e.kind() {
+        _ if e.is_retryable() => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::Network | ErrorKind::Action => StatusCode::BAD_GATEWAY,
+        ErrorKind::Device => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::Config | ErrorKind::Render | ErrorKind::Io => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let body = serde_json::json!({
+        "error": e.to_string(),
+        "kind": e.kind(),
+        "retryable": e.is_retryable(),
+    });
+    (status, Json(body)).into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct StatusResponse {
+    version: &'static str,
+    uptime_secs: u64,
+    page: String,
+    device: DeviceResponse,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct DeviceResponse {
+    connected: bool,
+    model: Option<String>,
+    serial: Option<String>,
+    firmware: Option<String>,
+    keys: Option<u8>,
+    /// Rows x columns.
+    layout: Option<String>,
+    key_size: Option<(usize, usize)>,
+    image_format: Option<String>,
+    /// Unix time the current connection was established.
+    connected_since: Option<u64>,
+    connection_uptime_secs: Option<u64>,
+    connects: u64,
+    failed_attempts: u64,
+    last_disconnect: Option<DisconnectResponse>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct DisconnectResponse {
+    reason: String,
+    /// Unix time of the disconnect.
+    at: u64,
+}
+
+async fn status(State(state): State<ApiState>) -> Json<StatusResponse> {
+    let device = device_response(&state.device.lock().unwrap(), SystemTime::now());
+    Json(StatusResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: state.started.elapsed().as_secs(),
+        page: state.current_page.load().to_string(),
+        device,
+    })
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn device_response(status: &DeviceStatus, now: SystemTime) -> DeviceResponse {
+    let info = status.info.as_ref();
+    DeviceResponse {
+        connected: info.is_some(),
+        model: info.map(|i| format!("{:?}", i.kind)),
+        serial: info.map(|i| i.serial.clone()),
+        firmware: info.and_then(|i| i.firmware.clone()),
+        keys: info.map(|i| i.keys),
+        layout: info.map(|i| format!("{}x{}", i.rows, i.columns)),
+        key_size: info.map(|i| i.key_size),
+        image_format: info.map(|i| i.image_format.clone()),
+        connected_since: status.connected_at.map(unix_secs),
+        connection_uptime_secs: status
+            .connected_at
+            .and_then(|at| now.duration_since(at).ok())
+            .map(|d| d.as_secs()),
+        connects: status.connects,
+        failed_attempts: status.failed_attempts,
+        last_disconnect: status.last_disconnect.as_ref().map(|d| DisconnectResponse {
+            reason: d.reason.clone(),
+            at: unix_secs(d.at),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Disconnect;
+    use std::time::Duration;
+
+    #[test]
+    fn disconnected_device_keeps_last_reason() {
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let status = DeviceStatus {
+            last_disconnect: Some(Disconnect {
+                reason: "device error: HID read failed".into(),
+                at,
+            }),
+            connects: 2,
+            failed_attempts: 5,
+            ..DeviceStatus::default()
+        };
+        let resp = device_response(&status, at + Duration::from_secs(60));
+        assert!(!resp.connected);
+        assert_eq!(resp.connection_uptime_secs, None);
+        assert_eq!(resp.connects, 2);
+        assert_eq!(
+            resp.last_disconnect,
+            Some(DisconnectResponse {
+                reason: "device error: HID read failed".into(),
+                at: 1_700_000_000,
+            })
+        );
+    }
+
+    #[test]
+    fn action_errors_map_to_status_by_kind() {
+        let shell = DeckError::Shell {
+            command: "false".into(),
+            message: "exit status 1".into(),
+        };
+        assert_eq!(action_error(&shell).status(), StatusCode::BAD_GATEWAY);
+        let config = DeckError::Config("bad".into());
+        assert_eq!(
+            action_error(&config).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            action_error(&DeckError::NoDevice).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use super::ApiState;
+use crate::config::schema::AppConfig;
+use crate::ids::{KeyIndex, PageId};
+use crate::usage::UsageStats;
+use axum::extract::State;
+use axum::Json;
+use serde::Serialize;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(super) struct ButtonStats {
+    page: PageId,
+    key: KeyIndex,
+    label: Option<String>,
+    presses: u64,
+    /// Unix time of the last press.
+    last_pressed: Option<u64>,
+}
+
+/// `GET /stats`: press counts of every configured button, most used first.
+/// Buttons never pressed are listed with 0 so dead keys stand out.
+pub(super) async fn usage(State(state): State<ApiState>) -> Json<Vec<ButtonStats>> {
+    let config = state.config.load();
+    Json(button_stats(&config, &state.usage.lock().unwrap()))
+}
+
+fn button_stats(config: &AppConfig, usage: &UsageStats) -> Vec<ButtonStats> {
+    let mut rows: Vec<ButtonStats> = config
+        .pages
+        .iter()
+        .flat_map(|(page_id, page)| {
+            page.buttons.iter().map(move |button| {
+                let counts = usage.get(page_id, button.key).cloned().unwrap_or_default();
+                ButtonStats {
+                    page: page_id.clone(),
+                    key: button.key,
+                    label: button.label.as_ref().map(|l| l.texts().join(" ")),
+                    presses: counts.presses,
+                    last_pressed: counts.last_pressed,
+                }
+            })
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.presses
+            .cmp(&a.presses)
+            .then_with(|| a.page.cmp(&b.page))
+            .then(a.key.cmp(&b.key))
+    });
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn lists_unpressed_buttons_last() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [deckd]
+            [pages.home]
+            name = "Home"
+            buttons = [{ key = 0, label = "Lights" }, { key = 1, label = "Fan" }]
+            "#,
+        )
+        .unwrap();
+        let mut usage = UsageStats::load(None);
+        usage.record("home", key(1), 100);
+
+        let rows = button_stats(&config, &usage);
+        assert_eq!(rows[0].key, 1);
+        assert_eq!(rows[0].presses, 1);
+        assert_eq!(rows[1].label.as_deref(), Some("Lights"));
+        assert_eq!(rows[1].presses, 0);
+        assert_eq!(rows[1].last_pressed, None);
+    }
+}
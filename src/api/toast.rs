@@ -0,0 +1,24 @@
+use super::{error, ApiState};
+use crate::event::DeckEvent;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ToastRequest {
+    message: String,
+}
+
+/// `POST /toast`: queue a notification banner across the top row.
+pub(super) async fn show(
+    State(state): State<ApiState>,
+    Json(request): Json<ToastRequest>,
+) -> Response {
+    if request.message.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "message is empty".into());
+    }
+    let _ = state.tx.send(DeckEvent::Toast(request.message));
+    StatusCode::ACCEPTED.into_response()
+}
@@ -0,0 +1,469 @@
+//! Config bundles: a config file with the icons it references, as one tar
+//! archive with a manifest of their SHA-256 hashes. `deckd pack` writes one
+//! to share a setup, `deckd push` sends one to the control API of a remote
+//! deckd, which checks it and installs it in place of its config, and a
+//! bundle can be given as the config itself.
+
+use crate::config::schema::AppConfig;
+use crate::error::{DeckError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+/// Name of the config file inside a bundle.
+pub const CONFIG_ENTRY: &str = "config.toml";
+
+/// Name of the manifest inside a bundle: the hash of every other entry.
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// File extension of bundles, which `config::load` reads the config of.
+pub const EXTENSION: &str = "tar";
+
+/// Directory next to the config where a bundle is unpacked and checked.
+const STAGING_DIR: &str = ".deckd-staging";
+
+/// Directory next to the config holding the files a bundle replaces until
+/// it is installed.
+const BACKUP_DIR: &str = ".deckd-backup";
+
+/// A config and the files it references, by path relative to the config
+/// directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bundle {
+    pub config: String,
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+/// The manifest of a bundle.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// SHA-256 (hex) of each entry by path.
+    files: BTreeMap<String, String>,
+}
+
+/// The SHA-256 of `data` in hex.
+fn sha256(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Whether `path` names a bundle rather than a TOML config.
+#[must_use]
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+/// Unpack the bundle at `path` into a new directory only this user can
+/// enter, under systemd's `RuntimeDirectory` or else the temporary
+/// directory, and return the path of its config there. The directory must
+/// not exist yet, so nobody can have prepared it or planted links in it.
+///
+/// # Errors
+/// Returns `DeckError::Io` if the archive cannot be read or unpacked, or
+/// `DeckError::Config` if it is not a valid bundle.
+pub fn extract(path: &Path) -> Result<PathBuf> {
+    let bundle = Bundle::from_tar(&std::fs::read(path)?)?;
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "bundle".into(), |s| s.to_string_lossy());
+    let base = std::env::var("RUNTIME_DIRECTORY")
+        .ok()
+        .and_then(|dirs| dirs.split(':').next().map(PathBuf::from))
+        .unwrap_or_else(std::env::temp_dir);
+    let dir = base.join(format!("deckd-{stem}-{:016x}", fastrand::u64(..)));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    bundle.write_to(&dir)
+}
+
+/// Whether `path` stays inside the directory it is relative to.
+fn is_contained(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_)))
+        && path.components().next().is_some()
+}
+
+/// Icons of `config` given relative to the config directory, the files
+/// a bundle carries. Absolute paths are left to the receiving host.
+#[must_use]
+pub fn assets(config: &AppConfig) -> Vec<PathBuf> {
+    let buttons = config
+        .pages
+        .values()
+        .flat_map(|page| &page.buttons)
+        .filter_map(|button| button.icon.as_deref());
+    let favorites = config
+        .pages
+        .values()
+        .filter_map(|page| page.media_browser.as_ref())
+        .flat_map(|media| &media.favorites)
+        .filter_map(|favorite| favorite.icon.as_deref());
+    let mut assets: Vec<PathBuf> = buttons
+        .chain(favorites)
+        .map(PathBuf::from)
+        .filter(|path| is_contained(path))
+        .collect();
+    assets.sort();
+    assets.dedup();
+    assets
+}
+
+impl Bundle {
+    /// The config at `config_path` and the icons it references.
+    ///
+    /// # Errors
+    /// Returns `DeckError` if the config is invalid or a referenced icon
+    /// cannot be read.
+    pub fn collect(config_path: &Path) -> Result<Self> {
+        let config = crate::config::load(config_path)?;
+        let dir = config_path.parent().unwrap_or(Path::new("."));
+        let mut files = BTreeMap::new();
+        for asset in assets(&config) {
+            let data = std::fs::read(dir.join(&asset))
+                .map_err(|e| DeckError::Config(format!("icon {}: {e}", asset.display())))?;
+            files.insert(asset, data);
+        }
+        Ok(Self {
+            config: std::fs::read_to_string(config_path)?,
+            files,
+        })
+    }
+
+    /// The entries of the bundle besides the manifest.
+    fn entries(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        std::iter::once((Path::new(CONFIG_ENTRY), self.config.as_bytes()))
+            .chain(self.files.iter().map(|(p, d)| (p.as_path(), d.as_slice())))
+    }
+
+    /// The bundle as a tar archive, its manifest first.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Io` if the archive cannot be written.
+    pub fn to_tar(&self) -> Result<Vec<u8>> {
+        let manifest = Manifest {
+            files: self
+                .entries()
+                .map(|(path, data)| (path.to_string_lossy().into_owned(), sha256(data)))
+                .collect(),
+        };
+        let manifest = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| DeckError::Config(format!("bundle manifest: {e}")))?;
+        let mut archive = tar::Builder::new(Vec::new());
+        let entries =
+            std::iter::once((Path::new(MANIFEST_ENTRY), manifest.as_slice())).chain(self.entries());
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            archive.append_data(&mut header, path, data)?;
+        }
+        Ok(archive.into_inner()?)
+    }
+
+    /// A bundle from a tar archive made by [`Bundle::to_tar`], with every
+    /// entry checked against the manifest.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Config` if the archive is malformed, has no
+    /// config or manifest, has paths leaving the config directory, or an
+    /// entry is missing from the manifest or does not match its hash.
+    pub fn from_tar(data: &[u8]) -> Result<Self> {
+        let invalid = |e: std::io::Error| DeckError::Config(format!("bundle: {e}"));
+        let mut archive = tar::Archive::new(data);
+        let mut manifest = None;
+        let mut config = None;
+        let mut files = BTreeMap::new();
+        for entry in archive.entries().map_err(invalid)? {
+            let mut entry = entry.map_err(invalid)?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path().map_err(invalid)?.into_owned();
+            if !is_contained(&path) {
+                return Err(DeckError::Config(format!(
+                    "bundle: path {} leaves the config directory",
+                    path.display()
+                )));
+            }
+            let mut content = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut content).map_err(invalid)?;
+            if path == Path::new(MANIFEST_ENTRY) {
+                let parsed: Manifest = serde_json::from_slice(&content)
+                    .map_err(|e| DeckError::Config(format!("bundle manifest: {e}")))?;
+                manifest = Some(parsed);
+                continue;
+            }
+            let Some(manifest) = &manifest else {
+                return Err(DeckError::Config(format!(
+                    "bundle: no {MANIFEST_ENTRY} before {}",
+                    path.display()
+                )));
+            };
+            if manifest.files.get(&*path.to_string_lossy()) != Some(&sha256(&content)) {
+                return Err(DeckError::Config(format!(
+                    "bundle: {} does not match the manifest",
+                    path.display()
+                )));
+            }
+            if path == Path::new(CONFIG_ENTRY) {
+                config = Some(
+                    String::from_utf8(content)
+                        .map_err(|_| DeckError::Config("bundle: config is not UTF-8".into()))?,
+                );
+            } else {
+                files.insert(path, content);
+            }
+        }
+        let config =
+            config.ok_or_else(|| DeckError::Config(format!("bundle: no {CONFIG_ENTRY}")))?;
+        let bundle = Self { config, files };
+        let listed = manifest.map_or(0, |m| m.files.len());
+        if listed != bundle.entries().count() {
+            return Err(DeckError::Config(
+                "bundle: entries listed in the manifest are missing".into(),
+            ));
+        }
+        Ok(bundle)
+    }
+
+    /// Write the bundle's config (as `config.toml`) and files into `dir`,
+    /// returning the path of the config. Files are only ever created, never
+    /// written through an existing file or symlink.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Io` if a file cannot be written or already
+    /// exists.
+    pub fn write_to(&self, dir: &Path) -> Result<PathBuf> {
+        for (path, data) in self.entries() {
+            let target = dir.join(path);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o644)
+                .custom_flags(libc::O_NOFOLLOW)
+                .open(&target)?
+                .write_all(data)?;
+        }
+        Ok(dir.join(CONFIG_ENTRY))
+    }
+
+    /// Replace the config at `config_path` and the bundle's files with the
+    /// bundle's, once its config has loaded and found every icon, and every
+    /// file is one of those icons: nothing else in the config directory
+    /// (secrets, other configs, ...) can be overwritten. The files
+    /// are moved into place one by one, the config last so the watcher
+    /// reloads it with its icons present; if one fails, those already
+    /// moved are put back.
+    ///
+    /// # Errors
+    /// Returns `DeckError` if the bundle's config is invalid, references
+    /// missing icons or does not reference a file, or a file cannot be
+    /// written.
+    pub fn install(&self, config_path: &Path) -> Result<()> {
+        let dir = config_path.parent().unwrap_or(Path::new("."));
+        let name = PathBuf::from(config_path.file_name().unwrap_or("config.toml".as_ref()));
+        let staging = dir.join(STAGING_DIR);
+        let backup = dir.join(BACKUP_DIR);
+        for scratch in [&staging, &backup] {
+            if scratch.exists() {
+                std::fs::remove_dir_all(scratch)?;
+            }
+        }
+
+        let mut order: Vec<(&Path, &[u8])> = self
+            .files
+            .iter()
+            .map(|(p, d)| (p.as_path(), d.as_slice()))
+            .collect();
+        order.push((&name, self.config.as_bytes()));
+        for (path, data) in &order {
+            let staged = staging.join(path);
+            if let Some(parent) = staged.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(staged, data)?;
+        }
+
+        let checked = crate::config::load(&staging.join(&name)).and_then(|config| {
+            let icons = assets(&config);
+            if let Some(path) = self
+                .files
+                .keys()
+                .find(|p| !icons.contains(p) || **p == name)
+            {
+                return Err(DeckError::Config(format!(
+                    "bundle: {} is not an icon of its config",
+                    path.display()
+                )));
+            }
+            match crate::config::missing_icons(&config, &staging).first() {
+                Some((page, key, path)) => Err(DeckError::Config(format!(
+                    "page '{page}', key {key}: missing icon {}",
+                    path.display()
+                ))),
+                None => Ok(()),
+            }
+        });
+        if let Err(e) = checked {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+
+        let mut moved: Vec<(&Path, bool)> = Vec::new();
+        let result = order.iter().try_for_each(|(path, _)| {
+            let target = dir.join(path);
+            let existed = target.exists();
+            if existed {
+                let saved = backup.join(path);
+                std::fs::create_dir_all(saved.parent().unwrap_or(&backup))?;
+                std::fs::rename(&target, saved)?;
+            } else if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            moved.push((path, existed));
+            std::fs::rename(staging.join(path), &target)
+        });
+        if let Err(e) = result {
+            for (path, existed) in moved.into_iter().rev() {
+                let target = dir.join(path);
+                if existed {
+                    let _ = std::fs::rename(backup.join(path), &target);
+                } else {
+                    let _ = std::fs::remove_file(&target);
+                }
+            }
+            return Err(DeckError::Config(format!("installing bundle: {e}")));
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+        let _ = std::fs::remove_dir_all(&backup);
+        info!(
+            "installed config bundle: {} with {} file(s)",
+            config_path.display(),
+            self.files.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[deckd]
+
+[[pages.home.buttons]]
+key = 0
+label = "Lamp"
+icon = "icons/lamp.png"
+"#;
+
+    #[test]
+    fn bundles_round_trip_and_install() {
+        let dir = std::env::temp_dir().join(format!("deckd-bundle-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.toml");
+        std::fs::write(&config_path, "# old\n").unwrap();
+
+        let bundle = Bundle {
+            config: CONFIG.into(),
+            files: BTreeMap::from([(PathBuf::from("icons/lamp.png"), vec![1, 2, 3])]),
+        };
+        let unpacked = Bundle::from_tar(&bundle.to_tar().unwrap()).unwrap();
+        assert_eq!(unpacked, bundle);
+
+        // Without its icon the bundle is refused and nothing changes.
+        let incomplete = Bundle {
+            config: CONFIG.into(),
+            files: BTreeMap::new(),
+        };
+        assert!(incomplete.install(&config_path).is_err());
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), "# old\n");
+
+        // Nor can it bring files its config does not use.
+        let mut stray = bundle.clone();
+        stray
+            .files
+            .insert(PathBuf::from("api_token"), b"mine".to_vec());
+        assert!(stray.install(&config_path).is_err());
+        assert!(!dir.join("api_token").exists());
+
+        bundle.install(&config_path).unwrap();
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), CONFIG);
+        assert_eq!(
+            std::fs::read(dir.join("icons/lamp.png")).unwrap(),
+            [1, 2, 3]
+        );
+        assert!(!dir.join(STAGING_DIR).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unpacks_only_into_new_files() {
+        let bundle = Bundle {
+            config: CONFIG.into(),
+            files: BTreeMap::from([(PathBuf::from("icons/lamp.png"), vec![1, 2, 3])]),
+        };
+        let archive =
+            std::env::temp_dir().join(format!("deckd-extract-{}.tar", std::process::id()));
+        std::fs::write(&archive, bundle.to_tar().unwrap()).unwrap();
+        let config = extract(&archive).unwrap();
+        let dir = config.parent().unwrap();
+        let mode = std::os::unix::fs::PermissionsExt::mode(&dir.metadata().unwrap().permissions());
+        assert_eq!(mode & 0o777, 0o700);
+        let again = extract(&archive).unwrap();
+        assert_ne!(again, config);
+        std::fs::remove_dir_all(again.parent().unwrap()).unwrap();
+
+        // A planted link is not written through.
+        std::fs::remove_file(&config).unwrap();
+        let victim = dir.join("victim");
+        std::fs::write(&victim, "keep").unwrap();
+        std::os::unix::fs::symlink(&victim, &config).unwrap();
+        std::fs::remove_file(dir.join("icons/lamp.png")).unwrap();
+        assert!(bundle.write_to(dir).is_err());
+        assert_eq!(std::fs::read_to_string(&victim).unwrap(), "keep");
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_file(&archive).unwrap();
+    }
+
+    #[test]
+    fn refuses_tampered_bundles() {
+        let bundle = Bundle {
+            config: CONFIG.into(),
+            files: BTreeMap::from([(PathBuf::from("icons/lamp.png"), vec![1, 2, 3])]),
+        };
+        let archive = bundle.to_tar().unwrap();
+        let at = archive.windows(3).position(|w| w == [1, 2, 3]).unwrap();
+        let mut tampered = archive.clone();
+        tampered[at] = 9;
+        let err = Bundle::from_tar(&tampered).unwrap_err().to_string();
+        assert!(err.contains("icons/lamp.png does not match"), "{err}");
+
+        // An archive without a manifest is not a bundle.
+        let mut plain = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(CONFIG.len() as u64);
+        plain
+            .append_data(&mut header, CONFIG_ENTRY, CONFIG.as_bytes())
+            .unwrap();
+        assert!(Bundle::from_tar(&plain.into_inner().unwrap()).is_err());
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_config_dir() {
+        assert!(is_contained(Path::new("icons/a.png")));
+        assert!(!is_contained(Path::new("../a.png")));
+        assert!(!is_contained(Path::new("/etc/passwd")));
+        assert!(!is_contained(Path::new("")));
+    }
+}
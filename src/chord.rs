@@ -0,0 +1,111 @@
+use crate::config::schema::{ActionConfig, ChordConfig};
+use crate::ids::{KeyIndex, PageId};
+
+/// What a key press means once chords are taken into account.
+#[derive(Debug, PartialEq)]
+pub enum Down<'a> {
+    /// An ordinary press: run the button's action now.
+    Press,
+    /// A modifier went down: wait for a chord key or the release.
+    Hold,
+    /// A chord was completed.
+    Chord(&'a ActionConfig),
+}
+
+#[derive(Debug)]
+struct Held {
+    key: KeyIndex,
+    page: PageId,
+    /// A chord was completed while this key was held.
+    used: bool,
+}
+
+/// Modifier keys currently held down.
+#[derive(Debug, Default)]
+pub struct ChordState {
+    held: Vec<Held>,
+}
+
+impl ChordState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A key went down on `page_id`, which defines `chords`.
+    pub fn down<'a>(
+        &mut self,
+        page_id: &PageId,
+        chords: &'a [ChordConfig],
+        key: KeyIndex,
+    ) -> Down<'a> {
+        for held in self.held.iter_mut().filter(|h| h.page == *page_id) {
+            if let Some(chord) = chords
+                .iter()
+                .find(|c| c.modifier == held.key && c.key == key)
+            {
+                held.used = true;
+                return Down::Chord(&chord.on_press);
+            }
+        }
+        if chords.iter().any(|c| c.modifier == key) {
+            self.held.push(Held {
+                key,
+                page: page_id.clone(),
+                used: false,
+            });
+            return Down::Hold;
+        }
+        Down::Press
+    }
+
+    /// A key was released. Returns the page of a modifier released without
+    /// completing a chord: its button's own action is due now.
+    pub fn up(&mut self, key: KeyIndex) -> Option<PageId> {
+        let index = self.held.iter().position(|h| h.key == key)?;
+        let held = self.held.remove(index);
+        (!held.used).then_some(held.page)
+    }
+
+    /// Forget held keys, e.g. when the device disconnects mid-press.
+    pub fn clear(&mut self) {
+        self.held.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    fn chords() -> Vec<ChordConfig> {
+        vec![ChordConfig {
+            modifier: key(0),
+            key: key(4),
+            on_press: ActionConfig::Home,
+        }]
+    }
+
+    #[test]
+    fn modifier_runs_chord_or_own_action() {
+        let chords = chords();
+        let mut state = ChordState::new();
+        let home = PageId::from("home");
+
+        assert_eq!(state.down(&home, &chords, key(0)), Down::Hold);
+        assert_eq!(
+            state.down(&home, &chords, key(4)),
+            Down::Chord(&ActionConfig::Home)
+        );
+        assert_eq!(state.up(key(4)), None);
+        assert_eq!(state.up(key(0)), None);
+
+        // Tapped alone, the modifier's own action runs on release.
+        assert_eq!(state.down(&home, &chords, key(0)), Down::Hold);
+        assert_eq!(state.up(key(0)).as_deref(), Some("home"));
+
+        // Without the modifier held, the chord key is an ordinary press.
+        assert_eq!(state.down(&home, &chords, key(4)), Down::Press);
+        assert_eq!(state.down(&"other".into(), &[], key(0)), Down::Press);
+    }
+}
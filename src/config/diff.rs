@@ -0,0 +1,236 @@
+use crate::config::schema::{AppConfig, ButtonConfig, DeckdConfig, PageConfig};
+use crate::ids::KeyIndex;
+use std::collections::BTreeMap;
+
+/// Summarize what changed between two configs, one entry per affected area
+/// (e.g. "page 'home': changed keys 3, 7"). Empty if nothing changed.
+#[must_use]
+pub fn summarize(old: &AppConfig, new: &AppConfig) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    let settings = changed_settings(&old.deckd, &new.deckd);
+    if !settings.is_empty() {
+        changes.push(format!("deckd: {}", settings.join(", ")));
+    }
+
+    let (added, removed, changed) = diff_maps(&old.themes, &new.themes);
+    push_list(&mut changes, "themes added", &added);
+    push_list(&mut changes, "themes removed", &removed);
+    push_list(&mut changes, "themes changed", &changed);
+
+    let (added, removed, changed) = diff_maps(&old.hooks, &new.hooks);
+    push_list(&mut changes, "hooks added", &added);
+    push_list(&mut changes, "hooks removed", &removed);
+    push_list(&mut changes, "hooks changed", &changed);
+
+    let (added, removed, changed) = diff_maps(&old.devices, &new.devices);
+    push_list(&mut changes, "devices added", &added);
+    push_list(&mut changes, "devices removed", &removed);
+    push_list(&mut changes, "devices changed", &changed);
+
+    let (added, removed, changed) = diff_maps(&old.alerts, &new.alerts);
+    push_list(&mut changes, "alerts added", &added);
+    push_list(&mut changes, "alerts removed", &removed);
+    push_list(&mut changes, "alerts changed", &changed);
+
+    let (added, removed, changed) = diff_maps(&old.pages, &new.pages);
+    push_list(&mut changes, "pages added", &added);
+    push_list(&mut changes, "pages removed", &removed);
+    for page_id in changed {
+        changes.push(format!(
+            "page '{page_id}': {}",
+            page_changes(&old.pages[page_id], &new.pages[page_id]).join("; ")
+        ));
+    }
+
+    changes
+}
+
+/// Names of the `[deckd]` settings that differ.
+fn changed_settings(old: &DeckdConfig, new: &DeckdConfig) -> Vec<&'static str> {
+    [
+        (
+            "brightness",
+            old.brightness != new.brightness
+                || old.brightness_ramp_ms != new.brightness_ramp_ms
+                || old.brightness_schedule != new.brightness_schedule,
+        ),
+        (
+            "reconnect",
+            old.reconnect_interval_ms != new.reconnect_interval_ms
+                || old.reconnect_max_interval_ms != new.reconnect_max_interval_ms
+                || old.reconnect_hook_after != new.reconnect_hook_after
+                || old.on_reconnect_failed != new.on_reconnect_failed,
+        ),
+        (
+            "startup_grace_ms",
+            old.startup_grace_ms != new.startup_grace_ms,
+        ),
+        ("home_page", old.home_page != new.home_page),
+        ("defaults", old.defaults != new.defaults),
+        (
+            "presence_entity",
+            old.presence_entity != new.presence_entity,
+        ),
+        ("quiet", old.quiet != new.quiet),
+        ("guest", old.guest != new.guest),
+        ("policy", old.policy != new.policy),
+        ("proxy", old.proxy != new.proxy),
+        ("dns", old.dns != new.dns),
+        ("prerender", old.prerender != new.prerender),
+        ("transition", old.transition != new.transition),
+        ("theme", old.theme != new.theme),
+        ("locale", old.locale != new.locale),
+        ("status", old.status != new.status),
+        ("ups", old.ups != new.ups),
+        ("api (needs restart)", old.api != new.api),
+        (
+            "user (needs restart)",
+            old.user != new.user || old.group != new.group,
+        ),
+        (
+            "device selection (needs restart)",
+            old.device_serial != new.device_serial || old.device_kind != new.device_kind,
+        ),
+        ("splash", old.splash != new.splash),
+        ("toast", old.toast != new.toast),
+        ("flush", old.flush != new.flush),
+        ("calibration", old.calibration != new.calibration),
+        ("feedback", old.feedback != new.feedback),
+        ("high_contrast", old.high_contrast != new.high_contrast),
+        (
+            "usage_file (needs restart)",
+            old.usage_file != new.usage_file,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect()
+}
+
+fn page_changes(old: &PageConfig, new: &PageConfig) -> Vec<String> {
+    let mut changes = Vec::new();
+    if old.name != new.name {
+        changes.push(format!("renamed to '{}'", new.name));
+    }
+    if old.brightness != new.brightness {
+        changes.push("brightness changed".to_string());
+    }
+    if old.chords != new.chords {
+        changes.push("chords changed".to_string());
+    }
+    if old.dials != new.dials {
+        changes.push("dials changed".to_string());
+    }
+    if old.pin != new.pin {
+        changes.push("pin changed".to_string());
+    }
+
+    let (added, removed, changed) = diff_maps(by_key(old), by_key(new));
+    for (label, keys) in [
+        ("added keys", added),
+        ("removed keys", removed),
+        ("changed keys", changed),
+    ] {
+        if !keys.is_empty() {
+            let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+            changes.push(format!("{label} {}", keys.join(", ")));
+        }
+    }
+    changes
+}
+
+fn by_key(page: &PageConfig) -> BTreeMap<KeyIndex, &ButtonConfig> {
+    let mut buttons = BTreeMap::new();
+    for button in &page.buttons {
+        buttons.entry(button.key).or_insert(button);
+    }
+    buttons
+}
+
+/// Keys only in `new`, only in `old`, and in both with different values, sorted.
+fn diff_maps<K: Ord + Copy, V: PartialEq>(
+    old: impl IntoIterator<Item = (K, V)>,
+    new: impl IntoIterator<Item = (K, V)>,
+) -> (Vec<K>, Vec<K>, Vec<K>) {
+    let old: BTreeMap<K, V> = old.into_iter().collect();
+    let new: BTreeMap<K, V> = new.into_iter().collect();
+    let added = new
+        .keys()
+        .filter(|k| !old.contains_key(k))
+        .copied()
+        .collect();
+    let removed = old
+        .keys()
+        .filter(|k| !new.contains_key(k))
+        .copied()
+        .collect();
+    let changed = old
+        .iter()
+        .filter(|(k, v)| new.get(k).is_some_and(|n| n != *v))
+        .map(|(k, _)| *k)
+        .collect();
+    (added, removed, changed)
+}
+
+fn push_list(changes: &mut Vec<String>, label: &str, names: &[impl AsRef<str>]) {
+    if !names.is_empty() {
+        let names: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
+        changes.push(format!("{label}: {}", names.join(", ")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> AppConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn summarizes_page_and_button_changes() {
+        let old = parse(
+            r##"
+            [deckd]
+            [pages.home]
+            name = "Home"
+            buttons = [
+                { key = 0, label = "A" },
+                { key = 1, label = "B" },
+                { key = 2, label = "C" },
+            ]
+            [pages.old]
+            name = "Old"
+            "##,
+        );
+        let new = parse(
+            r##"
+            [deckd]
+            brightness = 40
+            [deckd.defaults]
+            background = "#000000"
+            [pages.home]
+            name = "Home"
+            buttons = [
+                { key = 0, label = "A" },
+                { key = 1, label = "B2" },
+                { key = 5, label = "E" },
+            ]
+            [pages.media]
+            name = "Media"
+            "##,
+        );
+
+        assert_eq!(
+            summarize(&old, &new),
+            [
+                "deckd: brightness, defaults",
+                "pages added: media",
+                "pages removed: old",
+                "page 'home': added keys 5; removed keys 2; changed keys 1",
+            ]
+        );
+        assert!(summarize(&new, &new).is_empty());
+    }
+}
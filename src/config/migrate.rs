@@ -0,0 +1,134 @@
+use crate::error::{DeckError, Result};
+use toml::Table;
+
+/// Schema version written by this release. Configs without `version` are
+/// treated as version 1.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// A documented schema change that upgrades a config from `to - 1` to `to`.
+///
+/// `apply` rewrites the raw table (e.g. moves a renamed key) or, when a
+/// change cannot be migrated automatically, returns a `DeckError::Config`
+/// naming the old field and what to write instead.
+pub struct Migration {
+    pub to: u32,
+    /// One line for the changelog and the upgrade warning.
+    pub note: &'static str,
+    pub apply: fn(&mut Table) -> Result<()>,
+}
+
+/// Migrations in version order. Add one (and bump `CURRENT_VERSION`)
+/// whenever a field is renamed, moved or changes meaning.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// Bring a raw config table up to `CURRENT_VERSION`.
+///
+/// Returns the notes of the migrations that ran; empty if the config was
+/// already current and the table is unchanged.
+///
+/// # Errors
+/// Returns `DeckError::Config` if the version is invalid or newer than this
+/// build understands, or if a migration needs a manual change.
+pub fn migrate(table: &mut Table) -> Result<Vec<&'static str>> {
+    apply(table, CURRENT_VERSION, MIGRATIONS)
+}
+
+fn apply(table: &mut Table, current: u32, migrations: &[Migration]) -> Result<Vec<&'static str>> {
+    let version = match table.get("version") {
+        None => 1,
+        Some(value) => value
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|&v| v >= 1)
+            .ok_or_else(|| {
+                DeckError::Config(format!("version: expected a positive integer, got {value}"))
+            })?,
+    };
+    if version > current {
+        return Err(DeckError::Config(format!(
+            "config version {version} is newer than this deckd supports ({current}); upgrade deckd"
+        )));
+    }
+
+    let mut applied = Vec::new();
+    for migration in migrations.iter().filter(|m| m.to > version) {
+        (migration.apply)(table).map_err(|e| match e {
+            DeckError::Config(msg) => DeckError::Config(format!(
+                "upgrading config to version {}: {msg}",
+                migration.to
+            )),
+            other => other,
+        })?;
+        applied.push(migration.note);
+    }
+    if !applied.is_empty() {
+        table.insert("version".into(), toml::Value::Integer(current.into()));
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_font_size(table: &mut Table) -> Result<()> {
+        if let Some(defaults) = table
+            .get_mut("deckd")
+            .and_then(|d| d.get_mut("defaults"))
+            .and_then(toml::Value::as_table_mut)
+        {
+            if let Some(size) = defaults.remove("size") {
+                defaults.insert("font_size".into(), size);
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_legacy(table: &mut Table) -> Result<()> {
+        if table.contains_key("legacy") {
+            return Err(DeckError::Config(
+                "[legacy] was removed; move its buttons to [pages.home]".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            to: 2,
+            note: "deckd.defaults.size renamed to font_size",
+            apply: rename_font_size,
+        },
+        Migration {
+            to: 3,
+            note: "[legacy] removed",
+            apply: drop_legacy,
+        },
+    ];
+
+    #[test]
+    fn runs_pending_migrations() {
+        let mut table: Table = toml::from_str("[deckd.defaults]\nsize = 18\n").unwrap();
+        let notes = apply(&mut table, 3, TEST_MIGRATIONS).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(table["version"].as_integer(), Some(3));
+        assert_eq!(
+            table["deckd"]["defaults"]["font_size"].as_integer(),
+            Some(18)
+        );
+
+        let mut current: Table = toml::from_str("version = 3\n").unwrap();
+        assert!(apply(&mut current, 3, TEST_MIGRATIONS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_and_unmigratable_configs() {
+        let mut newer: Table = toml::from_str("version = 4\n").unwrap();
+        let err = apply(&mut newer, 3, TEST_MIGRATIONS).unwrap_err();
+        assert!(err.to_string().contains("upgrade deckd"));
+
+        let mut legacy: Table = toml::from_str("version = 2\n[legacy]\n").unwrap();
+        let err = apply(&mut legacy, 3, TEST_MIGRATIONS).unwrap_err();
+        assert!(err.to_string().contains("version 3: [legacy] was removed"));
+    }
+}
@@ -1,29 +1,238 @@
+pub mod diff;
+pub mod migrate;
+pub mod position;
 pub mod schema;
 pub mod watcher;
 
+use crate::action::proxmox::ProxmoxCommand;
+use crate::action::vpn::VpnKind;
 use crate::error::{DeckError, Result};
-use schema::AppConfig;
-use std::path::Path;
+use crate::ids::{KeyIndex, PageId};
+use crate::render::splash::Grid;
+use position::KeyPos;
+use schema::{ActionConfig, AppConfig, ButtonConfig, ContrastPreset};
+use std::path::{Path, PathBuf};
+
+/// Keys on the largest Stream Deck (the XL), which config keys are checked
+/// against. Keys beyond the connected model's are reported on connect.
+pub const KEY_COUNT: u8 = 32;
+
+/// Dials on a Stream Deck Plus, the only model with them.
+const MAX_DIALS: u8 = 4;
+
+/// Grid of a Stream Deck MK.2, which `pos` is resolved against until a
+/// deck connects.
+pub const DEFAULT_GRID: Grid = Grid {
+    columns: 5,
+    rows: 3,
+};
 
 /// Load and parse configuration from a TOML file.
 ///
 /// # Errors
 /// Returns `DeckError::ConfigNotFound` if the file doesn't exist,
 /// `DeckError::Io` on read errors, `DeckError::TomlParse` on syntax errors,
-/// or `DeckError::Config` on validation failures.
+/// or `DeckError::Config` on validation failures or an unsupported version.
 pub fn load(path: &Path) -> Result<AppConfig> {
     if !path.exists() {
         return Err(DeckError::ConfigNotFound(path.to_path_buf()));
     }
-
-    let content = std::fs::read_to_string(path)?;
+    // A bundle's config is read from the archive; unpacking it (for its
+    // icons) is up to the caller.
+    let content = if crate::bundle::is_bundle(path) {
+        crate::bundle::Bundle::from_tar(&std::fs::read(path)?)?.config
+    } else {
+        std::fs::read_to_string(path)?
+    };
     let content = expand_env_vars(&content);
-    let config: AppConfig = toml::from_str(&content)?;
 
+    let mut table: toml::Table = toml::from_str(&content)?;
+    let migrations = migrate::migrate(&mut table)?;
+    let mut config: AppConfig = if migrations.is_empty() {
+        // Parse the text again so errors keep their line and column.
+        toml::from_str(&content)?
+    } else {
+        for note in &migrations {
+            tracing::warn!("config migrated: {note}");
+        }
+        tracing::warn!(
+            "{} uses an old config version; set version = {} after applying the changes above",
+            path.display(),
+            migrate::CURRENT_VERSION
+        );
+        table.try_into()?
+    };
+
+    expand_rows(&mut config)?;
+    expand_remotes(&mut config);
+    if let Some(preset) = config.deckd.high_contrast {
+        apply_contrast(&mut config, preset);
+    }
+    check_positions(&config)?;
+    resolve_positions(&mut config, DEFAULT_GRID)?;
     validate(&config)?;
     Ok(config)
 }
 
+/// Buttons on keys that a deck with `key_count` keys lacks, e.g.
+/// "page 'home' key 20".
+#[must_use]
+pub fn keys_off_deck(config: &AppConfig, key_count: u8) -> Vec<String> {
+    let mut off: Vec<String> = config
+        .pages
+        .iter()
+        .flat_map(|(page_id, page)| {
+            page.buttons
+                .iter()
+                .filter(|b| b.key.get() >= key_count && !b.generated)
+                .map(move |b| format!("page '{page_id}' key {}", b.key))
+        })
+        .collect();
+    off.sort();
+    off
+}
+
+/// Set the `key` of every button placed by `pos` to its index on `grid`.
+///
+/// # Errors
+/// Returns `DeckError::Config` if a position is off the grid.
+pub fn resolve_positions(config: &mut AppConfig, grid: Grid) -> Result<()> {
+    for (page_id, page) in &mut config.pages {
+        for button in &mut page.buttons {
+            let Some(pos) = button.pos else {
+                continue;
+            };
+            button.key = pos.resolve(grid).ok_or_else(|| {
+                DeckError::Config(format!(
+                    "page '{page_id}': pos '{pos}' is off the {}x{} deck",
+                    grid.rows, grid.columns
+                ))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Add the named buttons of each page's `rows` layout to its buttons, placed
+/// by row and column.
+fn expand_rows(config: &mut AppConfig) -> Result<()> {
+    for (name, button) in &config.buttons {
+        if button.key != KeyIndex::UNSET || button.pos.is_some() {
+            return Err(DeckError::Config(format!(
+                "buttons.{name}: `key` and `pos` come from page rows"
+            )));
+        }
+    }
+    for (page_id, page) in &mut config.pages {
+        for (row, names) in (1..).zip(&page.rows) {
+            for (column, name) in (1..).zip(names) {
+                if name.is_empty() {
+                    continue;
+                }
+                let button = config.buttons.get(name).ok_or_else(|| {
+                    DeckError::Config(format!("page '{page_id}' rows: unknown button '{name}'"))
+                })?;
+                page.buttons.push(ButtonConfig {
+                    pos: Some(KeyPos::Cell { row, column }),
+                    ..button.clone()
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the remote control buttons of each `remote` page on the cells its
+/// own buttons leave free.
+fn expand_remotes(config: &mut AppConfig) {
+    for page in config.pages.values_mut() {
+        let Some(ref remote) = page.remote else {
+            continue;
+        };
+        let taken = |pos: KeyPos| {
+            page.buttons.iter().any(|b| {
+                // `key` buttons are matched by their cell on the default grid.
+                b.pos == Some(pos) || (b.pos.is_none() && pos.resolve(DEFAULT_GRID) == Some(b.key))
+            })
+        };
+        let buttons = crate::page::remote::buttons(remote, taken);
+        page.buttons.extend(buttons);
+    }
+}
+
+/// Replace the configured colors with those of `preset`: the defaults and
+/// themes take its colors, buttons lose their own, and buttons showing a
+/// state swap them while it is on.
+fn apply_contrast(config: &mut AppConfig, preset: ContrastPreset) {
+    let (background, text) = preset.colors();
+    config.deckd.defaults.background = background.into();
+    config.deckd.defaults.text_color = text.into();
+    for theme in config.themes.values_mut() {
+        theme.background = None;
+        theme.text_color = None;
+    }
+    for button in config.pages.values_mut().flat_map(|p| p.buttons.iter_mut()) {
+        button.background = None;
+        button.text_color = None;
+        button.state_backgrounds.clear();
+        button.thresholds.clear();
+        button.on_background = Some(text.into());
+        button.on_text_color = Some(background.into());
+    }
+}
+
+/// Every button needs exactly one of `key` and `pos`.
+fn check_positions(config: &AppConfig) -> Result<()> {
+    for (page_id, page) in &config.pages {
+        for button in &page.buttons {
+            match (button.key == KeyIndex::UNSET, button.pos) {
+                (true, None) => {
+                    return Err(DeckError::Config(format!(
+                        "page '{page_id}': button needs `key` or `pos`"
+                    )));
+                }
+                (false, Some(pos)) => {
+                    return Err(DeckError::Config(format!(
+                        "page '{page_id}' key {}: set `key` or `pos = \"{pos}\"`, not both",
+                        button.key
+                    )));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve a path from the config: absolute paths are used as-is,
+/// relative ones are taken relative to the config directory.
+#[must_use]
+pub fn resolve_path(path: &str, config_dir: &Path) -> PathBuf {
+    if Path::new(path).is_absolute() {
+        PathBuf::from(path)
+    } else {
+        config_dir.join(path)
+    }
+}
+
+/// List icons referenced by the config that do not exist on disk,
+/// as `(page_id, key, resolved path)` sorted by page and key.
+#[must_use]
+pub fn missing_icons(config: &AppConfig, config_dir: &Path) -> Vec<(PageId, KeyIndex, PathBuf)> {
+    let mut missing: Vec<(PageId, KeyIndex, PathBuf)> = config
+        .pages
+        .iter()
+        .flat_map(|(page_id, page)| {
+            page.buttons.iter().filter_map(move |button| {
+                let path = resolve_path(button.icon.as_deref()?, config_dir);
+                (!path.exists()).then(|| (page_id.clone(), button.key, path))
+            })
+        })
+        .collect();
+    missing.sort();
+    missing
+}
+
 /// Expand `${VAR}` and `$VAR` patterns in the config string.
 fn expand_env_vars(input: &str) -> String {
     let mut result = String::with_capacity(input.len());
@@ -68,18 +277,474 @@ fn validate(config: &AppConfig) -> Result<()> {
     if config.deckd.brightness > 100 {
         return Err(DeckError::Config("brightness must be 0-100".to_string()));
     }
+    if let Some((start, _)) = config
+        .deckd
+        .brightness_schedule
+        .iter()
+        .find(|(_, &level)| level > 100)
+    {
+        return Err(DeckError::Config(format!(
+            "deckd.brightness_schedule: brightness at {start} must be 0-100"
+        )));
+    }
+
+    if crate::render::locale::lookup(&config.deckd.locale).is_none() {
+        return Err(DeckError::Config(format!(
+            "deckd.locale: unsupported locale '{}'",
+            config.deckd.locale
+        )));
+    }
+
+    if let Some(ref status) = config.deckd.status {
+        if !config.pages.contains_key(status.page.as_str()) {
+            return Err(DeckError::Config(format!(
+                "deckd.status: page '{}' not found",
+                status.page
+            )));
+        }
+        if let Some(key) = status.key.filter(|&k| !on_deck(k)) {
+            return Err(DeckError::Config(format!(
+                "deckd.status: key {key} out of range (0-31)"
+            )));
+        }
+    }
+
+    if !(0.0..=1.0).contains(&config.deckd.feedback.volume) {
+        return Err(DeckError::Config(
+            "deckd.feedback: volume must be 0.0-1.0".into(),
+        ));
+    }
+
+    let calibration = &config.deckd.calibration;
+    let calibrations = std::iter::once(("default", &calibration.default)).chain(
+        calibration
+            .devices
+            .iter()
+            .map(|(serial, c)| (serial.as_str(), c)),
+    );
+    for (name, c) in calibrations {
+        if !(c.gamma > 0.0
+            && c.gamma.is_finite()
+            && c.brightness >= 0.0
+            && c.brightness.is_finite())
+        {
+            return Err(DeckError::Config(format!(
+                "deckd.calibration ({name}): gamma must be above 0 and brightness at least 0"
+            )));
+        }
+    }
+
+    if let Some(ref quiet) = config.deckd.quiet {
+        if quiet.between.is_none() && quiet.entity.is_none() {
+            return Err(DeckError::Config(
+                "deckd.quiet: needs between or an entity".into(),
+            ));
+        }
+        if quiet.brightness > 100 {
+            return Err(DeckError::Config(
+                "deckd.quiet: brightness must be 0-100".into(),
+            ));
+        }
+        if let Some(ref page) = quiet
+            .page
+            .as_ref()
+            .filter(|p| !config.pages.contains_key(*p))
+        {
+            return Err(DeckError::Config(format!(
+                "deckd.quiet: page '{page}' not found"
+            )));
+        }
+    }
+
+    if let Some(ref guest) = config.deckd.guest {
+        if let Some(page) = guest.pages.iter().find(|p| !config.pages.contains_key(*p)) {
+            return Err(DeckError::Config(format!(
+                "deckd.guest: page '{page}' not found"
+            )));
+        }
+        crate::render::canvas::parse_hex_color(&guest.color)
+            .map_err(|e| DeckError::Config(format!("deckd.guest: color: {e}")))?;
+        validate_kinds("deckd.guest.block", &guest.block)?;
+    }
+
+    if config.deckd.group.is_some() && config.deckd.user.is_none() {
+        return Err(DeckError::Config(
+            "deckd.group: needs deckd.user, or deckd would keep running as root".into(),
+        ));
+    }
+
+    let policy = &config.deckd.policy;
+    validate_kinds("deckd.policy.deny", &policy.deny)?;
+    if let Some(ref allow) = policy.allow {
+        validate_kinds("deckd.policy.allow", allow)?;
+    }
+
+    if let Some(ref proxy) = config.deckd.proxy {
+        if !proxy.url.starts_with(crate::secret::FILE_PREFIX) {
+            reqwest::Proxy::all(proxy.url.as_str())
+                .map_err(|e| DeckError::Config(format!("deckd.proxy: {e}")))?;
+        }
+    }
+
+    if let Some(ref ups) = config.deckd.ups {
+        if !config.pages.contains_key(ups.page.as_str()) {
+            return Err(DeckError::Config(format!(
+                "deckd.ups: page '{}' not found",
+                ups.page
+            )));
+        }
+    }
+
+    let selection = &config.deckd.theme;
+    let referenced = selection
+        .default
+        .iter()
+        .chain(selection.states.values())
+        .chain(selection.schedule.values());
+    for theme in referenced {
+        if !config.themes.contains_key(theme) {
+            return Err(DeckError::Config(format!(
+                "deckd.theme: unknown theme '{theme}'"
+            )));
+        }
+    }
+
+    if let Some(ref action) = config.deckd.on_reconnect_failed {
+        validate_action(action)
+            .map_err(|e| DeckError::Config(format!("deckd.on_reconnect_failed: {e}")))?;
+    }
+
+    for (name, action) in &config.hooks {
+        validate_action(action).map_err(|e| DeckError::Config(format!("hooks.{name}: {e}")))?;
+    }
+
+    if let Some(ref serial) = config.deckd.device_serial {
+        if config.devices.contains_key(serial) {
+            return Err(DeckError::Config(format!(
+                "deckd.device_serial: deck '{serial}' has its own [devices] section"
+            )));
+        }
+    }
+
+    for (serial, device) in &config.devices {
+        if !config.pages.contains_key(&device.home_page) {
+            return Err(DeckError::Config(format!(
+                "devices.{serial}: home page '{}' not found",
+                device.home_page
+            )));
+        }
+        if device.brightness.is_some_and(|b| b > 100) {
+            return Err(DeckError::Config(format!(
+                "devices.{serial}: brightness must be 0-100"
+            )));
+        }
+    }
+
+    for (name, alert) in &config.alerts {
+        let err = |e: String| DeckError::Config(format!("alerts.{name}: {e}"));
+        crate::source::check(&alert.entity).map_err(err)?;
+        if alert.page.is_none() && alert.pattern.is_none() {
+            return Err(err("needs a page or a pattern".into()));
+        }
+        if let Some(ref page) = alert
+            .page
+            .as_ref()
+            .filter(|p| !config.pages.contains_key(*p))
+        {
+            return Err(err(format!("page '{page}' not found")));
+        }
+        if let Some(ref pattern) = alert
+            .pattern
+            .as_ref()
+            .filter(|p| !config.patterns.contains_key(*p))
+        {
+            return Err(err(format!("unknown pattern '{pattern}'")));
+        }
+    }
+
+    for (name, pattern) in &config.patterns {
+        crate::render::canvas::parse_hex_color(&pattern.color)
+            .map_err(|e| DeckError::Config(format!("patterns.{name}: {e}")))?;
+        if pattern.times == 0 || pattern.on_ms == 0 {
+            return Err(DeckError::Config(format!(
+                "patterns.{name}: times and on_ms must be at least 1"
+            )));
+        }
+    }
 
     for (page_id, page) in &config.pages {
         for button in &page.buttons {
-            if button.key > 14 {
+            if let Some(ref entity) = button.state_entity {
+                crate::source::check(entity).map_err(|e| {
+                    DeckError::Config(format!("page '{page_id}' key {}: {e}", button.key))
+                })?;
+            }
+            if let Some(ref action) = button.on_press {
+                validate_action(action).map_err(|e| {
+                    DeckError::Config(format!("page '{page_id}' key {}: {e}", button.key))
+                })?;
+            }
+            if let Some(ref action) = button.on_error {
+                validate_action(action).map_err(|e| {
+                    DeckError::Config(format!("page '{page_id}' key {} on_error: {e}", button.key))
+                })?;
+            }
+            validate_templates(button).map_err(|e| {
+                DeckError::Config(format!("page '{page_id}' key {}: {e}", button.key))
+            })?;
+        }
+        for (hook, actions) in [("on_enter", &page.on_enter), ("on_exit", &page.on_exit)] {
+            for action in actions {
+                validate_action(action)
+                    .map_err(|e| DeckError::Config(format!("page '{page_id}' {hook}: {e}")))?;
+            }
+        }
+        if let Some(ref keypad) = page.keypad {
+            if !(1..=16).contains(&keypad.max_len) {
+                return Err(DeckError::Config(format!(
+                    "page '{page_id}' keypad: max_len must be 1-16"
+                )));
+            }
+            validate_action(&keypad.on_submit)
+                .map_err(|e| DeckError::Config(format!("page '{page_id}' keypad: {e}")))?;
+        }
+        if page.brightness.is_some_and(|b| b > 100) {
+            return Err(DeckError::Config(format!(
+                "page '{page_id}': brightness must be 0-100"
+            )));
+        }
+
+        for button in &page.buttons {
+            if !on_deck(button.key) {
                 return Err(DeckError::Config(format!(
-                    "page '{page_id}': button key {} out of range (0-14)",
+                    "page '{page_id}': button key {} out of range (0-31)",
                     button.key
                 )));
             }
         }
+
+        validate_pin(page.pin.as_deref())
+            .map_err(|e| DeckError::Config(format!("page '{page_id}': {e}")))?;
+        for button in &page.buttons {
+            validate_pin(button.pin.as_deref()).map_err(|e| {
+                DeckError::Config(format!("page '{page_id}' key {}: {e}", button.key))
+            })?;
+        }
+
+        for dial in &page.dials {
+            let name = format!("page '{page_id}' dial {}", dial.dial);
+            if dial.dial >= MAX_DIALS {
+                return Err(DeckError::Config(format!(
+                    "{name}: dial out of range (0-{})",
+                    MAX_DIALS - 1
+                )));
+            }
+            for action in dial.on_rotate.iter().chain(&dial.on_press) {
+                validate_action(action).map_err(|e| DeckError::Config(format!("{name}: {e}")))?;
+            }
+        }
+
+        for chord in &page.chords {
+            let name = format!("page '{page_id}' chord {}+{}", chord.modifier, chord.key);
+            if !on_deck(chord.modifier) || !on_deck(chord.key) {
+                return Err(DeckError::Config(format!(
+                    "{name}: key out of range (0-31)"
+                )));
+            }
+            if chord.modifier == chord.key {
+                return Err(DeckError::Config(format!(
+                    "{name}: modifier and key must differ"
+                )));
+            }
+            validate_action(&chord.on_press)
+                .map_err(|e| DeckError::Config(format!("{name}: {e}")))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `key` exists on the deck.
+fn on_deck(key: KeyIndex) -> bool {
+    KeyIndex::new(key.get(), KEY_COUNT).is_some()
+}
+
+/// Label templates and the `visible_if` condition must parse.
+fn validate_templates(button: &ButtonConfig) -> std::result::Result<(), String> {
+    let labels = button.label.iter().flat_map(|l| l.texts());
+    for text in labels.filter(|t| crate::template::is_template(t)) {
+        crate::template::check(text, false).map_err(|e| format!("label: {e}"))?;
+    }
+    if let Some(ref condition) = button.visible_if {
+        crate::template::check(condition, true).map_err(|e| format!("visible_if: {e}"))?;
+    }
+    Ok(())
+}
+
+/// A PIN is 1-8 key indices.
+fn validate_pin(pin: Option<&[KeyIndex]>) -> std::result::Result<(), String> {
+    match pin {
+        None => Ok(()),
+        Some([]) => Err("pin must not be empty".into()),
+        Some(pin) if pin.len() > 8 => Err("pin must be at most 8 keys".into()),
+        Some(pin) => match pin.iter().find(|&&k| !on_deck(k)) {
+            Some(key) => Err(format!("pin key {key} out of range (0-31)")),
+            None => Ok(()),
+        },
     }
+}
 
+/// Check an action for conflicting or malformed fields.
+/// Refuse action kinds that do not exist, so a typo cannot quietly leave an
+/// action allowed.
+fn validate_kinds(field: &str, kinds: &[String]) -> Result<()> {
+    match kinds
+        .iter()
+        .find(|k| !ActionConfig::KINDS.contains(&k.as_str()))
+    {
+        Some(kind) => Err(DeckError::Config(format!(
+            "{field}: unknown action type '{kind}'"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn validate_action(action: &ActionConfig) -> std::result::Result<(), String> {
+    if let ActionConfig::SetKey { key, .. }
+    | ActionConfig::ClearKey { key }
+    | ActionConfig::Flash { key: Some(key), .. } = action
+    {
+        if !on_deck(*key) {
+            return Err(format!("key {key} out of range (0-31)"));
+        }
+    }
+    if let ActionConfig::HaEvent { event, .. } = action {
+        if event.is_empty() || event.contains(['/', '?', '#']) {
+            return Err(format!("invalid HA event type '{event}'"));
+        }
+    }
+    if let ActionConfig::Navigate { page, picker } = action {
+        match picker {
+            None if page.is_empty() => return Err("navigate needs a page or a picker".into()),
+            Some(_) if !page.is_empty() => {
+                return Err("use only one of page and picker".into());
+            }
+            Some(entity) if !entity.starts_with("light.") => {
+                return Err(format!("picker '{entity}' is not a light"));
+            }
+            _ => {}
+        }
+    }
+    if let ActionConfig::Brightness { level, step } = action {
+        match (level, step) {
+            (Some(level), _) if *level > 100 => return Err("brightness must be 0-100".into()),
+            (Some(_), 0) | (None, 1..) | (None, ..0) => {}
+            (Some(_), _) => return Err("use only one of level and step".into()),
+            (None, 0) => return Err("brightness needs a level or a step".into()),
+        }
+    }
+    if let ActionConfig::Remote { host, .. } = action {
+        if host.is_empty() {
+            return Err("remote needs a host".into());
+        }
+    }
+    #[cfg(feature = "industrial")]
+    if let ActionConfig::KnxWrite { group, .. } = action {
+        crate::action::knx::group_address(group)?;
+    }
+    if let ActionConfig::Proxmox { command, name, .. } = action {
+        match (command, name) {
+            (ProxmoxCommand::Snapshot, Some(name)) => {
+                crate::action::proxmox::check_snapshot_name(name)?;
+            }
+            (ProxmoxCommand::Snapshot, None) => {}
+            (_, Some(_)) => return Err("proxmox: name is only used by snapshot".into()),
+            (_, None) => {}
+        }
+    }
+    #[cfg(feature = "kubernetes")]
+    if let ActionConfig::K8s {
+        deployment,
+        restart,
+        scale,
+    } = action
+    {
+        crate::action::k8s::object(deployment)?;
+        if *restart == scale.is_some() {
+            return Err("k8s needs either restart = true or scale".into());
+        }
+    }
+    #[cfg(feature = "bluetooth")]
+    if let ActionConfig::Bluetooth { device, .. } = action {
+        crate::action::bluetooth::check_address(device)?;
+    }
+    if let ActionConfig::Vpn {
+        kind,
+        interface,
+        exit_node,
+        ..
+    } = action
+    {
+        match kind {
+            VpnKind::Wireguard if interface.is_none() => {
+                return Err("wireguard vpn action needs an interface".into());
+            }
+            VpnKind::Wireguard if exit_node.is_some() => {
+                return Err("exit_node is for tailscale only".into());
+            }
+            _ => {}
+        }
+    }
+    if let ActionConfig::Snapcast {
+        volume,
+        mute,
+        group,
+        ..
+    } = action
+    {
+        crate::action::snapcast::Command::from_fields(*volume, *mute, group.as_deref())?;
+    }
+    if let ActionConfig::SceneSave { scene, entities } = action {
+        if entities.is_empty() {
+            return Err(format!("scene '{scene}' has no entities"));
+        }
+        if let Some(entity) = entities.iter().find(|e| !e.contains('.')) {
+            return Err(format!("scene '{scene}': invalid entity '{entity}'"));
+        }
+    }
+    if let ActionConfig::SceneSave { scene, .. } | ActionConfig::SceneRecall { scene } = action {
+        if scene.is_empty() {
+            return Err("empty scene name".into());
+        }
+    }
+    if let ActionConfig::HaService { service, .. } = action {
+        let valid = service.split_once('.').is_some_and(|(domain, name)| {
+            !domain.is_empty() && !name.is_empty() && !service.contains(['/', '?', '#'])
+        });
+        if !valid {
+            return Err(format!(
+                "invalid HA service '{service}' (expected <domain>.<service>)"
+            ));
+        }
+    }
+    if let ActionConfig::Http {
+        body, json, form, ..
+    } = action
+    {
+        let bodies = [body.is_some(), json.is_some(), form.is_some()];
+        if bodies.iter().filter(|&&set| set).count() > 1 {
+            return Err("use only one of body, json and form".into());
+        }
+        if let Some((key, _)) = form
+            .iter()
+            .flatten()
+            .find(|(_, v)| v.is_object() || v.is_array() || v.is_null())
+        {
+            return Err(format!(
+                "form field '{key}' must be a string, number or boolean"
+            ));
+        }
+    }
     Ok(())
 }
 
@@ -101,6 +766,299 @@ mod tests {
         assert_eq!(result, "url = \"${DECKD_NONEXISTENT}/path\"");
     }
 
+    #[test]
+    fn page_brightness_out_of_range() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[pages.home]
+brightness = 120
+"#,
+        )
+        .unwrap();
+        assert!(validate(&config).is_err());
+    }
+
+    #[test]
+    fn buttons_placed_by_position() {
+        let mut config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[pages.home]
+buttons = [{ pos = "bottom-right", label = "Off" }, { key = 0, label = "On" }]
+"#,
+        )
+        .unwrap();
+        check_positions(&config).unwrap();
+        resolve_positions(&mut config, DEFAULT_GRID).unwrap();
+        assert_eq!(config.pages["home"].buttons[0].key, 14);
+        let xl = Grid {
+            columns: 8,
+            rows: 4,
+        };
+        resolve_positions(&mut config, xl).unwrap();
+        assert_eq!(config.pages["home"].buttons[0].key, 31);
+
+        config.pages.get_mut("home").unwrap().buttons[0].pos = "r4c1".parse().ok();
+        assert!(resolve_positions(&mut config, DEFAULT_GRID).is_err());
+        config.pages.get_mut("home").unwrap().buttons[1].pos = "center".parse().ok();
+        assert!(check_positions(&config).is_err());
+    }
+
+    #[test]
+    fn rows_place_named_buttons() {
+        let mut config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[buttons.lights]
+label = "Lights"
+
+[buttons.media]
+label = "Media"
+
+[pages.home]
+rows = [["lights", "", "media"], ["", "lights"]]
+buttons = [{ key = 14, label = "Back" }]
+"#,
+        )
+        .unwrap();
+        expand_rows(&mut config).unwrap();
+        resolve_positions(&mut config, DEFAULT_GRID).unwrap();
+        let keys: Vec<u8> = config.pages["home"]
+            .buttons
+            .iter()
+            .map(|b| b.key.get())
+            .collect();
+        assert_eq!(keys, [14, 0, 2, 6]);
+
+        config.pages.get_mut("home").unwrap().rows = vec![vec!["scenes".into()]];
+        assert!(expand_rows(&mut config).is_err());
+    }
+
+    #[test]
+    fn reports_missing_icons() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[pages.home]
+
+[[pages.home.buttons]]
+key = 2
+icon = "icons/does-not-exist.png"
+
+[[pages.home.buttons]]
+key = 0
+label = "No icon"
+"#,
+        )
+        .unwrap();
+        let missing = missing_icons(&config, Path::new("/nonexistent"));
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].0, "home");
+        assert_eq!(missing[0].1, 2);
+        assert_eq!(
+            missing[0].2,
+            PathBuf::from("/nonexistent/icons/does-not-exist.png")
+        );
+    }
+
+    #[test]
+    fn http_body_variants_are_exclusive() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[[pages.home.buttons]]
+key = 0
+on_press = { action = "http", url = "http://ha.local/api", body = "x", json = { a = 1 } }
+"#,
+        )
+        .unwrap();
+        assert!(validate(&config).is_err());
+    }
+
+    #[test]
+    fn navigate_needs_page_or_light_picker() {
+        let parse = |action: &str| -> AppConfig {
+            toml::from_str(&format!(
+                "[deckd]\n[[pages.home.buttons]]\nkey = 0\non_press = {action}\n"
+            ))
+            .unwrap()
+        };
+        assert!(validate(&parse(r#"{ action = "navigate", picker = "light.desk" }"#)).is_ok());
+        assert!(validate(&parse(r#"{ action = "navigate" }"#)).is_err());
+        assert!(validate(&parse(r#"{ action = "navigate", picker = "switch.fan" }"#)).is_err());
+        let both = r#"{ action = "navigate", page = "home", picker = "light.desk" }"#;
+        assert!(validate(&parse(both)).is_err());
+    }
+
+    #[test]
+    fn action_kinds_must_exist() {
+        let parse = |deckd: &str| -> AppConfig {
+            toml::from_str(&format!("[deckd]\n{deckd}\n[pages.home]\n")).unwrap()
+        };
+        assert!(validate(&parse(r#"policy = { deny = ["shell", "k8s"] }"#)).is_ok());
+        assert!(validate(&parse(r#"policy = { deny = ["shel"] }"#)).is_err());
+        assert!(validate(&parse(r#"policy = { allow = ["http", "ha-service"] }"#)).is_err());
+        assert!(validate(&parse(r#"guest = { block = ["bluetooth"] }"#)).is_ok());
+        assert!(validate(&parse(r#"guest = { block = ["Shell"] }"#)).is_err());
+    }
+
+    #[test]
+    fn group_needs_a_user() {
+        let parse = |deckd: &str| -> AppConfig {
+            toml::from_str(&format!("[deckd]\n{deckd}\n[pages.home]\n")).unwrap()
+        };
+        assert!(validate(&parse("user = \"deckd\"\ngroup = \"plugdev\"")).is_ok());
+        assert!(validate(&parse("group = \"plugdev\"")).is_err());
+    }
+
+    #[test]
+    fn on_error_is_checked() {
+        let parse = |on_error: &str| -> AppConfig {
+            toml::from_str(&format!(
+                "[deckd]\n[[pages.home.buttons]]\nkey = 0\non_press = {{ action = \"shell\", command = \"true\" }}\non_error = {on_error}\n"
+            ))
+            .unwrap()
+        };
+        let notify = r#"{ action = "ha_service", service = "notify.phone", data = { message = "{error}" } }"#;
+        let config = parse(notify);
+        assert!(validate(&config).is_ok());
+        assert!(config.pages["home"].buttons[0].on_error.is_some());
+        assert!(validate(&parse(r#"{ action = "navigate" }"#)).is_err());
+    }
+
+    #[test]
+    fn page_hooks_are_checked() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[pages.movie]
+on_enter = [{ action = "ha_service", service = "light.turn_off", data = { entity_id = "light.living" } }]
+on_exit = [{ action = "scene_recall", scene = "living" }]
+"#,
+        )
+        .unwrap();
+        assert!(validate(&config).is_ok());
+        assert_eq!(config.pages["movie"].on_enter.len(), 1);
+
+        let config: AppConfig =
+            toml::from_str("[deckd]\n[pages.movie]\non_exit = [{ action = \"navigate\" }]\n")
+                .unwrap();
+        let err = validate(&config).unwrap_err().to_string();
+        assert!(err.contains("page 'movie' on_exit"), "{err}");
+    }
+
+    #[test]
+    fn high_contrast_replaces_colors() {
+        let mut config: AppConfig = toml::from_str(
+            r##"
+[deckd]
+high_contrast = "yellow"
+
+[themes.night]
+background = "#101010"
+
+[pages.home]
+buttons = [{ key = 0, background = "#336699", state_entity = "light.desk", on_background = "#ffcc00" }]
+"##,
+        )
+        .unwrap();
+        let preset = config.deckd.high_contrast.unwrap();
+        apply_contrast(&mut config, preset);
+        assert_eq!(config.deckd.defaults.background, "#000000");
+        assert_eq!(config.deckd.defaults.text_color, "#ffff00");
+        assert_eq!(config.themes["night"].background, None);
+        let button = &config.pages["home"].buttons[0];
+        assert_eq!(button.background, None);
+        assert_eq!(button.on_background.as_deref(), Some("#ffff00"));
+        assert_eq!(button.on_text_color.as_deref(), Some("#000000"));
+    }
+
+    #[test]
+    fn xl_keys_are_reported_on_smaller_decks() {
+        let config: AppConfig = toml::from_str(
+            "[deckd]\n[pages.home]\nbuttons = [{ key = 3 }, { key = 20 }, { key = 31 }]\n",
+        )
+        .unwrap();
+        assert!(validate(&config).is_ok());
+        assert_eq!(
+            keys_off_deck(&config, 15),
+            ["page 'home' key 20", "page 'home' key 31"]
+        );
+        assert!(keys_off_deck(&config, 32).is_empty());
+        let config: AppConfig =
+            toml::from_str("[deckd]\n[pages.home]\nbuttons = [{ key = 32 }]\n").unwrap();
+        assert!(validate(&config).is_err());
+    }
+
+    #[test]
+    fn flash_patterns_are_checked() {
+        let parse = |pattern: &str, key: u8| -> AppConfig {
+            toml::from_str(&format!(
+                "[deckd]\n[patterns.smoke]\n{pattern}\n[hooks.smoke]\n\
+                 action = \"flash\"\npattern = \"smoke\"\nkey = {key}\n"
+            ))
+            .unwrap()
+        };
+        let config = parse("times = 3", 4);
+        assert!(validate(&config).is_ok());
+        assert_eq!(config.patterns["smoke"].color, "#ff0000");
+        assert_eq!(config.patterns["smoke"].times, 3);
+        assert!(validate(&parse("color = \"#ff00zz\"", 4)).is_err());
+        assert!(validate(&parse("times = 0", 4)).is_err());
+        assert!(validate(&parse("times = 3", 32)).is_err());
+    }
+
+    #[test]
+    fn dial_actions_are_checked() {
+        let parse = |dial: &str| -> AppConfig {
+            toml::from_str(&format!(
+                "[deckd]\n[pages.home]\nname = \"Home\"\n[[pages.home.dials]]\n{dial}\n"
+            ))
+            .unwrap()
+        };
+        let config = parse(
+            "dial = 0\non_rotate = { action = \"brightness\", step = 5 }\n\
+             on_press = { action = \"brightness\", level = 80 }",
+        );
+        assert!(validate(&config).is_ok());
+        assert_eq!(
+            config.pages["home"].dials[0]
+                .on_press
+                .as_ref()
+                .unwrap()
+                .kind(),
+            "brightness"
+        );
+        assert!(validate(&parse("dial = 4\non_press = { action = \"home\" }")).is_err());
+        assert!(validate(&parse("dial = 1\non_press = { action = \"brightness\" }")).is_err());
+        assert!(validate(&parse(
+            "dial = 1\non_press = { action = \"brightness\", level = 120 }"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn unknown_theme_rejected() {
+        let config: AppConfig = toml::from_str(
+            r##"
+[deckd.theme]
+schedule = { "21:00" = "night" }
+
+[themes.day]
+background = "#ffffff"
+"##,
+        )
+        .unwrap();
+        assert!(validate(&config).is_err());
+    }
+
     #[test]
     fn load_example_config() {
         let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
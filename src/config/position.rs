@@ -0,0 +1,143 @@
+use crate::ids::KeyIndex;
+use crate::render::splash::Grid;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// A key given by its place on the grid instead of its index, so a config
+/// keeps its layout on decks with other geometries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum KeyPos {
+    /// `r<row>c<column>`, both counted from 1.
+    Cell {
+        row: u32,
+        column: u32,
+    },
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// The middle key; on an even row or column count, the one left of or
+    /// above the middle.
+    Center,
+}
+
+impl KeyPos {
+    /// The key at this position on `grid`, if the grid has it.
+    #[must_use]
+    pub fn resolve(self, grid: Grid) -> Option<KeyIndex> {
+        let (last_row, last_column) = (grid.rows.checked_sub(1)?, grid.columns.checked_sub(1)?);
+        let (row, column) = match self {
+            Self::Cell { row, column } => (row.checked_sub(1)?, column.checked_sub(1)?),
+            Self::TopLeft => (0, 0),
+            Self::TopRight => (0, last_column),
+            Self::BottomLeft => (last_row, 0),
+            Self::BottomRight => (last_row, last_column),
+            Self::Center => (last_row / 2, last_column / 2),
+        };
+        if row > last_row || column > last_column {
+            return None;
+        }
+        let count = u8::try_from(grid.rows * grid.columns).ok()?;
+        KeyIndex::new(u8::try_from(row * grid.columns + column).ok()?, count)
+    }
+}
+
+impl FromStr for KeyPos {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid key position '{s}' (expected r<row>c<column>, top-left, top-right, \
+                 bottom-left, bottom-right or center)"
+            )
+        };
+        Ok(match s {
+            "top-left" => Self::TopLeft,
+            "top-right" => Self::TopRight,
+            "bottom-left" => Self::BottomLeft,
+            "bottom-right" => Self::BottomRight,
+            "center" => Self::Center,
+            _ => {
+                let (row, column) = s
+                    .strip_prefix('r')
+                    .and_then(|rest| rest.split_once('c'))
+                    .ok_or_else(invalid)?;
+                let row: u32 = row.parse().map_err(|_| invalid())?;
+                let column: u32 = column.parse().map_err(|_| invalid())?;
+                if row == 0 || column == 0 {
+                    return Err(format!("key position '{s}': rows and columns count from 1"));
+                }
+                Self::Cell { row, column }
+            }
+        })
+    }
+}
+
+impl TryFrom<String> for KeyPos {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for KeyPos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cell { row, column } => write!(f, "r{row}c{column}"),
+            Self::TopLeft => f.write_str("top-left"),
+            Self::TopRight => f.write_str("top-right"),
+            Self::BottomLeft => f.write_str("bottom-left"),
+            Self::BottomRight => f.write_str("bottom-right"),
+            Self::Center => f.write_str("center"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MK2: Grid = Grid {
+        columns: 5,
+        rows: 3,
+    };
+    const XL: Grid = Grid {
+        columns: 8,
+        rows: 4,
+    };
+
+    fn resolve(pos: &str, grid: Grid) -> Option<u8> {
+        pos.parse::<KeyPos>()
+            .unwrap()
+            .resolve(grid)
+            .map(KeyIndex::get)
+    }
+
+    #[test]
+    fn positions_follow_the_grid() {
+        assert_eq!(resolve("r2c4", MK2), Some(8));
+        assert_eq!(resolve("r2c4", XL), Some(11));
+        assert_eq!(resolve("bottom-right", MK2), Some(14));
+        assert_eq!(resolve("bottom-right", XL), Some(31));
+        assert_eq!(resolve("top-right", MK2), Some(4));
+        assert_eq!(resolve("center", MK2), Some(7));
+        assert_eq!(resolve("r4c1", MK2), None);
+        assert_eq!(resolve("r4c1", XL), Some(24));
+    }
+
+    #[test]
+    fn parses_and_rejects() {
+        assert_eq!(
+            "r3c5".parse::<KeyPos>(),
+            Ok(KeyPos::Cell { row: 3, column: 5 })
+        );
+        assert_eq!(KeyPos::BottomLeft.to_string(), "bottom-left");
+        assert!("r0c1".parse::<KeyPos>().is_err());
+        assert!("middle".parse::<KeyPos>().is_err());
+        assert!("r2".parse::<KeyPos>().is_err());
+    }
+}
@@ -19,17 +19,23 @@ pub async fn watch_config(
 ) -> crate::error::Result<()> {
     let (notify_tx, mut notify_rx) = tokio::sync::mpsc::channel(16);
     let watch_path = config_path.clone();
+    // The directory is watched, as a config replaced by rename (by editors
+    // or `deckd push`) would end a watch on the file itself.
+    let watch_dir = config_path
+        .parent()
+        .map_or_else(|| PathBuf::from("."), PathBuf::from);
 
     // The notify watcher must live on a blocking thread.
     let _watcher_handle = tokio::task::spawn_blocking(move || {
         let rt_tx = notify_tx;
+        let config_file = watch_path.clone();
         let debouncer = new_debouncer(
             Duration::from_millis(500),
             move |events: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| {
                 match events {
                     Ok(evts) => {
                         for evt in evts {
-                            if evt.kind == DebouncedEventKind::Any {
+                            if evt.kind == DebouncedEventKind::Any && evt.path == config_file {
                                 let _ = rt_tx.blocking_send(evt.path);
                             }
                         }
@@ -46,7 +52,7 @@ pub async fn watch_config(
             Ok(mut d) => {
                 if let Err(e) = d
                     .watcher()
-                    .watch(&watch_path, notify::RecursiveMode::NonRecursive)
+                    .watch(&watch_dir, notify::RecursiveMode::NonRecursive)
                 {
                     warn!("failed to watch config file: {e}");
                     return;
@@ -75,7 +81,6 @@ pub async fn watch_config(
                     Ok(new_config) => {
                         let config = Arc::new(new_config);
                         let _ = tx.send(DeckEvent::ConfigReloaded(config));
-                        info!("config reloaded successfully");
                     }
                     Err(e) => {
                         warn!("config reload failed, keeping old config: {e}");
@@ -1,76 +1,381 @@
-use crate::config::schema::AppConfig;
+use crate::chord::{ChordState, Down};
+use crate::config::schema::{
+    ActionConfig, AppConfig, ButtonConfig, ButtonDefaults, FlushConfig, FlushMode, Label,
+    PageConfig, StatusSlot, StatusTile, TrackConfig, Transition,
+};
 use crate::config::watcher;
-use crate::device::{DeckHandle, DeviceManager};
-use crate::error::Result;
+use crate::device::backoff::ReconnectPolicy;
+use crate::device::brightness;
+use crate::device::{Deck, DeckHandle, DeviceManager, DeviceSelector, DeviceStatusHandle};
+use crate::error::{DeckError, ErrorContext, Result};
 use crate::event::DeckEvent;
+use crate::guest::Guests;
+use crate::history::StateHistory;
+use crate::ids::{KeyIndex, PageId};
+use crate::jobs::{JobTracker, Press};
+use crate::keypad::{KeypadEntry, KeypadStep};
+use crate::overrides::KeyOverrideStore;
 use crate::page::PageManager;
+use crate::pin::{PinEntry, Step, Unlock};
+use crate::render::canvas::grey_out;
+use crate::render::locale::Locale;
+use crate::render::page_cache::{PageCache, PageImages};
+use crate::render::splash::Grid;
+use crate::scene::SceneStore;
+use crate::schedule::TimeOfDay;
+use crate::status::{Issue, IssueSource, StatusLog};
+use crate::toast::ToastQueue;
+use crate::usage::UsageStats;
 use arc_swap::ArcSwap;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 const CHANNEL_CAPACITY: usize = 64;
-/// Stream Deck MK.2 has 15 keys (0-14).
-const NUM_KEYS: u8 = 15;
 
-/// Run the deckd daemon.
+/// Background of the keypad while it flashes for a rejected code.
+const KEYPAD_ALERT: &str = "#c0392b";
+
+/// Red flashes after a rejected code, and their on and off time.
+const KEYPAD_FLASHES: usize = 3;
+const KEYPAD_FLASH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(150);
+/// Maximum number of rendered pages kept for instant page switches.
+const PAGE_CACHE_SIZE: usize = 8;
+/// How long the goodbye text stays up before the display goes dark.
+const GOODBYE_HOLD: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often changed press counts are written to disk.
+const USAGE_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Run the deckd daemon until SIGINT, SIGTERM or a `Shutdown` event.
 ///
 /// # Errors
 /// Returns `DeckError` if a fatal error occurs in any subsystem.
 pub async fn run(config: AppConfig, config_path: PathBuf) -> Result<()> {
-    let cancel = CancellationToken::new();
-    let (tx, _) = broadcast::channel::<DeckEvent>(CHANNEL_CAPACITY);
+    let (user, group) = (config.deckd.user.as_deref(), config.deckd.group.as_deref());
+    if user.is_some() || group.is_some() {
+        if config.deckd.remote.is_none() {
+            let mut selectors = vec![main_selector(&config)];
+            selectors.extend(config.devices.keys().map(|serial| DeviceSelector {
+                serial: Some(serial.clone()),
+                ..DeviceSelector::default()
+            }));
+            crate::device::open_early(&selectors);
+        }
+        crate::privileges::drop_to(user, group)?;
+    }
+    let deckd = Deckd::start(config, config_path)?;
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    tokio::select! {
+        () = deckd.stopped() => {}
+        () = async { tokio::signal::ctrl_c().await.ok(); } => {
+            info!("received SIGINT, shutting down");
+        }
+        _ = sigterm.recv() => {
+            info!("received SIGTERM, shutting down");
+        }
+    }
+    deckd.shutdown().await;
+    Ok(())
+}
 
-    let shared_config = Arc::new(ArcSwap::from_pointee(config));
-    let mut page_manager = PageManager::new(&shared_config.load().deckd.home_page);
-    let deck_handle = crate::device::new_deck_handle();
+/// A running deckd instance, for embedding deck control in another program.
+///
+/// `start` brings up the device manager, config watcher, state watchers and
+/// (if configured) the control API; the methods drive it like button presses
+/// and API calls would. Signal handling is left to the caller.
+pub struct Deckd {
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+    config: Arc<ArcSwap<AppConfig>>,
+    render: RenderContext,
+    task: tokio::task::JoinHandle<()>,
+    /// Event loops of the `[devices]` decks.
+    devices: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl Deckd {
+    /// Start all subsystems and the event loop on the current tokio runtime.
+    ///
+    /// # Errors
+    /// Returns `DeckError` if the control API token cannot be resolved.
+    pub fn start(config: AppConfig, config_path: PathBuf) -> Result<Self> {
+        let cancel = CancellationToken::new();
+        let (tx, _) = broadcast::channel::<DeckEvent>(CHANNEL_CAPACITY);
+
+        let shared_config = Arc::new(ArcSwap::from_pointee(config));
+        let deck_handle = crate::device::new_deck_handle();
+        let device_status: DeviceStatusHandle = Arc::default();
+        let current_page = Arc::new(ArcSwap::from_pointee(
+            shared_config.load().deckd.home_page.clone(),
+        ));
+
+        let config_dir = config_path
+            .parent()
+            .map_or_else(|| PathBuf::from("."), PathBuf::from);
+        let usage = Arc::new(Mutex::new(UsageStats::load(crate::usage::usage_path(
+            shared_config.load().deckd.usage_file.as_deref(),
+            &config_dir,
+        ))));
+        let scenes = Arc::new(Mutex::new(SceneStore::load(crate::usage::state_path(
+            shared_config.load().deckd.scenes_file.as_deref(),
+            &config_dir,
+            crate::scene::DEFAULT_FILE,
+        ))));
+
+        let api_token = shared_config
+            .load()
+            .deckd
+            .api
+            .as_ref()
+            .and_then(|api| api.token.as_deref())
+            .map(crate::secret::resolve)
+            .transpose()?
+            .map(Arc::from);
+
+        // Subscribe before the device manager starts so the first connect is seen.
+        let ready_handle = tokio::spawn(notify_ready(
+            tx.subscribe(),
+            std::time::Duration::from_millis(shared_config.load().deckd.startup_grace_ms),
+        ));
+        let selector = main_selector(&shared_config.load());
+        let others = selector.exclude.clone();
+        let mut handles = vec![
+            spawn_device_manager(
+                &tx,
+                &cancel,
+                &shared_config,
+                &deck_handle,
+                &device_status,
+                selector,
+            ),
+            spawn_config_watcher(&tx, &cancel, &config_path),
+            tokio::spawn(crate::state::watch_presence(
+                Arc::clone(&shared_config),
+                tx.clone(),
+                cancel.clone(),
+            )),
+            tokio::spawn(crate::theme::watch_theme(
+                Arc::clone(&shared_config),
+                tx.clone(),
+                cancel.clone(),
+            )),
+            tokio::spawn(crate::alert::watch(
+                Arc::clone(&shared_config),
+                tx.clone(),
+                cancel.clone(),
+            )),
+            tokio::spawn(crate::quiet::watch(
+                Arc::clone(&shared_config),
+                tx.clone(),
+                cancel.clone(),
+            )),
+            tokio::spawn(brightness::watch_schedule(
+                Arc::clone(&shared_config),
+                tx.clone(),
+                cancel.clone(),
+            )),
+            tokio::spawn(crate::guest::watch(
+                Arc::clone(&shared_config),
+                tx.clone(),
+                cancel.clone(),
+            )),
+            tokio::spawn(crate::source::nut::watch(
+                Arc::clone(&shared_config),
+                Arc::clone(&current_page),
+                tx.clone(),
+                cancel.clone(),
+            )),
+        ];
+
+        // An add-on without its own API serves it to HA's ingress proxy.
+        let api = match shared_config.load().deckd.api.clone() {
+            Some(api) => Some((api, None)),
+            None if crate::addon::active() => Some((
+                crate::addon::ingress_api(),
+                Some(crate::addon::INGRESS_GATEWAY),
+            )),
+            None => None,
+        };
+        if let Some((api, peer)) = api {
+            let state = crate::api::ApiState {
+                device: Arc::clone(&device_status),
+                current_page: Arc::clone(&current_page),
+                started: std::time::Instant::now(),
+                config: Arc::clone(&shared_config),
+                usage: Arc::clone(&usage),
+                tx: tx.clone(),
+                token: api_token,
+                peer,
+                config_path: config_path.clone(),
+            };
+            if api.announce && !api.listen.ip().is_loopback() {
+                handles.push(tokio::spawn(crate::mdns::announce(
+                    api.listen,
+                    Arc::clone(&device_status),
+                    cancel.clone(),
+                )));
+            }
+            let api_cancel = cancel.clone();
+            handles.push(tokio::spawn(async move {
+                if let Err(e) = crate::api::serve(api.listen, state, api_cancel).await {
+                    error!("control API error: {e}");
+                }
+            }));
+        }
+
+        let devices = others
+            .into_iter()
+            .map(|serial| {
+                spawn_device(
+                    serial,
+                    &shared_config.load(),
+                    &config_dir,
+                    &tx,
+                    &cancel,
+                    &usage,
+                    &scenes,
+                )
+            })
+            .collect();
+
+        let render = RenderContext::new(deck_handle, config_dir, tx.clone());
+        let event_loop = EventLoop::new(
+            Arc::clone(&shared_config),
+            tx.clone(),
+            render.clone(),
+            current_page,
+            usage,
+            scenes,
+            None,
+        );
+        let task = tokio::spawn(run_event_loop(
+            event_loop,
+            tx.subscribe(),
+            cancel.clone(),
+            Some(ready_handle),
+            handles,
+        ));
+
+        Ok(Self {
+            tx,
+            cancel,
+            config: shared_config,
+            render,
+            task,
+            devices,
+        })
+    }
+
+    /// Show a page, as a `navigate` action would (including its PIN prompt).
+    pub fn navigate(&self, page_id: &str) {
+        let _ = self.tx.send(DeckEvent::NavigateTo(page_id.into()));
+    }
+
+    /// Press and release a key of the current page.
+    pub fn press(&self, key: KeyIndex) {
+        let _ = self.tx.send(DeckEvent::ButtonDown(key));
+        let _ = self.tx.send(DeckEvent::ButtonUp(key));
+    }
+
+    /// Pin the display brightness (0-100); `None` returns to the page and
+    /// config settings.
+    pub fn set_brightness(&self, level: Option<u8>) {
+        let _ = self
+            .tx
+            .send(DeckEvent::SetBrightness(level.map(|l| l.min(100))));
+    }
+
+    /// Receive every event on the bus: key presses, navigation, config
+    /// reloads, device connects and more.
+    #[must_use]
+    pub fn subscribe_events(&self) -> broadcast::Receiver<DeckEvent> {
+        self.tx.subscribe()
+    }
+
+    /// The event bus itself, to send any `DeckEvent`.
+    #[must_use]
+    pub fn events(&self) -> broadcast::Sender<DeckEvent> {
+        self.tx.clone()
+    }
 
-    let config_dir = config_path
-        .parent()
-        .map_or_else(|| PathBuf::from("."), PathBuf::from);
+    /// Render every key of a page to RGBA with fresh HA states, without
+    /// touching the device. Keys render at the connected deck's native key
+    /// size (72x72 while none is). Automatic theme switching is not applied.
+    ///
+    /// # Errors
+    /// Returns `DeckError::PageNotFound` if the page does not exist.
+    pub async fn render_page_to_images(&self, page_id: &str) -> Result<Vec<(u8, Vec<u8>)>> {
+        let config = self.config.load();
+        let page = config
+            .pages
+            .get(page_id)
+            .ok_or_else(|| DeckError::PageNotFound(page_id.to_string()))?;
+        let entities = collect_state_entities(&config, page_id);
+        let (states, _) = crate::state::fetch_states_checked(&entities).await;
+        Ok(render_page_images(
+            &self.render,
+            page_id,
+            page,
+            &self.render.defaults(&config),
+            &states,
+        ))
+    }
 
-    let device_handle = spawn_device_manager(&tx, &cancel, &shared_config, &deck_handle);
-    let watcher_handle = spawn_config_watcher(&tx, &cancel, &config_path);
+    /// Wait until the daemon stops by itself (a `Shutdown` event).
+    pub async fn stopped(&self) {
+        self.cancel.cancelled().await;
+    }
 
-    let mut rx = tx.subscribe();
-    let event_tx = tx.clone();
+    /// Stop everything: save state, show the goodbye screen, turn the
+    /// display off and wait (bounded) for the subsystems to finish.
+    pub async fn shutdown(self) {
+        self.cancel.cancel();
+        let _ = self.task.await;
+        for device in self.devices {
+            let _ = device.await;
+        }
+    }
+}
 
-    // Cached HA entity states for optimistic rendering on button press.
-    let last_states: Arc<std::sync::Mutex<HashMap<String, String>>> =
-        Arc::new(std::sync::Mutex::new(HashMap::new()));
+/// Drive the event loop until cancelled, then shut the subsystems down.
+async fn run_event_loop(
+    mut event_loop: EventLoop,
+    mut rx: broadcast::Receiver<DeckEvent>,
+    cancel: CancellationToken,
+    ready_handle: Option<tokio::task::JoinHandle<()>>,
+    handles: Vec<tokio::task::JoinHandle<()>>,
+) {
+    let tx = event_loop.tx.clone();
+    let usage = Arc::clone(&event_loop.usage);
 
-    // Periodic state poll interval (re-render to reflect HA state changes).
+    // Periodic state poll interval (re-render to reflect HA state and clock changes).
     let mut state_poll = tokio::time::interval(std::time::Duration::from_secs(5));
     state_poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut usage_flush = tokio::time::interval(USAGE_FLUSH_INTERVAL);
 
     info!(
         "deckd daemon running, home page: {}",
-        page_manager.current_page()
+        event_loop.page_manager.current_page()
     );
 
     loop {
+        crate::health::event_loop_beat();
         let event = tokio::select! {
             () = cancel.cancelled() => break,
-            () = async { tokio::signal::ctrl_c().await.ok(); } => {
-                info!("received SIGINT, shutting down");
-                cancel.cancel();
-                break;
-            }
             _ = state_poll.tick() => {
-                // Check if any buttons on the current page use state_entity.
-                let config = shared_config.load();
-                let page_id = page_manager.current_page();
-                let has_stateful = config.pages.get(page_id).is_some_and(|p| {
-                    p.buttons.iter().any(|b| b.state_entity.is_some())
-                });
-                if has_stateful {
+                if event_loop.current_page_is_dynamic() && event_loop.poll_due() {
                     let _ = tx.send(DeckEvent::RenderAll);
                 }
                 continue;
             }
+            _ = usage_flush.tick() => {
+                save_usage(&usage);
+                continue;
+            }
             event = rx.recv() => {
                 match event {
                     Ok(e) => e,
@@ -83,32 +388,65 @@ pub async fn run(config: AppConfig, config_path: PathBuf) -> Result<()> {
             }
         };
 
-        if handle_event(
-            event,
-            &shared_config,
-            &mut page_manager,
-            &tx,
-            &event_tx,
-            &deck_handle,
-            &config_dir,
-            &last_states,
-        ) {
-            cancel.cancel();
+        if event_loop.handle_event(event) {
             break;
         }
     }
 
     info!("daemon shutting down...");
     cancel.cancel();
+    if let Some(ready_handle) = ready_handle {
+        ready_handle.abort();
+    }
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]);
+    save_usage(&usage);
+
+    let config = event_loop.shared_config.load();
+    let _ = tokio::time::timeout(
+        std::time::Duration::from_secs(3),
+        show_goodbye(&event_loop.render, &config),
+    )
+    .await;
 
     let _ = tokio::time::timeout(std::time::Duration::from_secs(5), async {
-        let _ = device_handle.await;
-        let _ = watcher_handle.await;
+        for handle in handles {
+            let _ = handle.await;
+        }
     })
     .await;
 
     info!("daemon stopped");
-    Ok(())
+}
+
+/// Write press counts to disk if they changed.
+fn save_usage(usage: &Mutex<UsageStats>) {
+    if let Err(e) = usage.lock().unwrap().save() {
+        warn!("failed to save usage stats: {e}");
+    }
+}
+
+/// Tell systemd the daemon is ready once a deck connects, or when the startup
+/// grace period ends without one. A no-op when not run under systemd.
+async fn notify_ready(mut rx: broadcast::Receiver<DeckEvent>, grace: std::time::Duration) {
+    let connected = tokio::time::timeout(grace, async {
+        loop {
+            match rx.recv().await {
+                Ok(DeckEvent::DeviceConnected) => return,
+                Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+                _ => {}
+            }
+        }
+    })
+    .await;
+    if connected.is_err() {
+        info!(
+            "no Stream Deck within {}s of startup, reporting ready anyway",
+            grace.as_secs()
+        );
+    }
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]) {
+        warn!("failed to notify systemd: {e}");
+    }
 }
 
 fn spawn_device_manager(
@@ -116,19 +454,131 @@ fn spawn_device_manager(
     cancel: &CancellationToken,
     config: &Arc<ArcSwap<AppConfig>>,
     deck_handle: &DeckHandle,
+    device_status: &DeviceStatusHandle,
+    selector: DeviceSelector,
 ) -> tokio::task::JoinHandle<()> {
     let device_tx = tx.clone();
     let device_cancel = cancel.clone();
-    let reconnect_ms = config.load().deckd.reconnect_interval_ms;
+    let policy = ReconnectPolicy::from_config(&config.load().deckd);
+    let remote = config.load().deckd.remote.clone();
     let handle = Arc::clone(deck_handle);
+    let status = Arc::clone(device_status);
     tokio::spawn(async move {
-        let dm = DeviceManager::new(device_tx, device_cancel, reconnect_ms, handle);
+        let dm = DeviceManager::new(device_tx, device_cancel, policy, handle, status)
+            .with_remote(remote)
+            .with_selector(selector);
         if let Err(e) = dm.run().await {
             error!("device manager error: {e}");
         }
     })
 }
 
+/// Drive the `[devices]` deck `serial` with an event loop of its own: its
+/// own bus, page stack and copy of the config (buttons are placed on its
+/// grid), fed the daemon-wide events of the main bus. Usage counts and
+/// scenes are shared.
+fn spawn_device(
+    serial: String,
+    config: &AppConfig,
+    config_dir: &std::path::Path,
+    main_tx: &broadcast::Sender<DeckEvent>,
+    cancel: &CancellationToken,
+    usage: &Arc<Mutex<UsageStats>>,
+    scenes: &Arc<Mutex<SceneStore>>,
+) -> tokio::task::JoinHandle<()> {
+    let (tx, rx) = broadcast::channel::<DeckEvent>(CHANNEL_CAPACITY);
+    let cancel = cancel.child_token();
+    let shared_config = Arc::new(ArcSwap::from_pointee(config.clone()));
+    let deck_handle = crate::device::new_deck_handle();
+    let selector = DeviceSelector {
+        serial: Some(serial.clone()),
+        ..DeviceSelector::default()
+    };
+    let handles = vec![
+        spawn_device_manager(
+            &tx,
+            &cancel,
+            &shared_config,
+            &deck_handle,
+            &Arc::default(),
+            selector,
+        ),
+        tokio::spawn(forward_shared_events(
+            main_tx.subscribe(),
+            tx.clone(),
+            cancel.clone(),
+        )),
+    ];
+    let home = home_page(config, Some(&serial));
+    let render = RenderContext::new(deck_handle, config_dir.to_path_buf(), tx.clone());
+    let event_loop = EventLoop::new(
+        shared_config,
+        tx,
+        render,
+        Arc::new(ArcSwap::from_pointee(home)),
+        Arc::clone(usage),
+        Arc::clone(scenes),
+        Some(serial),
+    );
+    tokio::spawn(run_event_loop(event_loop, rx, cancel, None, handles))
+}
+
+/// Pass the events that concern every deck from the main bus to a
+/// `[devices]` deck: config reloads, themes, problems, alerts, quiet hours,
+/// guest mode, sleep and wake, diagnostics and shutdown.
+async fn forward_shared_events(
+    mut rx: broadcast::Receiver<DeckEvent>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    loop {
+        let event = tokio::select! {
+            () = cancel.cancelled() => return,
+            event = rx.recv() => match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+        let shared = matches!(
+            event,
+            DeckEvent::ConfigReloaded(_)
+                | DeckEvent::ThemeChanged(_)
+                | DeckEvent::Issue(_)
+                | DeckEvent::Alert(_)
+                | DeckEvent::Flash(_, None)
+                | DeckEvent::Quiet(_)
+                | DeckEvent::ScheduledBrightness(_)
+                | DeckEvent::SetGuest(_)
+                | DeckEvent::DisplaySleep
+                | DeckEvent::DisplayWake
+                | DeckEvent::Diagnostics(_)
+                | DeckEvent::Shutdown
+        );
+        if shared {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+/// Which deck the main event loop drives: as pinned by `device_serial` and
+/// `device_kind`, and none of the `[devices]` decks.
+fn main_selector(config: &AppConfig) -> DeviceSelector {
+    DeviceSelector {
+        serial: config.deckd.device_serial.clone(),
+        kind: config.deckd.device_kind.clone(),
+        exclude: config.devices.keys().cloned().collect(),
+    }
+}
+
+/// Home page of the `[devices]` deck `device`, or of the main deck.
+fn home_page(config: &AppConfig, device: Option<&str>) -> PageId {
+    device
+        .and_then(|serial| config.devices.get(serial))
+        .map_or(&config.deckd.home_page, |d| &d.home_page)
+        .clone()
+}
+
 fn spawn_config_watcher(
     tx: &broadcast::Sender<DeckEvent>,
     cancel: &CancellationToken,
@@ -137,322 +587,2242 @@ fn spawn_config_watcher(
     let watcher_tx = tx.clone();
     let watcher_cancel = cancel.clone();
     let watcher_path = config_path.to_path_buf();
+    crate::health::watcher_alive(true);
     tokio::spawn(async move {
         if let Err(e) = watcher::watch_config(watcher_path, watcher_tx, watcher_cancel).await {
             error!("config watcher error: {e}");
         }
+        crate::health::watcher_alive(false);
     })
 }
 
-/// Handle a single event. Returns `true` if the daemon should shut down.
-fn handle_event(
-    event: DeckEvent,
-    shared_config: &Arc<ArcSwap<AppConfig>>,
-    page_manager: &mut PageManager,
-    tx: &broadcast::Sender<DeckEvent>,
-    event_tx: &broadcast::Sender<DeckEvent>,
-    deck_handle: &DeckHandle,
-    config_dir: &std::path::Path,
-    last_states: &Arc<std::sync::Mutex<HashMap<String, String>>>,
-) -> bool {
-    match event {
-        DeckEvent::ButtonDown(key) => {
-            let config = shared_config.load();
-            if let Some(button) = page_manager.button_for_key(&config, key) {
-                // Optimistic render: immediately flip the cached visual state.
-                if let Some(ref entity_id) = button.state_entity {
-                    let mut cache = last_states.lock().unwrap();
-                    let current = cache.get(entity_id).map(|s| s.as_str());
-                    let flipped = match current {
-                        Some("on") => "off",
-                        _ => "on",
-                    };
-                    cache.insert(entity_id.clone(), flipped.to_string());
-                    let states = cache.clone();
-                    drop(cache);
-
-                    let button = button.clone();
-                    let defaults = config.deckd.defaults.clone();
-                    let handle = Arc::clone(deck_handle);
-                    let dir = config_dir.to_path_buf();
-                    tokio::spawn(async move {
-                        render_single_button_with_states(
-                            &button, &defaults, &handle, &dir, key, &states,
-                        )
-                        .await;
-                    });
-                }
+/// Shared handles needed by spawned render tasks.
+#[derive(Clone)]
+struct RenderContext {
+    deck_handle: DeckHandle,
+    config_dir: PathBuf,
+    /// Cached HA entity states for optimistic rendering on button press.
+    last_states: Arc<Mutex<HashMap<String, String>>>,
+    /// Recent values of entities drawn as sparklines.
+    history: Arc<Mutex<StateHistory>>,
+    /// Rendered pages for instant page switches.
+    page_cache: Arc<Mutex<PageCache>>,
+    /// Active theme, snapshotted into each render task.
+    theme: Option<String>,
+    /// Locale for label placeholders, from `deckd.locale`.
+    locale: &'static Locale,
+    /// Unacknowledged problems shown on the status tile.
+    status: Arc<Mutex<StatusLog>>,
+    /// Where the status is shown, from `deckd.status`.
+    status_tile: Option<StatusTile>,
+    /// Temporary key content pushed from outside, drawn over every page.
+    overrides: Arc<Mutex<KeyOverrideStore>>,
+    /// Notification banners; while one shows, page renders leave the top row alone.
+    toasts: Arc<Mutex<ToastQueue>>,
+    /// Keys show their index, page and binding instead of the page.
+    diagnostics: bool,
+    /// Running and finished actions of buttons with `track = true`.
+    jobs: Arc<Mutex<JobTracker>>,
+    /// Indicator style for tracked actions, from `deckd.track`.
+    track: TrackConfig,
+    /// Quiet hours: no page transitions or pre-rendering.
+    quiet: bool,
+    /// Guest mode, while it is on.
+    guest: Option<Guests>,
+    /// How pages are written, from `deckd.flush`.
+    flush: FlushConfig,
+    /// For reporting HA problems found while rendering.
+    tx: broadcast::Sender<DeckEvent>,
+}
 
-                if let Some(ref action) = button.on_press {
-                    let action = action.clone();
-                    let action_tx = event_tx.clone();
-                    let has_state = button.state_entity.is_some();
-                    let render_tx = tx.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = crate::action::execute(&action, &action_tx).await {
-                            error!("action error (key {key}): {e}");
-                        }
-                        // Wait for HA to process the state change before syncing.
-                        if has_state {
-                            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-                            let _ = render_tx.send(DeckEvent::RenderAll);
-                        }
-                    });
-                }
-            }
+impl RenderContext {
+    fn new(deck_handle: DeckHandle, config_dir: PathBuf, tx: broadcast::Sender<DeckEvent>) -> Self {
+        Self {
+            deck_handle,
+            config_dir,
+            last_states: Arc::new(Mutex::new(HashMap::new())),
+            history: Arc::new(Mutex::new(StateHistory::default())),
+            page_cache: Arc::new(Mutex::new(PageCache::new(PAGE_CACHE_SIZE))),
+            theme: None,
+            locale: crate::render::locale::resolve(crate::render::locale::DEFAULT_LOCALE),
+            status: Arc::new(Mutex::new(StatusLog::new())),
+            status_tile: None,
+            overrides: Arc::new(Mutex::new(KeyOverrideStore::new())),
+            toasts: Arc::new(Mutex::new(ToastQueue::new())),
+            diagnostics: false,
+            jobs: Arc::new(Mutex::new(JobTracker::new())),
+            track: TrackConfig::default(),
+            quiet: false,
+            guest: None,
+            flush: FlushConfig::default(),
+            tx,
         }
+    }
 
-        DeckEvent::ButtonUp(_) => {}
+    /// Keys of the connected deck, or of the default grid while none is.
+    fn key_count(&self) -> u8 {
+        self.deck_handle
+            .load()
+            .as_deref()
+            .map_or(crate::config::DEFAULT_GRID.keys(), |deck| {
+                deck.capabilities().keys
+            })
+    }
 
-        DeckEvent::DeviceConnected => {
-            info!("device connected, rendering all buttons");
-            // Set brightness on connect.
-            let brightness = shared_config.load().deckd.brightness;
-            let handle = Arc::clone(deck_handle);
-            tokio::spawn(async move {
-                if let Some(deck) = handle.load().as_deref() {
-                    if let Err(e) = deck.set_brightness(brightness).await {
-                        warn!("failed to set brightness: {e}");
-                    }
-                }
-            });
-            let _ = tx.send(DeckEvent::RenderAll);
+    /// Side of the key images of the connected deck, or of an MK.2's while
+    /// none is.
+    fn key_size(&self) -> u32 {
+        self.deck_handle
+            .load()
+            .as_deref()
+            .map_or(crate::render::canvas::BUTTON_SIZE, key_size)
+    }
+
+    /// The page transition to animate, none in quiet hours.
+    fn transition(&self, config: &AppConfig) -> Transition {
+        if self.quiet {
+            Transition::None
+        } else {
+            config.deckd.transition
         }
+    }
 
-        DeckEvent::DeviceDisconnected => {
-            info!("device disconnected, waiting for reconnect...");
+    /// Pick up render and action settings from a (re)loaded config.
+    fn apply_config(&mut self, config: &AppConfig) {
+        crate::action::limit::configure(&config.deckd.actions);
+        crate::action::shell::configure(config.deckd.shell_sandbox.as_ref());
+        crate::action::policy::configure(&config.deckd.policy);
+        crate::action::http::configure(config.deckd.proxy.as_ref());
+        crate::action::dns::configure(&config.deckd.dns);
+        crate::render::calibrate::configure(&config.deckd.calibration);
+        self.locale = crate::render::locale::resolve(&config.deckd.locale);
+        self.status_tile.clone_from(&config.deckd.status);
+        self.track.clone_from(&config.deckd.track);
+        self.flush = config.deckd.flush;
+        if self.guest.is_some() {
+            self.guest = Guests::new(config);
         }
+    }
 
-        DeckEvent::ConfigReloaded(new_config) => {
-            shared_config.store(new_config);
-            let config = shared_config.load();
-            page_manager.set_home_page(&config.deckd.home_page);
-            if !config.pages.contains_key(page_manager.current_page()) {
-                page_manager.go_home();
-            }
-            let _ = tx.send(DeckEvent::RenderAll);
+    /// `button` with the recorded values of its entity, if it draws a
+    /// sparkline.
+    fn with_history<'a>(&self, button: Cow<'a, ButtonConfig>) -> Cow<'a, ButtonConfig> {
+        let Some(entity) = button.state_entity.as_deref().filter(|_| button.sparkline) else {
+            return button;
+        };
+        let history = self.history.lock().unwrap().get(entity);
+        let mut button = button;
+        button.to_mut().history = history;
+        button
+    }
+
+    /// What the status display shows at `key` of `page_id`, if anything.
+    fn status_slot(&self, page_id: &str, key: KeyIndex) -> Option<StatusSlot> {
+        self.status_tile
+            .as_ref()
+            .and_then(|tile| tile.slot(page_id, key))
+    }
+
+    /// The override drawn at `key`, if any. When one has just expired the
+    /// page cache is dropped so cached pages stop showing it.
+    fn override_button(&self, key: KeyIndex) -> Option<ButtonConfig> {
+        let now = std::time::Instant::now();
+        let mut overrides = self.overrides.lock().unwrap();
+        if overrides.expire(now) {
+            self.page_cache.lock().unwrap().clear();
         }
+        overrides.button(key, now)
+    }
 
-        DeckEvent::NavigateTo(page_id) => {
-            let config = shared_config.load();
-            if config.pages.contains_key(&page_id) {
-                page_manager.navigate_to(&page_id);
-                let _ = tx.send(DeckEvent::RenderAll);
-            } else {
-                warn!("page not found: {page_id}");
+    /// Whether a notification banner covers `key`.
+    fn under_toast(&self, deck: &Deck, key: u8) -> bool {
+        self.toasts.lock().unwrap().current().is_some() && u32::from(key) < grid(deck).columns
+    }
+
+    /// Push a page render, skipping keys under a notification banner.
+    async fn push_page(&self, deck: &Deck, images: &[(u8, Vec<u8>)]) {
+        if self.flush.mode == FlushMode::Key {
+            for (key, rgba) in images {
+                self.push_key(deck, *key, rgba).await;
             }
+            return;
         }
+        let visible: Vec<(u8, &[u8])> = images
+            .iter()
+            .filter(|(key, _)| !self.under_toast(deck, *key))
+            .map(|(key, rgba)| (*key, rgba.as_slice()))
+            .collect();
+        if let Err(e) = deck.set_keys_rgba(&visible, key_size(deck)).await {
+            warn!("failed to set page images: {e}");
+        }
+    }
 
-        DeckEvent::NavigateBack => {
-            if page_manager.go_back() {
-                let _ = tx.send(DeckEvent::RenderAll);
+    /// Push a page render after the frames of `transition` from the
+    /// outgoing page's render, if there is one.
+    async fn push_page_from(
+        &self,
+        deck: &Deck,
+        from: Option<PageImages>,
+        images: &[(u8, Vec<u8>)],
+        transition: Transition,
+    ) {
+        if let Some(from) = from {
+            let frames = crate::render::transition::frames(
+                transition,
+                &from,
+                images,
+                grid(deck),
+                key_size(deck),
+            );
+            let mut pace = (self.flush.frame_ms > 0).then(|| {
+                tokio::time::interval(std::time::Duration::from_millis(self.flush.frame_ms))
+            });
+            for frame in frames {
+                if let Some(ref mut pace) = pace {
+                    pace.tick().await;
+                }
+                self.push_page(deck, &frame).await;
+            }
+            if let Some(ref mut pace) = pace {
+                pace.tick().await;
             }
         }
+        self.push_page(deck, images).await;
+    }
 
-        DeckEvent::NavigateHome => {
-            page_manager.go_home();
-            let _ = tx.send(DeckEvent::RenderAll);
+    /// Push one key render unless a notification banner covers it.
+    async fn push_key(&self, deck: &Deck, key: u8, rgba: &[u8]) {
+        if !self.under_toast(deck, key) {
+            push_key_image(deck, key, rgba).await;
         }
+    }
 
-        DeckEvent::RenderAll => {
-            let config = shared_config.load();
-            let page_id = page_manager.current_page().to_string();
-            if let Some(page) = config.pages.get(&page_id) {
-                info!(
-                    "rendering page '{}' ({} buttons)",
-                    page.name,
-                    page.buttons.len()
-                );
-                let config = Arc::clone(&config);
-                let handle = Arc::clone(deck_handle);
-                let dir = config_dir.to_path_buf();
-                let cache = Arc::clone(last_states);
-                tokio::spawn(async move {
-                    render_all_buttons(&config, &page_id, &handle, &dir, &cache).await;
-                });
+    /// Draw the indicator of a tracked action at `key` of `page_id`, if any.
+    fn draw_job(&self, page_id: &str, key: KeyIndex, rgba: &mut [u8]) {
+        let state = self
+            .jobs
+            .lock()
+            .unwrap()
+            .state(page_id, key, std::time::Instant::now());
+        if let Some(state) = state {
+            if let Err(e) =
+                crate::render::progress::overlay(rgba, self.key_size(), state, &self.track)
+            {
+                warn!("job indicator render error (key {key}): {e}");
             }
         }
+    }
 
-        DeckEvent::RenderButton(key) => {
-            let config = shared_config.load();
-            if let Some(button) = page_manager.button_for_key(&config, key) {
-                let button = button.clone();
-                let defaults = config.deckd.defaults.clone();
-                let handle = Arc::clone(deck_handle);
-                let dir = config_dir.to_path_buf();
-                tokio::spawn(async move {
-                    render_single_button(&button, &defaults, &handle, &dir, key).await;
-                });
-            }
-        }
+    /// Whether a tracked action at `key` of `page_id` is running or showing
+    /// its result.
+    fn has_job(&self, page_id: &str, key: KeyIndex) -> bool {
+        self.jobs
+            .lock()
+            .unwrap()
+            .state(page_id, key, std::time::Instant::now())
+            .is_some()
+    }
 
-        DeckEvent::Shutdown => {
-            info!("shutdown event received");
-            return true;
-        }
+    /// Diagnostic renders of every key of `page_id`.
+    fn diagnostic_images(&self, config: &AppConfig, page_id: &str) -> Vec<(u8, Vec<u8>)> {
+        use crate::render::diagnostic::{render_key_info, Binding};
+
+        let defaults = self.defaults(config);
+        let page = config.pages.get(page_id);
+        let size = self.key_size();
+        let overrides = self.overrides.lock().unwrap();
+        KeyIndex::all(self.key_count())
+            .filter_map(|key| {
+                let binding = if overrides.contains(key) {
+                    Binding::Override
+                } else if self.status_slot(page_id, key).is_some() {
+                    Binding::Status
+                } else {
+                    page.and_then(|p| p.buttons.iter().find(|b| b.key == key))
+                        .map_or(Binding::Empty, Binding::Button)
+                };
+                render_key_info(key.get(), page_id, binding, &defaults, size)
+                    .map_err(|e| warn!("diagnostic render error (key {key}): {e}"))
+                    .ok()
+                    .map(|rgba| (key.get(), rgba))
+            })
+            .collect()
     }
 
-    false
+    /// Button defaults with the active theme applied.
+    fn defaults(&self, config: &AppConfig) -> ButtonDefaults {
+        config.button_defaults(self.theme.as_deref())
+    }
 }
 
-/// Collect state_entity IDs from all buttons on a page.
-fn collect_state_entities(config: &AppConfig, page_id: &str) -> Vec<String> {
-    config
-        .pages
-        .get(page_id)
-        .map(|page| {
-            page.buttons
-                .iter()
-                .filter_map(|b| b.state_entity.clone())
-                .collect()
-        })
-        .unwrap_or_default()
+/// State owned by the main event loop.
+struct EventLoop {
+    shared_config: Arc<ArcSwap<AppConfig>>,
+    page_manager: PageManager,
+    tx: broadcast::Sender<DeckEvent>,
+    render: RenderContext,
+    /// Brightness last sent to the device, `None` until set after a connect.
+    brightness: Option<u8>,
+    /// Brightness targets for the fading controller task.
+    brightness_tx: tokio::sync::watch::Sender<Option<brightness::Target>>,
+    /// Brightness pinned through `SetBrightness`, over page and config.
+    brightness_override: Option<u8>,
+    /// Level of `[deckd.brightness_schedule]` in effect, if any.
+    scheduled_brightness: Option<u8>,
+    /// Display turned off by a `DisplaySleep` action or presence binding.
+    asleep: bool,
+    /// Page whose images were last pushed to the device.
+    shown_page: Option<PageId>,
+    /// Current page, published for the control API.
+    current_page: Arc<ArcSwap<PageId>>,
+    /// Page renders are held back until the connect splash has been shown.
+    splash_until: Option<tokio::time::Instant>,
+    /// Press counts per button.
+    usage: Arc<Mutex<UsageStats>>,
+    /// Modifier keys held down for chords.
+    chords: ChordState,
+    /// PIN prompt shown instead of the page.
+    pin: Option<PinEntry>,
+    /// Id of the last PIN prompt.
+    pin_seq: u64,
+    /// Grid of the last connected deck, which `pos` buttons are placed on.
+    grid: Grid,
+    /// Code being entered while a keypad page is shown.
+    keypad: Option<KeypadEntry>,
+    /// Snapshots saved by `scene_save`, shared by all decks.
+    scenes: Arc<Mutex<SceneStore>>,
+    /// Serial of the `[devices]` deck this loop drives, `None` for the main
+    /// deck.
+    device: Option<String>,
+    /// Page opened by the alert taking over the deck.
+    alert_page: Option<PageId>,
+    /// Page opened for quiet hours, the only one shown until they end.
+    quiet_page: Option<PageId>,
+    /// When the state poll last re-rendered the page.
+    polled: std::time::Instant,
 }
 
-/// Render all 15 buttons to the device. Fetches HA states first for stateful buttons.
-/// Updates the shared state cache with fresh values from HA.
-async fn render_all_buttons(
-    config: &AppConfig,
-    page_id: &str,
-    deck_handle: &DeckHandle,
-    config_dir: &std::path::Path,
-    state_cache: &std::sync::Mutex<HashMap<String, String>>,
-) {
-    let page = match config.pages.get(page_id) {
-        Some(p) => p,
-        None => return,
-    };
-
-    let entities = collect_state_entities(config, page_id);
-    let entity_states = crate::state::fetch_ha_states(&entities).await;
+impl EventLoop {
+    fn new(
+        shared_config: Arc<ArcSwap<AppConfig>>,
+        tx: broadcast::Sender<DeckEvent>,
+        mut render: RenderContext,
+        current_page: Arc<ArcSwap<PageId>>,
+        usage: Arc<Mutex<UsageStats>>,
+        scenes: Arc<Mutex<SceneStore>>,
+        device: Option<String>,
+    ) -> Self {
+        let page_manager = PageManager::new(&home_page(&shared_config.load(), device.as_deref()));
+        render.apply_config(&shared_config.load());
+        let brightness_tx = brightness::spawn_controller(Arc::clone(&render.deck_handle));
+        let scheduled_brightness =
+            brightness::scheduled_level(&shared_config.load().deckd, TimeOfDay::now());
+        Self {
+            shared_config,
+            page_manager,
+            tx,
+            render,
+            brightness: None,
+            brightness_tx,
+            brightness_override: None,
+            scheduled_brightness,
+            asleep: false,
+            shown_page: None,
+            current_page,
+            splash_until: None,
+            usage,
+            chords: ChordState::new(),
+            pin: None,
+            pin_seq: 0,
+            grid: crate::config::DEFAULT_GRID,
+            keypad: None,
+            scenes,
+            device,
+            alert_page: None,
+            quiet_page: None,
+            polled: std::time::Instant::now(),
+        }
+    }
 
-    // Update the cache with fresh HA values.
-    if let Ok(mut cache) = state_cache.lock() {
-        for (k, v) in &entity_states {
-            cache.insert(k.clone(), v.clone());
+    /// `config` with its `pos` buttons placed on the connected deck's grid.
+    /// Loading places them on the default grid, so that needs no copy.
+    fn place_buttons(&self, config: Arc<AppConfig>) -> Arc<AppConfig> {
+        let positioned = config
+            .pages
+            .values()
+            .any(|p| p.buttons.iter().any(|b| b.pos.is_some()));
+        if !positioned || self.grid == crate::config::DEFAULT_GRID {
+            return config;
+        }
+        let mut placed = AppConfig::clone(&config);
+        match crate::config::resolve_positions(&mut placed, self.grid) {
+            Ok(()) => Arc::new(placed),
+            Err(e) => {
+                warn!("{e}");
+                let issue = Issue::from_error(IssueSource::Device, &e);
+                let _ = self.tx.send(DeckEvent::Issue(issue));
+                config
+            }
         }
     }
 
-    let defaults = &config.deckd.defaults;
-    let handle = Arc::clone(deck_handle);
+    /// Whether any button on the current page tracks an entity state or
+    /// shows the clock, and so needs periodic re-rendering.
+    fn current_page_is_dynamic(&self) -> bool {
+        let config = self.shared_config.load();
+        self.page_manager
+            .current_page_config(&config)
+            .is_some_and(|p| {
+                p.picker.is_some()
+                    || p.snapcast.is_some()
+                    || p.locked.is_some()
+                    || p.locked_if.is_some()
+                    || p.buttons.iter().any(|b| {
+                        b.state_entity.is_some()
+                            || crate::lock::is_conditional(b)
+                            || crate::template::is_dynamic(b)
+                            || b.label.as_ref().is_some_and(|l| {
+                                l.texts().into_iter().any(crate::render::format::has_clock)
+                            })
+                    })
+            })
+    }
 
-    let mut images: Vec<(u8, image::DynamicImage)> = Vec::with_capacity(NUM_KEYS as usize);
+    /// Brightness of pages without their own: the deck's, the scheduled
+    /// one, or `deckd.brightness`.
+    fn default_brightness(&self, config: &AppConfig) -> u8 {
+        self.device
+            .as_ref()
+            .and_then(|serial| config.devices.get(serial)?.brightness)
+            .or(self.scheduled_brightness)
+            .unwrap_or(config.deckd.brightness)
+    }
 
-    for key in 0..NUM_KEYS {
-        let button = page.buttons.iter().find(|b| b.key == key);
-        let rgba_data = match button {
-            Some(btn) => match crate::render::render_button(btn, defaults, config_dir, &entity_states) {
-                Ok(data) => data,
-                Err(e) => {
-                    warn!("render error (key {key}): {e}");
-                    continue;
-                }
-            },
-            None => match crate::render::render_blank() {
-                Ok(data) => data,
-                Err(e) => {
-                    warn!("render blank error (key {key}): {e}");
-                    continue;
-                }
-            },
+    /// Apply the current page's brightness (or the global default) if it differs
+    /// from what the device is already set to.
+    fn apply_brightness(&mut self) {
+        let config = self.shared_config.load();
+        let quiet = config.deckd.quiet.as_ref().filter(|_| self.render.quiet);
+        let target = if self.asleep {
+            0
+        } else if let Some(level) = self.brightness_override {
+            level
+        } else if let Some(quiet) = quiet {
+            quiet.brightness
+        } else {
+            self.page_manager
+                .current_page_config(&config)
+                .and_then(|p| p.brightness)
+                .unwrap_or_else(|| self.default_brightness(&config))
         };
 
-        if let Some(img_buf) =
-            image::RgbaImage::from_raw(crate::render::canvas::BUTTON_SIZE, crate::render::canvas::BUTTON_SIZE, rgba_data)
-        {
-            images.push((key, image::DynamicImage::from(img_buf)));
+        if self.brightness == Some(target) {
+            return;
         }
-    }
-
-    let guard = handle.load();
-    let Some(deck) = guard.as_deref() else {
-        return;
-    };
-    for (key, img) in images {
-        if let Err(e) = deck.set_button_image(key, img).await {
-            warn!("failed to set button image (key {key}): {e}");
+        if self.render.deck_handle.load().is_none() {
+            return;
         }
+
+        debug!("setting brightness to {target}");
+        let _ = self.brightness_tx.send(Some(brightness::Target {
+            level: target,
+            ramp: std::time::Duration::from_millis(if self.render.quiet {
+                0
+            } else {
+                config.deckd.brightness_ramp_ms
+            }),
+            // Right after a connect the device's level is unknown.
+            immediate: self.brightness.is_none(),
+        }));
+        self.brightness = Some(target);
     }
-    if let Err(e) = deck.flush().await {
-        warn!("failed to flush button images: {e}");
-    }
-}
 
-/// Render a single button with pre-supplied entity states (no HA fetch).
-/// Used for optimistic rendering on button press.
-async fn render_single_button_with_states(
-    button: &crate::config::schema::ButtonConfig,
-    defaults: &crate::config::schema::ButtonDefaults,
-    deck_handle: &DeckHandle,
-    config_dir: &std::path::Path,
-    key: u8,
-    entity_states: &HashMap<String, String>,
-) {
-    let rgba_data = match crate::render::render_button(button, defaults, config_dir, entity_states) {
-        Ok(data) => data,
-        Err(e) => {
-            warn!("render error (key {key}): {e}");
-            return;
-        }
-    };
+    /// Handle a single event. Returns `true` if the daemon should shut down.
+    fn handle_event(&mut self, event: DeckEvent) -> bool {
+        match event {
+            DeckEvent::ButtonDown(_) if self.asleep => {
+                // The first press only wakes the display.
+                let _ = self.tx.send(DeckEvent::DisplayWake);
+            }
 
-    let Some(img_buf) = image::RgbaImage::from_raw(
-        crate::render::canvas::BUTTON_SIZE,
-        crate::render::canvas::BUTTON_SIZE,
-        rgba_data,
-    ) else {
-        return;
-    };
+            DeckEvent::ButtonDown(key) => self.handle_button_down(key),
 
-    let img = image::DynamicImage::from(img_buf);
-    let guard = deck_handle.load();
-    let Some(deck) = guard.as_deref() else {
-        return;
-    };
-    if let Err(e) = deck.set_button_image(key, img).await {
-        warn!("failed to set button image (key {key}): {e}");
-    }
-    if let Err(e) = deck.flush().await {
-        warn!("failed to flush button image: {e}");
-    }
-}
+            DeckEvent::ButtonUp(key) => self.handle_button_up(key),
 
-/// Render a single button to the device. Fetches HA state if needed.
-async fn render_single_button(
-    button: &crate::config::schema::ButtonConfig,
-    defaults: &crate::config::schema::ButtonDefaults,
-    deck_handle: &DeckHandle,
-    config_dir: &std::path::Path,
-    key: u8,
-) {
-    let entities: Vec<String> = button.state_entity.iter().cloned().collect();
-    let entity_states = crate::state::fetch_ha_states(&entities).await;
+            DeckEvent::DialRotate(..) | DeckEvent::DialPress(_) if self.asleep => {
+                let _ = self.tx.send(DeckEvent::DisplayWake);
+            }
 
-    let rgba_data = match crate::render::render_button(button, defaults, config_dir, &entity_states) {
-        Ok(data) => data,
-        Err(e) => {
-            warn!("render error (key {key}): {e}");
-            return;
-        }
-    };
+            DeckEvent::DialRotate(dial, delta) => self.run_dial(dial, Some(delta)),
 
-    let Some(img_buf) = image::RgbaImage::from_raw(
-        crate::render::canvas::BUTTON_SIZE,
-        crate::render::canvas::BUTTON_SIZE,
-        rgba_data,
-    ) else {
-        return;
-    };
+            DeckEvent::DialPress(dial) => self.run_dial(dial, None),
 
-    let img = image::DynamicImage::from(img_buf);
-    let guard = deck_handle.load();
-    let Some(deck) = guard.as_deref() else {
-        return;
-    };
-    if let Err(e) = deck.set_button_image(key, img).await {
-        warn!("failed to set button image (key {key}): {e}");
-    }
-    if let Err(e) = deck.flush().await {
-        warn!("failed to flush button image: {e}");
+            DeckEvent::RawInput(input) => {
+                debug!("unhandled device input: {input:?}");
+            }
+
+            DeckEvent::DeviceConnected => {
+                info!("device connected, rendering all buttons");
+                if let Some(deck) = Option::clone(&self.render.deck_handle.load()) {
+                    let grid = grid(&deck);
+                    if grid != self.grid {
+                        self.grid = grid;
+                        let config = self.place_buttons(self.shared_config.load_full());
+                        self.shared_config.store(config);
+                    }
+                    // Cached renders may be for another model's key size.
+                    self.render.page_cache.lock().unwrap().clear();
+                    let off = crate::config::keys_off_deck(&self.shared_config.load(), grid.keys());
+                    if !off.is_empty() {
+                        let message = format!(
+                            "{} button(s) not on this {}-key deck: {}",
+                            off.len(),
+                            grid.keys(),
+                            off.join(", ")
+                        );
+                        warn!("{message}");
+                        let _ = self
+                            .tx
+                            .send(DeckEvent::Issue(Issue::new(IssueSource::Device, message)));
+                    }
+                }
+                self.brightness = None;
+                self.shown_page = None;
+                self.apply_brightness();
+                if !self.show_splash() {
+                    let _ = self.tx.send(DeckEvent::RenderAll);
+                }
+            }
+
+            DeckEvent::DeviceDisconnected => {
+                info!("device disconnected, waiting for reconnect...");
+                self.brightness = None;
+                self.chords.clear();
+            }
+
+            DeckEvent::ConfigReloaded(new_config) => {
+                let new_config = self.keep_pickers(self.place_buttons(new_config));
+                let changes =
+                    crate::config::diff::summarize(&self.shared_config.load(), &new_config);
+                if changes.is_empty() {
+                    info!("config reloaded, no changes");
+                } else {
+                    info!("config reloaded: {}", changes.join("; "));
+                }
+                self.shared_config.store(new_config);
+                self.render.page_cache.lock().unwrap().clear();
+                let config = self.shared_config.load();
+                self.render.apply_config(&config);
+                let home = home_page(&config, self.device.as_deref());
+                self.render.guest = self.render.guest.take().map(|g| g.with_home(home.clone()));
+                self.page_manager.set_home_page(&home);
+                if !config.pages.contains_key(self.page_manager.current_page()) {
+                    let from = self.page_manager.current_page().clone();
+                    self.page_manager.go_home();
+                    self.run_page_hooks(&from);
+                }
+                let page_id = self.page_manager.current_page().clone();
+                if let Some(page) = config.pages.get(&page_id) {
+                    self.generate_page(&page_id, page, true);
+                }
+                self.apply_brightness();
+                let _ = self.tx.send(DeckEvent::RenderAll);
+            }
+
+            DeckEvent::NavigateTo(_)
+            | DeckEvent::OpenPicker(_)
+            | DeckEvent::NavigateBack
+            | DeckEvent::NavigateHome
+                if self.quiet_page.is_some() =>
+            {
+                debug!("quiet hours, staying on the quiet page");
+            }
+
+            DeckEvent::NavigateTo(ref page_id)
+                if self
+                    .render
+                    .guest
+                    .as_ref()
+                    .is_some_and(|g| !g.allows_page(page_id.as_str())) =>
+            {
+                info!("guest mode, page '{page_id}' is not open to guests");
+            }
+
+            DeckEvent::NavigateTo(page_id) => {
+                let config = self.shared_config.load();
+                match config.pages.get(page_id.as_str()) {
+                    Some(page) => match page.pin {
+                        Some(ref pin) if *self.page_manager.current_page() != page_id => {
+                            self.prompt_pin(pin, Unlock::Page(page_id));
+                        }
+                        _ => self.open_page(page_id),
+                    },
+                    None => warn!("page not found: {page_id}"),
+                }
+            }
+
+            DeckEvent::OpenPicker(entity) => {
+                let page_id = PageId::new(format!("picker:{entity}"));
+                if !self.shared_config.load().pages.contains_key(&page_id) {
+                    let mut config = AppConfig::clone(&self.shared_config.load());
+                    let page = PageConfig {
+                        name: entity.clone(),
+                        picker: Some(entity),
+                        ..PageConfig::default()
+                    };
+                    config.pages.insert(page_id.clone(), page);
+                    self.shared_config.store(Arc::new(config));
+                }
+                self.open_page(page_id);
+            }
+
+            DeckEvent::SceneSaved(name, scene) => {
+                info!("scene '{name}' saved ({} entities)", scene.len());
+                if let Err(e) = self.scenes.lock().unwrap().save(name, scene) {
+                    warn!("failed to save scenes: {e}");
+                }
+            }
+
+            DeckEvent::RecallScene(name) => {
+                let Some(scene) = self.scenes.lock().unwrap().get(&name).cloned() else {
+                    warn!("scene '{name}' has not been saved");
+                    let issue = Issue::new(IssueSource::Action, format!("no scene '{name}'"));
+                    let _ = self.tx.send(DeckEvent::Issue(issue));
+                    return false;
+                };
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = crate::scene::recall(&scene).await {
+                        warn!("scene '{name}': {e}");
+                        let issue = Issue::from_error(IssueSource::HomeAssistant, &e);
+                        let _ = tx.send(DeckEvent::Issue(issue));
+                    }
+                });
+            }
+
+            DeckEvent::NavigateBack => {
+                let from = self.page_manager.current_page().clone();
+                if self.page_manager.go_back() {
+                    self.run_page_hooks(&from);
+                    self.apply_brightness();
+                    let _ = self.tx.send(DeckEvent::RenderAll);
+                }
+            }
+
+            DeckEvent::NavigateHome => {
+                let from = self.page_manager.current_page().clone();
+                self.page_manager.go_home();
+                self.run_page_hooks(&from);
+                self.apply_brightness();
+                let _ = self.tx.send(DeckEvent::RenderAll);
+            }
+
+            DeckEvent::DisplaySleep => {
+                info!("display sleeping");
+                self.asleep = true;
+                self.apply_brightness();
+            }
+
+            DeckEvent::DisplayWake => {
+                if self.asleep {
+                    info!("display waking");
+                    self.asleep = false;
+                    self.apply_brightness();
+                }
+            }
+
+            DeckEvent::SetBrightness(level) => {
+                self.brightness_override = level;
+                self.apply_brightness();
+            }
+
+            DeckEvent::StepBrightness(step) => {
+                let current = self
+                    .brightness_override
+                    .or(self.brightness)
+                    .unwrap_or_else(|| self.default_brightness(&self.shared_config.load()));
+                self.brightness_override = Some((i32::from(current) + step).clamp(0, 100) as u8);
+                self.apply_brightness();
+            }
+
+            DeckEvent::ScheduledBrightness(level) => {
+                self.scheduled_brightness = level;
+                self.apply_brightness();
+            }
+
+            DeckEvent::ThemeChanged(theme) => {
+                if self.render.theme != theme {
+                    info!(
+                        "switching to theme '{}'",
+                        theme.as_deref().unwrap_or("default")
+                    );
+                    self.render.theme = theme;
+                    self.render.page_cache.lock().unwrap().clear();
+                    let _ = self.tx.send(DeckEvent::RenderAll);
+                }
+            }
+
+            DeckEvent::Issue(issue) => {
+                let message = format!("{}: {}", issue.source, issue.message);
+                let new = self.render.status.lock().unwrap().record(issue);
+                self.refresh_status();
+                if new && self.shared_config.load().deckd.toast.errors {
+                    let _ = self.tx.send(DeckEvent::Toast(message));
+                }
+            }
+
+            DeckEvent::Toast(message) => {
+                info!("notification: {message}");
+                let shown = self.render.toasts.lock().unwrap().push(message).is_some();
+                if shown {
+                    self.show_toast();
+                }
+            }
+
+            DeckEvent::Flash(name, key) => self.flash(&name, key),
+
+            DeckEvent::Alert(name) => self.take_over(name.as_deref()),
+
+            DeckEvent::Quiet(quiet) => self.set_quiet(quiet),
+
+            DeckEvent::SetGuest(on) => self.set_guest(on),
+
+            DeckEvent::DismissToast(id) => {
+                let dismissed = self.render.toasts.lock().unwrap().dismiss(id);
+                if dismissed {
+                    if self.render.toasts.lock().unwrap().current().is_some() {
+                        self.show_toast();
+                    } else {
+                        let _ = self.tx.send(DeckEvent::RenderAll);
+                    }
+                }
+            }
+
+            DeckEvent::RenderAll if self.splashing() => {
+                debug!("splash showing, deferring render");
+            }
+
+            DeckEvent::RenderAll if self.pin.is_some() => self.draw_pin(),
+
+            DeckEvent::RenderButton(_) if self.pin.is_some() || self.keypad.is_some() => {}
+
+            DeckEvent::PageGenerated(page_id, buttons) => {
+                let mut config = AppConfig::clone(&self.shared_config.load());
+                if let Some(page) = config.pages.get_mut(&page_id) {
+                    let before = page.buttons.iter().filter(|b| b.generated);
+                    if before.eq(buttons.iter()) {
+                        return false;
+                    }
+                    page.buttons.retain(|b| !b.generated);
+                    page.buttons.extend(buttons);
+                    self.shared_config.store(Arc::new(config));
+                    self.render.page_cache.lock().unwrap().remove(&page_id);
+                    if *self.page_manager.current_page() == page_id {
+                        let _ = self.tx.send(DeckEvent::RenderAll);
+                    }
+                }
+            }
+
+            DeckEvent::KeypadResult(page_id, _)
+                if self.keypad.as_ref().is_none_or(|k| k.page != page_id) => {}
+
+            DeckEvent::KeypadResult(_, accepted) => {
+                if accepted {
+                    info!("keypad code accepted");
+                    let _ = self.tx.send(DeckEvent::NavigateBack);
+                } else {
+                    warn!("keypad code rejected");
+                    self.flash_keypad();
+                }
+            }
+
+            DeckEvent::PinTimeout(id) => {
+                if self.pin.as_ref().is_some_and(|entry| entry.id == id) {
+                    info!("PIN entry timed out");
+                    self.pin = None;
+                    let _ = self.tx.send(DeckEvent::RenderAll);
+                }
+            }
+
+            DeckEvent::RenderAll => {
+                let config = self.shared_config.load();
+                let page_id = self.page_manager.current_page().clone();
+                if let Some(page) = config.pages.get(&page_id) {
+                    info!(
+                        "rendering page '{}' ({} buttons)",
+                        page.name,
+                        page.buttons.len()
+                    );
+                    // Only a page switch shows the cached render first; periodic
+                    // re-renders of the same page go straight to the fresh one.
+                    let page_changed = self.shown_page.as_deref() != Some(page_id.as_str());
+                    // The outgoing page's render, to animate away from.
+                    let from = match (page_changed, self.render.transition(&config)) {
+                        (false, _) | (_, Transition::None) => None,
+                        _ => self
+                            .shown_page
+                            .as_deref()
+                            .and_then(|shown| self.render.page_cache.lock().unwrap().get(shown)),
+                    };
+                    self.shown_page = Some(page_id.clone());
+                    self.current_page.store(Arc::new(page_id.clone()));
+
+                    if let Some(ref keypad) = page.keypad {
+                        if self.keypad.as_ref().is_none_or(|k| k.page != page_id) {
+                            self.keypad = Some(KeypadEntry::new(page_id, keypad.max_len));
+                        }
+                        self.draw_keypad();
+                        self.draw_toast();
+                        return false;
+                    }
+                    self.keypad = None;
+                    self.generate_page(&page_id, page, page_changed);
+
+                    let config = Arc::clone(&config);
+                    let ctx = self.render.clone();
+                    tokio::spawn(async move {
+                        render_all_buttons(&ctx, &config, &page_id, page_changed, from).await;
+                    });
+                    // After a reconnect or the splash the banner must be redrawn.
+                    self.draw_toast();
+                }
+            }
+
+            DeckEvent::SetKey(key, source, content) => {
+                let ttl = content.as_ref().and_then(|c| c.ttl_secs);
+                {
+                    let mut overrides = self.render.overrides.lock().unwrap();
+                    match content {
+                        Some(content) => {
+                            overrides.set(key, source, content, std::time::Instant::now());
+                        }
+                        None => {
+                            overrides.clear(key, source);
+                        }
+                    }
+                }
+                self.render.page_cache.lock().unwrap().clear();
+                if let Some(ttl) = ttl {
+                    let tx = self.tx.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(std::time::Duration::from_secs(ttl)).await;
+                        let _ = tx.send(DeckEvent::RenderButton(key));
+                    });
+                }
+                let _ = self.tx.send(DeckEvent::RenderButton(key));
+            }
+
+            DeckEvent::Diagnostics(enabled) => {
+                if self.render.diagnostics != enabled {
+                    info!(
+                        "diagnostic mode {}",
+                        if enabled { "enabled" } else { "disabled" }
+                    );
+                    self.render.diagnostics = enabled;
+                    let _ = self.tx.send(DeckEvent::RenderAll);
+                }
+            }
+
+            DeckEvent::RenderButton(_) if self.render.diagnostics => {
+                let _ = self.tx.send(DeckEvent::RenderAll);
+            }
+
+            DeckEvent::RenderButton(key) if self.splashing() => {
+                debug!("splash showing, skipping render of key {key}");
+            }
+
+            DeckEvent::RenderButton(key) => {
+                if let Some(button) = self.render.override_button(key) {
+                    let config = self.shared_config.load();
+                    let defaults = self.render.defaults(&config);
+                    let ctx = self.render.clone();
+                    let page_id = self.page_manager.current_page().to_string();
+                    tokio::spawn(async move {
+                        render_single_button(&ctx, &page_id, &button, &defaults, key).await;
+                    });
+                } else {
+                    self.render_key(key);
+                }
+            }
+
+            DeckEvent::ActionCompleted {
+                key,
+                action_kind,
+                result,
+                duration,
+            } => {
+                let on = key.map(|key| format!(" (key {key})")).unwrap_or_default();
+                let ms = duration.as_millis();
+                match result {
+                    Ok(()) => debug!("{action_kind} action{on} done in {ms} ms"),
+                    Err(e) => debug!("{action_kind} action{on} failed after {ms} ms: {e}"),
+                }
+            }
+
+            DeckEvent::Shutdown => {
+                info!("shutdown event received");
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Render the configured content of `key`: the status display or the
+    /// page's button.
+    fn render_key(&self, key: KeyIndex) {
+        if self
+            .render
+            .status_slot(self.page_manager.current_page(), key)
+            .is_some()
+        {
+            self.refresh_status();
+            return;
+        }
+        let config = self.shared_config.load();
+        let defaults = self.render.defaults(&config);
+        let ctx = self.render.clone();
+        let page_id = self.page_manager.current_page().to_string();
+        match self.page_manager.effective_button(&config, key) {
+            // Spinner frames come several times a second: use the last known
+            // states instead of asking HA for each one.
+            Some(button) if self.render.has_job(&page_id, key) => {
+                let button = button.into_owned();
+                let states = self.render.last_states.lock().unwrap().clone();
+                tokio::spawn(async move {
+                    render_single_button_with_states(
+                        &ctx, &page_id, &button, &defaults, key, &states,
+                    )
+                    .await;
+                });
+            }
+            Some(button) => {
+                let button = button.into_owned();
+                tokio::spawn(async move {
+                    render_single_button(&ctx, &page_id, &button, &defaults, key).await;
+                });
+            }
+            None => {
+                tokio::spawn(async move {
+                    let guard = ctx.deck_handle.load();
+                    let Some(deck) = guard.as_deref() else {
+                        return;
+                    };
+                    match crate::render::render_blank(key_size(deck)) {
+                        Ok(rgba) => ctx.push_key(deck, key.get(), &rgba).await,
+                        Err(e) => warn!("render blank error (key {key}): {e}"),
+                    }
+                });
+            }
+        }
+    }
+
+    /// Start the auto-dismiss timer of the current banner and draw it.
+    fn show_toast(&self) {
+        let Some(id) = self.render.toasts.lock().unwrap().current().map(|t| t.id) else {
+            return;
+        };
+        let duration = self.shared_config.load().deckd.toast.duration_ms;
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(duration)).await;
+            let _ = tx.send(DeckEvent::DismissToast(id));
+        });
+        self.draw_toast();
+    }
+
+    /// Whether the state poll should re-render now: every tick, or every
+    /// `poll_secs` in quiet hours.
+    fn poll_due(&mut self) -> bool {
+        let config = self.shared_config.load();
+        let interval = match config.deckd.quiet {
+            Some(ref quiet) if self.render.quiet => std::time::Duration::from_secs(quiet.poll_secs),
+            _ => std::time::Duration::ZERO,
+        };
+        if self.polled.elapsed() < interval {
+            return false;
+        }
+        self.polled = std::time::Instant::now();
+        true
+    }
+
+    /// Start or end quiet hours: dim, and show the quiet page (if any)
+    /// until they end.
+    fn set_quiet(&mut self, quiet: bool) {
+        if self.render.quiet == quiet {
+            return;
+        }
+        self.render.quiet = quiet;
+        let config = self.shared_config.load();
+        let page = config
+            .deckd
+            .quiet
+            .as_ref()
+            .and_then(|q| q.page.clone())
+            .filter(|page| quiet && config.pages.contains_key(page));
+        if let Some(page) = page {
+            if *self.page_manager.current_page() != page {
+                self.open_page(page.clone());
+            }
+            self.quiet_page = Some(page);
+        } else if let Some(page) = self.quiet_page.take() {
+            if *self.page_manager.current_page() == page && self.page_manager.go_back() {
+                self.run_page_hooks(&page);
+                let _ = self.tx.send(DeckEvent::RenderAll);
+            }
+        }
+        self.apply_brightness();
+    }
+
+    /// Turn guest mode on or off (`None` toggles it), leaving pages guests
+    /// cannot open.
+    fn set_guest(&mut self, on: Option<bool>) {
+        let config = self.shared_config.load();
+        let Some(guests) = Guests::new(&config) else {
+            warn!("guest mode is not configured ([deckd.guest])");
+            return;
+        };
+        let guests = guests.with_home(home_page(&config, self.device.as_deref()));
+        let on = on.unwrap_or(self.render.guest.is_none());
+        if on == self.render.guest.is_some() {
+            return;
+        }
+        info!("guest mode {}", if on { "on" } else { "off" });
+        if on {
+            let from = self.page_manager.current_page().clone();
+            if self
+                .page_manager
+                .retain(|page| guests.allows_page(page.as_str()))
+            {
+                self.run_page_hooks(&from);
+                self.apply_brightness();
+            }
+        }
+        self.render.guest = on.then_some(guests);
+        self.render.page_cache.lock().unwrap().clear();
+        let _ = self.tx.send(DeckEvent::RenderAll);
+    }
+
+    /// Show the page of the alert `name`, or leave the page of the previous
+    /// alert once all have cleared. A page left by hand is not returned from.
+    fn take_over(&mut self, name: Option<&str>) {
+        let config = self.shared_config.load();
+        let page = name
+            .and_then(|name| config.alerts.get(name)?.page.clone())
+            .filter(|page| config.pages.contains_key(page));
+        if page == self.alert_page {
+            return;
+        }
+        if let Some(previous) = self.alert_page.take() {
+            if *self.page_manager.current_page() == previous && self.page_manager.go_back() {
+                self.run_page_hooks(&previous);
+                self.apply_brightness();
+                let _ = self.tx.send(DeckEvent::RenderAll);
+            }
+        }
+        if let Some(page) = page {
+            if *self.page_manager.current_page() != page {
+                self.open_page(page.clone());
+                self.alert_page = Some(page);
+            }
+        }
+    }
+
+    /// Blink `key` (or every key) in the named pattern, then redraw it.
+    fn flash(&self, name: &str, key: Option<KeyIndex>) {
+        let Some(pattern) = self.shared_config.load().patterns.get(name).cloned() else {
+            warn!("flash: unknown pattern '{name}'");
+            return;
+        };
+        if self.splashing() {
+            return;
+        }
+        let Some(deck) = Option::clone(&self.render.deck_handle.load()) else {
+            return;
+        };
+        let keys: Vec<u8> = match key {
+            Some(key) => vec![key.get()],
+            None => {
+                let grid = grid(&deck);
+                (0..grid.columns * grid.rows).map(|k| k as u8).collect()
+            }
+        };
+        let tx = self.tx.clone();
+        let size = key_size(&deck);
+        tokio::spawn(async move {
+            let (lit, dark) = match (
+                crate::render::canvas::create_canvas(size, &pattern.color),
+                crate::render::render_blank(size),
+            ) {
+                (Ok(lit), Ok(dark)) => (lit.data().to_vec(), dark),
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!("flash render error: {e}");
+                    return;
+                }
+            };
+            let frame = |rgba: &Vec<u8>| -> Vec<(u8, Vec<u8>)> {
+                keys.iter().map(|&k| (k, rgba.clone())).collect()
+            };
+            let (lit, dark) = (frame(&lit), frame(&dark));
+            for _ in 0..pattern.times {
+                push_page_images(&deck, &lit).await;
+                tokio::time::sleep(std::time::Duration::from_millis(pattern.on_ms)).await;
+                push_page_images(&deck, &dark).await;
+                tokio::time::sleep(std::time::Duration::from_millis(pattern.off_ms)).await;
+            }
+            let _ = tx.send(key.map_or(DeckEvent::RenderAll, DeckEvent::RenderButton));
+        });
+    }
+
+    /// Draw the current banner across the top row, if one is showing.
+    fn draw_toast(&self) {
+        let Some(message) = self
+            .render
+            .toasts
+            .lock()
+            .unwrap()
+            .current()
+            .map(|t| t.message.clone())
+        else {
+            return;
+        };
+        if self.splashing() {
+            return;
+        }
+        let Some(deck) = Option::clone(&self.render.deck_handle.load()) else {
+            return;
+        };
+        let config = self.shared_config.load();
+        let toast = config.deckd.toast.clone();
+        let font = self.render.defaults(&config).font;
+        tokio::spawn(async move {
+            let columns = grid(&deck).columns;
+            match crate::render::splash::render_banner(
+                &toast,
+                &font,
+                &message,
+                columns,
+                key_size(&deck),
+            ) {
+                Ok(images) => push_page_images(&deck, &images).await,
+                Err(e) => warn!("notification render error: {e}"),
+            }
+        });
+    }
+
+    fn splashing(&self) -> bool {
+        self.splash_until
+            .is_some_and(|until| tokio::time::Instant::now() < until)
+    }
+
+    /// Show the connect splash, if configured, and schedule the page render
+    /// for when it ends. Returns `false` if there is no splash to show.
+    fn show_splash(&mut self) -> bool {
+        let config = self.shared_config.load();
+        let Some(splash) = config.deckd.splash.clone() else {
+            return false;
+        };
+        let Some(deck) = Option::clone(&self.render.deck_handle.load()) else {
+            return false;
+        };
+
+        let duration = std::time::Duration::from_millis(splash.duration_ms);
+        self.splash_until = Some(tokio::time::Instant::now() + duration);
+        let font = self.render.defaults(&config).font;
+        let config_dir = self.render.config_dir.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let rendered = crate::render::splash::render_splash(
+                &splash,
+                &font,
+                &config_dir,
+                grid(&deck),
+                key_size(&deck),
+            );
+            match rendered {
+                Ok(images) => push_page_images(&deck, &images).await,
+                Err(e) => warn!("splash render error: {e}"),
+            }
+            tokio::time::sleep(duration).await;
+            let _ = tx.send(DeckEvent::RenderAll);
+        });
+        true
+    }
+
+    /// Re-render the status keys if they are on screen. Only the status keys
+    /// are drawn (no HA fetch), so reporting an HA problem cannot loop.
+    fn refresh_status(&self) {
+        let Some(ref tile) = self.render.status_tile else {
+            return;
+        };
+        self.render.page_cache.lock().unwrap().remove(&tile.page);
+        if *self.page_manager.current_page() != tile.page
+            || self.splashing()
+            || self.render.diagnostics
+            || self.pin.is_some()
+        {
+            return;
+        }
+
+        let keys: Vec<(KeyIndex, StatusSlot)> = KeyIndex::all(self.grid.keys())
+            .filter_map(|key| tile.slot(&tile.page, key).map(|slot| (key, slot)))
+            .collect();
+        let defaults = self.render.defaults(&self.shared_config.load());
+        let ctx = self.render.clone();
+        tokio::spawn(async move {
+            let guard = ctx.deck_handle.load();
+            let Some(deck) = guard.as_deref() else {
+                return;
+            };
+            for (key, slot) in keys {
+                match render_status_image(&ctx, slot, &defaults) {
+                    Ok(rgba) => ctx.push_key(deck, key.get(), &rgba).await,
+                    Err(e) => warn!("status render error (key {key}): {e}"),
+                }
+            }
+        });
+    }
+
+    fn handle_button_down(&mut self, key: KeyIndex) {
+        crate::feedback::press(
+            &self.shared_config.load().deckd.feedback,
+            &self.render.config_dir,
+        );
+        let deck = Option::clone(&self.render.deck_handle.load());
+        if deck.is_some_and(|deck| self.render.under_toast(&deck, key.get())) {
+            if let Some(id) = self.render.toasts.lock().unwrap().current().map(|t| t.id) {
+                info!("notification dismissed");
+                let _ = self.tx.send(DeckEvent::DismissToast(id));
+            }
+            return;
+        }
+
+        if self.render.diagnostics {
+            info!(
+                "diagnostic mode: key {key} pressed on page '{}'",
+                self.page_manager.current_page()
+            );
+            return;
+        }
+
+        if self.pin.is_some() {
+            self.enter_pin(key);
+            return;
+        }
+
+        if self.keypad.is_some() {
+            self.press_keypad(key);
+            return;
+        }
+
+        if self
+            .render
+            .status_slot(self.page_manager.current_page(), key)
+            .is_some()
+        {
+            info!("status acknowledged");
+            self.render.status.lock().unwrap().clear();
+            self.refresh_status();
+            return;
+        }
+
+        let config = self.shared_config.load();
+        let page_id = self.page_manager.current_page();
+        let chords = config
+            .pages
+            .get(page_id)
+            .map_or(&[][..], |p| p.chords.as_slice());
+        match self.chords.down(page_id, chords, key) {
+            Down::Press => self.press_button(key),
+            Down::Hold => debug!("key {key} held as modifier"),
+            Down::Chord(action)
+                if self
+                    .render
+                    .guest
+                    .as_ref()
+                    .is_some_and(|g| !g.allows(action, false)) =>
+            {
+                info!("guest mode, chord with key {key} is not open to guests");
+            }
+            Down::Chord(action) => {
+                info!("chord completed with key {key} on page '{page_id}'");
+                let action = action.clone();
+                let tx = self.tx.clone();
+                let mut ctx = crate::action::ActionContext::new(self.render.locale);
+                ctx.key = Some(key);
+                let context = ErrorContext::key(page_id, key);
+                tokio::spawn(async move {
+                    let result = crate::action::execute_retrying(&action, &tx, &ctx).await;
+                    if let Err(e) = result.map_err(|e| e.with_context(context)) {
+                        error!("chord action error: {e}");
+                        let issue = Issue::from_error(IssueSource::Action, &e);
+                        let _ = tx.send(DeckEvent::Issue(issue));
+                    }
+                });
+            }
+        }
+    }
+
+    /// A modifier key was released without completing a chord: run its own
+    /// action, unless the page changed in the meantime.
+    fn handle_button_up(&mut self, key: KeyIndex) {
+        if let Some(page_id) = self.chords.up(key) {
+            if page_id == *self.page_manager.current_page() {
+                self.press_button(key);
+            }
+        }
+    }
+
+    /// Navigate to `page_id` (already checked to exist).
+    fn open_page(&mut self, page_id: PageId) {
+        let from = self.page_manager.current_page().clone();
+        self.page_manager.navigate_to(page_id);
+        self.run_page_hooks(&from);
+        self.apply_brightness();
+        let _ = self.tx.send(DeckEvent::RenderAll);
+    }
+
+    /// After navigating away from `from`: run its `on_exit` actions, then
+    /// the `on_enter` actions of the page now shown, unless it is the same
+    /// page. `{page.from}` and `{page.to}` name the pages.
+    fn run_page_hooks(&self, from: &PageId) {
+        let to = self.page_manager.current_page().clone();
+        if *from == to {
+            return;
+        }
+        let config = self.shared_config.load();
+        let exit = config.pages.get(from).map(|p| p.on_exit.iter());
+        let enter = config.pages.get(&to).map(|p| p.on_enter.iter());
+        let actions: Vec<ActionConfig> = exit
+            .into_iter()
+            .flatten()
+            .chain(enter.into_iter().flatten())
+            .cloned()
+            .collect();
+        if actions.is_empty() {
+            return;
+        }
+        let mut ctx = crate::action::ActionContext::new(self.render.locale);
+        ctx.vars.insert("page.from".into(), from.to_string());
+        ctx.vars.insert("page.to".into(), to.to_string());
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            for action in &actions {
+                if let Err(e) = crate::action::execute_retrying(action, &tx, &ctx).await {
+                    error!(
+                        "page hook action error ({} → {}): {e}",
+                        ctx.vars["page.from"], ctx.vars["page.to"]
+                    );
+                    let issue = Issue::from_error(IssueSource::Action, &e);
+                    let _ = tx.send(DeckEvent::Issue(issue));
+                }
+            }
+        });
+    }
+
+    /// Run the current page's action for turning (`delta`) or pressing a
+    /// dial. `{dial.index}` and `{dial.delta}` describe the input.
+    fn run_dial(&self, dial: u8, delta: Option<i8>) {
+        let config = self.shared_config.load();
+        let Some(dials) = self
+            .page_manager
+            .current_page_config(&config)
+            .map(|p| &p.dials)
+        else {
+            return;
+        };
+        let Some(entry) = dials.iter().find(|d| d.dial == dial) else {
+            debug!("no action for dial {dial}");
+            return;
+        };
+        let action = match delta {
+            Some(_) => entry.on_rotate.clone(),
+            None => entry.on_press.clone(),
+        };
+        let Some(action) = action else {
+            return;
+        };
+        if self
+            .render
+            .guest
+            .as_ref()
+            .is_some_and(|g| !g.allows(&action, false))
+        {
+            info!("guest mode, dial {dial} is not open to guests");
+            return;
+        }
+        let mut ctx = crate::action::ActionContext::new(self.render.locale);
+        ctx.vars.insert("dial.index".into(), dial.to_string());
+        ctx.vars
+            .insert("dial.delta".into(), delta.unwrap_or(0).to_string());
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::action::execute(&action, &tx, &ctx).await {
+                error!("dial {dial} action error: {e}");
+                let issue = Issue::from_error(IssueSource::Action, &e);
+                let _ = tx.send(DeckEvent::Issue(issue));
+            }
+        });
+    }
+
+    /// Replace the page with a PIN keypad; `target` opens once it is entered.
+    fn prompt_pin(&mut self, pin: &[KeyIndex], target: Unlock) {
+        self.pin_seq += 1;
+        let id = self.pin_seq;
+        info!("PIN required");
+        self.pin = Some(PinEntry::new(id, pin, target));
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(crate::pin::PIN_TIMEOUT).await;
+            let _ = tx.send(DeckEvent::PinTimeout(id));
+        });
+        self.draw_pin();
+    }
+
+    /// Feed a key press to the PIN prompt.
+    fn enter_pin(&mut self, key: KeyIndex) {
+        let Some(ref mut entry) = self.pin else {
+            return;
+        };
+        match entry.press(key) {
+            Step::More => self.draw_pin(),
+            Step::Unlocked(target) => {
+                info!("PIN accepted");
+                self.pin = None;
+                match target {
+                    Unlock::Page(page_id) => self.open_page(page_id),
+                    Unlock::Button { page, key } => {
+                        let _ = self.tx.send(DeckEvent::RenderAll);
+                        if page == *self.page_manager.current_page() {
+                            self.run_button(key);
+                        }
+                    }
+                }
+            }
+            Step::Wrong => {
+                warn!("wrong PIN entered");
+                self.pin = None;
+                let _ = self.tx.send(DeckEvent::RenderAll);
+                let _ = self.tx.send(DeckEvent::Toast("Wrong PIN".into()));
+            }
+        }
+    }
+
+    /// Fetch what a generated page shows and fill in its buttons: a media
+    /// player's sources when the page is `opened`; a light picker's state or
+    /// Snapcast rooms on every render so they stay live.
+    fn generate_page(&self, page_id: &PageId, page: &PageConfig, opened: bool) {
+        let tx = self.tx.clone();
+        let page_id = page_id.clone();
+        if let Some(ref entity) = page.picker {
+            let entity = entity.clone();
+            spawn_generate(tx, page_id, IssueSource::HomeAssistant, async move {
+                let state = crate::action::ha::entity(&entity).await?;
+                Ok(crate::page::picker::buttons(&entity, &state))
+            });
+        } else if let Some(ref snapcast) = page.snapcast {
+            let free = free_keys(page, self.grid.keys());
+            let server = snapcast.server.clone();
+            spawn_generate(tx, page_id, IssueSource::Action, async move {
+                let rooms = crate::action::snapcast::status(&server).await?;
+                Ok(crate::page::snapcast::buttons(&server, &rooms, free))
+            });
+        } else if let Some(browser) = page.media_browser.as_ref().filter(|_| opened) {
+            let free = free_keys(page, self.grid.keys());
+            let browser = browser.clone();
+            spawn_generate(tx, page_id, IssueSource::HomeAssistant, async move {
+                let state = crate::action::ha::entity(&browser.entity).await?;
+                Ok(crate::page::media::buttons(&browser, &state, free))
+            });
+        }
+    }
+
+    /// Carry the picker pages opened so far, which are not in the config
+    /// file, over to a reloaded config.
+    fn keep_pickers(&self, mut config: Arc<AppConfig>) -> Arc<AppConfig> {
+        let current = self.shared_config.load();
+        for (page_id, page) in &current.pages {
+            if page.picker.is_some() && !config.pages.contains_key(page_id) {
+                Arc::make_mut(&mut config)
+                    .pages
+                    .insert(page_id.clone(), page.clone());
+            }
+        }
+        config
+    }
+
+    /// Feed a key press to the keypad page.
+    fn press_keypad(&mut self, key: KeyIndex) {
+        let Some(ref mut entry) = self.keypad else {
+            return;
+        };
+        match entry.press(key) {
+            KeypadStep::Changed => self.draw_keypad(),
+            KeypadStep::Back => {
+                let _ = self.tx.send(DeckEvent::NavigateBack);
+            }
+            KeypadStep::Submit(code) => {
+                let page_id = entry.page.clone();
+                self.draw_keypad();
+                let config = self.shared_config.load();
+                let Some(keypad) = config.pages.get(&page_id).and_then(|p| p.keypad.as_ref())
+                else {
+                    return;
+                };
+                info!("keypad code submitted on page '{page_id}'");
+                let action = keypad.on_submit.clone();
+                let tx = self.tx.clone();
+                let mut ctx = crate::action::ActionContext::new(self.render.locale);
+                ctx.vars.insert("code".into(), code);
+                tokio::spawn(async move {
+                    let result = crate::action::execute(&action, &tx, &ctx).await;
+                    if let Err(ref e) = result {
+                        debug!("keypad action failed: {e}");
+                    }
+                    let _ = tx.send(DeckEvent::KeypadResult(page_id, result.is_ok()));
+                });
+            }
+            KeypadStep::Ignored => {}
+        }
+    }
+
+    /// Draw the keypad page with the code entered so far.
+    fn draw_keypad(&self) {
+        let Some(ref entry) = self.keypad else {
+            return;
+        };
+        let labels = keypad_labels(entry, self.grid.keys());
+        let defaults = self.render.defaults(&self.shared_config.load());
+        let ctx = self.render.clone();
+        tokio::spawn(async move {
+            let images = render_keypad(&ctx, &labels, &defaults);
+            let guard = ctx.deck_handle.load();
+            if let Some(deck) = guard.as_deref() {
+                ctx.push_page(deck, &images).await;
+            }
+        });
+    }
+
+    /// Flash the keypad red a few times after a rejected code.
+    fn flash_keypad(&self) {
+        let Some(ref entry) = self.keypad else {
+            return;
+        };
+        let labels = keypad_labels(entry, self.grid.keys());
+        let defaults = self.render.defaults(&self.shared_config.load());
+        let ctx = self.render.clone();
+        tokio::spawn(async move {
+            let normal = render_keypad(&ctx, &labels, &defaults);
+            let alert = ButtonDefaults {
+                background: KEYPAD_ALERT.into(),
+                ..defaults
+            };
+            let alert = render_keypad(&ctx, &labels, &alert);
+            for images in [&alert, &normal].repeat(KEYPAD_FLASHES) {
+                {
+                    let guard = ctx.deck_handle.load();
+                    let Some(deck) = guard.as_deref() else {
+                        return;
+                    };
+                    ctx.push_page(deck, images).await;
+                }
+                tokio::time::sleep(KEYPAD_FLASH_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Draw the PIN keypad: every key shows its index and the entry progress.
+    fn draw_pin(&self) {
+        let Some(ref entry) = self.pin else {
+            return;
+        };
+        let (entered, total) = entry.progress();
+        let dots = format!("{}{}", "•".repeat(entered), "·".repeat(total - entered));
+        let config = self.shared_config.load();
+        let defaults = self.render.defaults(&config);
+        let ctx = self.render.clone();
+        let keys = self.grid.keys();
+        tokio::spawn(async move {
+            let size = ctx.key_size();
+            let now = chrono::Local::now().naive_local();
+            let images: Vec<(u8, Vec<u8>)> = KeyIndex::all(keys)
+                .filter_map(|key| {
+                    let button = ButtonConfig {
+                        key,
+                        label: Some(Label::Text(format!("{key}\n{dots}"))),
+                        ..ButtonConfig::default()
+                    };
+                    crate::render::render_button(
+                        &button,
+                        &defaults,
+                        &ctx.config_dir,
+                        &HashMap::new(),
+                        ctx.locale,
+                        now,
+                        size,
+                    )
+                    .map_err(|e| warn!("PIN keypad render error (key {key}): {e}"))
+                    .ok()
+                    .map(|rgba| (key.get(), rgba))
+                })
+                .collect();
+            let guard = ctx.deck_handle.load();
+            if let Some(deck) = guard.as_deref() {
+                ctx.push_page(deck, &images).await;
+            }
+        });
+    }
+
+    /// Run the configured button at `key` of the current page, asking for
+    /// its PIN first if it has one.
+    fn press_button(&mut self, key: KeyIndex) {
+        let config = self.shared_config.load();
+        let Some(button) = self.page_manager.effective_button(&config, key) else {
+            return;
+        };
+        let locked = crate::lock::is_locked(
+            &button,
+            &self.render.last_states.lock().unwrap(),
+            TimeOfDay::now(),
+        );
+        if locked {
+            info!("key {key} is locked, ignoring press");
+            return;
+        }
+        if !crate::template::is_visible(&button, self.render.locale) {
+            debug!("key {key} is hidden, ignoring press");
+            return;
+        }
+        if self
+            .render
+            .guest
+            .as_ref()
+            .is_some_and(|g| !g.allows_button(&button))
+        {
+            info!("guest mode, key {key} is not open to guests");
+            return;
+        }
+        match button.pin {
+            Some(ref pin) if button.on_press.is_some() => {
+                let page = self.page_manager.current_page().clone();
+                self.prompt_pin(pin, Unlock::Button { page, key });
+            }
+            _ => self.run_button(key),
+        }
+    }
+
+    /// Run the configured button at `key` of the current page.
+    fn run_button(&self, key: KeyIndex) {
+        let config = self.shared_config.load();
+        let Some(button) = self.page_manager.button_for_key(&config, key) else {
+            return;
+        };
+        let page_id = self.page_manager.current_page().clone();
+        let mut cancel = None;
+        if button.track && button.on_press.is_some() {
+            let press =
+                self.render
+                    .jobs
+                    .lock()
+                    .unwrap()
+                    .press(&page_id, key, std::time::Instant::now());
+            match press {
+                Press::Start(token) => cancel = Some(token),
+                Press::Cancel => {
+                    info!("key {key}: cancelling running action");
+                    let _ = self.tx.send(DeckEvent::RenderButton(key));
+                    return;
+                }
+                Press::Ignore => return,
+            }
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.usage.lock().unwrap().record(&page_id, key, now);
+
+        let mut action_ctx = crate::action::ActionContext::new(self.render.locale);
+        action_ctx.key = Some(key);
+
+        // Optimistic render: immediately flip the cached visual state.
+        let overridden = self.render.overrides.lock().unwrap().contains(key);
+        if let Some(entity_id) = button.state_entity.as_ref().filter(|_| !overridden) {
+            let mut cache = self.render.last_states.lock().unwrap();
+            let current = cache.get(entity_id).map(|s| s.as_str());
+            action_ctx.state = current.map(str::to_string);
+            if let Some(link) = current.and_then(|s| crate::source::feed::link(entity_id, s)) {
+                action_ctx.vars.insert("link".into(), link);
+            }
+            // Only on/off states flip; a headline or a count stays as it is.
+            let flipped = match current {
+                Some("on") => Some("off"),
+                Some("off") | None => Some("on"),
+                Some(_) => None,
+            };
+            if let Some(flipped) = flipped {
+                cache.insert(entity_id.clone(), flipped.to_string());
+                let states = cache.clone();
+                drop(cache);
+
+                let button = button.clone();
+                let defaults = self.render.defaults(&config);
+                let ctx = self.render.clone();
+                let page_id = page_id.clone();
+                tokio::spawn(async move {
+                    render_single_button_with_states(
+                        &ctx, &page_id, &button, &defaults, key, &states,
+                    )
+                    .await;
+                });
+            }
+        }
+
+        if let Some(ref action) = button.on_press {
+            let action = action.clone();
+            let on_error = button.on_error.clone();
+            let action_tx = self.tx.clone();
+            let has_state = button.state_entity.is_some();
+            let context = ErrorContext::key(&page_id, key);
+            let job = cancel.map(|cancel| Job {
+                jobs: Arc::clone(&self.render.jobs),
+                page_id,
+                key,
+                hold: std::time::Duration::from_millis(config.deckd.track.hold_ms),
+                cancel,
+            });
+            tokio::spawn(async move {
+                let run = crate::action::execute_retrying(&action, &action_tx, &action_ctx);
+                let result = match job {
+                    Some(job) => job.run(&action_tx, run).await,
+                    None => run.await,
+                };
+                if let Err(e) = result.map_err(|e| e.with_context(context)) {
+                    error!("action error: {e}");
+                    let issue = Issue::from_error(IssueSource::Action, &e);
+                    let _ = action_tx.send(DeckEvent::Issue(issue));
+                    if let Some(ref on_error) = on_error {
+                        let mut error_ctx = action_ctx.clone();
+                        error_ctx.vars.insert("error".into(), e.to_string());
+                        if let Err(e) =
+                            crate::action::execute(on_error, &action_tx, &error_ctx).await
+                        {
+                            error!("on_error action error (key {key}): {e}");
+                        }
+                    }
+                }
+                // Wait for HA to process the state change before syncing.
+                if has_state {
+                    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                    let _ = action_tx.send(DeckEvent::RenderAll);
+                }
+            });
+        }
+    }
+}
+
+/// A tracked action started by a key press.
+struct Job {
+    jobs: Arc<Mutex<JobTracker>>,
+    page_id: PageId,
+    key: KeyIndex,
+    /// How long the result stays on the key.
+    hold: std::time::Duration,
+    /// Cancelled by a second press.
+    cancel: CancellationToken,
+}
+
+impl Job {
+    /// Run the action, redrawing the key for every spinner frame, for the
+    /// result, and once more when the result is no longer shown. Cancelling
+    /// drops `run`, which aborts HTTP requests and kills shell commands; a
+    /// cancelled action counts as failed but is not reported as an issue.
+    async fn run(
+        self,
+        tx: &broadcast::Sender<DeckEvent>,
+        run: impl std::future::Future<Output = Result<()>>,
+    ) -> Result<()> {
+        let key = self.key;
+        tokio::pin!(run);
+        let mut frames = tokio::time::interval(crate::jobs::SPINNER_FRAME);
+        let outcome = loop {
+            tokio::select! {
+                result = &mut run => break Some(result),
+                () = self.cancel.cancelled() => {
+                    info!("key {key}: action cancelled");
+                    break None;
+                }
+                _ = frames.tick() => {
+                    let _ = tx.send(DeckEvent::RenderButton(key));
+                }
+            }
+        };
+
+        self.jobs.lock().unwrap().finish(
+            &self.page_id,
+            key,
+            matches!(outcome, Some(Ok(()))),
+            std::time::Instant::now(),
+            self.hold,
+        );
+        let _ = tx.send(DeckEvent::RenderButton(key));
+        let tx = tx.clone();
+        let hold = self.hold;
+        tokio::spawn(async move {
+            tokio::time::sleep(hold).await;
+            let _ = tx.send(DeckEvent::RenderButton(key));
+        });
+        outcome.unwrap_or(Ok(()))
+    }
+}
+
+/// Collect state_entity IDs from all buttons on a page.
+fn collect_state_entities(config: &AppConfig, page_id: &str) -> Vec<String> {
+    config
+        .pages
+        .get(page_id)
+        .map(|page| {
+            let locks = page
+                .buttons
+                .iter()
+                .filter_map(|b| b.locked_if.as_ref())
+                .chain(page.locked_if.as_ref())
+                .map(|c| c.entity.clone());
+            let badges = page.buttons.iter().filter_map(|b| b.badge_entity.clone());
+            let templates = page
+                .buttons
+                .iter()
+                .flat_map(crate::template::button_entities);
+            let mut entities: Vec<String> = page
+                .buttons
+                .iter()
+                .filter_map(|b| b.state_entity.clone())
+                .chain(badges)
+                .chain(locks)
+                .chain(templates)
+                .collect();
+            entities.sort();
+            entities.dedup();
+            entities
+        })
+        .unwrap_or_default()
+}
+
+/// Render every key of the device. Fetches HA states first for stateful buttons.
+/// Updates the shared state cache with fresh values from HA.
+///
+/// When `page_changed` is set and the page was rendered before, the cached
+/// images are pushed first so the switch is instant. The first push animates
+/// from the `from` render per `deckd.transition`. Afterwards, pages linked
+/// from this one are pre-rendered (if `deckd.prerender` is enabled).
+async fn render_all_buttons(
+    ctx: &RenderContext,
+    config: &AppConfig,
+    page_id: &str,
+    page_changed: bool,
+    mut from: Option<PageImages>,
+) {
+    let page = match config.pages.get(page_id) {
+        Some(p) => p,
+        None => return,
+    };
+
+    if ctx.diagnostics {
+        let images = ctx.diagnostic_images(config, page_id);
+        if let Some(deck) = ctx.deck_handle.load().as_deref() {
+            ctx.push_page(deck, &images).await;
+        }
+        return;
+    }
+
+    if page_changed {
+        let cached = ctx.page_cache.lock().unwrap().get(page_id);
+        if let (Some(images), Some(deck)) = (cached, ctx.deck_handle.load().as_deref()) {
+            debug!("showing cached render of page '{page_id}'");
+            ctx.push_page_from(deck, from.take(), &images, ctx.transition(config))
+                .await;
+        }
+    }
+
+    let entities = collect_state_entities(config, page_id);
+    let (entity_states, problem) = crate::state::fetch_states_checked(&entities).await;
+    if let Some(problem) = problem {
+        let issue = Issue::new(IssueSource::HomeAssistant, problem);
+        let _ = ctx.tx.send(DeckEvent::Issue(issue));
+    }
+
+    // Update the cache with fresh HA values.
+    if let Ok(mut cache) = ctx.last_states.lock() {
+        for (k, v) in &entity_states {
+            cache.insert(k.clone(), v.clone());
+        }
+    }
+    if let Ok(mut history) = ctx.history.lock() {
+        let now = std::time::Instant::now();
+        let sparklines = page.buttons.iter().filter(|b| b.sparkline);
+        for entity in sparklines.filter_map(|b| b.state_entity.as_ref()) {
+            if let Some(state) = entity_states.get(entity) {
+                history.record(entity, state, now);
+            }
+        }
+    }
+
+    let images: PageImages = Arc::new(render_page_images(
+        ctx,
+        page_id,
+        page,
+        &ctx.defaults(config),
+        &entity_states,
+    ));
+    ctx.page_cache
+        .lock()
+        .unwrap()
+        .insert(page_id, Arc::clone(&images));
+
+    let guard = ctx.deck_handle.load();
+    let Some(deck) = guard.as_deref() else {
+        return;
+    };
+    ctx.push_page_from(deck, from, &images, ctx.transition(config))
+        .await;
+
+    if config.deckd.prerender && !ctx.quiet {
+        let linked: Vec<(PageId, PageConfig)> = crate::page::linked_pages(page)
+            .into_iter()
+            .filter(|id| !ctx.page_cache.lock().unwrap().contains(id))
+            .filter_map(|id| Some((id.clone(), config.pages.get(id)?.clone())))
+            .collect();
+        if linked.is_empty() {
+            return;
+        }
+        let (ctx, defaults) = (ctx.clone(), ctx.defaults(config));
+        // Rasterizing and encoding is CPU-bound; keep it off the executor.
+        let prerender = tokio::task::spawn_blocking(move || {
+            prerender_linked_pages(&ctx, &linked, &defaults);
+        });
+        if let Err(e) = prerender.await {
+            warn!("pre-rendering failed: {e}");
+        }
+    }
+}
+
+/// Render every key of a page to RGBA. Keys without a button are blank.
+fn render_page_images(
+    ctx: &RenderContext,
+    page_id: &str,
+    page: &PageConfig,
+    defaults: &ButtonDefaults,
+    entity_states: &HashMap<String, String>,
+) -> Vec<(u8, Vec<u8>)> {
+    let key_count = ctx.key_count();
+    let size = ctx.key_size();
+    let now = chrono::Local::now().naive_local();
+    let mut images: Vec<(u8, Vec<u8>)> = Vec::with_capacity(usize::from(key_count));
+
+    for key in KeyIndex::all(key_count) {
+        let overridden = ctx.override_button(key);
+        if overridden.is_none() {
+            if let Some(slot) = ctx.status_slot(page_id, key) {
+                match render_status_image(ctx, slot, defaults) {
+                    Ok(data) => images.push((key.get(), data)),
+                    Err(e) => warn!("status render error (key {key}): {e}"),
+                }
+                continue;
+            }
+        }
+
+        let button = match overridden {
+            Some(button) => Some(Cow::Owned(button)),
+            None => page
+                .buttons
+                .iter()
+                .find(|b| b.key == key)
+                .map(|b| crate::lock::effective(b, page)),
+        }
+        .filter(|button| crate::template::is_visible(button, ctx.locale))
+        .map(|button| ctx.with_history(button));
+        let rgba_data = match button {
+            Some(btn) => {
+                match crate::render::render_button(
+                    &btn,
+                    defaults,
+                    &ctx.config_dir,
+                    entity_states,
+                    ctx.locale,
+                    now,
+                    size,
+                ) {
+                    Ok(mut data) => {
+                        if crate::lock::is_locked(&btn, entity_states, TimeOfDay::now()) {
+                            grey_out(&mut data);
+                        } else if let Some(ref guests) = ctx.guest {
+                            guests.mark(&btn, &mut data, size);
+                        }
+                        ctx.draw_job(page_id, key, &mut data);
+                        data
+                    }
+                    Err(e) => {
+                        warn!("render error (key {key}): {e}");
+                        continue;
+                    }
+                }
+            }
+            None => match crate::render::render_blank(size) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("render blank error (key {key}): {e}");
+                    continue;
+                }
+            },
+        };
+        images.push((key.get(), rgba_data));
+    }
+
+    images
+}
+
+/// Render a key taken over by the status display.
+fn render_status_image(
+    ctx: &RenderContext,
+    slot: StatusSlot,
+    defaults: &ButtonDefaults,
+) -> Result<Vec<u8>> {
+    let size = ctx.key_size();
+    let log = ctx.status.lock().unwrap();
+    match slot {
+        StatusSlot::Summary => crate::render::status::render_status_tile(&log, defaults, size),
+        StatusSlot::Recent(index) => {
+            crate::render::status::render_issue(log.get(index), defaults, size)
+        }
+    }
+}
+
+/// Speculatively render the `linked` pages, reachable from the current one
+/// and not cached yet, using the last known entity states, and warm the
+/// device's encode cache so switching to them costs only USB writes. Blocks
+/// while rendering.
+fn prerender_linked_pages(
+    ctx: &RenderContext,
+    linked: &[(PageId, PageConfig)],
+    defaults: &ButtonDefaults,
+) {
+    let guard = ctx.deck_handle.load();
+    let Some(deck) = guard.as_deref() else {
+        return;
+    };
+    let states = ctx
+        .last_states
+        .lock()
+        .map(|cache| cache.clone())
+        .unwrap_or_default();
+
+    for (linked_id, linked) in linked {
+        let images = render_page_images(ctx, linked_id, linked, defaults, &states);
+        for (key, rgba) in &images {
+            if let Err(e) = deck.preload(rgba, key_size(deck)) {
+                debug!("pre-encode failed (page '{linked_id}', key {key}): {e}");
+            }
+        }
+        debug!("pre-rendered page '{linked_id}'");
+        ctx.page_cache
+            .lock()
+            .unwrap()
+            .insert(linked_id, Arc::new(images));
+    }
+}
+
+/// Render a single button with pre-supplied entity states (no HA fetch).
+/// Used for optimistic rendering on button press.
+async fn render_single_button_with_states(
+    ctx: &RenderContext,
+    page_id: &str,
+    button: &ButtonConfig,
+    defaults: &ButtonDefaults,
+    key: KeyIndex,
+    entity_states: &HashMap<String, String>,
+) {
+    let button = ctx.with_history(Cow::Borrowed(button));
+    let size = ctx.key_size();
+    let now = chrono::Local::now().naive_local();
+    let rendered = if crate::template::is_visible(&button, ctx.locale) {
+        crate::render::render_button(
+            &button,
+            defaults,
+            &ctx.config_dir,
+            entity_states,
+            ctx.locale,
+            now,
+            size,
+        )
+    } else {
+        crate::render::render_blank(size)
+    };
+    let rgba_data = match rendered {
+        Ok(mut data) => {
+            if crate::lock::is_locked(&button, entity_states, TimeOfDay::now()) {
+                grey_out(&mut data);
+            } else if let Some(ref guests) = ctx.guest {
+                guests.mark(&button, &mut data, size);
+            }
+            ctx.draw_job(page_id, key, &mut data);
+            data
+        }
+        Err(e) => {
+            warn!("render error (key {key}): {e}");
+            return;
+        }
+    };
+
+    let guard = ctx.deck_handle.load();
+    let Some(deck) = guard.as_deref() else {
+        return;
+    };
+    ctx.push_key(deck, key.get(), &rgba_data).await;
+}
+
+/// Render a single button to the device. Fetches HA state if needed.
+async fn render_single_button(
+    ctx: &RenderContext,
+    page_id: &str,
+    button: &ButtonConfig,
+    defaults: &ButtonDefaults,
+    key: KeyIndex,
+) {
+    let entities: Vec<String> = button
+        .state_entity
+        .iter()
+        .chain(&button.badge_entity)
+        .chain(button.locked_if.as_ref().map(|c| &c.entity))
+        .cloned()
+        .chain(crate::template::button_entities(button))
+        .collect();
+    let entity_states = crate::state::fetch_states(&entities).await;
+    render_single_button_with_states(ctx, page_id, button, defaults, key, &entity_states).await;
+}
+
+/// Keys of `page` without a button of its own (of `key_count`), for
+/// generated buttons.
+fn free_keys(page: &PageConfig, key_count: u8) -> Vec<KeyIndex> {
+    KeyIndex::all(key_count)
+        .filter(|&key| !page.buttons.iter().any(|b| !b.generated && b.key == key))
+        .collect()
+}
+
+/// Run `generate` for the buttons of a generated page and send them as
+/// `PageGenerated`, or report why they could not be fetched as an issue of
+/// `source`.
+fn spawn_generate(
+    tx: broadcast::Sender<DeckEvent>,
+    page_id: PageId,
+    source: IssueSource,
+    generate: impl std::future::Future<Output = Result<Vec<ButtonConfig>>> + Send + 'static,
+) {
+    tokio::spawn(async move {
+        match generate.await {
+            Ok(buttons) => {
+                debug!("generated page '{page_id}': {} buttons", buttons.len());
+                let _ = tx.send(DeckEvent::PageGenerated(page_id, buttons));
+            }
+            Err(e) => {
+                let e = e.with_context(ErrorContext {
+                    page: Some(page_id),
+                    ..ErrorContext::default()
+                });
+                warn!("generated page: {e}");
+                let issue = Issue::from_error(source, &e);
+                let _ = tx.send(DeckEvent::Issue(issue));
+            }
+        }
+    });
+}
+
+/// The label of each of `key_count` keys of the keypad page.
+fn keypad_labels(entry: &KeypadEntry, key_count: u8) -> Vec<(KeyIndex, Option<String>)> {
+    KeyIndex::all(key_count)
+        .map(|key| (key, entry.label(key)))
+        .collect()
+}
+
+/// Render the keypad page; keys without a label are blank.
+fn render_keypad(
+    ctx: &RenderContext,
+    labels: &[(KeyIndex, Option<String>)],
+    defaults: &ButtonDefaults,
+) -> Vec<(u8, Vec<u8>)> {
+    let size = ctx.key_size();
+    let now = chrono::Local::now().naive_local();
+    labels
+        .iter()
+        .filter_map(|(key, label)| {
+            let rgba = match label {
+                Some(label) => {
+                    let button = ButtonConfig {
+                        key: *key,
+                        label: Some(Label::Text(label.clone())),
+                        ..ButtonConfig::default()
+                    };
+                    crate::render::render_button(
+                        &button,
+                        defaults,
+                        &ctx.config_dir,
+                        &HashMap::new(),
+                        ctx.locale,
+                        now,
+                        size,
+                    )
+                }
+                None => crate::render::render_blank(size),
+            };
+            rgba.map_err(|e| warn!("keypad render error (key {key}): {e}"))
+                .ok()
+                .map(|rgba| (key.get(), rgba))
+        })
+        .collect()
+}
+
+/// Key grid of a connected deck.
+fn grid(deck: &Deck) -> Grid {
+    let caps = deck.capabilities();
+    Grid {
+        columns: u32::from(caps.columns),
+        rows: u32::from(caps.rows),
+    }
+}
+
+/// Side of the key images of a connected deck.
+fn key_size(deck: &Deck) -> u32 {
+    deck.capabilities().render_size()
+}
+
+/// On clean shutdown, replace the page with the goodbye text (or blank keys)
+/// and turn the display off, so nothing is left burning at full brightness.
+async fn show_goodbye(ctx: &RenderContext, config: &AppConfig) {
+    let Some(deck) = Option::clone(&ctx.deck_handle.load()) else {
+        return;
+    };
+    let splash = config.deckd.splash.as_ref();
+    let font = ctx.defaults(config).font;
+    match crate::render::splash::render_goodbye(splash, &font, grid(&deck), key_size(&deck)) {
+        Ok(images) => push_page_images(&deck, &images).await,
+        Err(e) => warn!("goodbye render error: {e}"),
+    }
+    if splash.is_some_and(|s| s.goodbye.is_some()) {
+        tokio::time::sleep(GOODBYE_HOLD).await;
+    }
+    if let Err(e) = deck.set_brightness(0).await {
+        warn!("failed to turn off display: {e}");
+    }
+}
+
+/// Push a full page of rendered key images.
+async fn push_page_images(deck: &Deck, images: &[(u8, Vec<u8>)]) {
+    for (key, rgba) in images {
+        push_key_image(deck, *key, rgba).await;
+    }
+}
+
+/// Encode and write one rendered key image, logging failures.
+async fn push_key_image(deck: &Deck, key: u8, rgba: &[u8]) {
+    if let Err(e) = deck.set_key_rgba(key, rgba, key_size(deck)).await {
+        warn!("failed to set button image (key {key}): {e}");
     }
 }
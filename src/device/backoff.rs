@@ -0,0 +1,132 @@
+use crate::config::schema::{ActionConfig, DeckdConfig};
+use crate::render::locale::Locale;
+use std::time::Duration;
+
+/// Retry interval while waiting for the device during the startup grace period.
+pub const STARTUP_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How the device manager retries after a failed connect or a disconnect.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first retry.
+    pub base: Duration,
+    /// Upper bound for the delay.
+    pub max: Duration,
+    /// Consecutive failed attempts before `on_failed` runs (0 = never).
+    pub hook_after: u32,
+    /// Action run once per failure streak (e.g. notify HA, power-cycle a hub).
+    pub on_failed: Option<ActionConfig>,
+    /// Locale for templated fields of `on_failed`.
+    pub locale: &'static Locale,
+    /// Fast retries right after startup, before the first connect.
+    pub startup_grace: Duration,
+}
+
+impl ReconnectPolicy {
+    #[must_use]
+    pub fn from_config(config: &DeckdConfig) -> Self {
+        Self {
+            base: Duration::from_millis(config.reconnect_interval_ms),
+            max: Duration::from_millis(
+                config
+                    .reconnect_max_interval_ms
+                    .max(config.reconnect_interval_ms),
+            ),
+            hook_after: config.reconnect_hook_after,
+            on_failed: config.on_reconnect_failed.clone(),
+            locale: crate::render::locale::resolve(&config.locale),
+            startup_grace: Duration::from_millis(config.startup_grace_ms),
+        }
+    }
+
+    /// The fast startup retry interval, if the device has never connected and
+    /// the daemon started less than `startup_grace` ago.
+    #[must_use]
+    pub fn startup_retry(&self, since_start: Duration, connects: u64) -> Option<Duration> {
+        (connects == 0 && since_start < self.startup_grace).then_some(STARTUP_RETRY_INTERVAL)
+    }
+}
+
+/// Exponential backoff with jitter.
+#[derive(Debug)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    #[must_use]
+    pub const fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// Consecutive failures since the last reset.
+    #[must_use]
+    pub const fn attempts(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Record a failure and return how long to wait before retrying:
+    /// `base * 2^attempt`, capped at `max`, then randomized to 50-100% of
+    /// that so several daemons on one hub don't retry in lockstep.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.ceiling();
+        self.attempt = self.attempt.saturating_add(1);
+        delay.mul_f64(fastrand::f64().mul_add(0.5, 0.5))
+    }
+
+    /// Start over after a successful connect.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Delay before jitter for the current attempt.
+    fn ceiling(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempt.min(16));
+        self.base.saturating_mul(factor).min(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_and_caps() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+        let ceilings: Vec<_> = (0..6)
+            .map(|_| {
+                let ceiling = backoff.ceiling();
+                let delay = backoff.next_delay();
+                assert!(delay <= ceiling && delay >= ceiling / 2);
+                ceiling.as_secs()
+            })
+            .collect();
+        assert_eq!(ceilings, [1, 2, 4, 8, 10, 10]);
+        assert_eq!(backoff.attempts(), 6);
+
+        backoff.reset();
+        assert_eq!(backoff.ceiling(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn startup_retry_only_before_first_connect() {
+        let policy = ReconnectPolicy {
+            base: Duration::from_secs(2),
+            max: Duration::from_secs(30),
+            hook_after: 10,
+            on_failed: None,
+            locale: crate::render::locale::resolve("en-US"),
+            startup_grace: Duration::from_secs(15),
+        };
+        let early = Duration::from_secs(3);
+        assert_eq!(policy.startup_retry(early, 0), Some(STARTUP_RETRY_INTERVAL));
+        assert_eq!(policy.startup_retry(early, 1), None);
+        assert_eq!(policy.startup_retry(Duration::from_secs(20), 0), None);
+    }
+}
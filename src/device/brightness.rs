@@ -0,0 +1,164 @@
+use super::DeckHandle;
+use crate::config::schema::{AppConfig, DeckdConfig};
+use crate::event::DeckEvent;
+use crate::schedule::{self, TimeOfDay};
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Time between brightness steps while ramping.
+const STEP_INTERVAL: Duration = Duration::from_millis(25);
+
+/// How often `[deckd.brightness_schedule]` is re-evaluated.
+const SCHEDULE_POLL: Duration = Duration::from_secs(15);
+
+/// A brightness the display should reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub level: u8,
+    /// How long to take getting there from the current level.
+    pub ramp: Duration,
+    /// Set the level at once, e.g. after a reconnect when the device's
+    /// current level is unknown.
+    pub immediate: bool,
+}
+
+/// Start the task that fades the display to each new target. A target sent
+/// mid-ramp continues from the level reached so far. The task ends when the
+/// returned sender is dropped.
+#[must_use]
+pub fn spawn_controller(deck_handle: DeckHandle) -> watch::Sender<Option<Target>> {
+    let (tx, rx) = watch::channel(None);
+    tokio::spawn(run(deck_handle, rx));
+    tx
+}
+
+async fn run(deck_handle: DeckHandle, mut rx: watch::Receiver<Option<Target>>) {
+    let mut current: Option<u8> = None;
+    while rx.changed().await.is_ok() {
+        let Some(target) = *rx.borrow_and_update() else {
+            continue;
+        };
+        let levels = match current {
+            Some(from) if !target.immediate => ramp_levels(from, target.level, steps(target.ramp)),
+            _ => vec![target.level],
+        };
+        debug!(
+            "brightness {current:?} -> {} in {} steps",
+            target.level,
+            levels.len()
+        );
+
+        for (i, level) in levels.into_iter().enumerate() {
+            if i > 0 {
+                tokio::select! {
+                    () = tokio::time::sleep(STEP_INTERVAL) => {}
+                    // Superseded: the next target ramps from where we are.
+                    _ = rx.changed() => {
+                        rx.mark_changed();
+                        break;
+                    }
+                }
+            }
+            let Some(deck) = Option::clone(&deck_handle.load()) else {
+                current = None;
+                break;
+            };
+            if let Err(e) = deck.set_brightness(level).await {
+                warn!("failed to set brightness: {e}");
+                current = None;
+                break;
+            }
+            current = Some(level);
+        }
+    }
+}
+
+/// The level `[deckd.brightness_schedule]` sets at `now`, if it has entries.
+#[must_use]
+pub fn scheduled_level(deckd: &DeckdConfig, now: TimeOfDay) -> Option<u8> {
+    schedule::active_entry(&deckd.brightness_schedule, now).copied()
+}
+
+/// Re-evaluate `[deckd.brightness_schedule]` periodically and emit
+/// `ScheduledBrightness` when the level in effect changes, including when
+/// the schedule is added or removed.
+pub async fn watch_schedule(
+    config: Arc<ArcSwap<AppConfig>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(SCHEDULE_POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut active = None;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let level = scheduled_level(&config.load().deckd, TimeOfDay::now());
+        if level != active {
+            if let Some(level) = level {
+                info!("scheduled brightness: {level}");
+            }
+            let _ = tx.send(DeckEvent::ScheduledBrightness(level));
+            active = level;
+        }
+    }
+}
+
+fn steps(ramp: Duration) -> usize {
+    (ramp.as_millis() / STEP_INTERVAL.as_millis()).max(1) as usize
+}
+
+/// Intermediate levels from `from` (exclusive) to `to` (inclusive) in at
+/// most `steps` evenly spaced steps, without repeats.
+fn ramp_levels(from: u8, to: u8, steps: usize) -> Vec<u8> {
+    let (from_f, to_f) = (f32::from(from), f32::from(to));
+    let mut levels: Vec<u8> = (1..=steps)
+        .map(|i| (from_f + (to_f - from_f) * i as f32 / steps as f32).round() as u8)
+        .filter(|&level| level != from)
+        .collect();
+    levels.dedup();
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_evenly_without_repeats() {
+        assert_eq!(ramp_levels(80, 0, 4), [60, 40, 20, 0]);
+        assert_eq!(ramp_levels(10, 13, 10), [11, 12, 13]);
+        assert!(ramp_levels(50, 50, 8).is_empty());
+        assert_eq!(steps(Duration::ZERO), 1);
+    }
+
+    #[test]
+    fn schedule_sets_the_level_until_the_next_start() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd.brightness_schedule]
+"07:00" = 80
+"22:00" = 10
+"#,
+        )
+        .unwrap();
+        let at = |time: &str| scheduled_level(&config.deckd, time.parse().unwrap());
+        assert_eq!(at("12:00"), Some(80));
+        assert_eq!(at("02:00"), Some(10));
+        assert_eq!(at("22:00"), Some(10));
+        assert_eq!(at("07:00"), Some(80));
+        let unscheduled: AppConfig = toml::from_str("[deckd]").unwrap();
+        assert_eq!(
+            scheduled_level(&unscheduled.deckd, "02:00".parse().unwrap()),
+            None
+        );
+    }
+}
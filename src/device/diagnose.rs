@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Elgato's USB vendor ID.
+pub const ELGATO_VENDOR_ID: u16 = 0x0fd9;
+
+/// Directories searched for udev rules.
+pub const UDEV_RULE_DIRS: [&str; 3] = [
+    "/etc/udev/rules.d",
+    "/lib/udev/rules.d",
+    "/usr/lib/udev/rules.d",
+];
+
+/// A Stream Deck hidraw device node.
+#[derive(Debug, Clone)]
+pub struct HidrawNode {
+    pub path: PathBuf,
+    /// Whether the node can be opened read-write by this process.
+    pub accessible: bool,
+}
+
+/// Find hidraw nodes belonging to Elgato devices (Linux only; empty elsewhere).
+#[must_use]
+pub fn elgato_hidraw_nodes() -> Vec<HidrawNode> {
+    let Ok(entries) = fs::read_dir("/sys/class/hidraw") else {
+        return Vec::new();
+    };
+
+    let mut nodes: Vec<HidrawNode> = entries
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path().join("device/uevent"))
+                .ok()
+                .and_then(|uevent| parse_hid_vendor(&uevent))
+                == Some(ELGATO_VENDOR_ID)
+        })
+        .map(|entry| {
+            let path = Path::new("/dev").join(entry.file_name());
+            let accessible = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .is_ok();
+            HidrawNode { path, accessible }
+        })
+        .collect();
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    nodes
+}
+
+/// The first Stream Deck node this process lacks permission for, if any.
+#[must_use]
+pub fn permission_problem() -> Option<PathBuf> {
+    elgato_hidraw_nodes()
+        .into_iter()
+        .find(|node| !node.accessible)
+        .map(|node| node.path)
+}
+
+/// Extract the vendor ID from a hidraw `uevent` file
+/// (`HID_ID=0003:00000FD9:0000006D`).
+#[must_use]
+pub fn parse_hid_vendor(uevent: &str) -> Option<u16> {
+    let id = uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_ID="))?;
+    let vendor = id.split(':').nth(1)?;
+    u32::from_str_radix(vendor, 16)
+        .ok()
+        .and_then(|v| u16::try_from(v).ok())
+}
+
+/// Installed udev rule files that mention the Elgato vendor ID.
+#[must_use]
+pub fn elgato_udev_rules() -> Vec<PathBuf> {
+    let vendor = format!("{ELGATO_VENDOR_ID:04x}");
+    UDEV_RULE_DIRS
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path).is_ok_and(|rules| rules.to_ascii_lowercase().contains(&vendor))
+        })
+        .collect()
+}
+
+/// Supplementary group IDs of this process, from `/proc/self/status`.
+#[must_use]
+pub fn process_groups() -> Vec<u32> {
+    fs::read_to_string("/proc/self/status")
+        .map(|status| parse_status_groups(&status))
+        .unwrap_or_default()
+}
+
+/// Group names by ID, from `/etc/group`.
+#[must_use]
+pub fn group_names() -> HashMap<u32, String> {
+    fs::read_to_string("/etc/group")
+        .map(|groups| parse_group_file(&groups))
+        .unwrap_or_default()
+}
+
+fn parse_status_groups(status: &str) -> Vec<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Groups:"))
+        .map(|ids| {
+            ids.split_whitespace()
+                .filter_map(|id| id.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_group_file(groups: &str) -> HashMap<u32, String> {
+    groups
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let gid = fields.nth(1)?.parse().ok()?;
+            Some((gid, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hid_vendor() {
+        let uevent =
+            "DRIVER=hid-generic\nHID_ID=0003:00000FD9:00000080\nHID_NAME=Elgato Stream Deck MK.2\n";
+        assert_eq!(parse_hid_vendor(uevent), Some(ELGATO_VENDOR_ID));
+        assert_eq!(parse_hid_vendor("DRIVER=hid-generic\n"), None);
+    }
+
+    #[test]
+    fn parses_groups() {
+        assert_eq!(
+            parse_status_groups("Name:\tdeckd\nGroups:\t20 46 1000 \nNSpid:\t1\n"),
+            [20, 46, 1000]
+        );
+        let names = parse_group_file("root:x:0:\nplugdev:x:46:pi,deckd\n");
+        assert_eq!(names.get(&46).map(String::as_str), Some("plugdev"));
+    }
+}
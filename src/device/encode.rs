@@ -0,0 +1,101 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Number of encoded key images kept per device.
+///
+/// Large enough to hold several full XL pages, small enough to stay well
+/// under a megabyte of JPEG data on a Pi Zero.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Cache of key images already encoded into the device's native format.
+///
+/// Keyed by a hash of the rendered RGBA buffer, so identical renders
+/// (blank keys, unchanged buttons on re-render, pages flipped back and forth)
+/// skip the resize/rotate/JPEG step entirely. Oldest entries are evicted first.
+pub struct ImageCache {
+    entries: HashMap<u64, Arc<Vec<u8>>>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl ImageCache {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Hash a rendered RGBA buffer into a cache key.
+    #[must_use]
+    pub fn key_for(rgba: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        rgba.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Look up an encoded image.
+    #[must_use]
+    pub fn get(&self, key: u64) -> Option<Arc<Vec<u8>>> {
+        self.entries.get(&key).cloned()
+    }
+
+    /// Store an encoded image, evicting the oldest entry when full.
+    pub fn insert(&mut self, key: u64, encoded: Arc<Vec<u8>>) {
+        if self.entries.insert(key, encoded).is_some() {
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.entries.remove(&old);
+            }
+        }
+    }
+
+    /// Number of cached images.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_oldest_first() {
+        let mut cache = ImageCache::new(2);
+        cache.insert(1, Arc::new(vec![1]));
+        cache.insert(2, Arc::new(vec![2]));
+        cache.insert(3, Arc::new(vec![3]));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.get(3).as_deref(), Some(&vec![3]));
+    }
+
+    #[test]
+    fn identical_buffers_share_key() {
+        let a = vec![0u8; 64];
+        let b = vec![0u8; 64];
+        assert_eq!(ImageCache::key_for(&a), ImageCache::key_for(&b));
+        assert_ne!(ImageCache::key_for(&a), ImageCache::key_for(&[1u8; 64]));
+    }
+}
@@ -1,43 +1,89 @@
-use crate::error::{DeckError, Result};
-use crate::event::DeckEvent;
-use elgato_streamdeck::asynchronous::AsyncStreamDeck;
+use crate::device::Deck;
+use crate::error::Result;
+use crate::event::{DeckEvent, RawInput};
+use crate::ids::KeyIndex;
 use elgato_streamdeck::StreamDeckInput;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
-/// Read button events from the Stream Deck, forwarding to broadcast channel.
+/// Read input events from the Stream Deck, forwarding to broadcast channel.
+///
+/// Key presses become `ButtonDown`/`ButtonUp`; encoder and touch strip input
+/// is forwarded as `DeckEvent::RawInput`.
 ///
 /// # Errors
 /// Returns `DeckError::Hid` if the device disconnects or a read error occurs.
 pub async fn read_input_loop(
-    deck: Arc<AsyncStreamDeck>,
+    deck: Arc<Deck>,
     tx: broadcast::Sender<DeckEvent>,
     cancel: CancellationToken,
 ) -> Result<()> {
+    let key_count = deck.capabilities().keys;
+    let mut dials_down: Vec<bool> = Vec::new();
     loop {
         if cancel.is_cancelled() {
             return Ok(());
         }
 
         // read_input uses block_in_place internally, poll at 60Hz.
-        let input = deck
-            .read_input(60.0)
-            .await
-            .map_err(|e| DeckError::Hid(e.to_string()))?;
+        let input = deck.read_input().await?;
 
-        if let StreamDeckInput::ButtonStateChange(buttons) = input {
-            for (idx, &pressed) in buttons.iter().enumerate() {
-                let key = idx as u8;
-                if pressed {
-                    debug!("button {key} down");
-                    let _ = tx.send(DeckEvent::ButtonDown(key));
-                } else {
-                    debug!("button {key} up");
-                    let _ = tx.send(DeckEvent::ButtonUp(key));
+        match input {
+            StreamDeckInput::ButtonStateChange(buttons) => {
+                for (idx, &pressed) in buttons.iter().enumerate() {
+                    let Some(key) = KeyIndex::new(idx as u8, key_count) else {
+                        continue;
+                    };
+                    if pressed {
+                        debug!("button {key} down");
+                        let _ = tx.send(DeckEvent::ButtonDown(key));
+                    } else {
+                        debug!("button {key} up");
+                        let _ = tx.send(DeckEvent::ButtonUp(key));
+                    }
+                }
+            }
+            StreamDeckInput::NoData => {}
+            StreamDeckInput::EncoderStateChange(ref states) => {
+                for (idx, &down) in states.iter().enumerate() {
+                    if down && !dials_down.get(idx).copied().unwrap_or(false) {
+                        debug!("dial {idx} pressed");
+                        let _ = tx.send(DeckEvent::DialPress(idx as u8));
+                    }
                 }
+                dials_down.clone_from(states);
+                forward_raw(input, &tx);
             }
+            StreamDeckInput::EncoderTwist(ref deltas) => {
+                for (idx, &delta) in deltas.iter().enumerate().filter(|(_, &d)| d != 0) {
+                    debug!("dial {idx} turned {delta}");
+                    let _ = tx.send(DeckEvent::DialRotate(idx as u8, delta));
+                }
+                forward_raw(input, &tx);
+            }
+            other => forward_raw(other, &tx),
         }
     }
 }
+
+/// Forward device input as `DeckEvent::RawInput` for integrations.
+fn forward_raw(input: StreamDeckInput, tx: &broadcast::Sender<DeckEvent>) {
+    if let Some(raw) = raw_input(input) {
+        debug!("raw input: {raw:?}");
+        let _ = tx.send(DeckEvent::RawInput(raw));
+    }
+}
+
+/// Convert non-button device input into a `RawInput` event payload.
+fn raw_input(input: StreamDeckInput) -> Option<RawInput> {
+    match input {
+        StreamDeckInput::EncoderStateChange(states) => Some(RawInput::EncoderStateChange(states)),
+        StreamDeckInput::EncoderTwist(deltas) => Some(RawInput::EncoderTwist(deltas)),
+        StreamDeckInput::TouchScreenPress(x, y) => Some(RawInput::TouchPress { x, y }),
+        StreamDeckInput::TouchScreenLongPress(x, y) => Some(RawInput::TouchLongPress { x, y }),
+        StreamDeckInput::TouchScreenSwipe(from, to) => Some(RawInput::TouchSwipe { from, to }),
+        StreamDeckInput::NoData | StreamDeckInput::ButtonStateChange(_) => None,
+    }
+}
@@ -1,17 +1,41 @@
+pub mod backoff;
+pub mod brightness;
+pub mod diagnose;
+pub mod encode;
 pub mod input;
+pub mod remote;
 
 use crate::error::{DeckError, Result};
 use crate::event::DeckEvent;
+use crate::status::{Issue, IssueSource};
 use arc_swap::ArcSwap;
+use backoff::{Backoff, ReconnectPolicy};
 use elgato_streamdeck::asynchronous::AsyncStreamDeck;
-use std::sync::Arc;
-use std::time::Duration;
+use elgato_streamdeck::info::{ImageFormat, ImageMirroring, ImageMode, ImageRotation, Kind};
+use elgato_streamdeck::StreamDeckInput;
+use encode::ImageCache;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
-use tracing::{info, warn};
+use tracing::{debug, error, info, warn};
+
+/// Decks opened by [`open_early`], until their manager takes them.
+static OPENED: Mutex<Vec<Arc<Deck>>> = Mutex::new(Vec::new());
+
+/// Open the deck each of `selectors` picks now, while deckd may still have
+/// the privileges to, for the device managers to take later.
+pub(crate) fn open_early(selectors: &[DeviceSelector]) {
+    for selector in selectors {
+        match DeviceManager::discover_and_connect(selector) {
+            Ok(deck) => OPENED.lock().unwrap().push(deck),
+            Err(e) => warn!("cannot open Stream Deck before dropping privileges: {e}"),
+        }
+    }
+}
 
 /// Shared handle to the currently connected Stream Deck (if any).
-pub type DeckHandle = Arc<ArcSwap<Option<Arc<AsyncStreamDeck>>>>;
+pub type DeckHandle = Arc<ArcSwap<Option<Arc<Deck>>>>;
 
 /// Create a new empty deck handle.
 #[must_use]
@@ -19,12 +43,385 @@ pub fn new_deck_handle() -> DeckHandle {
     Arc::new(ArcSwap::from_pointee(None))
 }
 
+/// What a model has and how its key images are encoded. Model-specific
+/// decisions go through this, so a Mini, an Original and an MK.2 share one
+/// config format.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    pub keys: u8,
+    pub rows: u8,
+    pub columns: u8,
+    pub encoders: u8,
+    /// Whether the keys have screens (the Pedal's do not).
+    pub visual: bool,
+    /// Native key image: size, encoding, rotation and mirroring.
+    pub image_format: ImageFormat,
+}
+
+impl Capabilities {
+    #[must_use]
+    pub fn of(kind: Kind) -> Self {
+        Self {
+            keys: kind.key_count(),
+            rows: kind.row_count(),
+            columns: kind.column_count(),
+            encoders: kind.encoder_count(),
+            visual: kind.is_visual(),
+            image_format: kind.key_image_format(),
+        }
+    }
+
+    /// Key image size in pixels (width, height).
+    #[must_use]
+    pub const fn key_size(&self) -> (usize, usize) {
+        self.image_format.size
+    }
+
+    /// Side of the key images to render: the native size, or
+    /// [`BUTTON_SIZE`](crate::render::canvas::BUTTON_SIZE) for models
+    /// without screens.
+    #[must_use]
+    pub fn render_size(&self) -> u32 {
+        match self.image_format.size.0 {
+            0 => crate::render::canvas::BUTTON_SIZE,
+            width => width as u32,
+        }
+    }
+
+    /// The key image format, e.g. "BMP, rotated 90°, mirrored".
+    #[must_use]
+    pub fn image_description(&self) -> String {
+        describe_format(self.image_format)
+    }
+}
+
+fn describe_format(format: ImageFormat) -> String {
+    let mut parts = vec![match format.mode {
+        ImageMode::None => "none",
+        ImageMode::BMP => "BMP",
+        ImageMode::JPEG => "JPEG",
+    }
+    .to_string()];
+    match format.rotation {
+        ImageRotation::Rot0 => {}
+        ImageRotation::Rot90 => parts.push("rotated 90°".into()),
+        ImageRotation::Rot180 => parts.push("rotated 180°".into()),
+        ImageRotation::Rot270 => parts.push("rotated 270°".into()),
+    }
+    match format.mirror {
+        ImageMirroring::None => {}
+        ImageMirroring::X => parts.push("mirrored horizontally".into()),
+        ImageMirroring::Y => parts.push("mirrored vertically".into()),
+        ImageMirroring::Both => parts.push("mirrored".into()),
+    }
+    parts.join(", ")
+}
+
+/// Model, identity and key geometry of a Stream Deck.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub kind: Kind,
+    pub serial: String,
+    /// `None` if the device could not be opened to ask.
+    pub firmware: Option<String>,
+    pub keys: u8,
+    pub rows: u8,
+    pub columns: u8,
+    pub encoders: u8,
+    /// Key image size in pixels (width, height).
+    pub key_size: (usize, usize),
+    /// Native key image format, see [`Capabilities::image_description`].
+    pub image_format: String,
+}
+
+impl DeviceInfo {
+    /// Static information about a model, without firmware.
+    #[must_use]
+    pub fn from_kind(kind: Kind, serial: String) -> Self {
+        let caps = Capabilities::of(kind);
+        Self {
+            kind,
+            serial,
+            firmware: None,
+            keys: caps.keys,
+            rows: caps.rows,
+            columns: caps.columns,
+            encoders: caps.encoders,
+            key_size: caps.key_size(),
+            image_format: caps.image_description(),
+        }
+    }
+
+    /// Query an open device, including its firmware version.
+    pub async fn query(device: &AsyncStreamDeck, serial: String) -> Self {
+        let mut info = Self::from_kind(device.kind(), serial);
+        info.firmware = device.firmware_version().await.ok();
+        info
+    }
+}
+
+/// List all connected Stream Decks. Each device is opened briefly to read its
+/// firmware version; devices that cannot be opened are still listed.
+///
+/// # Errors
+/// Returns `DeckError::Hid` if the HID stack cannot be initialized.
+pub async fn list_devices() -> Result<Vec<DeviceInfo>> {
+    let hid = elgato_streamdeck::new_hidapi().map_err(|e| DeckError::Hid(e.to_string()))?;
+
+    let mut infos = Vec::new();
+    for (kind, serial) in elgato_streamdeck::list_devices(&hid) {
+        let info = match AsyncStreamDeck::connect(&hid, kind, &serial) {
+            Ok(device) => DeviceInfo::query(&device, serial).await,
+            Err(e) => {
+                warn!("cannot open {kind:?} ({serial}): {e}");
+                DeviceInfo::from_kind(kind, serial)
+            }
+        };
+        infos.push(info);
+    }
+    Ok(infos)
+}
+
+/// How a deck is reached.
+enum Backend {
+    Usb(AsyncStreamDeck),
+    /// Through a `deckd agent` on another host.
+    Remote(remote::Link),
+}
+
+/// A connected Stream Deck together with its cache of encoded key images.
+pub struct Deck {
+    backend: Backend,
+    kind: Kind,
+    serial: String,
+    images: Mutex<ImageCache>,
+}
+
+impl Deck {
+    #[must_use]
+    pub fn new(device: AsyncStreamDeck, serial: String) -> Self {
+        Self {
+            kind: device.kind(),
+            backend: Backend::Usb(device),
+            serial,
+            images: Mutex::new(ImageCache::default()),
+        }
+    }
+
+    /// A deck driven by a `deckd agent`.
+    #[must_use]
+    pub fn remote(link: remote::Link) -> Self {
+        Self {
+            kind: link.kind(),
+            serial: link.serial().to_string(),
+            backend: Backend::Remote(link),
+            images: Mutex::new(ImageCache::default()),
+        }
+    }
+
+    /// The serial number the device was opened with.
+    #[must_use]
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+
+    /// The detected device model.
+    #[must_use]
+    pub const fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Keys, layout and image format of the model.
+    #[must_use]
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::of(self.kind)
+    }
+
+    /// Model, serial and firmware of the deck.
+    pub async fn info(&self) -> DeviceInfo {
+        match &self.backend {
+            Backend::Usb(device) => DeviceInfo::query(device, self.serial.clone()).await,
+            Backend::Remote(link) => link.info(),
+        }
+    }
+
+    /// Set display brightness (0-100).
+    ///
+    /// # Errors
+    /// Returns `DeckError::Device` if the device rejects the request.
+    pub async fn set_brightness(&self, percent: u8) -> Result<()> {
+        match &self.backend {
+            Backend::Usb(device) => device
+                .set_brightness(percent)
+                .await
+                .map_err(|e| DeckError::Device(e.to_string())),
+            Backend::Remote(link) => link.set_brightness(percent).await,
+        }
+    }
+
+    /// Wait for the next input, polling at 60Hz.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Hid` if the device (or agent) is gone.
+    pub async fn read_input(&self) -> Result<StreamDeckInput> {
+        match &self.backend {
+            Backend::Usb(device) => device
+                .read_input(60.0)
+                .await
+                .map_err(|e| DeckError::Hid(e.to_string())),
+            Backend::Remote(link) => link.read_input().await,
+        }
+    }
+
+    /// Write an image already in the device's native format to a key.
+    async fn write_image(&self, key: u8, encoded: &[u8]) -> Result<()> {
+        match &self.backend {
+            Backend::Usb(device) => device
+                .write_image(key, encoded)
+                .await
+                .map_err(|e| DeckError::Device(e.to_string())),
+            Backend::Remote(link) => link.write_image(key, encoded).await,
+        }
+    }
+
+    /// Push a rendered `size`x`size` RGBA image to a key.
+    ///
+    /// The image is encoded to the device's native format (rotated/mirrored
+    /// JPEG or BMP) at most once per distinct render and written directly,
+    /// so re-pushing unchanged keys costs only a hash and a USB write.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Render` if encoding fails, or `DeckError::Device`
+    /// if the write fails.
+    pub async fn set_key_rgba(&self, key: u8, rgba: &[u8], size: u32) -> Result<()> {
+        if !self.capabilities().visual {
+            return Ok(());
+        }
+        let encoded = self.encode(rgba, size)?;
+        self.write_image(key, &encoded).await
+    }
+
+    /// Push several rendered `size`x`size` RGBA images, encoding all of them
+    /// before the first write so the keys change in one burst.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Render` if encoding fails (nothing is written
+    /// then), or `DeckError::Device` if a write fails.
+    pub async fn set_keys_rgba(&self, images: &[(u8, &[u8])], size: u32) -> Result<()> {
+        if !self.capabilities().visual {
+            return Ok(());
+        }
+        let encoded = images
+            .iter()
+            .map(|&(key, rgba)| Ok((key, self.encode(rgba, size)?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (key, data) in encoded {
+            self.write_image(key, &data).await?;
+        }
+        Ok(())
+    }
+
+    /// Encode a rendered image into the cache without writing it, so a later
+    /// `set_key_rgba` with the same pixels is just a USB write.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Render` if encoding fails.
+    pub fn preload(&self, rgba: &[u8], size: u32) -> Result<()> {
+        self.encode(rgba, size).map(|_| ())
+    }
+
+    fn encode(&self, rgba: &[u8], size: u32) -> Result<Arc<Vec<u8>>> {
+        let calibration = crate::render::calibrate::for_device(&self.serial);
+        let mut hash = ImageCache::key_for(rgba);
+        if !calibration.is_identity() {
+            hash ^= calibration.fingerprint();
+        }
+        if let Some(hit) = self.images.lock().unwrap().get(hash) {
+            return Ok(hit);
+        }
+
+        let mut rgba = rgba.to_vec();
+        if !calibration.is_identity() {
+            calibration.apply(&mut rgba, size);
+        }
+        let img = image::RgbaImage::from_raw(size, size, rgba)
+            .ok_or_else(|| DeckError::Render("key image buffer has wrong size".into()))?;
+        let encoded = elgato_streamdeck::images::convert_image(self.kind(), img.into())
+            .map_err(|e| DeckError::Render(format!("image encode failed: {e}")))?;
+        let encoded = Arc::new(encoded);
+
+        self.images
+            .lock()
+            .unwrap()
+            .insert(hash, Arc::clone(&encoded));
+        Ok(encoded)
+    }
+}
+
+/// Connection state and counters, shared with the control API.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceStatus {
+    /// The connected device, `None` while disconnected.
+    pub info: Option<DeviceInfo>,
+    /// When the current connection was established.
+    pub connected_at: Option<SystemTime>,
+    /// Why and when the device was last lost.
+    pub last_disconnect: Option<Disconnect>,
+    /// Successful connects, including the first one.
+    pub connects: u64,
+    /// Failed connect attempts since startup.
+    pub failed_attempts: u64,
+}
+
+/// A lost connection.
+#[derive(Debug, Clone)]
+pub struct Disconnect {
+    pub reason: String,
+    pub at: SystemTime,
+}
+
+/// Shared handle to the device connection state.
+pub type DeviceStatusHandle = Arc<Mutex<DeviceStatus>>;
+
+/// Which of the connected decks a `DeviceManager` drives.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSelector {
+    /// Only the deck with this serial number.
+    pub serial: Option<String>,
+    /// Only decks of this model, e.g. `"Xl"` (case-insensitive).
+    pub kind: Option<String>,
+    /// Decks driven by other managers, never picked.
+    pub exclude: Vec<String>,
+}
+
+impl DeviceSelector {
+    /// The first of `devices` this selector accepts.
+    fn pick<'a>(&self, devices: &'a [(Kind, String)]) -> Option<&'a (Kind, String)> {
+        devices
+            .iter()
+            .find(|(kind, serial)| self.accepts(*kind, serial))
+    }
+
+    fn accepts(&self, kind: Kind, serial: &str) -> bool {
+        self.serial.as_ref().is_none_or(|s| s == serial)
+            && self
+                .kind
+                .as_ref()
+                .is_none_or(|k| format!("{kind:?}").eq_ignore_ascii_case(k))
+            && !self.exclude.iter().any(|s| s == serial)
+    }
+}
+
 /// Manages discovery, connection, and reconnection of a Stream Deck device.
 pub struct DeviceManager {
     tx: broadcast::Sender<DeckEvent>,
     cancel: CancellationToken,
-    reconnect_interval: Duration,
+    policy: ReconnectPolicy,
     handle: DeckHandle,
+    status: DeviceStatusHandle,
+    /// Address of a `deckd agent` to use instead of USB.
+    remote: Option<String>,
+    selector: DeviceSelector,
 }
 
 impl DeviceManager {
@@ -32,30 +429,84 @@ impl DeviceManager {
     pub fn new(
         tx: broadcast::Sender<DeckEvent>,
         cancel: CancellationToken,
-        reconnect_interval_ms: u64,
+        policy: ReconnectPolicy,
         handle: DeckHandle,
+        status: DeviceStatusHandle,
     ) -> Self {
         Self {
             tx,
             cancel,
-            reconnect_interval: Duration::from_millis(reconnect_interval_ms),
+            policy,
             handle,
+            status,
+            remote: None,
+            selector: DeviceSelector::default(),
         }
     }
 
+    /// Drive only the USB deck `selector` picks.
+    #[must_use]
+    pub fn with_selector(mut self, selector: DeviceSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Drive the deck of the `deckd agent` at `addr` instead of one on USB.
+    #[must_use]
+    pub fn with_remote(mut self, addr: Option<String>) -> Self {
+        self.remote = addr;
+        self
+    }
+
     /// Run the device manager loop: discover -> connect -> read -> reconnect on disconnect.
     ///
+    /// Failed connects are retried with exponential backoff and jitter; after
+    /// `hook_after` consecutive failures the `on_failed` action runs once.
+    ///
     /// # Errors
     /// Returns `DeckError` if a fatal device error occurs.
     pub async fn run(self) -> Result<()> {
+        let mut backoff = Backoff::new(self.policy.base, self.policy.max);
+        // Connect failures are reported once per streak, not on every retry.
+        let mut failure_reported = false;
+        let mut hook_fired = false;
+        let started = Instant::now();
         loop {
             if self.cancel.is_cancelled() {
                 return Ok(());
             }
 
-            match Self::discover_and_connect() {
+            let connected = match &self.remote {
+                Some(addr) => {
+                    let token = crate::secret::from_env("DECKD_AGENT_TOKEN", "deckd_agent_token");
+                    remote::Link::connect(addr, token.ok().as_deref())
+                        .await
+                        .map(|link| Arc::new(Deck::remote(link)))
+                }
+                None => Self::discover_and_connect(&self.selector),
+            };
+            let delay = match connected {
                 Ok(deck) => {
-                    info!("Stream Deck connected");
+                    let info = deck.info().await;
+                    let connects = {
+                        let mut status = self.status.lock().unwrap();
+                        status.info = Some(info);
+                        status.connected_at = Some(SystemTime::now());
+                        status.connects += 1;
+                        status.connects
+                    };
+                    if connects > 1 {
+                        info!(
+                            "Stream Deck reconnected after {} failed attempt(s) (reconnect #{})",
+                            backoff.attempts(),
+                            connects - 1
+                        );
+                    } else {
+                        info!("Stream Deck connected");
+                    }
+                    backoff.reset();
+                    failure_reported = false;
+                    hook_fired = false;
                     self.handle.store(Arc::new(Some(Arc::clone(&deck))));
                     let _ = self.tx.send(DeckEvent::DeviceConnected);
 
@@ -64,35 +515,182 @@ impl DeviceManager {
                     {
                         warn!("device disconnected: {e}");
                         self.handle.store(Arc::new(None));
+                        {
+                            let mut status = self.status.lock().unwrap();
+                            status.info = None;
+                            status.connected_at = None;
+                            status.last_disconnect = Some(Disconnect {
+                                reason: e.to_string(),
+                                at: SystemTime::now(),
+                            });
+                        }
                         let _ = self.tx.send(DeckEvent::DeviceDisconnected);
+                        let issue = Issue::new(
+                            IssueSource::Device,
+                            format!("disconnected: {}", e.user_message()),
+                        );
+                        let _ = self.tx.send(DeckEvent::Issue(issue));
                     }
+                    self.policy.base
                 }
                 Err(e) => {
-                    warn!("no device found: {e}");
+                    let connects = {
+                        let mut status = self.status.lock().unwrap();
+                        status.failed_attempts += 1;
+                        status.connects
+                    };
+                    let startup_retry = self.policy.startup_retry(started.elapsed(), connects);
+                    let delay = if let Some(delay) = startup_retry {
+                        // The USB stack may still be coming up at boot.
+                        debug!("waiting for device: {e}");
+                        delay
+                    } else {
+                        let delay = backoff.next_delay();
+                        warn!(
+                            "device connect failed: {e} (attempt {}, retrying in {}ms)",
+                            backoff.attempts(),
+                            delay.as_millis()
+                        );
+                        delay
+                    };
+                    // Not finding a device is normal while waiting for one.
+                    if !failure_reported && !matches!(e, DeckError::NoDevice) {
+                        let issue = Issue::from_error(IssueSource::Device, &e);
+                        let _ = self.tx.send(DeckEvent::Issue(issue));
+                        failure_reported = true;
+                    }
+                    if !hook_fired
+                        && self.policy.hook_after > 0
+                        && backoff.attempts() >= self.policy.hook_after
+                    {
+                        hook_fired = true;
+                        self.run_failure_hook(backoff.attempts());
+                    }
+                    delay
                 }
-            }
+            };
 
             tokio::select! {
                 () = self.cancel.cancelled() => return Ok(()),
-                () = tokio::time::sleep(self.reconnect_interval) => {}
+                () = tokio::time::sleep(delay) => {}
             }
         }
     }
 
-    fn discover_and_connect() -> Result<Arc<AsyncStreamDeck>> {
+    /// Run `on_reconnect_failed` in the background.
+    fn run_failure_hook(&self, attempts: u32) {
+        let Some(action) = self.policy.on_failed.clone() else {
+            return;
+        };
+        warn!("device still missing after {attempts} attempts, running on_reconnect_failed");
+        let tx = self.tx.clone();
+        let ctx = crate::action::ActionContext::new(self.policy.locale);
+        tokio::spawn(async move {
+            if let Err(e) = crate::action::execute(&action, &tx, &ctx).await {
+                error!("on_reconnect_failed action error: {e}");
+            }
+        });
+    }
+
+    /// Open the first Stream Deck on USB that `selector` accepts.
+    pub(crate) fn discover_and_connect(selector: &DeviceSelector) -> Result<Arc<Deck>> {
+        {
+            let mut opened = OPENED.lock().unwrap();
+            if let Some(i) = opened
+                .iter()
+                .position(|deck| selector.accepts(deck.kind(), deck.serial()))
+            {
+                return Ok(opened.swap_remove(i));
+            }
+        }
+
         let hid = elgato_streamdeck::new_hidapi().map_err(|e| DeckError::Hid(e.to_string()))?;
 
         let devices = elgato_streamdeck::list_devices(&hid);
         if devices.is_empty() {
-            return Err(DeckError::NoDevice);
+            return Err(
+                diagnose::permission_problem().map_or(DeckError::NoDevice, |path| {
+                    DeckError::PermissionDenied { path }
+                }),
+            );
         }
 
-        let (kind, serial) = &devices[0];
+        let Some((kind, serial)) = selector.pick(&devices) else {
+            debug!("none of {} Stream Deck(s) is selected", devices.len());
+            return Err(DeckError::NoDevice);
+        };
         info!("found Stream Deck {:?} (serial: {})", kind, serial);
 
-        let deck = AsyncStreamDeck::connect(&hid, *kind, serial)
-            .map_err(|e| DeckError::Device(e.to_string()))?;
+        let deck = AsyncStreamDeck::connect(&hid, *kind, serial).map_err(|e| {
+            // hidapi only says the open failed; find out whether it was permissions.
+            diagnose::permission_problem().map_or_else(
+                || DeckError::Device(e.to_string()),
+                |path| DeckError::PermissionDenied { path },
+            )
+        })?;
+
+        Ok(Arc::new(Deck::new(deck, serial.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_decks_by_serial_and_kind() {
+        let devices = [
+            (Kind::Mk2, "AAA".to_string()),
+            (Kind::Xl, "BBB".to_string()),
+        ];
+        let pick = |selector: DeviceSelector| selector.pick(&devices).map(|(_, s)| s.as_str());
+        assert_eq!(pick(DeviceSelector::default()), Some("AAA"));
+        assert_eq!(
+            pick(DeviceSelector {
+                exclude: vec!["AAA".into()],
+                ..DeviceSelector::default()
+            }),
+            Some("BBB")
+        );
+        assert_eq!(
+            pick(DeviceSelector {
+                serial: Some("CCC".into()),
+                ..DeviceSelector::default()
+            }),
+            None
+        );
+        assert_eq!(
+            pick(DeviceSelector {
+                kind: Some("xl".into()),
+                ..DeviceSelector::default()
+            }),
+            Some("BBB")
+        );
+    }
 
-        Ok(Arc::new(deck))
+    #[test]
+    fn describes_image_formats() {
+        let format = |mode, rotation, mirror| ImageFormat {
+            mode,
+            size: (72, 72),
+            rotation,
+            mirror,
+        };
+        assert_eq!(
+            describe_format(format(
+                ImageMode::JPEG,
+                ImageRotation::Rot180,
+                ImageMirroring::None
+            )),
+            "JPEG, rotated 180°"
+        );
+        assert_eq!(
+            describe_format(format(
+                ImageMode::BMP,
+                ImageRotation::Rot0,
+                ImageMirroring::Both
+            )),
+            "BMP, mirrored"
+        );
     }
 }
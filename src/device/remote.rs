@@ -0,0 +1,485 @@
+//! Driving a deck over TCP: `deckd agent` runs on the host the deck is
+//! plugged into and only does USB I/O, while the daemon with the config
+//! renders and encodes key images and streams them over. Keeps a Pi Zero
+//! nearly idle with animations running on a bigger machine.
+//!
+//! Frames are a big-endian `u32` length, a tag byte and the payload. The
+//! daemon opens with a hello carrying the token, the agent answers with the
+//! deck's model and serial (or an error), then images and brightness flow
+//! to the agent and input back.
+
+use super::{Deck, DeviceInfo};
+use crate::error::{DeckError, Result};
+use elgato_streamdeck::info::Kind;
+use elgato_streamdeck::StreamDeckInput;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Port `deckd agent` listens on by default.
+pub const DEFAULT_PORT: u16 = 8766;
+
+/// Largest frame accepted; key images are a few KB.
+const MAX_FRAME: usize = 1 << 20;
+
+/// How long the handshake may take.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long reading input waits before reporting none, so the input loop
+/// notices shutdown.
+const INPUT_POLL: Duration = Duration::from_millis(100);
+
+const TAG_HELLO: u8 = 1;
+const TAG_INPUT: u8 = 2;
+const TAG_IMAGE: u8 = 3;
+const TAG_BRIGHTNESS: u8 = 4;
+const TAG_ERROR: u8 = 5;
+
+/// Models known by name on the wire.
+const KINDS: [Kind; 10] = [
+    Kind::Original,
+    Kind::OriginalV2,
+    Kind::Mini,
+    Kind::MiniMk2,
+    Kind::Xl,
+    Kind::XlV2,
+    Kind::Mk2,
+    Kind::Pedal,
+    Kind::Plus,
+    Kind::Neo,
+];
+
+/// The daemon's hello.
+#[derive(Debug, Serialize, Deserialize)]
+struct ClientHello {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+/// The agent's hello: the deck it drives.
+#[derive(Debug, Serialize, Deserialize)]
+struct AgentHello {
+    kind: String,
+    serial: String,
+    #[serde(default)]
+    firmware: Option<String>,
+}
+
+/// Device input on the wire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WireInput {
+    Buttons { pressed: Vec<bool> },
+    Encoders { pressed: Vec<bool> },
+    Twist { deltas: Vec<i8> },
+    TouchPress { x: u16, y: u16 },
+    TouchLongPress { x: u16, y: u16 },
+    TouchSwipe { from: (u16, u16), to: (u16, u16) },
+}
+
+impl WireInput {
+    fn from_input(input: StreamDeckInput) -> Option<Self> {
+        Some(match input {
+            StreamDeckInput::NoData => return None,
+            StreamDeckInput::ButtonStateChange(pressed) => Self::Buttons { pressed },
+            StreamDeckInput::EncoderStateChange(pressed) => Self::Encoders { pressed },
+            StreamDeckInput::EncoderTwist(deltas) => Self::Twist { deltas },
+            StreamDeckInput::TouchScreenPress(x, y) => Self::TouchPress { x, y },
+            StreamDeckInput::TouchScreenLongPress(x, y) => Self::TouchLongPress { x, y },
+            StreamDeckInput::TouchScreenSwipe(from, to) => Self::TouchSwipe { from, to },
+        })
+    }
+
+    fn into_input(self) -> StreamDeckInput {
+        match self {
+            Self::Buttons { pressed } => StreamDeckInput::ButtonStateChange(pressed),
+            Self::Encoders { pressed } => StreamDeckInput::EncoderStateChange(pressed),
+            Self::Twist { deltas } => StreamDeckInput::EncoderTwist(deltas),
+            Self::TouchPress { x, y } => StreamDeckInput::TouchScreenPress(x, y),
+            Self::TouchLongPress { x, y } => StreamDeckInput::TouchScreenLongPress(x, y),
+            Self::TouchSwipe { from, to } => StreamDeckInput::TouchScreenSwipe(from, to),
+        }
+    }
+}
+
+fn kind_name(kind: Kind) -> String {
+    format!("{kind:?}")
+}
+
+fn kind_from_name(name: &str) -> Option<Kind> {
+    KINDS.into_iter().find(|kind| kind_name(*kind) == name)
+}
+
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> std::io::Result<(u8, Vec<u8>)> {
+    let len = reader.read_u32().await? as usize;
+    if len == 0 || len > MAX_FRAME {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("bad frame length {len}"),
+        ));
+    }
+    let tag = reader.read_u8().await?;
+    let mut payload = vec![0; len - 1];
+    reader.read_exact(&mut payload).await?;
+    Ok((tag, payload))
+}
+
+async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    tag: u8,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.extend(((payload.len() + 1) as u32).to_be_bytes());
+    frame.push(tag);
+    frame.extend(payload);
+    writer.write_all(&frame).await
+}
+
+/// Notice a host that vanished without closing the connection.
+fn keepalive(stream: &TcpStream) {
+    let keepalive = socket2::TcpKeepalive::new()
+        .with_time(Duration::from_secs(10))
+        .with_interval(Duration::from_secs(5));
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        warn!("cannot enable TCP keepalive: {e}");
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap_or_default()
+}
+
+/// A connection to a `deckd agent`.
+pub struct Link {
+    kind: Kind,
+    serial: String,
+    firmware: Option<String>,
+    writer: tokio::sync::Mutex<OwnedWriteHalf>,
+    inputs: tokio::sync::Mutex<mpsc::Receiver<StreamDeckInput>>,
+    reader: tokio::task::JoinHandle<()>,
+}
+
+impl Link {
+    /// Connect to the agent at `addr` (`host[:port]`) and learn its deck.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Device` if the agent cannot be reached, refuses
+    /// the token or has no deck.
+    pub async fn connect(addr: &str, token: Option<&str>) -> Result<Self> {
+        let addr = with_default_port(addr);
+        let failed = |e: &dyn std::fmt::Display| DeckError::Device(format!("agent {addr}: {e}"));
+        let handshake = async {
+            let stream = TcpStream::connect(&addr).await?;
+            stream.set_nodelay(true)?;
+            keepalive(&stream);
+            let (mut reader, mut writer) = stream.into_split();
+            let hello = ClientHello {
+                token: token.map(str::to_string),
+            };
+            write_frame(&mut writer, TAG_HELLO, &json(&hello)).await?;
+            let answer = read_frame(&mut reader).await?;
+            Ok::<_, std::io::Error>((reader, writer, answer))
+        };
+        let (mut reader, writer, answer) = tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+            .await
+            .map_err(|_| failed(&"handshake timed out"))?
+            .map_err(|e| failed(&e))?;
+        let hello: AgentHello = match answer {
+            (TAG_HELLO, payload) => serde_json::from_slice(&payload).map_err(|e| failed(&e))?,
+            (TAG_ERROR, message) => return Err(failed(&String::from_utf8_lossy(&message))),
+            (tag, _) => return Err(failed(&format!("unexpected frame {tag}"))),
+        };
+        let kind = kind_from_name(&hello.kind)
+            .ok_or_else(|| failed(&format!("unknown model {}", hello.kind)))?;
+
+        let (tx, rx) = mpsc::channel(64);
+        let reader = tokio::spawn(async move {
+            while let Ok((tag, payload)) = read_frame(&mut reader).await {
+                if tag != TAG_INPUT {
+                    continue;
+                }
+                match serde_json::from_slice::<WireInput>(&payload) {
+                    Ok(input) => {
+                        if tx.send(input.into_input()).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("agent sent bad input: {e}"),
+                }
+            }
+        });
+        info!("connected to agent {addr}: {:?} ({})", kind, hello.serial);
+        Ok(Self {
+            kind,
+            serial: hello.serial,
+            firmware: hello.firmware,
+            writer: tokio::sync::Mutex::new(writer),
+            inputs: tokio::sync::Mutex::new(rx),
+            reader,
+        })
+    }
+
+    /// The model of the remote deck.
+    #[must_use]
+    pub const fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// The serial of the remote deck.
+    #[must_use]
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+
+    pub(super) fn info(&self) -> DeviceInfo {
+        let mut info = DeviceInfo::from_kind(self.kind, self.serial.clone());
+        info.firmware.clone_from(&self.firmware);
+        info
+    }
+
+    async fn send(&self, tag: u8, payload: &[u8]) -> Result<()> {
+        write_frame(&mut *self.writer.lock().await, tag, payload)
+            .await
+            .map_err(|e| DeckError::Device(format!("agent: {e}")))
+    }
+
+    pub(super) async fn write_image(&self, key: u8, data: &[u8]) -> Result<()> {
+        let mut payload = Vec::with_capacity(data.len() + 1);
+        payload.push(key);
+        payload.extend(data);
+        self.send(TAG_IMAGE, &payload).await
+    }
+
+    pub(super) async fn set_brightness(&self, percent: u8) -> Result<()> {
+        self.send(TAG_BRIGHTNESS, &[percent]).await
+    }
+
+    /// The next input, or `NoData` if there was none for a moment.
+    pub(super) async fn read_input(&self) -> Result<StreamDeckInput> {
+        let mut inputs = self.inputs.lock().await;
+        match tokio::time::timeout(INPUT_POLL, inputs.recv()).await {
+            Ok(Some(input)) => Ok(input),
+            Ok(None) => Err(DeckError::Hid("agent closed the connection".into())),
+            Err(_) => Ok(StreamDeckInput::NoData),
+        }
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// `addr` (a host name, IP address, `host:port` or `[v6]:port`) with
+/// [`DEFAULT_PORT`] added unless it names a port.
+fn with_default_port(addr: &str) -> String {
+    if addr.parse::<SocketAddr>().is_ok() {
+        return addr.to_string();
+    }
+    if let Ok(ip) = addr
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        return SocketAddr::new(ip, DEFAULT_PORT).to_string();
+    }
+    match addr.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => addr.to_string(),
+        _ => format!("{addr}:{DEFAULT_PORT}"),
+    }
+}
+
+/// What a daemon asks of the agent.
+enum Command {
+    Image(u8, Vec<u8>),
+    Brightness(u8),
+}
+
+/// Serve the deck on this host's USB to one daemon at a time on `listen`
+/// until cancelled. With a `token`, daemons must present it; without one,
+/// only a loopback address may be listened on.
+///
+/// # Errors
+/// Returns `DeckError::Config` for a non-loopback `listen` without a token,
+/// or `DeckError::Io` if `listen` cannot be bound.
+pub async fn serve(
+    listen: SocketAddr,
+    token: Option<String>,
+    cancel: CancellationToken,
+) -> Result<()> {
+    if token.is_none() && !listen.ip().is_loopback() {
+        return Err(DeckError::Config(format!(
+            "agent: set DECKD_AGENT_TOKEN to listen on {listen}, or listen on 127.0.0.1"
+        )));
+    }
+    let listener = TcpListener::bind(listen).await?;
+    info!("agent listening on {listen}");
+    let token: Option<Arc<str>> = token.map(Into::into);
+    // Held by the session driving the deck; later ones are turned away.
+    let deck_lock = Arc::new(tokio::sync::Mutex::new(()));
+    loop {
+        let (stream, peer) = tokio::select! {
+            () = cancel.cancelled() => return Ok(()),
+            accepted = listener.accept() => accepted?,
+        };
+        info!("render host {peer} connected");
+        // Each connection gets its own task, so one that stalls in the
+        // handshake holds up no other.
+        let (token, deck_lock, cancel) = (token.clone(), Arc::clone(&deck_lock), cancel.clone());
+        tokio::spawn(async move {
+            match session(stream, token.as_deref(), &deck_lock, &cancel).await {
+                Ok(()) => info!("render host {peer} disconnected"),
+                Err(e) => warn!("render host {peer}: {e}"),
+            }
+        });
+    }
+}
+
+/// Handle one daemon: check its hello, open the deck unless another daemon
+/// drives it, then relay.
+async fn session(
+    stream: TcpStream,
+    token: Option<&str>,
+    deck_lock: &tokio::sync::Mutex<()>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    keepalive(&stream);
+    let (mut reader, mut writer) = stream.into_split();
+    let hello = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_frame(&mut reader))
+        .await
+        .map_err(|_| DeckError::Device("handshake timed out".into()))??;
+    let hello: ClientHello = match hello {
+        (TAG_HELLO, payload) => serde_json::from_slice(&payload)
+            .map_err(|e| DeckError::Device(format!("bad hello: {e}")))?,
+        (tag, _) => return Err(DeckError::Device(format!("unexpected frame {tag}"))),
+    };
+    if let Some(token) = token {
+        let presented = hello.token.as_deref().unwrap_or_default();
+        if !crate::secret::tokens_match(presented, token) {
+            write_frame(&mut writer, TAG_ERROR, b"wrong token").await?;
+            return Err(DeckError::Device("wrong token".into()));
+        }
+    }
+    let Ok(_driving) = deck_lock.try_lock() else {
+        write_frame(
+            &mut writer,
+            TAG_ERROR,
+            b"deck in use by another render host",
+        )
+        .await?;
+        return Err(DeckError::Device(
+            "deck in use by another render host".into(),
+        ));
+    };
+    let deck = match super::DeviceManager::discover_and_connect(&super::DeviceSelector::default()) {
+        Ok(deck) => deck,
+        Err(e) => {
+            write_frame(&mut writer, TAG_ERROR, e.user_message().as_bytes()).await?;
+            return Err(e);
+        }
+    };
+    let info = deck.info().await;
+    let hello = AgentHello {
+        kind: kind_name(info.kind),
+        serial: info.serial,
+        firmware: info.firmware,
+    };
+    write_frame(&mut writer, TAG_HELLO, &json(&hello)).await?;
+
+    // Frames are read by their own task, as a read cut short by `select!`
+    // would lose its bytes.
+    let (tx, mut commands) = mpsc::channel(64);
+    let reader = tokio::spawn(async move {
+        while let Ok((tag, payload)) = read_frame(&mut reader).await {
+            let command = match (tag, payload.split_first()) {
+                (TAG_IMAGE, Some((&key, data))) => Command::Image(key, data.to_vec()),
+                (TAG_BRIGHTNESS, Some((&percent, _))) => Command::Brightness(percent),
+                _ => continue,
+            };
+            if tx.send(command).await.is_err() {
+                break;
+            }
+        }
+    });
+    let result = relay(&deck, &mut writer, &mut commands, cancel).await;
+    reader.abort();
+    // Nothing drives the display any more.
+    let _ = deck.set_brightness(0).await;
+    result
+}
+
+async fn relay(
+    deck: &Arc<Deck>,
+    writer: &mut OwnedWriteHalf,
+    commands: &mut mpsc::Receiver<Command>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return Ok(()),
+            command = commands.recv() => match command {
+                Some(Command::Image(key, data)) => deck.write_image(key, &data).await?,
+                Some(Command::Brightness(percent)) => deck.set_brightness(percent).await?,
+                None => return Ok(()),
+            },
+            input = deck.read_input() => {
+                if let Some(input) = WireInput::from_input(input?) {
+                    write_frame(writer, TAG_INPUT, &json(&input)).await?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn frames_and_input_round_trip() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        let input = WireInput::from_input(StreamDeckInput::TouchScreenSwipe((1, 2), (30, 4)));
+        write_frame(&mut a, TAG_INPUT, &json(&input)).await.unwrap();
+        write_frame(&mut a, TAG_BRIGHTNESS, &[40]).await.unwrap();
+
+        let (tag, payload) = read_frame(&mut b).await.unwrap();
+        assert_eq!(tag, TAG_INPUT);
+        let received: WireInput = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(Some(received.clone()), input);
+        assert!(matches!(
+            received.into_input(),
+            StreamDeckInput::TouchScreenSwipe((1, 2), (30, 4))
+        ));
+        assert_eq!(
+            read_frame(&mut b).await.unwrap(),
+            (TAG_BRIGHTNESS, vec![40])
+        );
+        assert!(WireInput::from_input(StreamDeckInput::NoData).is_none());
+
+        assert!(matches!(
+            kind_from_name(&kind_name(Kind::Mk2)),
+            Some(Kind::Mk2)
+        ));
+        assert!(kind_from_name("Toaster").is_none());
+    }
+
+    #[test]
+    fn adds_the_default_port_unless_given() {
+        assert_eq!(with_default_port("pi-zero"), "pi-zero:8766");
+        assert_eq!(with_default_port("pi-zero:9000"), "pi-zero:9000");
+        assert_eq!(with_default_port("192.168.1.5"), "192.168.1.5:8766");
+        assert_eq!(with_default_port("fd00::5"), "[fd00::5]:8766");
+        assert_eq!(with_default_port("[fd00::5]"), "[fd00::5]:8766");
+        assert_eq!(with_default_port("[fd00::5]:9000"), "[fd00::5]:9000");
+    }
+}
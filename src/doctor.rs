@@ -0,0 +1,109 @@
+use crate::device::diagnose;
+use std::os::unix::fs::MetadataExt;
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+fn report(level: Level, message: &str) {
+    let tag = match level {
+        Level::Ok => "ok",
+        Level::Warn => "warn",
+        Level::Fail => "FAIL",
+    };
+    println!("[{tag:>4}] {message}");
+}
+
+/// Check the environment deckd needs: udev rules, hidraw access, group
+/// membership, HID enumeration, and Home Assistant connectivity. Prints one
+/// line per check; returns `false` if any check failed.
+pub async fn run() -> bool {
+    let mut ok = true;
+    let mut check = |level: Level, message: &str| {
+        ok &= level != Level::Fail;
+        report(level, message);
+    };
+
+    // udev rules.
+    let rules = diagnose::elgato_udev_rules();
+    if rules.is_empty() {
+        check(
+            Level::Warn,
+            "no udev rule for Elgato devices (vendor 0fd9): copy udev/40-streamdeck.rules to /etc/udev/rules.d/",
+        );
+    } else {
+        for rule in &rules {
+            check(Level::Ok, &format!("udev rule: {}", rule.display()));
+        }
+    }
+
+    // hidraw nodes and permissions.
+    let nodes = diagnose::elgato_hidraw_nodes();
+    let groups = diagnose::process_groups();
+    let names = diagnose::group_names();
+    let running_as_root = std::fs::metadata("/proc/self").is_ok_and(|m| m.uid() == 0);
+    if nodes.is_empty() {
+        check(
+            Level::Warn,
+            "no Stream Deck hidraw device found (is it plugged in?)",
+        );
+    }
+    for node in &nodes {
+        if node.accessible {
+            check(Level::Ok, &format!("{} is accessible", node.path.display()));
+            continue;
+        }
+        check(
+            Level::Fail,
+            &format!("{}: permission denied", node.path.display()),
+        );
+        if let Ok(meta) = std::fs::metadata(&node.path) {
+            let gid = meta.gid();
+            let group = names.get(&gid).cloned().unwrap_or_else(|| gid.to_string());
+            if !running_as_root && !groups.contains(&gid) {
+                check(
+                    Level::Fail,
+                    &format!(
+                        "not a member of group '{group}' which owns {} (usermod -aG {group} $USER, then log in again)",
+                        node.path.display()
+                    ),
+                );
+            } else if meta.mode() & 0o060 != 0o060 {
+                check(
+                    Level::Fail,
+                    &format!(
+                        "{} is mode {:o}: reload udev rules (udevadm control --reload-rules && udevadm trigger)",
+                        node.path.display(),
+                        meta.mode() & 0o777
+                    ),
+                );
+            }
+        }
+    }
+
+    // HID enumeration through the same stack the daemon uses.
+    match elgato_streamdeck::new_hidapi() {
+        Ok(hid) => {
+            let devices = elgato_streamdeck::list_devices(&hid);
+            if devices.is_empty() {
+                check(Level::Fail, "hidapi found no Stream Deck");
+            }
+            for (kind, serial) in &devices {
+                check(Level::Ok, &format!("hidapi: {kind:?} (serial {serial})"));
+            }
+        }
+        Err(e) => check(Level::Fail, &format!("hidapi init failed: {e}")),
+    }
+
+    // Home Assistant.
+    match crate::state::check_ha_connection().await {
+        Ok(url) => check(Level::Ok, &format!("Home Assistant reachable at {url}")),
+        Err(e) => check(Level::Warn, &format!("Home Assistant: {e}")),
+    }
+
+    ok
+}
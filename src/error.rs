@@ -1,3 +1,5 @@
+use crate::ids::{KeyIndex, PageId};
+use std::fmt;
 use std::path::PathBuf;
 
 /// Central error type for deckd.
@@ -18,6 +20,13 @@ pub enum DeckError {
     #[error("no Stream Deck found")]
     NoDevice,
 
+    #[error(
+        "permission denied opening {}: install udev/40-streamdeck.rules and add this user \
+         to the plugdev group (run `deckd doctor` for details)",
+        path.display()
+    )]
+    PermissionDenied { path: PathBuf },
+
     #[error("render error: {0}")]
     Render(String),
 
@@ -36,6 +45,12 @@ pub enum DeckError {
     #[error("HTTP action failed: {0}")]
     Http(#[from] reqwest::Error),
 
+    #[error("cannot read secret file {}: {source}", path.display())]
+    Secret {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[error("shell command failed: {command}: {message}")]
     Shell { command: String, message: String },
 
@@ -50,6 +65,232 @@ pub enum DeckError {
 
     #[error("watcher error: {0}")]
     Watcher(String),
+
+    #[error("{context}: {source}")]
+    Context {
+        context: ErrorContext,
+        source: Box<DeckError>,
+    },
+}
+
+/// What kind of failure an error is, for deciding how to report or retry it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The config is invalid or refers to something missing.
+    Config,
+    /// A remote service could not be reached or answered badly.
+    Network,
+    /// The Stream Deck is missing, busy or not accessible.
+    Device,
+    /// Drawing a key failed.
+    Render,
+    /// An action ran but failed, e.g. a shell command's exit status.
+    Action,
+    /// Local file or process IO failed.
+    Io,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Config => "config",
+            Self::Network => "network",
+            Self::Device => "device",
+            Self::Render => "render",
+            Self::Action => "action",
+            Self::Io => "io",
+        })
+    }
+}
+
+/// Where an error happened: which page, key and action.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub page: Option<PageId>,
+    pub key: Option<KeyIndex>,
+    /// The action type, e.g. "http" or "shell".
+    pub action: Option<&'static str>,
+}
+
+impl ErrorContext {
+    #[must_use]
+    pub fn key(page: &PageId, key: KeyIndex) -> Self {
+        Self {
+            page: Some(page.clone()),
+            key: Some(key),
+            action: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn action(mut self, action: &'static str) -> Self {
+        self.action = Some(action);
+        self
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(ref action) = self.action {
+            parts.push(format!("{action} action"));
+        }
+        if let Some(key) = self.key {
+            parts.push(format!("key {key}"));
+        }
+        if let Some(ref page) = self.page {
+            parts.push(format!("page '{page}'"));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl DeckError {
+    /// Attach where the error happened. Context added twice is merged,
+    /// keeping the innermost values.
+    #[must_use]
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::Context {
+                context: inner,
+                source,
+            } => Self::Context {
+                context: ErrorContext {
+                    page: inner.page.or(context.page),
+                    key: inner.key.or(context.key),
+                    action: inner.action.or(context.action),
+                },
+                source,
+            },
+            source => Self::Context {
+                context,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Where the error happened, if recorded.
+    #[must_use]
+    pub const fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context.
+    #[must_use]
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Config(_)
+            | Self::ConfigNotFound(_)
+            | Self::TomlParse(_)
+            | Self::Secret { .. }
+            | Self::PageNotFound(_) => ErrorKind::Config,
+            Self::Http(_) => ErrorKind::Network,
+            Self::Device(_) | Self::NoDevice | Self::PermissionDenied { .. } | Self::Hid(_) => {
+                ErrorKind::Device
+            }
+            Self::Render(_) | Self::Font(_) | Self::Icon { .. } => ErrorKind::Render,
+            Self::Action(_) | Self::Shell { .. } => ErrorKind::Action,
+            Self::Io(_) | Self::Watcher(_) => ErrorKind::Io,
+            Self::Context { source, .. } => source.kind(),
+        }
+    }
+
+    /// Whether trying again may succeed without anything being changed.
+    /// Only failures that cannot have had an effect yet count: a request
+    /// that never connected, not one that timed out after being sent.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(e) => e.is_connect(),
+            Self::Device(_) | Self::NoDevice | Self::Hid(_) => true,
+            Self::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::ConnectionRefused
+            ),
+            Self::Context { source, .. } => source.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// A short message for the deck itself: the context and what went
+    /// wrong, without transport details that do not fit on a key.
+    #[must_use]
+    pub fn user_message(&self) -> String {
+        let message = match self.root() {
+            Self::Http(e) if e.is_connect() => "cannot connect".to_string(),
+            Self::Http(e) if e.is_timeout() => "timed out".to_string(),
+            Self::Http(e) => e
+                .status()
+                .map_or_else(|| "request failed".to_string(), |s| format!("HTTP {s}")),
+            Self::Shell { .. } => "command failed".to_string(),
+            Self::PermissionDenied { .. } => "permission denied, see deckd doctor".to_string(),
+            Self::Secret { path, .. } => format!("cannot read {}", path.display()),
+            other => other.to_string(),
+        };
+        match self.context() {
+            Some(context) => format!("{context}: {message}"),
+            None => message,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, DeckError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn kind_and_retryability() {
+        let refused = DeckError::Io(std::io::ErrorKind::ConnectionRefused.into());
+        assert_eq!(refused.kind(), ErrorKind::Io);
+        assert!(refused.is_retryable());
+        assert!(DeckError::NoDevice.is_retryable());
+        let config = DeckError::Config("bad".into());
+        assert_eq!(config.kind(), ErrorKind::Config);
+        assert!(!config.is_retryable());
+        let shell = DeckError::Shell {
+            command: "false".into(),
+            message: "exit status 1".into(),
+        };
+        assert_eq!(shell.kind(), ErrorKind::Action);
+        assert!(!shell.is_retryable());
+    }
+
+    #[test]
+    fn context_wraps_and_merges() {
+        let page = PageId::from("home");
+        let e = DeckError::Shell {
+            command: "false".into(),
+            message: "exit status 1".into(),
+        }
+        .with_context(ErrorContext::default().action("shell"))
+        .with_context(ErrorContext::key(&page, key(3)));
+        assert_eq!(e.kind(), ErrorKind::Action);
+        assert_eq!(e.context().and_then(|c| c.key), Some(key(3)));
+        assert_eq!(
+            e.to_string(),
+            "shell action, key 3, page 'home': shell command failed: false: exit status 1"
+        );
+        assert_eq!(
+            e.user_message(),
+            "shell action, key 3, page 'home': command failed"
+        );
+        assert!(matches!(e.root(), DeckError::Shell { .. }));
+    }
+}
@@ -1,14 +1,22 @@
-use crate::config::schema::AppConfig;
+use crate::config::schema::{AppConfig, ButtonConfig, KeyContent};
+use crate::ids::{KeyIndex, PageId};
+use crate::overrides::OverrideSource;
+use crate::scene::Scene;
+use crate::status::Issue;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Events flowing through the broadcast channel connecting all subsystems.
 #[derive(Debug, Clone)]
 pub enum DeckEvent {
-    /// A button was pressed (key index 0-14).
-    ButtonDown(u8),
+    /// A button was pressed.
+    ButtonDown(KeyIndex),
 
-    /// A button was released (key index 0-14).
-    ButtonUp(u8),
+    /// A button was released.
+    ButtonUp(KeyIndex),
+
+    /// Non-button input (encoders, touch strip) from models like the Stream Deck Plus.
+    RawInput(RawInput),
 
     /// Stream Deck device connected.
     DeviceConnected,
@@ -20,7 +28,16 @@ pub enum DeckEvent {
     ConfigReloaded(Arc<AppConfig>),
 
     /// Navigate to a named page.
-    NavigateTo(String),
+    NavigateTo(PageId),
+
+    /// Open the color picker page of a light, creating it on first use.
+    OpenPicker(String),
+
+    /// A `scene_save` action captured these entity states.
+    SceneSaved(String, Scene),
+
+    /// Restore the saved scene of this name.
+    RecallScene(String),
 
     /// Go back one page in the stack.
     NavigateBack,
@@ -28,12 +45,109 @@ pub enum DeckEvent {
     /// Go to the home page.
     NavigateHome,
 
+    /// Turn the display off until woken.
+    DisplaySleep,
+
+    /// Turn the display back on.
+    DisplayWake,
+
+    /// The active theme changed (`None` = plain `deckd.defaults`).
+    ThemeChanged(Option<String>),
+
+    /// A problem to summarize on the status tile (failed action, HA or device trouble).
+    Issue(Issue),
+
+    /// Show temporary content on a key from a source (`None` clears that
+    /// source's content).
+    SetKey(KeyIndex, OverrideSource, Option<KeyContent>),
+
+    /// Queue a notification banner.
+    Toast(String),
+
+    /// Take down the banner with this id, if it is still showing.
+    DismissToast(u64),
+
+    /// Blink a key (or the whole deck, for `None`) in the named pattern.
+    Flash(String, Option<KeyIndex>),
+
+    /// The alert now taking over the deck changed (`None` = all cleared).
+    Alert(Option<String>),
+
+    /// Quiet hours started (`true`) or ended.
+    Quiet(bool),
+
+    /// Turn guest mode on or off (`None` toggles it).
+    SetGuest(Option<bool>),
+
+    /// Turn the diagnostic key overlay on or off.
+    Diagnostics(bool),
+
+    /// Give up the PIN prompt with this id, if it is still open.
+    PinTimeout(u64),
+
+    /// Buttons generated for a page (e.g. a `media_browser`), replacing the
+    /// ones generated before.
+    PageGenerated(PageId, Vec<ButtonConfig>),
+
+    /// The code entered on a keypad page was accepted (`true`) or rejected
+    /// by its `on_submit` action.
+    KeypadResult(PageId, bool),
+
+    /// Pin the brightness to a level, over page and config settings
+    /// (`None` goes back to them).
+    SetBrightness(Option<u8>),
+
+    /// Pin the brightness this many points above (or below) the current one.
+    StepBrightness(i32),
+
+    /// The level `[deckd.brightness_schedule]` sets for now (`None` = no
+    /// schedule).
+    ScheduledBrightness(Option<u8>),
+
+    /// A dial was turned by this many detents (negative = counter-clockwise).
+    DialRotate(u8, i8),
+
+    /// A dial was pressed.
+    DialPress(u8),
+
+    /// An action finished, successfully or not. Sent for every execution
+    /// (a retry is another one), whatever started it.
+    ActionCompleted {
+        /// The key whose press ran it, if a key did.
+        key: Option<KeyIndex>,
+        /// The action type, e.g. `http`.
+        action_kind: &'static str,
+        /// The error message if it failed.
+        result: Result<(), String>,
+        duration: Duration,
+    },
+
     /// Re-render all buttons on the current page.
     RenderAll,
 
     /// Re-render a single button by key index.
-    RenderButton(u8),
+    RenderButton(KeyIndex),
 
     /// Shutdown the daemon.
     Shutdown,
 }
+
+/// Device input that has no first-class handling yet, forwarded as-is so
+/// integrations subscribed to the event bus can consume it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawInput {
+    /// Encoder (dial) press state, one entry per dial.
+    EncoderStateChange(Vec<bool>),
+
+    /// Encoder rotation deltas, one entry per dial (negative = counter-clockwise).
+    EncoderTwist(Vec<i8>),
+
+    /// Short tap on the touch strip at (x, y).
+    TouchPress { x: u16, y: u16 },
+
+    /// Long press on the touch strip at (x, y).
+    TouchLongPress { x: u16, y: u16 },
+
+    /// Swipe across the touch strip.
+    TouchSwipe { from: (u16, u16), to: (u16, u16) },
+}
@@ -0,0 +1,134 @@
+//! Audible confirmation of key presses (`[deckd.feedback]`), played
+//! through the host's audio by an external player such as `aplay`. The
+//! player is part of the daemon's own settings, not an action, so
+//! `[deckd.policy]` and guest mode do not apply to it.
+
+use crate::config::schema::{FeedbackConfig, PressSound};
+use std::f32::consts::TAU;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Sample rate of the built-in sounds.
+const SAMPLE_RATE: u32 = 22_050;
+
+/// Milliseconds of the built-in click.
+const CLICK_MS: u64 = 8;
+
+/// Play the press sound of `feedback`, if any, without waiting for it.
+pub fn press(feedback: &FeedbackConfig, config_dir: &std::path::Path) {
+    if feedback.sound.is_none() && feedback.file.is_none() {
+        return;
+    }
+    let mut words = feedback.player.split_whitespace();
+    let Some(program) = words.next() else {
+        return;
+    };
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(words)
+        .envs(crate::privileges::env())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let wav = match (&feedback.file, feedback.sound) {
+        (Some(file), _) => {
+            command.arg(config_dir.join(file));
+            None
+        }
+        (None, Some(PressSound::Beep)) => Some(wav(&tone(
+            feedback.frequency,
+            feedback.duration_ms,
+            feedback.volume,
+        ))),
+        (None, _) => Some(wav(&click(feedback.volume))),
+    };
+    if wav.is_some() {
+        command.arg("-").stdin(Stdio::piped());
+    }
+    let program = program.to_string();
+    tokio::spawn(async move {
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("press sound: cannot run {program}: {e}");
+                return;
+            }
+        };
+        if let (Some(wav), Some(mut stdin)) = (wav, child.stdin.take()) {
+            let _ = stdin.write_all(&wav).await;
+        }
+        let _ = child.wait().await;
+    });
+}
+
+/// A sine tone of `frequency` Hz, faded in and out to avoid pops.
+fn tone(frequency: u32, duration_ms: u64, volume: f32) -> Vec<i16> {
+    let count = (u64::from(SAMPLE_RATE) * duration_ms / 1000) as usize;
+    let fade = (count / 10).max(1);
+    (0..count)
+        .map(|i| {
+            let envelope = (i.min(count - 1 - i) as f32 / fade as f32).min(1.0);
+            let t = i as f32 / SAMPLE_RATE as f32;
+            sample((TAU * frequency as f32 * t).sin() * envelope * volume)
+        })
+        .collect()
+}
+
+/// A short, quickly decaying tick.
+fn click(volume: f32) -> Vec<i16> {
+    let count = (u64::from(SAMPLE_RATE) * CLICK_MS / 1000) as usize;
+    (0..count)
+        .map(|i| {
+            let decay = (-(i as f32) / (count as f32 / 5.0)).exp();
+            let square = if (i / 6) % 2 == 0 { 1.0 } else { -1.0 };
+            sample(square * decay * volume)
+        })
+        .collect()
+}
+
+fn sample(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16
+}
+
+/// `samples` as a 16-bit mono WAV file.
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + samples.len() * 2);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        out.extend_from_slice(&s.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_wav_of_the_tone() {
+        let samples = tone(880, 100, 0.5);
+        assert_eq!(samples.len(), 2205);
+        // Faded in and out, and never above the volume.
+        assert_eq!(samples[0], 0);
+        assert!(samples.iter().all(|s| s.unsigned_abs() <= 16_384));
+        assert!(samples.iter().any(|s| s.unsigned_abs() > 16_000));
+
+        let file = wav(&samples);
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[8..12], b"WAVE");
+        assert_eq!(file.len(), 44 + 2 * 2205);
+        assert_eq!(u32::from_le_bytes(file[40..44].try_into().unwrap()), 4410);
+    }
+}
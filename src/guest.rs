@@ -0,0 +1,200 @@
+//! Guest mode (`[deckd.guest]`): visitors can use the allowed pages, but
+//! keys with risky actions are greyed out and do nothing, and every other key
+//! is marked with a stripe in the guest color.
+
+use crate::config::schema::{ActionConfig, AppConfig, ButtonConfig, GuestConfig};
+use crate::event::DeckEvent;
+use crate::ids::PageId;
+use crate::render::canvas::{grey_out, parse_hex_color, scale};
+use crate::state::fetch_ha_states;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// How often the guest entity is checked.
+const GUEST_POLL: Duration = Duration::from_secs(15);
+
+/// Height of the stripe along the top of usable 72 px keys.
+const STRIPE: usize = 4;
+
+/// Guest mode while it is on.
+#[derive(Debug, Clone)]
+pub struct Guests {
+    settings: GuestConfig,
+    /// Always open to guests, to have somewhere to go back to.
+    home: PageId,
+}
+
+impl Guests {
+    /// Guest mode with the settings of `config`, if it has any.
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Option<Self> {
+        Some(Self {
+            settings: config.deckd.guest.clone()?,
+            home: config.deckd.home_page.clone(),
+        })
+    }
+
+    /// The same guest mode for a deck with another home page.
+    #[must_use]
+    pub fn with_home(self, home: PageId) -> Self {
+        Self { home, ..self }
+    }
+
+    /// Whether guests may open `page`: the home page, a listed page, or a
+    /// light picker.
+    #[must_use]
+    pub fn allows_page(&self, page: &str) -> bool {
+        page == self.home.as_str()
+            || page.starts_with("picker:")
+            || self.settings.pages.iter().any(|p| p.as_str() == page)
+    }
+
+    /// Whether guests may run `action`. Blocked kinds never run, navigation
+    /// only to pages guests may open, and `guest` (leaving guest mode) only
+    /// from a key with a `pin`.
+    #[must_use]
+    pub fn allows(&self, action: &ActionConfig, pin: bool) -> bool {
+        match action {
+            _ if self.settings.block.iter().any(|kind| kind == action.kind()) => false,
+            ActionConfig::Guest { .. } => pin,
+            ActionConfig::Navigate { page, picker: None } => self.allows_page(page.as_str()),
+            _ => true,
+        }
+    }
+
+    /// Whether guests may press `button`: its `on_press` and, since a failed
+    /// press runs it, its `on_error` must both be allowed.
+    #[must_use]
+    pub fn allows_button(&self, button: &ButtonConfig) -> bool {
+        button
+            .on_press
+            .as_ref()
+            .is_none_or(|action| self.allows(action, button.pin.is_some()))
+            && button
+                .on_error
+                .as_ref()
+                .is_none_or(|action| self.allows(action, false))
+    }
+
+    /// Mark a rendered `size`x`size` key: greyed out if guests cannot use
+    /// it, otherwise with a stripe in the guest color along its top.
+    pub fn mark(&self, button: &ButtonConfig, rgba: &mut [u8], size: u32) {
+        if !self.allows_button(button) {
+            grey_out(rgba);
+            return;
+        }
+        let Ok(color) = parse_hex_color(&self.settings.color) else {
+            return;
+        };
+        let color = color.to_color_u8();
+        let rows = (STRIPE as f32 * scale(size)).round() as usize;
+        let stripe = (rows * size as usize * 4).min(rgba.len());
+        for pixel in rgba[..stripe].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.red(), color.green(), color.blue(), 255]);
+        }
+    }
+}
+
+/// Follow the guest entity, if any: emit `SetGuest` whenever its state
+/// starts or stops matching, so a toggle from the deck or the API holds
+/// until the entity changes.
+pub async fn watch(
+    config: Arc<ArcSwap<AppConfig>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(GUEST_POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut last = None;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let cfg = config.load_full();
+        let Some((entity, equals)) = cfg
+            .deckd
+            .guest
+            .as_ref()
+            .and_then(|g| Some((g.entity.as_ref()?, &g.equals)))
+        else {
+            last = None;
+            continue;
+        };
+        let Some(state) = fetch_ha_states(std::slice::from_ref(entity))
+            .await
+            .remove(entity)
+        else {
+            continue;
+        };
+        let guest = state == *equals;
+        if last != Some(guest) {
+            if last.is_some() || guest {
+                info!(
+                    "{entity} is {state}, guest mode {}",
+                    if guest { "on" } else { "off" }
+                );
+                let _ = tx.send(DeckEvent::SetGuest(Some(guest)));
+            }
+            last = Some(guest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guests_are_kept_to_allowed_pages_and_actions() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd.guest]
+pages = ["lights"]
+
+[pages.home]
+name = "Home"
+
+[pages.lights]
+name = "Lights"
+
+[pages.servers]
+name = "Servers"
+"#,
+        )
+        .unwrap();
+        let guests = Guests::new(&config).unwrap();
+        let action = |toml: &str| -> ActionConfig { toml::from_str(toml).unwrap() };
+
+        assert!(guests.allows_page("home"));
+        assert!(guests.allows_page("picker:light.desk"));
+        assert!(!guests.allows_page("servers"));
+        let lights = action("action = \"navigate\"\npage = \"lights\"");
+        let servers = action("action = \"navigate\"\npage = \"servers\"");
+        let reboot = action("action = \"shell\"\ncommand = \"sudo reboot\"");
+        let leave = action("action = \"guest\"\non = false");
+        assert!(guests.allows(&lights, false));
+        assert!(!guests.allows(&servers, false));
+        assert!(!guests.allows(&reboot, true));
+        assert!(!guests.allows(&leave, false));
+        assert!(guests.allows(&leave, true));
+        let assist = action("action = \"ha_assist\"\ntext = \"unlock the front door\"");
+        assert!(!guests.allows(&assist, false));
+
+        let button = |toml: &str| -> ButtonConfig { toml::from_str(toml).unwrap() };
+        let fallback = button(
+            r#"
+key = 0
+on_press = { action = "navigate", page = "lights" }
+on_error = { action = "shell", command = "sudo reboot" }
+"#,
+        );
+        assert!(!guests.allows_button(&fallback));
+    }
+}
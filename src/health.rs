@@ -0,0 +1,122 @@
+//! Liveness of the daemon's parts, reported by `GET /healthz`.
+
+use serde::Serialize;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The event loop counts as stalled when it has not turned for this long;
+/// it turns at least every 5 s on its state poll.
+const STALLED: Duration = Duration::from_secs(30);
+
+/// What the parts last reported.
+#[derive(Debug, Default)]
+struct Health {
+    ha_success: Option<SystemTime>,
+    ha_error: Option<String>,
+    ha_url: Option<String>,
+    watcher_alive: bool,
+    event_loop_beat: Option<Instant>,
+}
+
+static HEALTH: LazyLock<Mutex<Health>> = LazyLock::new(Mutex::default);
+
+/// Record the outcome of a fetch of HA states: `None` if it succeeded, else
+/// the problem.
+pub fn ha_fetched(problem: Option<&str>) {
+    let mut health = HEALTH.lock().unwrap();
+    match problem {
+        None => {
+            health.ha_success = Some(SystemTime::now());
+            health.ha_error = None;
+        }
+        Some(problem) => health.ha_error = Some(problem.to_string()),
+    }
+}
+
+/// Record which of the Home Assistant URLs last answered.
+pub fn ha_answered(url: &str) {
+    HEALTH.lock().unwrap().ha_url = Some(url.to_string());
+}
+
+/// Record whether the config watcher runs.
+pub fn watcher_alive(alive: bool) {
+    HEALTH.lock().unwrap().watcher_alive = alive;
+}
+
+/// Record that the event loop turned.
+pub fn event_loop_beat() {
+    HEALTH.lock().unwrap().event_loop_beat = Some(Instant::now());
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Report {
+    /// Whether deckd works: the deck is connected, the config watcher runs
+    /// and the event loop turns. Home Assistant being down does not count.
+    pub healthy: bool,
+    pub device_connected: bool,
+    pub home_assistant: HomeAssistant,
+    pub config_watcher_alive: bool,
+    pub event_loop_alive: bool,
+    /// Seconds since the event loop last turned.
+    pub event_loop_idle_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct HomeAssistant {
+    /// Unix time of the last fetch of states that succeeded.
+    pub last_success: Option<u64>,
+    /// Problem of the last fetch, if it failed.
+    pub error: Option<String>,
+    /// Base URL that last answered, of those in `HA_URL`.
+    pub url: Option<String>,
+}
+
+/// The health of the daemon now, given whether a deck is connected.
+#[must_use]
+pub fn report(device_connected: bool) -> Report {
+    build_report(&HEALTH.lock().unwrap(), device_connected, Instant::now())
+}
+
+fn build_report(health: &Health, device_connected: bool, now: Instant) -> Report {
+    let idle = health.event_loop_beat.map(|beat| now.duration_since(beat));
+    let event_loop_alive = idle.is_some_and(|idle| idle < STALLED);
+    Report {
+        healthy: device_connected && health.watcher_alive && event_loop_alive,
+        device_connected,
+        home_assistant: HomeAssistant {
+            last_success: health
+                .ha_success
+                .map(|at| at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())),
+            error: health.ha_error.clone(),
+            url: health.ha_url.clone(),
+        },
+        config_watcher_alive: health.watcher_alive,
+        event_loop_alive,
+        event_loop_idle_secs: idle.map(|idle| idle.as_secs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unhealthy_when_a_part_is_down() {
+        let now = Instant::now();
+        let mut health = Health {
+            ha_error: Some("unreachable".into()),
+            watcher_alive: true,
+            event_loop_beat: Some(now),
+            ..Health::default()
+        };
+        let report = build_report(&health, true, now + Duration::from_secs(3));
+        assert!(report.healthy);
+        assert_eq!(report.event_loop_idle_secs, Some(3));
+        assert_eq!(report.home_assistant.error.as_deref(), Some("unreachable"));
+
+        assert!(!build_report(&health, false, now).healthy);
+        assert!(!build_report(&health, true, now + STALLED).healthy);
+        health.watcher_alive = false;
+        assert!(!build_report(&health, true, now).healthy);
+    }
+}
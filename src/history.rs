@@ -0,0 +1,69 @@
+//! Recent numeric values of entity states, drawn as sparklines.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Values kept per entity, one per column pair of a key.
+pub const POINTS: usize = 36;
+
+/// Renders closer together than this add no new value, so a burst of page
+/// switches does not squeeze the time axis.
+const MIN_GAP: Duration = Duration::from_secs(4);
+
+/// The last [`POINTS`] numeric values of entities, oldest first.
+#[derive(Debug, Default)]
+pub struct StateHistory {
+    series: HashMap<String, (Instant, VecDeque<f32>)>,
+}
+
+impl StateHistory {
+    /// Add `state` of `entity` if it is numeric and the last value is at
+    /// least [`MIN_GAP`] old.
+    pub fn record(&mut self, entity: &str, state: &str, now: Instant) {
+        let Ok(value) = state.parse::<f32>() else {
+            return;
+        };
+        if let Some((at, values)) = self.series.get_mut(entity) {
+            if now.duration_since(*at) < MIN_GAP {
+                return;
+            }
+            if values.len() == POINTS {
+                values.pop_front();
+            }
+            values.push_back(value);
+            *at = now;
+        } else {
+            self.series
+                .insert(entity.to_string(), (now, VecDeque::from([value])));
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, entity: &str) -> Vec<f32> {
+        self.series
+            .get(entity)
+            .map(|(_, values)| values.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_spaced_numeric_values() {
+        let mut history = StateHistory::default();
+        let start = Instant::now();
+        for i in 0..40u16 {
+            let at = start + MIN_GAP * u32::from(i);
+            history.record("net:eth0/down", &i.to_string(), at);
+            history.record("net:eth0/down", "999", at + Duration::from_secs(1));
+        }
+        history.record("light.desk", "on", start);
+        let values = history.get("net:eth0/down");
+        assert_eq!(values.len(), POINTS);
+        assert_eq!((values[0], values[POINTS - 1]), (4.0, 39.0));
+        assert!(history.get("light.desk").is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+
+/// A page id: the `<id>` of a `[pages.<id>]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PageId(String);
+
+impl PageId {
+    #[must_use]
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for PageId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for PageId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Lets maps keyed by `PageId` be looked up with a `&str`.
+impl Borrow<str> for PageId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for PageId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<String> for PageId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl PartialEq<str> for PageId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for PageId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+/// A key on the deck, numbered from 0 left to right, top to bottom.
+///
+/// Keys from the device are checked against its key count with `new`;
+/// keys from the config are checked by config validation.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct KeyIndex(u8);
+
+impl KeyIndex {
+    /// Placeholder for a button placed by `pos`, until the position is
+    /// resolved. It is off every deck.
+    pub const UNSET: Self = Self(u8::MAX);
+
+    /// The key at `index` on a deck with `key_count` keys, if it has one.
+    #[must_use]
+    pub const fn new(index: u8, key_count: u8) -> Option<Self> {
+        if index < key_count {
+            Some(Self(index))
+        } else {
+            None
+        }
+    }
+
+    /// Every key of a deck with `key_count` keys.
+    pub fn all(key_count: u8) -> impl Iterator<Item = Self> {
+        (0..key_count).map(Self)
+    }
+
+    #[must_use]
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<KeyIndex> for u8 {
+    fn from(key: KeyIndex) -> Self {
+        key.0
+    }
+}
+
+impl From<KeyIndex> for u32 {
+    fn from(key: KeyIndex) -> Self {
+        u32::from(key.0)
+    }
+}
+
+impl PartialEq<u8> for KeyIndex {
+    fn eq(&self, other: &u8) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for KeyIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A key for tests, on a deck big enough for any index used.
+#[cfg(test)]
+pub(crate) fn key(index: u8) -> KeyIndex {
+    KeyIndex(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn key_index_checks_geometry() {
+        assert_eq!(KeyIndex::new(14, 15).map(KeyIndex::get), Some(14));
+        assert_eq!(KeyIndex::new(15, 15), None);
+        assert_eq!(KeyIndex::new(20, 32).map(u8::from), Some(20));
+        assert_eq!(KeyIndex::all(6).count(), 6);
+    }
+
+    #[test]
+    fn page_id_looks_up_by_str() {
+        let pages = HashMap::from([(PageId::from("home"), 1)]);
+        assert_eq!(pages.get("home"), Some(&1));
+        assert_eq!(PageId::from("home"), "home");
+        assert_eq!(PageId::new("lights").to_string(), "lights");
+    }
+}
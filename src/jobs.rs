@@ -0,0 +1,172 @@
+use crate::ids::KeyIndex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Time each spinner frame of a running job is shown.
+pub const SPINNER_FRAME: Duration = Duration::from_millis(125);
+
+/// Spinner frames per revolution.
+pub const SPINNER_FRAMES: u8 = 8;
+
+/// What a tracked button shows on top of its normal render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running { frame: u8 },
+    Cancelling { frame: u8 },
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+enum Phase {
+    Running {
+        since: Instant,
+        cancel: CancellationToken,
+    },
+    Cancelling {
+        since: Instant,
+    },
+    Done {
+        ok: bool,
+        until: Instant,
+    },
+}
+
+/// What a press of a tracked button should do.
+#[derive(Debug)]
+pub enum Press {
+    /// Run the action; it stops early once the token is cancelled.
+    Start(CancellationToken),
+    /// The running action was asked to stop.
+    Cancel,
+    /// Already cancelling; nothing to do.
+    Ignore,
+}
+
+/// Lifecycle of actions on buttons with `track = true`: running (and maybe
+/// cancelling), then the result held for a while. Keyed by page and key, so
+/// a job keeps running (and is shown again) when the user navigates away and
+/// back.
+#[derive(Debug, Default)]
+pub struct JobTracker {
+    jobs: HashMap<(String, KeyIndex), Phase>,
+}
+
+impl JobTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a press of `key` on `page`: start its action, or cancel it if
+    /// it is still running from an earlier press.
+    pub fn press(&mut self, page: &str, key: KeyIndex, now: Instant) -> Press {
+        let id = (page.to_string(), key);
+        match self.jobs.get(&id) {
+            Some(Phase::Running { cancel, .. }) => {
+                cancel.cancel();
+                self.jobs.insert(id, Phase::Cancelling { since: now });
+                Press::Cancel
+            }
+            Some(Phase::Cancelling { .. }) => Press::Ignore,
+            _ => {
+                let cancel = CancellationToken::new();
+                self.jobs.insert(
+                    id,
+                    Phase::Running {
+                        since: now,
+                        cancel: cancel.clone(),
+                    },
+                );
+                Press::Start(cancel)
+            }
+        }
+    }
+
+    /// Record the outcome, shown for `hold`.
+    pub fn finish(&mut self, page: &str, key: KeyIndex, ok: bool, now: Instant, hold: Duration) {
+        self.jobs.insert(
+            (page.to_string(), key),
+            Phase::Done {
+                ok,
+                until: now + hold,
+            },
+        );
+    }
+
+    /// What to show at `key` of `page`; results past their hold are dropped.
+    pub fn state(&mut self, page: &str, key: KeyIndex, now: Instant) -> Option<JobState> {
+        let id = (page.to_string(), key);
+        match *self.jobs.get(&id)? {
+            Phase::Running { since, .. } => Some(JobState::Running {
+                frame: frame(since, now),
+            }),
+            Phase::Cancelling { since } => Some(JobState::Cancelling {
+                frame: frame(since, now),
+            }),
+            Phase::Done { until, .. } if now >= until => {
+                self.jobs.remove(&id);
+                None
+            }
+            Phase::Done { ok: true, .. } => Some(JobState::Succeeded),
+            Phase::Done { ok: false, .. } => Some(JobState::Failed),
+        }
+    }
+}
+
+fn frame(since: Instant, now: Instant) -> u8 {
+    let elapsed = now.saturating_duration_since(since);
+    let frame = elapsed.as_millis() / SPINNER_FRAME.as_millis();
+    (frame % u128::from(SPINNER_FRAMES)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn runs_then_holds_result() {
+        let now = Instant::now();
+        let hold = Duration::from_secs(3);
+        let mut jobs = JobTracker::new();
+
+        assert!(matches!(jobs.press("home", key(4), now), Press::Start(_)));
+        assert!(matches!(jobs.press("media", key(4), now), Press::Start(_)));
+        assert_eq!(
+            jobs.state("home", key(4), now + SPINNER_FRAME * 3),
+            Some(JobState::Running { frame: 3 })
+        );
+        assert_eq!(
+            jobs.state("home", key(4), now + SPINNER_FRAME * 9),
+            Some(JobState::Running { frame: 1 })
+        );
+
+        let done = now + Duration::from_secs(5);
+        jobs.finish("home", key(4), false, done, hold);
+        assert_eq!(jobs.state("home", key(4), done), Some(JobState::Failed));
+        assert_eq!(jobs.state("home", key(4), done + hold), None);
+        assert!(matches!(
+            jobs.press("home", key(4), done + hold),
+            Press::Start(_)
+        ));
+    }
+
+    #[test]
+    fn second_press_cancels() {
+        let now = Instant::now();
+        let mut jobs = JobTracker::new();
+        let Press::Start(token) = jobs.press("home", key(2), now) else {
+            panic!("expected the job to start");
+        };
+
+        assert!(matches!(jobs.press("home", key(2), now), Press::Cancel));
+        assert!(token.is_cancelled());
+        assert_eq!(
+            jobs.state("home", key(2), now),
+            Some(JobState::Cancelling { frame: 0 })
+        );
+        assert!(matches!(jobs.press("home", key(2), now), Press::Ignore));
+    }
+}
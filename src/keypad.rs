@@ -0,0 +1,131 @@
+use crate::ids::{KeyIndex, PageId};
+
+/// What a key does on a keypad page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeypadKey {
+    Digit(char),
+    /// Shows the masked code entered so far.
+    Display,
+    Clear,
+    Back,
+    Submit,
+}
+
+/// Keypad layout on the 5x3 grid:
+///
+/// ```text
+///  1  2  3  ••  Clear
+///  4  5  6  0   Back
+///  7  8  9  -   OK
+/// ```
+const LAYOUT: [Option<KeypadKey>; 15] = {
+    use KeypadKey::{Back, Clear, Digit, Display, Submit};
+    [
+        Some(Digit('1')),
+        Some(Digit('2')),
+        Some(Digit('3')),
+        Some(Display),
+        Some(Clear),
+        Some(Digit('4')),
+        Some(Digit('5')),
+        Some(Digit('6')),
+        Some(Digit('0')),
+        Some(Back),
+        Some(Digit('7')),
+        Some(Digit('8')),
+        Some(Digit('9')),
+        None,
+        Some(Submit),
+    ]
+};
+
+/// The keypad role of `key`, if it has one.
+#[must_use]
+pub fn role(key: KeyIndex) -> Option<KeypadKey> {
+    LAYOUT.get(usize::from(key.get())).copied().flatten()
+}
+
+/// Result of one key press on a keypad page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeypadStep {
+    /// The code changed and the keypad needs redrawing.
+    Changed,
+    Submit(String),
+    Back,
+    Ignored,
+}
+
+/// A code being entered on a keypad page.
+#[derive(Debug)]
+pub struct KeypadEntry {
+    pub page: PageId,
+    code: String,
+    max_len: usize,
+}
+
+impl KeypadEntry {
+    #[must_use]
+    pub const fn new(page: PageId, max_len: usize) -> Self {
+        Self {
+            page,
+            code: String::new(),
+            max_len,
+        }
+    }
+
+    pub fn press(&mut self, key: KeyIndex) -> KeypadStep {
+        match role(key) {
+            Some(KeypadKey::Digit(digit)) if self.code.len() < self.max_len => {
+                self.code.push(digit);
+                KeypadStep::Changed
+            }
+            Some(KeypadKey::Clear) if !self.code.is_empty() => {
+                self.code.clear();
+                KeypadStep::Changed
+            }
+            Some(KeypadKey::Submit) if !self.code.is_empty() => {
+                KeypadStep::Submit(std::mem::take(&mut self.code))
+            }
+            Some(KeypadKey::Back) => KeypadStep::Back,
+            _ => KeypadStep::Ignored,
+        }
+    }
+
+    /// The label of `key`: its digit or command, or the masked code.
+    #[must_use]
+    pub fn label(&self, key: KeyIndex) -> Option<String> {
+        Some(match role(key)? {
+            KeypadKey::Digit(digit) => digit.to_string(),
+            KeypadKey::Display if self.code.is_empty() => "Code".into(),
+            KeypadKey::Display => "•".repeat(self.code.len()),
+            KeypadKey::Clear => "Clear".into(),
+            KeypadKey::Back => "Back".into(),
+            KeypadKey::Submit => "OK".into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn collects_and_submits_the_code() {
+        let mut entry = KeypadEntry::new("alarm".into(), 4);
+        assert_eq!(entry.press(key(14)), KeypadStep::Ignored);
+        for k in [0, 8, 11, 6, 2] {
+            entry.press(key(k));
+        }
+        // The fifth digit is over the limit.
+        assert_eq!(entry.label(key(3)).as_deref(), Some("••••"));
+        assert_eq!(entry.press(key(14)), KeypadStep::Submit("1085".into()));
+        assert_eq!(entry.label(key(3)).as_deref(), Some("Code"));
+
+        entry.press(key(12));
+        assert_eq!(entry.press(key(4)), KeypadStep::Changed);
+        assert_eq!(entry.press(key(14)), KeypadStep::Ignored);
+        assert_eq!(entry.press(key(9)), KeypadStep::Back);
+        assert_eq!(entry.label(key(13)), None);
+    }
+}
@@ -8,11 +8,43 @@
 )]
 
 pub mod action;
+pub mod addon;
+pub mod alert;
+pub mod api;
+pub mod bundle;
+pub mod chord;
 pub mod config;
 pub mod daemon;
 pub mod device;
+pub mod doctor;
 pub mod error;
 pub mod event;
+pub mod feedback;
+pub mod guest;
+pub mod health;
+pub mod history;
+pub mod ids;
+pub mod jobs;
+pub mod keypad;
+pub mod lock;
+pub mod logging;
+pub mod mdns;
+pub mod overrides;
+pub mod pack;
 pub mod page;
+pub mod pin;
+pub mod privileges;
+pub mod quiet;
 pub mod render;
+pub mod scene;
+pub mod schedule;
+pub mod secret;
+pub mod source;
 pub mod state;
+pub mod status;
+pub mod template;
+pub mod theme;
+pub mod toast;
+pub mod usage;
+
+pub use daemon::Deckd;
@@ -0,0 +1,82 @@
+use crate::config::schema::{ButtonConfig, PageConfig};
+use crate::schedule::{in_range, TimeOfDay};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// The button with the page's `locked`/`locked_if` filled in where it has
+/// none of its own.
+#[must_use]
+pub fn effective<'a>(button: &'a ButtonConfig, page: &PageConfig) -> Cow<'a, ButtonConfig> {
+    let inherit_schedule = button.locked.is_none() && page.locked.is_some();
+    let inherit_condition = button.locked_if.is_none() && page.locked_if.is_some();
+    if !inherit_schedule && !inherit_condition {
+        return Cow::Borrowed(button);
+    }
+    let mut button = button.clone();
+    if inherit_schedule {
+        button.locked.clone_from(&page.locked);
+    }
+    if inherit_condition {
+        button.locked_if.clone_from(&page.locked_if);
+    }
+    Cow::Owned(button)
+}
+
+/// Whether `button` is locked at `now`, given the known entity states.
+#[must_use]
+pub fn is_locked(button: &ButtonConfig, states: &HashMap<String, String>, now: TimeOfDay) -> bool {
+    let by_schedule = button
+        .locked
+        .as_ref()
+        .is_some_and(|lock| in_range(lock.between[0], lock.between[1], now));
+    let by_state = button
+        .locked_if
+        .as_ref()
+        .is_some_and(|cond| states.get(&cond.entity) == Some(&cond.equals));
+    by_schedule || by_state
+}
+
+/// Whether `button`'s lock can change on its own and needs periodic checks.
+#[must_use]
+pub fn is_conditional(button: &ButtonConfig) -> bool {
+    button.locked.is_some() || button.locked_if.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AppConfig;
+
+    #[test]
+    fn button_lock_replaces_page_lock() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [deckd]
+            [pages.kids]
+            locked = { between = ["20:00", "07:00"] }
+            buttons = [
+                { key = 0, label = "TV" },
+                { key = 1, label = "Lights", locked = { between = ["22:00", "06:00"] } },
+                { key = 2, label = "Alarm", locked_if = { entity = "alarm_control_panel.home", equals = "armed_away" } },
+            ]
+            "#,
+        )
+        .unwrap();
+        let page = &config.pages["kids"];
+        let at = |s: &str| s.parse::<TimeOfDay>().unwrap();
+        let states = HashMap::from([(
+            "alarm_control_panel.home".to_string(),
+            "disarmed".to_string(),
+        )]);
+        let locked = |key: usize, now: &str| {
+            is_locked(&effective(&page.buttons[key], page), &states, at(now))
+        };
+
+        assert!(locked(0, "21:00"));
+        assert!(!locked(1, "21:00"));
+        assert!(locked(1, "23:00"));
+        // Locked by the page's hours, not by the alarm state.
+        assert!(locked(2, "21:00"));
+        assert!(!locked(2, "12:00"));
+    }
+}
@@ -0,0 +1,203 @@
+//! Log setup from `[deckd.logging]`: levels per target, and an optional log
+//! file rotated by size and time for installs outside systemd.
+
+use crate::config::schema::{LogRotation, LoggingConfig};
+use chrono::{DateTime, Local};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
+
+/// The `[deckd.logging]` table of the config at `config_path`, read before
+/// the config is loaded so loading is logged too. A config that does not
+/// parse gives the defaults; loading it reports why.
+#[must_use]
+pub fn settings(config_path: &Path) -> LoggingConfig {
+    std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|text| toml::from_str::<toml::Table>(&text).ok())
+        .and_then(|mut table| table.get_mut("deckd")?.as_table_mut()?.remove("logging"))
+        .and_then(|logging| logging.try_into().ok())
+        .unwrap_or_default()
+}
+
+/// The filter of `settings`: deckd's level and those of other targets.
+/// `RUST_LOG` replaces it when set.
+///
+/// # Errors
+/// Returns a description of a level that does not parse.
+pub fn filter(settings: &LoggingConfig) -> Result<EnvFilter, String> {
+    if let Ok(filter) = EnvFilter::try_from_default_env() {
+        return Ok(filter);
+    }
+    let directives: Vec<String> = std::iter::once(format!("deckd={}", settings.level))
+        .chain(
+            settings
+                .targets
+                .iter()
+                .map(|(target, level)| format!("{target}={level}")),
+        )
+        .collect();
+    EnvFilter::try_new(directives.join(",")).map_err(|e| format!("deckd.logging: {e}"))
+}
+
+/// Install the global subscriber: to stdout (as JSON with `json`) and, if
+/// configured, to the log file relative to `config_dir`.
+///
+/// # Errors
+/// Returns `DeckError::Io` if the log file cannot be opened, or
+/// `DeckError::Config` if a level does not parse.
+pub fn init(settings: &LoggingConfig, config_dir: &Path, json: bool) -> crate::error::Result<()> {
+    let filter = filter(settings).map_err(crate::error::DeckError::Config)?;
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = Vec::new();
+    if settings.stdout || settings.file.is_none() {
+        layers.push(if json {
+            fmt::layer().json().boxed()
+        } else {
+            fmt::layer().boxed()
+        });
+    }
+    if let Some(ref file) = settings.file {
+        let writer = Mutex::new(RotatingFile::open(
+            config_dir.join(file),
+            settings.max_size_mb * 1024 * 1024,
+            settings.rotate,
+            settings.keep,
+        )?);
+        layers.push(if json {
+            fmt::layer().json().with_writer(writer).boxed()
+        } else {
+            fmt::layer().with_ansi(false).with_writer(writer).boxed()
+        });
+    }
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .init();
+    Ok(())
+}
+
+/// A log file that moves itself to `<path>.1` (shifting older ones up) when
+/// it reaches its size limit or a new day or hour begins.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    /// Bytes after which a new file starts (0 = no limit).
+    max_size: u64,
+    rotation: LogRotation,
+    /// The day or hour the file is for.
+    period: String,
+    keep: usize,
+}
+
+impl RotatingFile {
+    /// Append to the file at `path`, creating it and its directory.
+    ///
+    /// # Errors
+    /// Returns the error of creating the directory or opening the file.
+    pub fn open(
+        path: PathBuf,
+        max_size: u64,
+        rotation: LogRotation,
+        keep: usize,
+    ) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        let written = metadata
+            .modified()
+            .map_or_else(|_| Local::now(), DateTime::from);
+        Ok(Self {
+            period: period(rotation, written),
+            size: metadata.len(),
+            path,
+            file,
+            max_size,
+            rotation,
+            keep,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for n in (1..self.keep).rev() {
+            let _ = std::fs::rename(self.rotated(n), self.rotated(n + 1));
+        }
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            std::fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = period(self.rotation, Local::now());
+        let full =
+            self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size;
+        if full || now != self.period {
+            self.period = now;
+            if let Err(e) = self.rotate() {
+                eprintln!("deckd: cannot rotate {}: {e}", self.path.display());
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// The day or hour of `at`, or nothing for rotation by size only.
+fn period(rotation: LogRotation, at: DateTime<Local>) -> String {
+    match rotation {
+        LogRotation::Never => String::new(),
+        LogRotation::Daily => at.format("%Y-%m-%d").to_string(),
+        LogRotation::Hourly => at.format("%Y-%m-%d %H").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_by_size_keeping_the_newest() {
+        let dir = std::env::temp_dir().join(format!("deckd-log-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("logs/deckd.log");
+        let mut file = RotatingFile::open(path.clone(), 10, LogRotation::Never, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        let read = |p: &Path| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(&path), "fourth\n");
+        assert_eq!(read(&file.rotated(1)), "third\n");
+        assert_eq!(read(&file.rotated(2)), "second\n");
+        assert!(!file.rotated(3).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,16 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use tracing::info;
-use tracing_subscriber::{fmt, EnvFilter};
 
 /// deckd — headless Stream Deck daemon for Raspberry Pi
 #[derive(Parser)]
 #[command(name = "deckd", version, about)]
 struct Cli {
-    /// Path to the config file (TOML).
-    #[arg(short, long, default_value = "/etc/deckd/config.toml")]
-    config: PathBuf,
+    /// Path to the config file (TOML) [default: /etc/deckd/config.toml, or
+    /// /config/deckd/config.toml as a Home Assistant add-on].
+    #[arg(short, long)]
+    config: Option<PathBuf>,
 
     /// Enable JSON log output (for journald).
     #[arg(long)]
@@ -18,31 +19,328 @@ struct Cli {
     /// Validate config and exit.
     #[arg(long)]
     check: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check udev rules, hidraw permissions, group membership and HA connectivity.
+    Doctor,
+
+    /// List connected Stream Decks with model, serial, firmware and key layout.
+    Devices,
+
+    /// Send the config and its icons to the control API of remote deckds,
+    /// which check and install it. The token is `--token` or
+    /// `DECKD_API_TOKEN`.
+    Push {
+        /// Base URLs of the remote APIs, e.g. `http://pi-hallway:8765`.
+        #[arg(required = true)]
+        urls: Vec<String>,
+
+        /// Bearer token of the remote APIs.
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    /// Write the config and its icons to one archive with their hashes,
+    /// which `--config` and `unpack` accept.
+    Pack {
+        /// Archive to write, e.g. `hallway.tar`.
+        output: PathBuf,
+    },
+
+    /// Add the pages of a page pack (a file or URL) to the config, asking
+    /// for the entities they need unless given with `--set`.
+    InstallPack {
+        /// Pack file (`.toml` or a bundle made by `pack`) or its URL.
+        source: String,
+
+        /// Entity for a placeholder of the pack, e.g. `player=media_player.den`.
+        #[arg(long = "set", value_name = "NAME=ENTITY")]
+        set: Vec<String>,
+
+        /// Install without asking, even if the pack runs actions guest mode
+        /// blocks by default, such as `shell`.
+        #[arg(long)]
+        yes: bool,
+
+        /// Allow fetching the pack over plain `http://`.
+        #[arg(long)]
+        allow_http: bool,
+    },
+
+    /// Check an archive made by `pack` and write its files into a directory.
+    Unpack {
+        /// Archive to read.
+        archive: PathBuf,
+
+        /// Directory to write the config and icons into.
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Only drive the Stream Deck on this host's USB, for a deckd elsewhere
+    /// with `remote` set to this host. Daemons must present
+    /// `DECKD_AGENT_TOKEN` if it is set.
+    Agent {
+        /// Address to listen on; other than loopback only with
+        /// `DECKD_AGENT_TOKEN` set.
+        #[arg(long, default_value_t = std::net::SocketAddr::from(([0, 0, 0, 0], deckd::device::remote::DEFAULT_PORT)))]
+        listen: std::net::SocketAddr,
+    },
+
+    /// Write a printable overview of every page: the keys as the deck
+    /// shows them, with their labels, actions and descriptions.
+    ExportCheatsheet {
+        #[arg(long, value_enum, default_value_t = SheetFormat::Html)]
+        format: SheetFormat,
+
+        /// File to write (default `cheatsheet.html` or `cheatsheet.png`).
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Re-render the snapshot test buttons and overwrite the reference images.
+    RenderTest {
+        /// Directory of the reference images.
+        #[arg(long, default_value = deckd::render::snapshot::GOLDEN_DIR)]
+        dir: PathBuf,
+
+        /// Only compare against the reference images; fail on differences.
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SheetFormat {
+    Html,
+    Png,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    // Init tracing.
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("deckd=info"));
-
-    if cli.json {
-        fmt().with_env_filter(filter).json().init();
-    } else {
-        fmt().with_env_filter(filter).init();
-    }
+    let config_path = cli.config.unwrap_or_else(|| {
+        PathBuf::from(if deckd::addon::active() {
+            deckd::addon::CONFIG_FILE
+        } else {
+            "/etc/deckd/config.toml"
+        })
+    });
+    let config_path = config_path.canonicalize().unwrap_or(config_path);
+    // A bundle runs from where it is unpacked; icons resolve against that.
+    let bundle = (deckd::bundle::is_bundle(&config_path) && cli.command.is_none())
+        .then(|| deckd::bundle::extract(&config_path))
+        .transpose()?;
+    let logging = match bundle {
+        Some(ref unpacked) => deckd::logging::settings(unpacked),
+        None => deckd::logging::settings(&config_path),
+    };
+    let log_dir = config_path.parent().unwrap_or(std::path::Path::new("."));
+    deckd::logging::init(&logging, log_dir, cli.json)?;
 
     info!("deckd v{}", env!("CARGO_PKG_VERSION"));
 
+    let config_path = match bundle {
+        Some(unpacked) => {
+            info!(
+                "unpacked {} to {}",
+                config_path.display(),
+                unpacked.display()
+            );
+            unpacked
+        }
+        None => config_path,
+    };
+
+    match cli.command {
+        Some(Command::Push { urls, token }) => {
+            let token = token.or_else(|| std::env::var("DECKD_API_TOKEN").ok());
+            let bundle = deckd::bundle::Bundle::collect(&config_path)?;
+            let archive = bundle.to_tar()?;
+            let client = http_client(&deckd::config::load(&config_path)?)?;
+            let mut failed = 0;
+            for url in &urls {
+                match push(&client, url, token.as_deref(), archive.clone()).await {
+                    Ok(()) => println!("{url}: installed ({} files)", bundle.files.len() + 1),
+                    Err(e) => {
+                        eprintln!("{url}: {e}");
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("push failed for {failed} of {} target(s)", urls.len());
+            }
+            return Ok(());
+        }
+        Some(Command::Pack { output }) => {
+            let bundle = deckd::bundle::Bundle::collect(&config_path)?;
+            std::fs::write(&output, bundle.to_tar()?)?;
+            println!(
+                "wrote {} ({} files)",
+                output.display(),
+                bundle.files.len() + 1
+            );
+            return Ok(());
+        }
+        Some(Command::Unpack { archive, dir }) => {
+            let bundle = deckd::bundle::Bundle::from_tar(&std::fs::read(&archive)?)?;
+            std::fs::create_dir_all(&dir)?;
+            let config = bundle.write_to(&dir)?;
+            println!(
+                "wrote {} and {} icon(s)",
+                config.display(),
+                bundle.files.len()
+            );
+            return Ok(());
+        }
+        Some(Command::InstallPack {
+            source,
+            set,
+            yes,
+            allow_http,
+        }) => {
+            if source.starts_with("http://") && !allow_http {
+                anyhow::bail!(
+                    "refusing to fetch a pack over plain http, which anyone on the way can change; \
+                     use https or pass --allow-http"
+                );
+            }
+            let data = if source.starts_with("http://") || source.starts_with("https://") {
+                http_client(&deckd::config::load(&config_path)?)?
+                    .get(&source)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?
+                    .to_vec()
+            } else {
+                std::fs::read(&source)?
+            };
+            let pack = deckd::pack::Pack::parse(&data)?;
+            println!("{}", pack.info.name);
+            if !pack.info.description.is_empty() {
+                println!("{}", pack.info.description);
+            }
+            println!("pages: {}", pack.pages().join(", "));
+            let kinds: Vec<_> = pack.action_kinds().into_iter().collect();
+            println!("actions: {}", kinds.join(", "));
+            let risky = pack.risky_kinds();
+            if !risky.is_empty()
+                && !yes
+                && !confirm(&format!(
+                    "the pack runs {} actions; add it",
+                    risky.join(", ")
+                ))?
+            {
+                anyhow::bail!("page pack not installed");
+            }
+            let mut entities = BTreeMap::new();
+            for pair in &set {
+                let (name, entity) = pair
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("expected NAME=ENTITY, got '{pair}'"))?;
+                entities.insert(name.to_string(), entity.to_string());
+            }
+            for (name, description) in &pack.info.entities {
+                if !entities.contains_key(name) {
+                    entities.insert(name.clone(), prompt(&format!("{description} ({name})"))?);
+                }
+            }
+            pack.install(&config_path, &entities)?;
+            println!("added page(s): {}", pack.pages().join(", "));
+            for var in pack.missing_secrets() {
+                eprintln!("warning: the pack needs {var}, which is not set");
+            }
+            return Ok(());
+        }
+        Some(Command::Agent { listen }) => {
+            let token = deckd::secret::from_env("DECKD_AGENT_TOKEN", "deckd_agent_token").ok();
+            let cancel = tokio_util::sync::CancellationToken::new();
+            let shutdown = cancel.clone();
+            let mut sigterm =
+                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                shutdown.cancel();
+            });
+            deckd::device::remote::serve(listen, token, cancel).await?;
+            return Ok(());
+        }
+        Some(Command::Doctor) => {
+            if !deckd::doctor::run().await {
+                anyhow::bail!("doctor found problems");
+            }
+            return Ok(());
+        }
+        Some(Command::Devices) => {
+            print_devices(&deckd::device::list_devices().await?);
+            return Ok(());
+        }
+        Some(Command::ExportCheatsheet { format, output }) => {
+            let config = deckd::config::load(&config_path)?;
+            let config_dir = config_path.parent().unwrap_or(std::path::Path::new("."));
+            let (data, default) = match format {
+                SheetFormat::Html => (
+                    deckd::render::cheatsheet::html(&config, config_dir)?.into_bytes(),
+                    "cheatsheet.html",
+                ),
+                SheetFormat::Png => (
+                    deckd::render::cheatsheet::png(&config, config_dir)?,
+                    "cheatsheet.png",
+                ),
+            };
+            let output = output.unwrap_or_else(|| PathBuf::from(default));
+            std::fs::write(&output, data)?;
+            println!("wrote {} ({} pages)", output.display(), config.pages.len());
+            return Ok(());
+        }
+        Some(Command::RenderTest { dir, check: true }) => {
+            let mismatches = deckd::render::snapshot::check_goldens(&dir)?;
+            for mismatch in &mismatches {
+                eprintln!("{}: {}", mismatch.name, mismatch.reason);
+            }
+            if !mismatches.is_empty() {
+                anyhow::bail!("{} snapshot(s) differ", mismatches.len());
+            }
+            println!("snapshots OK");
+            return Ok(());
+        }
+        Some(Command::RenderTest { dir, check: false }) => {
+            for path in deckd::render::snapshot::write_goldens(&dir)? {
+                println!("wrote {}", path.display());
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
     // Load config.
-    let config_path = cli
-        .config
-        .canonicalize()
-        .unwrap_or_else(|_| cli.config.clone());
     let config = deckd::config::load(&config_path)?;
 
     if cli.check {
+        let config_dir = config_path.parent().unwrap_or(std::path::Path::new("."));
+        let missing = deckd::config::missing_icons(&config, config_dir);
+        for (page_id, key, path) in &missing {
+            eprintln!(
+                "missing icon: page '{page_id}', key {key}: {}",
+                path.display()
+            );
+        }
+        if !missing.is_empty() {
+            anyhow::bail!("{} missing icon(s)", missing.len());
+        }
+
         println!(
             "config OK: {} pages, {} total buttons",
             config.pages.len(),
@@ -62,3 +360,95 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Ask for a line on the terminal.
+fn prompt(question: &str) -> anyhow::Result<String> {
+    use std::io::{IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("{question}: not given with --set");
+    }
+    loop {
+        print!("{question}: ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            anyhow::bail!("{question}: no answer");
+        }
+        let answer = line.trim();
+        if !answer.is_empty() {
+            return Ok(answer.to_string());
+        }
+    }
+}
+
+/// Ask `question` on the terminal; only `y` or `yes` confirm.
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    use std::io::{IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("{question}? pass --yes to confirm");
+    }
+    print!("{question}? [y/N] ");
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(matches!(line.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// A client that resolves and proxies like the daemon, per `[deckd.dns]`
+/// and `[deckd.proxy]` of `config`.
+fn http_client(config: &deckd::config::schema::AppConfig) -> anyhow::Result<reqwest::Client> {
+    deckd::action::http::configure(config.deckd.proxy.as_ref());
+    deckd::action::dns::configure(&config.deckd.dns);
+    Ok(deckd::action::http::build_client(false, None, None)?)
+}
+
+/// Send a bundle archive to the control API at `url`.
+async fn push(
+    client: &reqwest::Client,
+    url: &str,
+    token: Option<&str>,
+    archive: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut request = client
+        .put(format!("{}/config", url.trim_end_matches('/')))
+        .header(reqwest::header::CONTENT_TYPE, "application/x-tar")
+        .body(archive);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let resp = request.send().await?;
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    match body["error"].as_str() {
+        Some(message) => anyhow::bail!("{status}: {message}"),
+        None => anyhow::bail!("{status}"),
+    }
+}
+
+fn print_devices(devices: &[deckd::device::DeviceInfo]) {
+    if devices.is_empty() {
+        println!("no Stream Deck found (run `deckd doctor` to check permissions)");
+        return;
+    }
+
+    println!(
+        "{:<12} {:<16} {:<10} {:>4} {:>6} {:>8} {:>8}  IMAGE",
+        "MODEL", "SERIAL", "FIRMWARE", "KEYS", "LAYOUT", "KEY PX", "ENCODERS"
+    );
+    for dev in devices {
+        println!(
+            "{:<12} {:<16} {:<10} {:>4} {:>6} {:>8} {:>8}  {}",
+            format!("{:?}", dev.kind),
+            dev.serial,
+            dev.firmware.as_deref().unwrap_or("?"),
+            dev.keys,
+            format!("{}x{}", dev.rows, dev.columns),
+            format!("{}x{}", dev.key_size.0, dev.key_size.1),
+            dev.encoders,
+            dev.image_format,
+        );
+    }
+}
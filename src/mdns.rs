@@ -0,0 +1,304 @@
+//! mDNS/DNS-SD announcement of the control API as `_deckd._tcp`, so
+//! companion apps and HA discovery find the daemon without an address.
+//! Answers queries for the service and re-announces when the deck changes.
+
+use crate::device::DeviceStatusHandle;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const PORT: u16 = 5353;
+
+/// The service type announced.
+const SERVICE: &str = "_deckd._tcp.local";
+
+/// Where DNS-SD browsers enumerate service types.
+const SERVICES: &str = "_services._dns-sd._udp.local";
+
+/// Time to live of the records, in seconds.
+const TTL: u32 = 120;
+
+/// How often the connected deck is checked for a change of serial.
+const CHECK: Duration = Duration::from_secs(10);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+/// Class IN, with the cache-flush bit for records only this host owns.
+const CLASS_IN: u16 = 1;
+const CLASS_FLUSH: u16 = 0x8001;
+
+/// What is announced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Announcement {
+    /// Instance name, e.g. `deckd on pi-hallway`.
+    instance: String,
+    /// Host name with `.local`.
+    host: String,
+    ip: Ipv4Addr,
+    port: u16,
+    /// Serial of the connected deck, if any.
+    serial: Option<String>,
+}
+
+impl Announcement {
+    fn instance_name(&self) -> String {
+        format!("{}.{SERVICE}", self.instance)
+    }
+
+    /// The names this host answers questions about.
+    fn answers(&self, name: &str) -> bool {
+        [SERVICE, SERVICES, &self.instance_name(), &self.host]
+            .iter()
+            .any(|own| own.eq_ignore_ascii_case(name))
+    }
+
+    /// A response carrying all records, with `ttl` (0 says goodbye).
+    fn response(&self, ttl: u32) -> Vec<u8> {
+        let instance = self.instance_name();
+        let mut txt = vec![format!("version={}", env!("CARGO_PKG_VERSION"))];
+        txt.extend(
+            self.serial
+                .as_ref()
+                .map(|serial| format!("serial={serial}")),
+        );
+        let mut txt_data = Vec::new();
+        for entry in &txt {
+            txt_data.push(entry.len().min(255) as u8);
+            txt_data.extend(&entry.as_bytes()[..entry.len().min(255)]);
+        }
+        let mut srv_data = vec![0, 0, 0, 0];
+        srv_data.extend(self.port.to_be_bytes());
+        srv_data.extend(encode_name(&self.host));
+
+        let records: [(&str, u16, u16, Vec<u8>); 5] = [
+            (SERVICES, TYPE_PTR, CLASS_IN, encode_name(SERVICE)),
+            (SERVICE, TYPE_PTR, CLASS_IN, encode_name(&instance)),
+            (&instance, TYPE_SRV, CLASS_FLUSH, srv_data),
+            (&instance, TYPE_TXT, CLASS_FLUSH, txt_data),
+            (&self.host, TYPE_A, CLASS_FLUSH, self.ip.octets().to_vec()),
+        ];
+        // ID 0, flags: response, authoritative; no questions.
+        let mut packet = vec![0, 0, 0x84, 0, 0, 0];
+        packet.extend((records.len() as u16).to_be_bytes());
+        packet.extend([0, 0, 0, 0]);
+        for (name, kind, class, data) in records {
+            packet.extend(encode_name(name));
+            packet.extend(kind.to_be_bytes());
+            packet.extend(class.to_be_bytes());
+            packet.extend(ttl.to_be_bytes());
+            packet.extend((data.len() as u16).to_be_bytes());
+            packet.extend(data);
+        }
+        packet
+    }
+}
+
+/// `name` as DNS labels.
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend(label);
+    }
+    out.push(0);
+    out
+}
+
+/// The name at `offset` of `packet`, following compression pointers, and
+/// the offset after it.
+fn decode_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..32 {
+        let len = *packet.get(offset)?;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(offset + 1)));
+            }
+            0xC0.. => {
+                let pointer =
+                    usize::from(u16::from_be_bytes([len & 0x3F, *packet.get(offset + 1)?]));
+                end.get_or_insert(offset + 2);
+                offset = pointer;
+            }
+            _ => {
+                let label = packet.get(offset + 1..offset + 1 + usize::from(len))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                offset += 1 + usize::from(len);
+            }
+        }
+    }
+    None
+}
+
+/// The names asked about in a query, for the record types announced.
+fn questions(packet: &[u8]) -> Vec<String> {
+    let flags = packet.get(2).copied().unwrap_or(0x80);
+    if flags & 0x80 != 0 {
+        return Vec::new();
+    }
+    let count = packet
+        .get(4..6)
+        .map_or(0, |c| u16::from_be_bytes([c[0], c[1]]));
+    let mut offset = 12;
+    let mut names = Vec::new();
+    for _ in 0..count {
+        let Some((name, after)) = decode_name(packet, offset) else {
+            break;
+        };
+        let Some(kind) = packet.get(after..after + 2) else {
+            break;
+        };
+        let kind = u16::from_be_bytes([kind[0], kind[1]]);
+        if matches!(kind, TYPE_A | TYPE_PTR | TYPE_TXT | TYPE_SRV | TYPE_ANY) {
+            names.push(name);
+        }
+        offset = after + 4;
+    }
+    names
+}
+
+/// This host's name, without `.local`.
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "deckd".into())
+}
+
+/// The address other hosts on the LAN reach this one at: the one the API
+/// listens on, else the one multicast goes out from.
+fn lan_address(listen: IpAddr) -> Option<Ipv4Addr> {
+    match listen {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+        _ => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+            socket.connect((GROUP, PORT)).ok()?;
+            match socket.local_addr().ok()?.ip() {
+                IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// A socket on the mDNS port in the multicast group, shared with any other
+/// responder on the host (e.g. Avahi).
+fn bind() -> std::io::Result<tokio::net::UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, PORT).into())?;
+    socket.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_ttl_v4(255)?;
+    tokio::net::UdpSocket::from_std(socket.into())
+}
+
+/// Announce the control API listening on `listen` until cancelled, then
+/// withdraw it.
+pub async fn announce(listen: SocketAddr, device: DeviceStatusHandle, cancel: CancellationToken) {
+    let Some(ip) = lan_address(listen.ip()) else {
+        warn!("mDNS: no LAN address to announce");
+        return;
+    };
+    let socket = match bind() {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("mDNS: {e}");
+            return;
+        }
+    };
+    let serial = || {
+        device
+            .lock()
+            .unwrap()
+            .info
+            .as_ref()
+            .map(|info| info.serial.clone())
+    };
+    let host = hostname();
+    let mut announcement = Announcement {
+        instance: format!("deckd on {host}"),
+        host: format!("{host}.local"),
+        ip,
+        port: listen.port(),
+        serial: serial(),
+    };
+    let group = SocketAddr::from((GROUP, PORT));
+    info!("mDNS: announcing {SERVICE} on {ip}:{}", listen.port());
+
+    // RFC 6762 8.3: announce twice, a second apart.
+    let mut unsolicited = 2;
+    let mut check = tokio::time::interval(CHECK);
+    let mut buf = [0u8; 1500];
+    loop {
+        if unsolicited > 0 {
+            let _ = socket.send_to(&announcement.response(TTL), group).await;
+            unsolicited -= 1;
+        }
+        tokio::select! {
+            () = cancel.cancelled() => break,
+            () = tokio::time::sleep(Duration::from_secs(1)), if unsolicited > 0 => {}
+            _ = check.tick() => {
+                let current = serial();
+                if current != announcement.serial {
+                    debug!("mDNS: deck changed, announcing again");
+                    announcement.serial = current;
+                    unsolicited = 2;
+                }
+            }
+            received = socket.recv_from(&mut buf) => {
+                let Ok((len, _)) = received else { continue };
+                if questions(&buf[..len]).iter().any(|name| announcement.answers(name)) {
+                    let _ = socket.send_to(&announcement.response(TTL), group).await;
+                }
+            }
+        }
+    }
+    let _ = socket.send_to(&announcement.response(0), group).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_queries_for_the_service() {
+        let announcement = Announcement {
+            instance: "deckd on pi".into(),
+            host: "pi.local".into(),
+            ip: Ipv4Addr::new(192, 168, 1, 20),
+            port: 8765,
+            serial: Some("CL12K1A00042".into()),
+        };
+
+        // A PTR query for the service, then an A query compressed against it.
+        let mut query = vec![0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+        query.extend(encode_name(SERVICE));
+        query.extend([0, 12, 0, 1]);
+        query.extend([2, b'p', b'i', 0xC0, 12 + 7 + 5]);
+        query.extend([0, 1, 0, 1]);
+        let names = questions(&query);
+        assert_eq!(names, vec!["_deckd._tcp.local", "pi.local"]);
+        assert!(names.iter().all(|name| announcement.answers(name)));
+        assert!(!announcement.answers("_http._tcp.local"));
+
+        let response = announcement.response(TTL);
+        assert!(questions(&response).is_empty());
+        assert_eq!(decode_name(&response, 12), Some((SERVICES.into(), 12 + 30)));
+        let bytes = |s: &[u8]| response.windows(s.len()).any(|w| w == s);
+        assert!(bytes(b"serial=CL12K1A00042"));
+        assert!(bytes(&[0, 0, 0, 0, 0x22, 0x3D]));
+        assert!(bytes(&[192, 168, 1, 20]));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::config::schema::{ButtonConfig, KeyContent};
+use crate::ids::KeyIndex;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Who set an override. Each source holds at most one override per key, so
+/// clearing one source's content uncovers another's instead of racing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideSource {
+    /// `PUT /keys/<key>` on the control API.
+    Api,
+    /// A `set_key` action.
+    Action,
+}
+
+impl fmt::Display for OverrideSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Api => "API",
+            Self::Action => "action",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    source: OverrideSource,
+    content: KeyContent,
+    expires: Option<Instant>,
+    /// Order of setting, so the newest wins among equal priorities.
+    seq: u64,
+}
+
+/// Temporary key content drawn instead of the page's button on every page.
+/// A key may hold overrides from several sources; the one with the highest
+/// `priority` shows, and when it expires or is cleared the next one does.
+#[derive(Debug, Default)]
+pub struct KeyOverrideStore {
+    keys: HashMap<KeyIndex, Vec<Entry>>,
+    seq: u64,
+}
+
+impl KeyOverrideStore {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show `content` at `key`, replacing the earlier override of `source`.
+    pub fn set(
+        &mut self,
+        key: KeyIndex,
+        source: OverrideSource,
+        content: KeyContent,
+        now: Instant,
+    ) {
+        self.seq += 1;
+        let entries = self.keys.entry(key).or_default();
+        entries.retain(|e| e.source != source);
+        entries.push(Entry {
+            source,
+            expires: content.ttl_secs.map(|ttl| now + Duration::from_secs(ttl)),
+            content,
+            seq: self.seq,
+        });
+    }
+
+    /// Whether `key` has an override, expired or not.
+    #[must_use]
+    pub fn contains(&self, key: KeyIndex) -> bool {
+        self.keys.contains_key(&key)
+    }
+
+    /// Remove the override of `source` at `key`. Returns `false` if there
+    /// was none.
+    pub fn clear(&mut self, key: KeyIndex, source: OverrideSource) -> bool {
+        let Some(entries) = self.keys.get_mut(&key) else {
+            return false;
+        };
+        let before = entries.len();
+        entries.retain(|e| e.source != source);
+        let removed = entries.len() < before;
+        if entries.is_empty() {
+            self.keys.remove(&key);
+        }
+        removed
+    }
+
+    /// Drop every expired override. Returns `true` if any was dropped, so
+    /// renders showing it are stale.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let mut dropped = false;
+        self.keys.retain(|_, entries| {
+            let before = entries.len();
+            entries.retain(|e| e.expires.is_none_or(|at| now < at));
+            dropped |= entries.len() < before;
+            !entries.is_empty()
+        });
+        dropped
+    }
+
+    /// The button to draw at `key`: the highest-priority unexpired override,
+    /// the newest among equals.
+    #[must_use]
+    pub fn button(&self, key: KeyIndex, now: Instant) -> Option<ButtonConfig> {
+        self.keys
+            .get(&key)?
+            .iter()
+            .filter(|e| e.expires.is_none_or(|at| now < at))
+            .max_by_key(|e| (e.content.priority, e.seq))
+            .map(|e| e.content.to_button(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    fn content(label: &str, priority: i32, ttl_secs: Option<u64>) -> KeyContent {
+        KeyContent {
+            label: Some(label.into()),
+            priority,
+            ttl_secs,
+            ..KeyContent::default()
+        }
+    }
+
+    fn label(store: &KeyOverrideStore, at: Instant) -> Option<String> {
+        let button = store.button(key(7), at)?;
+        match button.label? {
+            crate::config::schema::Label::Text(text) => Some(text),
+            crate::config::schema::Label::Lines(_) => None,
+        }
+    }
+
+    #[test]
+    fn overrides_expire() {
+        let now = Instant::now();
+        let mut store = KeyOverrideStore::new();
+        store.set(
+            key(7),
+            OverrideSource::Api,
+            content("Front door", 0, Some(30)),
+            now,
+        );
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(store.button(key(7), later).unwrap().key, 7);
+        assert!(!store.expire(later));
+        let expired = now + Duration::from_secs(30);
+        assert!(store.button(key(7), expired).is_none());
+        assert!(store.expire(expired));
+        assert!(!store.contains(key(7)));
+        assert!(!store.clear(key(7), OverrideSource::Api));
+    }
+
+    #[test]
+    fn highest_priority_shows_and_restores() {
+        let now = Instant::now();
+        let mut store = KeyOverrideStore::new();
+        store.set(
+            key(7),
+            OverrideSource::Action,
+            content("Build OK", 0, None),
+            now,
+        );
+        store.set(
+            key(7),
+            OverrideSource::Api,
+            content("Doorbell", 10, Some(30)),
+            now,
+        );
+        assert_eq!(label(&store, now).as_deref(), Some("Doorbell"));
+
+        // A lower priority set later stays underneath.
+        store.set(
+            key(7),
+            OverrideSource::Action,
+            content("Build failed", 0, None),
+            now,
+        );
+        assert_eq!(label(&store, now).as_deref(), Some("Doorbell"));
+
+        // The doorbell expires and the build result shows again.
+        let later = now + Duration::from_secs(30);
+        store.expire(later);
+        assert_eq!(label(&store, later).as_deref(), Some("Build failed"));
+        assert!(store.clear(key(7), OverrideSource::Action));
+        assert!(store.button(key(7), later).is_none());
+    }
+}
@@ -0,0 +1,290 @@
+//! Page packs: ready-made pages to share and install into any config. A
+//! pack is a config (or a bundle of one with its icons) with a `[pack]`
+//! table naming the entities its pages need, which are written as
+//! `@name@` placeholders and filled in on install, and the secrets they
+//! read from the environment.
+
+use crate::bundle::Bundle;
+use crate::error::{DeckError, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tracing::info;
+
+/// Tables of a pack merged into the config.
+const MERGED: [&str; 2] = ["pages", "buttons"];
+
+/// The `[pack]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct PackInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+
+    /// Entities the pages need, by placeholder name, with a description
+    /// shown when asking for them.
+    #[serde(default)]
+    pub entities: BTreeMap<String, String>,
+
+    /// Environment variables (or `<VAR>_FILE` secrets) the pages read.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+}
+
+/// A page pack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pack {
+    pub info: PackInfo,
+    /// The `pages` and `buttons` tables as written in the pack.
+    tables: toml::Table,
+    /// Icons of the pack, relative to the config directory.
+    files: BTreeMap<std::path::PathBuf, Vec<u8>>,
+}
+
+impl Pack {
+    /// A pack from a bundle made by `deckd pack`, or the text of a pack
+    /// config without icons.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Config` if the bundle is invalid or the config
+    /// has no `[pack]` table or no pages.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let bundle = match std::str::from_utf8(data) {
+            Ok(text) if toml::from_str::<toml::Table>(text).is_ok() => Bundle {
+                config: text.to_string(),
+                files: BTreeMap::new(),
+            },
+            _ => Bundle::from_tar(data)?,
+        };
+        let mut table: toml::Table = toml::from_str(&bundle.config)?;
+        let info: PackInfo = table
+            .remove("pack")
+            .ok_or_else(|| DeckError::Config("page pack: no [pack] table".into()))?
+            .try_into()
+            .map_err(|e| DeckError::Config(format!("page pack: [pack]: {e}")))?;
+        let tables: toml::Table = MERGED
+            .iter()
+            .filter_map(|name| Some((name.to_string(), table.remove(*name)?)))
+            .collect();
+        if !tables.contains_key("pages") {
+            return Err(DeckError::Config(format!(
+                "page pack '{}' has no pages",
+                info.name
+            )));
+        }
+        Ok(Self {
+            info,
+            tables,
+            files: bundle.files,
+        })
+    }
+
+    /// Ids of the pages the pack adds.
+    #[must_use]
+    pub fn pages(&self) -> Vec<&str> {
+        self.tables
+            .get("pages")
+            .and_then(toml::Value::as_table)
+            .map(|pages| pages.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Kinds of the actions the pack's pages and buttons run.
+    #[must_use]
+    pub fn action_kinds(&self) -> BTreeSet<&str> {
+        fn collect<'a>(value: &'a toml::Value, kinds: &mut BTreeSet<&'a str>) {
+            match value {
+                toml::Value::Array(items) => {
+                    for item in items {
+                        collect(item, kinds);
+                    }
+                }
+                toml::Value::Table(table) => {
+                    if let Some(kind) = table.get("action").and_then(toml::Value::as_str) {
+                        kinds.insert(kind);
+                    }
+                    for item in table.values() {
+                        collect(item, kinds);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut kinds = BTreeSet::new();
+        for table in self.tables.values() {
+            collect(table, &mut kinds);
+        }
+        kinds
+    }
+
+    /// The kinds of [`Self::action_kinds`] that guest mode blocks by
+    /// default (`shell`, `proxmox`, ...), which should only be added
+    /// knowingly.
+    #[must_use]
+    pub fn risky_kinds(&self) -> Vec<&str> {
+        let block = crate::config::schema::default_guest_block();
+        self.action_kinds()
+            .into_iter()
+            .filter(|kind| block.iter().any(|b| b == kind))
+            .collect()
+    }
+
+    /// Declared secrets that are not configured.
+    #[must_use]
+    pub fn missing_secrets(&self) -> Vec<&str> {
+        self.info
+            .secrets
+            .iter()
+            .filter(|var| crate::secret::from_env(var, &var.to_lowercase()).is_err())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The config text at `config` with the pack's pages and named buttons
+    /// appended, placeholders replaced by `entities`.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Config` if an entity is not given or a page or
+    /// named button already exists in the config.
+    pub fn merge(&self, config: &str, entities: &BTreeMap<String, String>) -> Result<String> {
+        if let Some(name) = self
+            .info
+            .entities
+            .keys()
+            .find(|name| !entities.contains_key(*name))
+        {
+            return Err(DeckError::Config(format!(
+                "page pack '{}': no entity given for '{name}'",
+                self.info.name
+            )));
+        }
+        let existing: toml::Table = toml::from_str(config)?;
+        for (name, table) in &self.tables {
+            let taken = existing.get(name).and_then(toml::Value::as_table);
+            let mut new = table.as_table().into_iter().flat_map(toml::Table::keys);
+            if let Some(id) = new.find(|id| taken.is_some_and(|t| t.contains_key(*id))) {
+                return Err(DeckError::Config(format!(
+                    "page pack '{}': {name}.{id} already exists",
+                    self.info.name
+                )));
+            }
+        }
+        let mut tables = toml::Value::Table(self.tables.clone());
+        substitute(&mut tables, entities);
+        let added = toml::to_string(&tables)
+            .map_err(|e| DeckError::Config(format!("page pack '{}': {e}", self.info.name)))?;
+        let mut merged = config.trim_end().to_string();
+        merged.push_str(&format!("\n\n# Page pack: {}\n", self.info.name));
+        merged.push_str(&added);
+        Ok(merged)
+    }
+
+    /// Merge the pack into the config at `config_path` and add its icons,
+    /// checked and installed like a pushed bundle.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Config` if the pack does not merge, an icon of
+    /// the same name with other content exists, or the merged config does
+    /// not load; `DeckError::Io` if files cannot be read or written.
+    pub fn install(&self, config_path: &Path, entities: &BTreeMap<String, String>) -> Result<()> {
+        let dir = config_path.parent().unwrap_or(Path::new("."));
+        for (path, data) in &self.files {
+            if std::fs::read(dir.join(path)).is_ok_and(|existing| existing != *data) {
+                return Err(DeckError::Config(format!(
+                    "page pack '{}': {} already exists with other content",
+                    self.info.name,
+                    path.display()
+                )));
+            }
+        }
+        let config = std::fs::read_to_string(config_path)?;
+        let bundle = Bundle {
+            config: self.merge(&config, entities)?,
+            files: self.files.clone(),
+        };
+        bundle.install(config_path)?;
+        info!(
+            "installed page pack '{}': {}",
+            self.info.name,
+            self.pages().join(", ")
+        );
+        Ok(())
+    }
+}
+
+/// Replace `@name@` in every string of `value` with the entity `name`.
+fn substitute(value: &mut toml::Value, entities: &BTreeMap<String, String>) {
+    match value {
+        toml::Value::String(s) => {
+            for (name, entity) in entities {
+                *s = s.replace(&format!("@{name}@"), entity);
+            }
+        }
+        toml::Value::Array(items) => {
+            for item in items {
+                substitute(item, entities);
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                substitute(item, entities);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"
+[deckd]
+
+[pack]
+name = "Media Room"
+entities = { player = "Media player to control" }
+secrets = ["DECKD_TEST_UNSET_SECRET"]
+
+[pages.media]
+name = "Media"
+
+[[pages.media.buttons]]
+key = 0
+label = "Play"
+state_entity = "@player@"
+on_press = { action = "ha_service", service = "media_player.media_play_pause", data = { entity_id = "@player@" } }
+"#;
+
+    #[test]
+    fn merges_pages_with_entities() {
+        let pack = Pack::parse(PACK.as_bytes()).unwrap();
+        assert_eq!(pack.pages(), ["media"]);
+        assert_eq!(pack.missing_secrets(), ["DECKD_TEST_UNSET_SECRET"]);
+        assert_eq!(pack.action_kinds(), BTreeSet::from(["ha_service"]));
+        assert!(pack.risky_kinds().is_empty());
+        let risky =
+            format!("{PACK}on_error = {{ action = \"shell\", command = \"logger failed\" }}\n");
+        assert_eq!(
+            Pack::parse(risky.as_bytes()).unwrap().risky_kinds(),
+            ["shell"]
+        );
+
+        let config = "[deckd]\n\n[pages.home]\nname = \"Home\"\n";
+        let entities = BTreeMap::from([("player".into(), "media_player.den".into())]);
+        let merged = pack.merge(config, &entities).unwrap();
+        let parsed: toml::Table = toml::from_str(&merged).unwrap();
+        let button = &parsed["pages"]["media"]["buttons"][0];
+        assert_eq!(button["state_entity"].as_str(), Some("media_player.den"));
+        assert_eq!(
+            button["on_press"]["data"]["entity_id"].as_str(),
+            Some("media_player.den")
+        );
+        assert!(parsed["pages"].get("home").is_some());
+        assert!(merged.starts_with(config.trim_end()));
+
+        assert!(pack.merge(config, &BTreeMap::new()).is_err());
+        let err = pack.merge(&merged, &entities).unwrap_err().to_string();
+        assert!(err.contains("pages.media already exists"), "{err}");
+    }
+}
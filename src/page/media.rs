@@ -0,0 +1,102 @@
+use crate::config::schema::{ActionConfig, ButtonConfig, Label, MediaBrowserConfig};
+use crate::ids::KeyIndex;
+use serde_json::{json, Value};
+
+/// Background of the source the player is currently on.
+const CURRENT_SOURCE_BACKGROUND: &str = "#2e7d32";
+
+/// Buttons for the media browser on the keys in `free`: the favorites, then
+/// the player's sources from its `state` object. Entries beyond the free
+/// keys are left out.
+#[must_use]
+pub fn buttons(
+    config: &MediaBrowserConfig,
+    state: &Value,
+    free: impl IntoIterator<Item = KeyIndex>,
+) -> Vec<ButtonConfig> {
+    let entity = &config.entity;
+    let favorites = config.favorites.iter().map(|favorite| ButtonConfig {
+        label: Some(Label::Text(favorite.label.clone())),
+        icon: favorite.icon.clone(),
+        on_press: Some(ActionConfig::HaService {
+            service: "media_player.play_media".into(),
+            data: Some(json!({
+                "entity_id": entity,
+                "media_content_id": favorite.content_id,
+                "media_content_type": favorite.content_type,
+            })),
+        }),
+        ..ButtonConfig::default()
+    });
+
+    let attributes = &state["attributes"];
+    let current = attributes["source"].as_str();
+    let sources = attributes["source_list"]
+        .as_array()
+        .filter(|_| config.sources)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|source| ButtonConfig {
+            label: Some(Label::Text(source.to_string())),
+            background: (current == Some(source)).then(|| CURRENT_SOURCE_BACKGROUND.into()),
+            on_press: Some(ActionConfig::HaService {
+                service: "media_player.select_source".into(),
+                data: Some(json!({ "entity_id": entity, "source": source })),
+            }),
+            ..ButtonConfig::default()
+        });
+
+    free.into_iter()
+        .zip(favorites.chain(sources))
+        .map(|(key, button)| ButtonConfig {
+            key,
+            generated: true,
+            ..button
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::MediaFavorite;
+    use crate::ids::key;
+
+    #[test]
+    fn favorites_then_sources_on_free_keys() {
+        let config = MediaBrowserConfig {
+            entity: "media_player.living_room".into(),
+            sources: true,
+            favorites: vec![MediaFavorite {
+                label: "Jazz".into(),
+                content_id: "spotify:playlist:1".into(),
+                content_type: "playlist".into(),
+                icon: None,
+            }],
+        };
+        let state = json!({
+            "state": "playing",
+            "attributes": { "source": "TV", "source_list": ["Radio", "TV", "Bluetooth"] }
+        });
+        let buttons = buttons(&config, &state, [key(1), key(2), key(5)]);
+        let keys: Vec<u8> = buttons.iter().map(|b| b.key.get()).collect();
+        assert_eq!(keys, [1, 2, 5]);
+        assert!(buttons.iter().all(|b| b.generated));
+        assert_eq!(buttons[0].label, Some(Label::Text("Jazz".into())));
+        let Some(ActionConfig::HaService {
+            ref service,
+            ref data,
+        }) = buttons[2].on_press
+        else {
+            panic!("expected a service call");
+        };
+        assert_eq!(service, "media_player.select_source");
+        assert_eq!(data.as_ref().unwrap()["source"], "TV");
+        assert_eq!(
+            buttons[2].background.as_deref(),
+            Some(CURRENT_SOURCE_BACKGROUND)
+        );
+        assert_eq!(buttons[1].background, None);
+    }
+}
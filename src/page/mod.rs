@@ -1,34 +1,39 @@
-use crate::config::schema::{AppConfig, ButtonConfig, PageConfig};
+pub mod media;
+pub mod picker;
+pub mod remote;
+pub mod snapcast;
+
+use crate::config::schema::{ActionConfig, AppConfig, ButtonConfig, PageConfig};
+use crate::ids::{KeyIndex, PageId};
+use std::borrow::Cow;
 use tracing::{debug, info};
 
 /// Manages the page stack and provides button lookups.
 pub struct PageManager {
     /// Stack of page IDs. Last element is the current page.
-    stack: Vec<String>,
-    home_page: String,
+    stack: Vec<PageId>,
+    home_page: PageId,
 }
 
 impl PageManager {
     #[must_use]
-    pub fn new(home_page: &str) -> Self {
+    pub fn new(home_page: &PageId) -> Self {
         Self {
-            stack: vec![home_page.to_string()],
-            home_page: home_page.to_string(),
+            stack: vec![home_page.clone()],
+            home_page: home_page.clone(),
         }
     }
 
     /// Get the current page ID.
     #[must_use]
-    pub fn current_page(&self) -> &str {
-        self.stack
-            .last()
-            .map_or(self.home_page.as_str(), String::as_str)
+    pub fn current_page(&self) -> &PageId {
+        self.stack.last().unwrap_or(&self.home_page)
     }
 
     /// Navigate to a page by ID, pushing onto the stack.
-    pub fn navigate_to(&mut self, page_id: &str) {
+    pub fn navigate_to(&mut self, page_id: PageId) {
         info!("navigate: {} → {page_id}", self.current_page());
-        self.stack.push(page_id.to_string());
+        self.stack.push(page_id);
     }
 
     /// Go back one page. Returns true if the page changed.
@@ -52,37 +57,114 @@ impl PageManager {
     /// Look up the current page config.
     #[must_use]
     pub fn current_page_config<'a>(&self, config: &'a AppConfig) -> Option<&'a PageConfig> {
-        config.pages.get(self.current_page())
+        config.pages.get(self.current_page().as_str())
     }
 
     /// Look up a button config by key index on the current page.
     #[must_use]
-    pub fn button_for_key<'a>(&self, config: &'a AppConfig, key: u8) -> Option<&'a ButtonConfig> {
+    pub fn button_for_key<'a>(
+        &self,
+        config: &'a AppConfig,
+        key: KeyIndex,
+    ) -> Option<&'a ButtonConfig> {
         self.current_page_config(config)?
             .buttons
             .iter()
             .find(|b| b.key == key)
     }
 
+    /// Like `button_for_key`, with the page's lock settings applied.
+    #[must_use]
+    pub fn effective_button<'a>(
+        &self,
+        config: &'a AppConfig,
+        key: KeyIndex,
+    ) -> Option<Cow<'a, ButtonConfig>> {
+        let page = self.current_page_config(config)?;
+        let button = page.buttons.iter().find(|b| b.key == key)?;
+        Some(crate::lock::effective(button, page))
+    }
+
+    /// Drop the pages `keep` rejects from the stack, going home if none is
+    /// left. Returns true if the current page changed.
+    pub fn retain(&mut self, keep: impl Fn(&PageId) -> bool) -> bool {
+        let current = self.current_page().clone();
+        self.stack.retain(|page| keep(page));
+        if self.stack.is_empty() {
+            self.stack.push(self.home_page.clone());
+        }
+        *self.current_page() != current
+    }
+
     /// Update home page (e.g., after config reload).
-    pub fn set_home_page(&mut self, home: &str) {
-        self.home_page = home.to_string();
+    pub fn set_home_page(&mut self, home: &PageId) {
+        self.home_page.clone_from(home);
     }
 }
 
+/// Page IDs reachable from a page through its `navigate` buttons, in key order
+/// and without duplicates.
+#[must_use]
+pub fn linked_pages(page: &PageConfig) -> Vec<&PageId> {
+    let mut buttons: Vec<&ButtonConfig> = page.buttons.iter().collect();
+    buttons.sort_by_key(|b| b.key);
+
+    let mut linked: Vec<&PageId> = Vec::new();
+    for button in buttons {
+        if let Some(ActionConfig::Navigate { page, picker: None }) = &button.on_press {
+            if !linked.contains(&page) {
+                linked.push(page);
+            }
+        }
+    }
+    linked
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn linked_pages_from_navigate_buttons() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[pages.home]
+
+[[pages.home.buttons]]
+key = 3
+on_press = { action = "navigate", page = "scenes" }
+
+[[pages.home.buttons]]
+key = 1
+on_press = { action = "navigate", page = "lights" }
+
+[[pages.home.buttons]]
+key = 2
+on_press = { action = "navigate", page = "lights" }
+
+[[pages.home.buttons]]
+key = 4
+on_press = { action = "back" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            linked_pages(&config.pages["home"]),
+            vec!["lights", "scenes"]
+        );
+    }
+
     #[test]
     fn navigation_stack() {
-        let mut pm = PageManager::new("home");
+        let mut pm = PageManager::new(&PageId::from("home"));
         assert_eq!(pm.current_page(), "home");
 
-        pm.navigate_to("lights");
+        pm.navigate_to("lights".into());
         assert_eq!(pm.current_page(), "lights");
 
-        pm.navigate_to("scenes");
+        pm.navigate_to("scenes".into());
         assert_eq!(pm.current_page(), "scenes");
 
         assert!(pm.go_back());
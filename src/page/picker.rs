@@ -0,0 +1,184 @@
+use crate::config::schema::{ActionConfig, ButtonConfig, Label};
+use crate::ids::KeyIndex;
+use serde_json::{json, Value};
+
+/// Color swatches on keys 0-6: red, orange, yellow, green, blue, purple, pink.
+const COLORS: [[u8; 3]; 7] = [
+    [255, 0, 0],
+    [255, 128, 0],
+    [255, 220, 0],
+    [0, 200, 0],
+    [0, 90, 255],
+    [140, 0, 255],
+    [255, 60, 160],
+];
+
+/// Color temperature steps on keys 7-9, warm to cool, with the swatch drawn
+/// for each.
+const TEMPERATURES: [(u32, &str); 3] = [(2700, "#ffb46b"), (4000, "#ffd6aa"), (6500, "#f4f3ff")];
+
+/// Keys the layout is made for (the 5x3 grid).
+const KEY_COUNT: u8 = 15;
+
+/// Brightness change of the `-` and `+` keys, in percent.
+const BRIGHTNESS_STEP: i32 = 10;
+
+/// Color modes in `supported_color_modes` that take `rgb_color`.
+const RGB_MODES: [&str; 5] = ["hs", "xy", "rgb", "rgbw", "rgbww"];
+
+/// Buttons of the color picker page of the light `entity` in its `state`
+/// object. Keys the light cannot use (colors on a white-only bulb) stay empty.
+///
+/// ```text
+///  red     orange  yellow  green   blue
+///  purple  pink    2700K   4000K   6500K
+///  Back    -       state   +
+/// ```
+#[must_use]
+pub fn buttons(entity: &str, state: &Value) -> Vec<ButtonConfig> {
+    let attributes = &state["attributes"];
+    let modes: Vec<&str> = attributes["supported_color_modes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let rgb = modes.iter().any(|mode| RGB_MODES.contains(mode));
+    let dimmable = modes.iter().any(|&mode| mode != "onoff");
+    let kelvin = |name: &str| attributes[name].as_u64();
+    let warmest = kelvin("min_color_temp_kelvin").unwrap_or(0);
+    let coolest = kelvin("max_color_temp_kelvin").unwrap_or(u64::MAX);
+
+    let turn_on = |mut data: Value| {
+        data["entity_id"] = json!(entity);
+        Some(ActionConfig::HaService {
+            service: "light.turn_on".into(),
+            data: Some(data),
+        })
+    };
+
+    let colors = COLORS.iter().map(|&[r, g, b]| {
+        rgb.then(|| ButtonConfig {
+            background: Some(format!("#{r:02x}{g:02x}{b:02x}")),
+            on_press: turn_on(json!({ "rgb_color": [r, g, b] })),
+            ..ButtonConfig::default()
+        })
+    });
+    let temperatures = TEMPERATURES.iter().map(|&(k, swatch)| {
+        let supported = modes.contains(&"color_temp") && (warmest..=coolest).contains(&k.into());
+        supported.then(|| ButtonConfig {
+            label: Some(Label::Text(format!("{k}K"))),
+            background: Some(swatch.into()),
+            text_color: Some("#000000".into()),
+            on_press: turn_on(json!({ "color_temp_kelvin": k })),
+            ..ButtonConfig::default()
+        })
+    });
+    let step = |label: &str, pct: i32| {
+        dimmable.then(|| ButtonConfig {
+            label: Some(Label::Text(label.into())),
+            on_press: turn_on(json!({ "brightness_step_pct": pct })),
+            ..ButtonConfig::default()
+        })
+    };
+    let controls = [
+        Some(ButtonConfig {
+            label: Some(Label::Text("Back".into())),
+            on_press: Some(ActionConfig::Back),
+            ..ButtonConfig::default()
+        }),
+        step("-", -BRIGHTNESS_STEP),
+        Some(readout(entity, state)),
+        step("+", BRIGHTNESS_STEP),
+    ];
+
+    KeyIndex::all(KEY_COUNT)
+        .zip(colors.chain(temperatures).chain(controls))
+        .filter_map(|(key, button)| {
+            Some(ButtonConfig {
+                key,
+                generated: true,
+                ..button?
+            })
+        })
+        .collect()
+}
+
+/// The key showing the light's name, state and brightness, in its current
+/// color while on. Pressing it toggles the light.
+fn readout(entity: &str, state: &Value) -> ButtonConfig {
+    let attributes = &state["attributes"];
+    let name = attributes["friendly_name"].as_str().unwrap_or(entity);
+    let on = state["state"] == "on";
+    let status = match attributes["brightness"].as_u64() {
+        Some(brightness) if on => format!("{}%", (brightness * 100 + 127) / 255),
+        _ if on => "On".into(),
+        _ => "Off".into(),
+    };
+    let color = attributes["rgb_color"]
+        .as_array()
+        .filter(|_| on)
+        .and_then(|rgb| {
+            let channel = |i: usize| rgb.get(i)?.as_u64();
+            Some(format!(
+                "#{:02x}{:02x}{:02x}",
+                channel(0)?,
+                channel(1)?,
+                channel(2)?
+            ))
+        });
+    ButtonConfig {
+        label: Some(Label::Text(format!("{name}\n{status}"))),
+        text_color: color.is_some().then(|| "#000000".into()),
+        background: color,
+        on_press: Some(ActionConfig::HaService {
+            service: "light.toggle".into(),
+            data: Some(json!({ "entity_id": entity })),
+        }),
+        ..ButtonConfig::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_only_light_gets_temperatures_and_brightness() {
+        let state = json!({
+            "state": "on",
+            "attributes": {
+                "friendly_name": "Desk",
+                "brightness": 153,
+                "supported_color_modes": ["color_temp"],
+                "min_color_temp_kelvin": 2200,
+                "max_color_temp_kelvin": 5000
+            }
+        });
+        let buttons = buttons("light.desk", &state);
+        let keys: Vec<u8> = buttons.iter().map(|b| b.key.get()).collect();
+        // No color swatches and no 6500K step.
+        assert_eq!(keys, [7, 8, 10, 11, 12, 13]);
+        assert!(buttons.iter().all(|b| b.generated));
+        assert_eq!(buttons[4].label, Some(Label::Text("Desk\n60%".into())));
+        let Some(ActionConfig::HaService { ref data, .. }) = buttons[3].on_press else {
+            panic!("expected a service call");
+        };
+        let data = data.as_ref().unwrap();
+        assert_eq!(data["brightness_step_pct"], -10);
+        assert_eq!(data["entity_id"], "light.desk");
+    }
+
+    #[test]
+    fn color_light_shows_its_color() {
+        let state = json!({
+            "state": "on",
+            "attributes": { "supported_color_modes": ["hs"], "rgb_color": [255, 128, 0] }
+        });
+        let buttons = buttons("light.strip", &state);
+        assert_eq!(buttons[0].background.as_deref(), Some("#ff0000"));
+        let readout = buttons.iter().find(|b| b.key == 12).unwrap();
+        assert_eq!(readout.background.as_deref(), Some("#ff8000"));
+        assert_eq!(readout.label, Some(Label::Text("light.strip\nOn".into())));
+    }
+}
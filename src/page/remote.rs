@@ -0,0 +1,96 @@
+use crate::action::remote::RemoteKey;
+use crate::config::position::KeyPos;
+use crate::config::schema::{ActionConfig, ButtonConfig, Label, RemoteConfig};
+
+/// Remote page layout, placed by grid position. `Exit` (`None`) leaves the
+/// page with the deck's `back` action.
+///
+/// ```text
+///  Power  Home  Up    Back  Vol +
+///  Left   OK    Right Mute  Vol -
+///  Rew    Down  Play  Fwd   Exit
+/// ```
+const LAYOUT: [[(&str, Option<RemoteKey>); 5]; 3] = {
+    use RemoteKey::{
+        Back, Down, Forward, Home, Left, Mute, PlayPause, Power, Rewind, Right, Select, Up,
+        VolumeDown, VolumeUp,
+    };
+    [
+        [
+            ("Power", Some(Power)),
+            ("Home", Some(Home)),
+            ("Up", Some(Up)),
+            ("Back", Some(Back)),
+            ("Vol +", Some(VolumeUp)),
+        ],
+        [
+            ("Left", Some(Left)),
+            ("OK", Some(Select)),
+            ("Right", Some(Right)),
+            ("Mute", Some(Mute)),
+            ("Vol -", Some(VolumeDown)),
+        ],
+        [
+            ("Rew", Some(Rewind)),
+            ("Down", Some(Down)),
+            ("Play", Some(PlayPause)),
+            ("Fwd", Some(Forward)),
+            ("Exit", None),
+        ],
+    ]
+};
+
+/// Buttons of a remote page for `remote`, on the cells `taken` leaves free.
+#[must_use]
+pub fn buttons(remote: &RemoteConfig, taken: impl Fn(KeyPos) -> bool) -> Vec<ButtonConfig> {
+    let mut buttons = Vec::new();
+    for (row, keys) in (1..).zip(LAYOUT) {
+        for (column, (label, key)) in (1..).zip(keys) {
+            let pos = KeyPos::Cell { row, column };
+            if taken(pos) {
+                continue;
+            }
+            let on_press = match key {
+                Some(key) => ActionConfig::Remote {
+                    kind: remote.kind,
+                    host: remote.host.clone(),
+                    key,
+                },
+                None => ActionConfig::Back,
+            };
+            buttons.push(ButtonConfig {
+                pos: Some(pos),
+                label: Some(Label::Text(label.into())),
+                on_press: Some(on_press),
+                ..ButtonConfig::default()
+            });
+        }
+    }
+    buttons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::remote::RemoteKind;
+
+    #[test]
+    fn fills_free_cells() {
+        let remote = RemoteConfig {
+            kind: RemoteKind::Roku,
+            host: "tv.lan".into(),
+        };
+        let buttons = buttons(&remote, |pos| pos == KeyPos::Cell { row: 1, column: 1 });
+        assert_eq!(buttons.len(), 14);
+        assert_eq!(buttons[0].pos, Some(KeyPos::Cell { row: 1, column: 2 }));
+        assert_eq!(
+            buttons[5].on_press,
+            Some(ActionConfig::Remote {
+                kind: RemoteKind::Roku,
+                host: "tv.lan".into(),
+                key: RemoteKey::Select,
+            })
+        );
+        assert_eq!(buttons[13].on_press, Some(ActionConfig::Back));
+    }
+}
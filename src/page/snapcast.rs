@@ -0,0 +1,76 @@
+use crate::action::snapcast::Room;
+use crate::config::schema::{ActionConfig, ButtonConfig, Label};
+use crate::ids::KeyIndex;
+
+/// Text color of rooms that are muted or offline.
+const INACTIVE_TEXT: &str = "#808080";
+
+/// A key per room on the keys in `free`, showing its volume as a bar;
+/// pressing it toggles the room's mute. Rooms beyond the free keys are left
+/// out.
+#[must_use]
+pub fn buttons(
+    server: &str,
+    rooms: &[Room],
+    free: impl IntoIterator<Item = KeyIndex>,
+) -> Vec<ButtonConfig> {
+    free.into_iter()
+        .zip(rooms)
+        .map(|(key, room)| {
+            let status = match (room.connected, room.muted) {
+                (false, _) => "Offline".to_string(),
+                (true, true) => "Muted".to_string(),
+                (true, false) => format!("{}%", room.volume),
+            };
+            let active = room.connected && !room.muted;
+            ButtonConfig {
+                key,
+                generated: true,
+                label: Some(Label::Text(format!("{}\n{status}", room.name))),
+                text_color: (!active).then(|| INACTIVE_TEXT.into()),
+                level: room.connected.then(|| f32::from(room.volume) / 100.0),
+                on_press: Some(ActionConfig::Snapcast {
+                    server: server.to_string(),
+                    client: room.id.clone(),
+                    volume: 0,
+                    mute: true,
+                    group: None,
+                }),
+                ..ButtonConfig::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn rooms_show_volume_and_toggle_mute() {
+        let room = |name: &str, volume, muted| Room {
+            id: format!("id-{name}"),
+            name: name.into(),
+            group: "g1".into(),
+            group_name: String::new(),
+            volume,
+            muted,
+            connected: true,
+        };
+        let rooms = [room("Kitchen", 40, false), room("Bath", 70, true)];
+        let buttons = buttons("http://snap:1780", &rooms, [key(0), key(4)]);
+        assert_eq!(buttons[1].key, 4);
+        assert_eq!(buttons[0].label, Some(Label::Text("Kitchen\n40%".into())));
+        assert_eq!(buttons[0].level, Some(0.4));
+        assert_eq!(buttons[1].text_color.as_deref(), Some(INACTIVE_TEXT));
+        let Some(ActionConfig::Snapcast {
+            ref client, mute, ..
+        }) = buttons[1].on_press
+        else {
+            panic!("expected a snapcast action");
+        };
+        assert_eq!(client, "id-Bath");
+        assert!(mute);
+    }
+}
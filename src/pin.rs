@@ -0,0 +1,84 @@
+use crate::ids::{KeyIndex, PageId};
+use std::time::Duration;
+
+/// Time to enter a PIN before the prompt gives up and the page returns.
+pub const PIN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// What a correct PIN opens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unlock {
+    /// Navigate to the page.
+    Page(PageId),
+    /// Run the button's action once.
+    Button { page: PageId, key: KeyIndex },
+}
+
+/// Result of one key press during PIN entry.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Step {
+    More,
+    Unlocked(Unlock),
+    Wrong,
+}
+
+/// A PIN prompt in progress: a sequence of key indices entered on the deck.
+#[derive(Debug)]
+pub struct PinEntry {
+    /// Distinguishes prompts so a stale timeout is ignored.
+    pub id: u64,
+    pin: Vec<KeyIndex>,
+    entered: Vec<KeyIndex>,
+    target: Unlock,
+}
+
+impl PinEntry {
+    #[must_use]
+    pub fn new(id: u64, pin: &[KeyIndex], target: Unlock) -> Self {
+        Self {
+            id,
+            pin: pin.to_vec(),
+            entered: Vec::new(),
+            target,
+        }
+    }
+
+    /// Enter one key. The PIN is only checked once it has been entered in
+    /// full, so a wrong key does not give itself away.
+    pub fn press(&mut self, key: KeyIndex) -> Step {
+        self.entered.push(key);
+        if self.entered.len() < self.pin.len() {
+            Step::More
+        } else if self.entered == self.pin {
+            Step::Unlocked(self.target.clone())
+        } else {
+            Step::Wrong
+        }
+    }
+
+    /// Keys entered so far and the PIN length.
+    #[must_use]
+    pub fn progress(&self) -> (usize, usize) {
+        (self.entered.len(), self.pin.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn checks_the_full_sequence() {
+        let target = Unlock::Page("door".into());
+        let mut entry = PinEntry::new(1, &[key(3), key(1), key(4)], target.clone());
+        assert_eq!(entry.press(key(3)), Step::More);
+        assert_eq!(entry.press(key(1)), Step::More);
+        assert_eq!(entry.progress(), (2, 3));
+        assert_eq!(entry.press(key(4)), Step::Unlocked(target.clone()));
+
+        let mut entry = PinEntry::new(2, &[key(3), key(1), key(4)], target);
+        assert_eq!(entry.press(key(9)), Step::More);
+        assert_eq!(entry.press(key(1)), Step::More);
+        assert_eq!(entry.press(key(4)), Step::Wrong);
+    }
+}
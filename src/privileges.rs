@@ -0,0 +1,173 @@
+//! Dropping root (`[deckd] user` / `group`): deckd can start as root to open
+//! the decks before udev rules grant access, then runs everything else,
+//! actions included, as an unprivileged user.
+
+use crate::error::{DeckError, Result};
+use std::ffi::{CStr, CString};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// Room for the strings of a passwd or group entry.
+const ENTRY_BUFFER: usize = 16 * 1024;
+
+/// `HOME`, `USER` and `LOGNAME` of the user switched to. Kept here rather
+/// than set in the environment, which other threads may be reading.
+static USER_ENV: OnceLock<[(&str, String); 3]> = OnceLock::new();
+
+/// A user to switch to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct User {
+    name: CString,
+    uid: libc::uid_t,
+    /// Primary group.
+    gid: libc::gid_t,
+    home: String,
+}
+
+/// Switch to `user` and `group` (by name or number) for good. The group
+/// defaults to the user's primary group; supplementary groups become the
+/// user's. Does nothing unless running as root.
+///
+/// # Errors
+/// Returns `DeckError::Config` for an unknown user or group, or a group
+/// without a user (which would keep running as root), and `DeckError::Io`
+/// if the switch fails, or could be undone.
+pub fn drop_to(user: Option<&str>, group: Option<&str>) -> Result<()> {
+    let Some(user) = user else {
+        return match group {
+            Some(_) => Err(DeckError::Config(
+                "deckd.group: needs deckd.user, or deckd would keep running as root".into(),
+            )),
+            None => Ok(()),
+        };
+    };
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    if unsafe { libc::geteuid() } != 0 {
+        warn!("deckd.user / deckd.group set but not running as root, keeping the current user");
+        return Ok(());
+    }
+
+    let user = lookup_user(user)
+        .ok_or_else(|| DeckError::Config(format!("deckd.user: no user '{user}'")))?;
+    let gid = match group {
+        Some(name) => lookup_group(name)
+            .ok_or_else(|| DeckError::Config(format!("deckd.group: no group '{name}'")))?,
+        None => user.gid,
+    };
+
+    // SAFETY: plain syscalls on valid ids and a valid NUL-terminated name;
+    // glibc applies set*id to every thread of the process.
+    unsafe {
+        if libc::initgroups(user.name.as_ptr(), gid) != 0 || libc::setgid(gid) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if libc::setuid(user.uid) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if libc::setuid(0) == 0 {
+            return Err(std::io::Error::other("root privileges could be regained").into());
+        }
+    }
+
+    let name = user.name.to_string_lossy().into_owned();
+    info!(
+        "dropped privileges to user {name} (uid {}, gid {gid})",
+        user.uid
+    );
+    USER_ENV.get_or_init(|| {
+        [
+            ("HOME", user.home),
+            ("USER", name.clone()),
+            ("LOGNAME", name),
+        ]
+    });
+    Ok(())
+}
+
+/// The variables that differ for the user switched to, if any, to set on
+/// the commands deckd runs so they see that user's home, not root's.
+pub fn env() -> impl Iterator<Item = (&'static str, &'static str)> {
+    USER_ENV
+        .get()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| (*name, value.as_str()))
+}
+
+/// The environment variable `name` as the user switched to would see it.
+pub fn var(name: &str) -> Option<String> {
+    match env().find(|&(n, _)| n == name) {
+        Some((_, value)) => Some(value.to_string()),
+        None => std::env::var(name).ok(),
+    }
+}
+
+/// The user called `name`, or with the uid `name`.
+fn lookup_user(name: &str) -> Option<User> {
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; ENTRY_BUFFER];
+    let mut found = std::ptr::null_mut();
+    let by_name = CString::new(name).ok()?;
+    // SAFETY: every pointer is valid for the call, and `buf` outlives the
+    // strings `entry` points into.
+    unsafe {
+        libc::getpwnam_r(
+            by_name.as_ptr(),
+            &mut entry,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        );
+        if found.is_null() {
+            let uid = name.parse().ok()?;
+            libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found);
+        }
+        if found.is_null() {
+            return None;
+        }
+        Some(User {
+            name: CStr::from_ptr(entry.pw_name).to_owned(),
+            uid: entry.pw_uid,
+            gid: entry.pw_gid,
+            home: CStr::from_ptr(entry.pw_dir).to_string_lossy().into_owned(),
+        })
+    }
+}
+
+/// The id of the group called `name`, or with the gid `name`.
+fn lookup_group(name: &str) -> Option<libc::gid_t> {
+    let mut entry: libc::group = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; ENTRY_BUFFER];
+    let mut found = std::ptr::null_mut();
+    let by_name = CString::new(name).ok()?;
+    // SAFETY: as in `lookup_user`.
+    unsafe {
+        libc::getgrnam_r(
+            by_name.as_ptr(),
+            &mut entry,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        );
+        if found.is_null() {
+            let gid = name.parse().ok()?;
+            libc::getgrgid_r(gid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found);
+        }
+        (!found.is_null()).then_some(entry.gr_gid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_users_and_groups_by_name_or_id() {
+        let root = lookup_user("root").unwrap();
+        assert_eq!((root.uid, root.gid), (0, 0));
+        assert_eq!(lookup_user("0").unwrap().name.to_str(), Ok("root"));
+        assert_eq!(lookup_group("root"), Some(0));
+        assert_eq!(lookup_user("no-such-deckd-user"), None);
+        assert_eq!(lookup_group("no-such-deckd-group"), None);
+    }
+}
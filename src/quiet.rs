@@ -0,0 +1,88 @@
+//! Quiet hours (`[deckd.quiet]`): overnight the deck dims, stops animating
+//! and polls less, optionally showing a single page.
+
+use crate::config::schema::{AppConfig, QuietHours};
+use crate::event::DeckEvent;
+use crate::schedule::{in_range, TimeOfDay};
+use crate::state::fetch_ha_states;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// How often the quiet entity and hours are re-evaluated.
+const QUIET_POLL: Duration = Duration::from_secs(15);
+
+/// Whether it is quiet at `now`: within the hours, or while the entity is
+/// in the quiet state.
+#[must_use]
+pub fn is_quiet(quiet: &QuietHours, entity_state: Option<&str>, now: TimeOfDay) -> bool {
+    let by_hours = quiet
+        .between
+        .is_some_and(|[start, end]| in_range(start, end, now));
+    let by_state = quiet.entity.is_some() && entity_state == Some(quiet.equals.as_str());
+    by_hours || by_state
+}
+
+/// Re-evaluate `[deckd.quiet]` periodically and emit `Quiet` when it starts
+/// or ends.
+pub async fn watch(
+    config: Arc<ArcSwap<AppConfig>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(QUIET_POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut active = false;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let cfg = config.load_full();
+        let quiet = match cfg.deckd.quiet {
+            Some(ref quiet) => {
+                let entity_state = match quiet.entity {
+                    Some(ref entity) => fetch_ha_states(std::slice::from_ref(entity))
+                        .await
+                        .remove(entity),
+                    None => None,
+                };
+                is_quiet(quiet, entity_state.as_deref(), TimeOfDay::now())
+            }
+            None => false,
+        };
+
+        if quiet != active {
+            info!("quiet hours {}", if quiet { "started" } else { "ended" });
+            let _ = tx.send(DeckEvent::Quiet(quiet));
+            active = quiet;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_by_hours_or_entity() {
+        let quiet: QuietHours = toml::from_str(
+            r#"
+between = ["22:00", "06:30"]
+entity = "input_boolean.night"
+"#,
+        )
+        .unwrap();
+        let at = |s: &str| s.parse::<TimeOfDay>().unwrap();
+        assert!(is_quiet(&quiet, None, at("23:00")));
+        assert!(is_quiet(&quiet, Some("off"), at("06:00")));
+        assert!(!is_quiet(&quiet, Some("off"), at("06:30")));
+        assert!(is_quiet(&quiet, Some("on"), at("12:00")));
+        assert_eq!(quiet.brightness, 1);
+    }
+}
@@ -0,0 +1,108 @@
+//! Color correction of key images for the panel (`[deckd.calibration]`):
+//! gamma, brightness and ordered dithering, per device serial.
+
+use crate::config::schema::{CalibrationConfig, ColorCalibration};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{LazyLock, RwLock};
+
+/// 4x4 Bayer matrix, the order in which pixels of a flat area round up.
+const BAYER: [u8; 16] = [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5];
+
+static CALIBRATION: LazyLock<RwLock<CalibrationConfig>> = LazyLock::new(RwLock::default);
+
+/// Use the calibration of a (re)loaded config.
+pub fn configure(config: &CalibrationConfig) {
+    CALIBRATION.write().unwrap().clone_from(config);
+}
+
+/// The calibration of the deck with `serial`: its own, else the default.
+#[must_use]
+pub fn for_device(serial: &str) -> ColorCalibration {
+    let config = CALIBRATION.read().unwrap();
+    config
+        .devices
+        .get(serial)
+        .copied()
+        .unwrap_or(config.default)
+}
+
+impl ColorCalibration {
+    /// Whether images pass through unchanged.
+    #[must_use]
+    pub fn is_identity(&self) -> bool {
+        (self.gamma - 1.0).abs() < f32::EPSILON
+            && (self.brightness - 1.0).abs() < f32::EPSILON
+            && !self.dither
+    }
+
+    /// A hash of the settings, to keep encoded images of different
+    /// calibrations apart.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.gamma.to_bits(), self.brightness.to_bits(), self.dither).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Correct an RGBA image `width` pixels wide in place. Alpha is kept.
+    pub fn apply(&self, rgba: &mut [u8], width: u32) {
+        let lut: Vec<f32> = (0..=255u8)
+            .map(|v| {
+                let linear = (f32::from(v) / 255.0).powf(self.gamma) * self.brightness;
+                linear.clamp(0.0, 1.0) * 255.0
+            })
+            .collect();
+        let width = width.max(1) as usize;
+        for (i, pixel) in rgba.chunks_exact_mut(4).enumerate() {
+            let threshold = if self.dither {
+                let (x, y) = (i % width, i / width);
+                (f32::from(BAYER[(y % 4) * 4 + x % 4]) + 0.5) / 16.0
+            } else {
+                0.5
+            };
+            for channel in &mut pixel[..3] {
+                *channel = (lut[usize::from(*channel)] + threshold).floor().min(255.0) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calibration(gamma: f32, brightness: f32, dither: bool) -> ColorCalibration {
+        ColorCalibration {
+            gamma,
+            brightness,
+            dither,
+        }
+    }
+
+    #[test]
+    fn corrects_and_dithers() {
+        let flat = |value: u8| -> Vec<u8> { [value, value, value, 255].repeat(16) };
+
+        let identity = ColorCalibration::default();
+        assert!(identity.is_identity());
+        let mut image = flat(100);
+        identity.apply(&mut image, 4);
+        assert_eq!(image, flat(100));
+
+        let mut image = flat(128);
+        calibration(2.2, 1.0, false).apply(&mut image, 4);
+        assert!(image[0] < 64 && image[3] == 255);
+
+        let mut image = flat(200);
+        calibration(1.0, 0.5, false).apply(&mut image, 4);
+        assert_eq!(image, flat(100));
+
+        // 127.5 on average: half the pixels round down, half up.
+        let mut image = flat(255);
+        calibration(1.0, 0.5, true).apply(&mut image, 4);
+        let reds: Vec<u8> = image.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(reds.iter().filter(|&&r| r == 127).count(), 8);
+        assert_eq!(reds.iter().filter(|&&r| r == 128).count(), 8);
+    }
+}
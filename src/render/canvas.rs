@@ -1,19 +1,43 @@
 use crate::error::{DeckError, Result};
-use tiny_skia::{Color, Pixmap, Transform};
+use crate::render::color_names;
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
 
-/// Stream Deck MK.2 button size in pixels.
+/// Stream Deck MK.2 button size in pixels. Sizes in the config (fonts,
+/// icons) and of the drawn elements are for keys this size, and scaled up
+/// for models with larger keys.
 pub const BUTTON_SIZE: u32 = 72;
 
-/// Create a new pixmap filled with a solid background color.
+/// How much larger than on a `BUTTON_SIZE` key things are drawn on a key of
+/// `size` pixels.
+#[must_use]
+pub fn scale(size: u32) -> f32 {
+    size as f32 / BUTTON_SIZE as f32
+}
+
+/// Create a new `size`x`size` key pixmap filled with a solid background color.
+///
+/// # Errors
+/// Returns `DeckError::Render` if the hex color is invalid or pixmap creation fails.
+pub fn create_canvas(size: u32, bg_hex: &str) -> Result<Pixmap> {
+    create_canvas_sized(size, size, bg_hex)
+}
+
+/// Create a pixmap of any size filled with a solid background color.
 ///
 /// # Errors
 /// Returns `DeckError::Render` if the hex color is invalid or pixmap creation fails.
-pub fn create_canvas(bg_hex: &str) -> Result<Pixmap> {
-    let mut pixmap = Pixmap::new(BUTTON_SIZE, BUTTON_SIZE)
+pub fn create_canvas_sized(width: u32, height: u32, bg_hex: &str) -> Result<Pixmap> {
+    let mut pixmap = Pixmap::new(width, height)
         .ok_or_else(|| DeckError::Render("failed to create pixmap".into()))?;
 
     let color = parse_hex_color(bg_hex)?;
-    pixmap.fill(color);
+    if color.is_opaque() {
+        pixmap.fill(color);
+    } else {
+        // Keys can't show transparency: blend translucent backgrounds onto black.
+        pixmap.fill(Color::BLACK);
+        overlay(&mut pixmap, color);
+    }
     Ok(pixmap)
 }
 
@@ -29,31 +53,66 @@ pub fn composite(canvas: &mut Pixmap, src: &Pixmap, x: i32, y: i32) {
     );
 }
 
-/// Parse a hex color string like "#1a1a2e" or "#fff" into a tiny-skia Color.
+/// Draw a color over the whole canvas, blending by its alpha channel.
+///
+/// Used for translucent overlays, e.g. `#00000080` to darken a key.
+pub fn overlay(canvas: &mut Pixmap, color: Color) {
+    let Some(rect) = Rect::from_xywh(0.0, 0.0, canvas.width() as f32, canvas.height() as f32)
+    else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    canvas.fill_rect(rect, &paint, Transform::identity(), None);
+}
+
+/// Grey out a rendered key in place: desaturate and darken it, the look of
+/// a locked button.
+pub fn grey_out(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let luma = (u32::from(px[0]) * 77 + u32::from(px[1]) * 150 + u32::from(px[2]) * 29) >> 8;
+        let grey = (luma * 2 / 5) as u8;
+        px[..3].fill(grey);
+    }
+}
+
+/// Parse a color string into a tiny-skia Color.
+///
+/// Accepts hex as `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, CSS named colors
+/// like "tomato" or "slategray", and "transparent".
 ///
 /// # Errors
-/// Returns `DeckError::Render` if the hex string is malformed.
+/// Returns `DeckError::Render` if the color is malformed or unknown.
 pub fn parse_hex_color(hex: &str) -> Result<Color> {
-    let hex = hex.trim_start_matches('#');
-    let parse_err = || DeckError::Render(format!("invalid hex color: #{hex}"));
-
-    let (r, g, b) = match hex.len() {
-        3 => {
-            let r = u8::from_str_radix(&hex[0..1].repeat(2), 16).map_err(|_| parse_err())?;
-            let g = u8::from_str_radix(&hex[1..2].repeat(2), 16).map_err(|_| parse_err())?;
-            let b = u8::from_str_radix(&hex[2..3].repeat(2), 16).map_err(|_| parse_err())?;
-            (r, g, b)
+    let input = hex.trim();
+    if !input.starts_with('#') {
+        if input.eq_ignore_ascii_case("transparent") {
+            return Ok(Color::TRANSPARENT);
         }
-        6 => {
-            let r = u8::from_str_radix(&hex[0..2], 16).map_err(|_| parse_err())?;
-            let g = u8::from_str_radix(&hex[2..4], 16).map_err(|_| parse_err())?;
-            let b = u8::from_str_radix(&hex[4..6], 16).map_err(|_| parse_err())?;
-            (r, g, b)
+        if let Some([r, g, b]) = color_names::lookup(input) {
+            return Ok(Color::from_rgba8(r, g, b, 255));
         }
+    }
+
+    let hex = input.trim_start_matches('#');
+    let parse_err = || DeckError::Render(format!("invalid color: {input}"));
+    if !hex.is_ascii() {
+        return Err(parse_err());
+    }
+
+    // Short forms repeat each digit ("f" -> "ff").
+    let short = |i: usize| u8::from_str_radix(&hex[i..=i].repeat(2), 16).map_err(|_| parse_err());
+    let long = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| parse_err());
+
+    let (r, g, b, a) = match hex.len() {
+        3 => (short(0)?, short(1)?, short(2)?, 255),
+        4 => (short(0)?, short(1)?, short(2)?, short(3)?),
+        6 => (long(0)?, long(2)?, long(4)?, 255),
+        8 => (long(0)?, long(2)?, long(4)?, long(6)?),
         _ => return Err(parse_err()),
     };
 
-    Ok(Color::from_rgba8(r, g, b, 255))
+    Ok(Color::from_rgba8(r, g, b, a))
 }
 
 #[cfg(test)]
@@ -73,10 +132,55 @@ mod tests {
         assert_eq!(c.green(), 1.0);
     }
 
+    #[test]
+    fn parse_hex_with_alpha() {
+        let c = parse_hex_color("#ff000080").unwrap();
+        assert_eq!(c.red(), 1.0);
+        assert_eq!(c.alpha(), 0x80 as f32 / 255.0);
+
+        let c = parse_hex_color("#0f08").unwrap();
+        assert_eq!(c.green(), 1.0);
+        assert_eq!(c.alpha(), 0x88 as f32 / 255.0);
+    }
+
+    #[test]
+    fn parse_named_colors() {
+        let c = parse_hex_color("tomato").unwrap();
+        assert_eq!(c.red(), 1.0);
+        assert_eq!(c.green(), 0x63 as f32 / 255.0);
+        assert!(parse_hex_color("SlateGray").is_ok());
+        assert_eq!(parse_hex_color("transparent").unwrap().alpha(), 0.0);
+    }
+
+    #[test]
+    fn grey_out_desaturates() {
+        let mut rgba = [255, 0, 0, 255, 255, 255, 255, 255];
+        grey_out(&mut rgba);
+        assert_eq!(rgba[0], rgba[1]);
+        assert_eq!(rgba[1], rgba[2]);
+        assert_eq!(rgba[3], 255);
+        assert_eq!(rgba[4], 102);
+    }
+
+    #[test]
+    fn parse_invalid_colors() {
+        assert!(parse_hex_color("#12345").is_err());
+        assert!(parse_hex_color("notacolor").is_err());
+        assert!(parse_hex_color("#ééé").is_err());
+    }
+
+    #[test]
+    fn translucent_background_blends_onto_black() {
+        let pm = create_canvas(BUTTON_SIZE, "#ffffff80").unwrap();
+        let px = pm.pixel(0, 0).unwrap();
+        assert_eq!(px.alpha(), 255);
+        assert_eq!(px.red(), 0x80);
+    }
+
     #[test]
     fn create_canvas_basic() {
-        let pm = create_canvas("#000000").unwrap();
-        assert_eq!(pm.width(), BUTTON_SIZE);
-        assert_eq!(pm.height(), BUTTON_SIZE);
+        let pm = create_canvas(96, "#000000").unwrap();
+        assert_eq!(pm.width(), 96);
+        assert_eq!(pm.height(), 96);
     }
 }
@@ -0,0 +1,304 @@
+//! Printable cheat sheet of every page of a config, for `deckd
+//! export-cheatsheet`: each page drawn as the deck shows it (with no entity
+//! states known), next to what its keys do.
+
+use crate::config::schema::{ActionConfig, AppConfig, ButtonConfig, PageConfig};
+use crate::config::DEFAULT_GRID;
+use crate::error::{DeckError, Result};
+use crate::ids::PageId;
+use crate::render::canvas::{composite, create_canvas_sized, BUTTON_SIZE};
+use crate::render::splash::Grid;
+use crate::render::text;
+use base64::Engine;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use tiny_skia::{IntSize, Pixmap};
+
+/// Space around and between keys.
+const GAP: u32 = 12;
+
+/// Height of the action caption under each key of the PNG.
+const CAPTION: u32 = 22;
+
+/// Height of the page titles of the PNG.
+const TITLE: u32 = 40;
+
+/// Characters of an action caption that fit under a key.
+const CAPTION_CHARS: usize = 14;
+
+const PAPER: &str = "#ffffff";
+const INK: &str = "#202020";
+
+const XL_GRID: Grid = Grid {
+    columns: 8,
+    rows: 4,
+};
+
+/// The sheet as one HTML document, with the page images inlined.
+///
+/// # Errors
+/// Returns `DeckError::Render` if a key fails to render.
+pub fn html(config: &AppConfig, config_dir: &Path) -> Result<String> {
+    let grid = grid(config);
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>deckd cheat sheet</title>\n<style>\n\
+         body { font-family: sans-serif; color: #202020; margin: 2em; }\n\
+         section { break-inside: avoid; margin-bottom: 2.5em; }\n\
+         h2 small { color: #888; font-weight: normal; }\n\
+         table { border-collapse: collapse; margin-top: 1em; }\n\
+         th, td { text-align: left; padding: 0.2em 1em 0.2em 0; vertical-align: top; }\n\
+         td code { font-size: 0.9em; }\n\
+         </style>\n</head>\n<body>\n<h1>deckd cheat sheet</h1>\n",
+    );
+    for (id, page) in pages(config) {
+        let image = encode_png(&draw_page(config, page, config_dir, grid, 0)?)?;
+        let _ = write!(
+            out,
+            "<section>\n<h2>{} <small>{}</small></h2>\n\
+             <img src=\"data:image/png;base64,{}\" alt=\"{}\">\n",
+            escape(&page.name),
+            escape(id.as_str()),
+            base64::engine::general_purpose::STANDARD.encode(image),
+            escape(&page.name),
+        );
+
+        let mut rows = String::new();
+        let mut buttons: Vec<&ButtonConfig> = page.buttons.iter().collect();
+        buttons.sort_by_key(|b| b.key);
+        for button in buttons {
+            let label = button
+                .label
+                .as_ref()
+                .map(|l| l.texts().join(" ").replace('\n', " "));
+            let _ = writeln!(
+                rows,
+                "<tr><td>{}</td><td>{}</td><td><code>{}</code></td><td>{}</td></tr>",
+                button.key,
+                escape(label.as_deref().unwrap_or_default()),
+                escape(&button.on_press.as_ref().map(summary).unwrap_or_default()),
+                escape(button.description.as_deref().unwrap_or_default()),
+            );
+        }
+        for chord in &page.chords {
+            let _ = writeln!(
+                rows,
+                "<tr><td>{} + {}</td><td></td><td><code>{}</code></td><td>chord</td></tr>",
+                chord.modifier,
+                chord.key,
+                escape(&summary(&chord.on_press)),
+            );
+        }
+        for dial in &page.dials {
+            for (how, action) in [("turn", &dial.on_rotate), ("press", &dial.on_press)] {
+                if let Some(action) = action {
+                    let _ = writeln!(
+                        rows,
+                        "<tr><td>dial {}</td><td></td><td><code>{}</code></td><td>{how}</td></tr>",
+                        dial.dial,
+                        escape(&summary(action)),
+                    );
+                }
+            }
+        }
+        if !rows.is_empty() {
+            let _ = write!(
+                out,
+                "<table>\n<tr><th>Key</th><th>Label</th><th>Action</th><th>Description</th></tr>\n\
+                 {rows}</table>\n"
+            );
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    Ok(out)
+}
+
+/// The sheet as one PNG: the pages stacked under their names, with the
+/// action of each key written under it.
+///
+/// # Errors
+/// Returns `DeckError::Render` if a key fails to render or the image
+/// cannot be encoded.
+pub fn png(config: &AppConfig, config_dir: &Path) -> Result<Vec<u8>> {
+    let grid = grid(config);
+    let font = config.button_defaults(None).font;
+    let mut sections = Vec::new();
+    for (id, page) in pages(config) {
+        let drawn = draw_page(config, page, config_dir, grid, CAPTION)?;
+        let mut title = create_canvas_sized(drawn.width(), TITLE, PAPER)?;
+        let name = format!("{} ({id})", page.name);
+        text::render_text_in_area(&mut title, &name, INK, 18.0, &font, 0.0, TITLE as f32)?;
+        sections.push(title);
+        sections.push(drawn);
+    }
+
+    let width = sections.first().map_or(BUTTON_SIZE, Pixmap::width);
+    let height = sections.iter().map(Pixmap::height).sum::<u32>().max(1);
+    let mut sheet = create_canvas_sized(width, height, PAPER)?;
+    let mut y = 0;
+    for section in &sections {
+        composite(&mut sheet, section, 0, y as i32);
+        y += section.height();
+    }
+    encode_png(&sheet)
+}
+
+/// A short description of what an action does, e.g. `navigate → lights`.
+#[must_use]
+pub fn summary(action: &ActionConfig) -> String {
+    let kind = action.kind();
+    match action {
+        ActionConfig::Navigate {
+            picker: Some(picker),
+            ..
+        } => format!("{kind} → {picker}"),
+        ActionConfig::Navigate { page, .. } => format!("{kind} → {page}"),
+        ActionConfig::HaService { service, .. } => service.clone(),
+        ActionConfig::HaEvent { event, .. } => format!("{kind} {event}"),
+        ActionConfig::HaAssist { text, .. } => format!("{kind} \"{text}\""),
+        ActionConfig::Http { method, url, .. } => format!("{method} {url}"),
+        ActionConfig::Shell { command, .. } => command.clone(),
+        ActionConfig::SceneSave { scene, .. } | ActionConfig::SceneRecall { scene } => {
+            format!("{kind} {scene}")
+        }
+        ActionConfig::Flash { pattern, .. } => format!("{kind} {pattern}"),
+        ActionConfig::Brightness {
+            level: Some(level), ..
+        } => format!("{kind} {level}"),
+        ActionConfig::Brightness { step, .. } => format!("{kind} {step:+}"),
+        _ => kind.to_string(),
+    }
+}
+
+/// Grid that fits every key of the config: an MK.2's, or an XL's once a key
+/// lies past it.
+fn grid(config: &AppConfig) -> Grid {
+    let fits = config
+        .pages
+        .values()
+        .flat_map(|p| &p.buttons)
+        .all(|b| b.key.get() < DEFAULT_GRID.keys());
+    if fits {
+        DEFAULT_GRID
+    } else {
+        XL_GRID
+    }
+}
+
+/// Pages in sheet order: the home page first, then by id.
+fn pages(config: &AppConfig) -> Vec<(&PageId, &PageConfig)> {
+    let mut pages: Vec<_> = config.pages.iter().collect();
+    pages.sort_by_key(|(id, _)| (**id != config.deckd.home_page, *id));
+    pages
+}
+
+/// Draw a page's keys on paper, with a band of `caption` pixels under each
+/// for the action of the key (none if 0).
+fn draw_page(
+    config: &AppConfig,
+    page: &PageConfig,
+    config_dir: &Path,
+    grid: Grid,
+    caption: u32,
+) -> Result<Pixmap> {
+    let defaults = config.button_defaults(None);
+    let locale = crate::render::locale::resolve(&config.deckd.locale);
+    let now = chrono::Local::now().naive_local();
+    let states = HashMap::new();
+    let cell = (BUTTON_SIZE + GAP, BUTTON_SIZE + caption + GAP);
+    let mut pm = create_canvas_sized(GAP + grid.columns * cell.0, GAP + grid.rows * cell.1, PAPER)?;
+
+    for index in 0..grid.keys() {
+        let x = GAP + u32::from(index) % grid.columns * cell.0;
+        let y = GAP + u32::from(index) / grid.columns * cell.1;
+        let button = page.buttons.iter().find(|b| b.key.get() == index);
+        let rgba = match button {
+            Some(button) => crate::render::render_button(
+                button,
+                &defaults,
+                config_dir,
+                &states,
+                locale,
+                now,
+                BUTTON_SIZE,
+            )?,
+            None => crate::render::render_blank(BUTTON_SIZE)?,
+        };
+        let key = IntSize::from_wh(BUTTON_SIZE, BUTTON_SIZE)
+            .and_then(|size| Pixmap::from_vec(rgba, size))
+            .ok_or_else(|| DeckError::Render("rendered key has the wrong size".into()))?;
+        composite(&mut pm, &key, x as i32, y as i32);
+
+        let action = button.and_then(|b| b.on_press.as_ref());
+        if let (true, Some(action)) = (caption > 0, action) {
+            let mut band = create_canvas_sized(BUTTON_SIZE, caption, PAPER)?;
+            let text = shorten(&summary(action), CAPTION_CHARS);
+            text::render_text_in_area(&mut band, &text, INK, 9.0, &defaults.font, 0.0, 14.0)?;
+            composite(&mut pm, &band, x as i32, (y + BUTTON_SIZE) as i32);
+        }
+    }
+    Ok(pm)
+}
+
+/// Cut `text` to `max` characters, ending in an ellipsis if it was longer.
+fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+fn encode_png(pm: &Pixmap) -> Result<Vec<u8>> {
+    pm.encode_png()
+        .map_err(|e| DeckError::Render(format!("failed to encode PNG: {e}")))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_pages_keys_and_actions() {
+        let config: AppConfig = toml::from_str(
+            r#"
+[deckd]
+
+[pages.home]
+name = "Home"
+
+[[pages.home.buttons]]
+key = 0
+label = "Lights"
+description = "Opens the <light> page"
+on_press = { action = "navigate", page = "lights" }
+
+[[pages.home.dials]]
+dial = 0
+on_rotate = { action = "brightness", step = 5 }
+
+[pages.lights]
+name = "Lights"
+"#,
+        )
+        .unwrap();
+        let html = html(&config, Path::new(".")).unwrap();
+        assert!(html.find("<h2>Home").unwrap() < html.find("<h2>Lights").unwrap());
+        assert!(html.contains("<td>Lights</td><td><code>navigate → lights</code>"));
+        assert!(html.contains("Opens the &lt;light&gt; page"));
+        assert!(html.contains("<td>dial 0</td><td></td><td><code>brightness +5</code>"));
+        assert!(png(&config, Path::new("."))
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::schema::{ActionConfig, ButtonConfig, ButtonDefaults};
+use crate::error::Result;
+use crate::render::canvas::{create_canvas, scale};
+use crate::render::status::wrap;
+use crate::render::text::{self, TextLine};
+
+/// Background of every key in diagnostic mode, so no real page is mistaken for it.
+const BACKGROUND: &str = "#3b2f00";
+
+/// Characters per line of the page id and binding.
+const WRAP_WIDTH: usize = 11;
+
+/// What a key is bound to on the current page.
+#[derive(Debug, Clone, Copy)]
+pub enum Binding<'a> {
+    Empty,
+    Status,
+    Override,
+    Button(&'a ButtonConfig),
+}
+
+impl Binding<'_> {
+    /// Short description, e.g. "navigate lights" or "http".
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Empty => "empty".into(),
+            Self::Status => "status".into(),
+            Self::Override => "override".into(),
+            Self::Button(button) => match &button.on_press {
+                None => "no action".into(),
+                Some(ActionConfig::Navigate {
+                    picker: Some(entity),
+                    ..
+                }) => format!("picker {entity}"),
+                Some(ActionConfig::Navigate { page, .. }) => format!("navigate {page}"),
+                Some(action) => action.kind().into(),
+            },
+        }
+    }
+}
+
+/// Render a `size`x`size` key showing its index, the page id and what it is
+/// bound to.
+///
+/// # Errors
+/// Returns `DeckError::Render` if canvas creation or text rendering fails.
+pub fn render_key_info(
+    key: u8,
+    page_id: &str,
+    binding: Binding<'_>,
+    defaults: &ButtonDefaults,
+    size: u32,
+) -> Result<Vec<u8>> {
+    let mut pm = create_canvas(size, BACKGROUND)?;
+    let index = format!("#{key}");
+    let page = wrap(page_id, WRAP_WIDTH, 1);
+    let bound = wrap(&binding.describe(), WRAP_WIDTH, 2);
+
+    let s = scale(size);
+    let mut lines = vec![line(&index, 18.0 * s, "#ffd54f", defaults)];
+    lines.extend(page.iter().map(|p| line(p, 10.0 * s, "#ffffff", defaults)));
+    lines.extend(bound.iter().map(|b| line(b, 10.0 * s, "#bdbdbd", defaults)));
+    text::render_lines_in_area(&mut pm, &lines, 0.0, size as f32)?;
+    Ok(pm.data().to_vec())
+}
+
+fn line<'a>(
+    text: &'a str,
+    size: f32,
+    color: &'a str,
+    defaults: &'a ButtonDefaults,
+) -> TextLine<'a> {
+    TextLine {
+        text,
+        color,
+        size,
+        font: &defaults.font,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_bindings() {
+        let mut button = ButtonConfig::default();
+        assert_eq!(Binding::Button(&button).describe(), "no action");
+        button.on_press = Some(ActionConfig::Navigate {
+            page: "lights".into(),
+            picker: None,
+        });
+        assert_eq!(Binding::Button(&button).describe(), "navigate lights");
+        button.on_press = Some(ActionConfig::Navigate {
+            page: crate::ids::PageId::default(),
+            picker: Some("light.desk".into()),
+        });
+        assert_eq!(Binding::Button(&button).describe(), "picker light.desk");
+        button.on_press = Some(ActionConfig::DisplaySleep);
+        assert_eq!(Binding::Button(&button).describe(), "display_sleep");
+        assert_eq!(Binding::Empty.describe(), "empty");
+    }
+}
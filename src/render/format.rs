@@ -0,0 +1,280 @@
+use crate::config::schema::NumberFormat;
+use crate::render::locale::Locale;
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::borrow::Cow;
+
+/// Placeholder in label text replaced by the formatted `state_entity` value.
+pub const STATE_PLACEHOLDER: &str = "{state}";
+
+/// Clock placeholders: local time, day and month, short weekday name.
+pub const CLOCK_PLACEHOLDERS: [&str; 3] = ["{time}", "{date}", "{weekday}"];
+
+/// Shown in place of `{state}` until the entity state is known.
+const UNKNOWN_STATE: &str = "--";
+
+/// Values available to label placeholders.
+pub struct LabelVars<'a> {
+    /// Current `state_entity` state, if known.
+    pub state: Option<&'a str>,
+    pub format: &'a NumberFormat,
+    pub locale: &'a Locale,
+    /// Local time used for clock placeholders.
+    pub now: NaiveDateTime,
+}
+
+/// Whether a label shows the clock and so must be re-rendered as time passes.
+#[must_use]
+pub fn has_clock(text: &str) -> bool {
+    CLOCK_PLACEHOLDERS.iter().any(|p| text.contains(p))
+}
+
+/// Substitute `{state}` and the clock placeholders in a label, or render it
+/// if it is a template (see [`crate::template`]). A template that fails is
+/// shown as written.
+#[must_use]
+pub fn expand_label<'a>(text: &'a str, vars: &LabelVars<'_>) -> Cow<'a, str> {
+    if !text.contains('{') {
+        return Cow::Borrowed(text);
+    }
+    if crate::template::is_template(text) {
+        let ctx = crate::template::Context {
+            state: vars.state,
+            format: Some(vars.format),
+            vars: None,
+            locale: vars.locale,
+            now: vars.now,
+        };
+        return match crate::template::render(text, &ctx) {
+            Ok(rendered) => Cow::Owned(rendered),
+            Err(e) => {
+                tracing::warn!("label '{text}': {e}");
+                Cow::Borrowed(text)
+            }
+        };
+    }
+    let mut out = text.to_string();
+    if out.contains(STATE_PLACEHOLDER) {
+        let value = vars.state.map_or_else(
+            || UNKNOWN_STATE.to_string(),
+            |s| {
+                if vars.format.countdown {
+                    countdown(s, vars.locale, vars.now)
+                } else {
+                    format_value(s, vars.format, vars.locale)
+                }
+            },
+        );
+        out = out.replace(STATE_PLACEHOLDER, &value);
+    }
+    if has_clock(&out) {
+        out = expand_clock(&out, vars.locale, vars.now);
+    }
+    Cow::Owned(out)
+}
+
+/// Substitute the clock placeholders using `locale`.
+#[must_use]
+pub fn expand_clock(text: &str, locale: &Locale, now: NaiveDateTime) -> String {
+    text.replace("{time}", &locale.format_time(now))
+        .replace("{date}", &locale.format_date(now))
+        .replace("{weekday}", locale.weekday(now))
+}
+
+/// Format a raw state string using the locale's separators. Non-numeric
+/// states are returned unchanged.
+#[must_use]
+pub fn format_value(raw: &str, format: &NumberFormat, locale: &Locale) -> String {
+    let Ok(value) = raw.trim().parse::<f64>() else {
+        return raw.to_string();
+    };
+    if !value.is_finite() {
+        return raw.to_string();
+    }
+    let value = value * format.scale;
+
+    let mut out = if format.duration {
+        humanize_duration(value)
+    } else {
+        let fixed = match format.precision {
+            Some(p) => format!("{value:.*}", usize::from(p)),
+            None => value.to_string(),
+        };
+        let separator = if format.thousands {
+            locale.thousands
+        } else {
+            ""
+        };
+        localize_number(&fixed, separator, locale.decimal)
+    };
+
+    if let Some(ref unit) = format.unit {
+        out.push_str(unit);
+    }
+    out
+}
+
+/// The time from `now` until the point in time `raw` (RFC 3339, local
+/// `YYYY-MM-DDTHH:MM:SS`, or Unix seconds or milliseconds): "in 7 min",
+/// "now" within the minute or once passed, and the clock time an hour or
+/// more ahead. Other states are returned unchanged.
+#[must_use]
+pub fn countdown(raw: &str, locale: &Locale, now: NaiveDateTime) -> String {
+    let raw = raw.trim();
+    let at = match raw.parse::<i64>() {
+        // Seconds would be past the year 2286.
+        Ok(ms) if ms > 10_000_000_000 => DateTime::from_timestamp_millis(ms),
+        Ok(secs) => DateTime::from_timestamp(secs, 0),
+        Err(_) => DateTime::parse_from_rfc3339(raw).ok().map(|at| at.to_utc()),
+    }
+    .map(|at| at.with_timezone(&Local).naive_local())
+    .or_else(|| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S").ok());
+    let Some(at) = at else {
+        return raw.to_string();
+    };
+    match (at - now).num_seconds() {
+        ..60 => "now".to_string(),
+        secs @ ..3_600 => format!("in {} min", secs / 60),
+        _ => locale.format_time(at),
+    }
+}
+
+/// Insert `separator` between groups of three digits in the integer part of
+/// a plain formatted number and swap its decimal point for `decimal`.
+fn localize_number(number: &str, separator: &str, decimal: char) -> String {
+    let (sign, rest) = number
+        .strip_prefix('-')
+        .map_or(("", number), |rest| ("-", rest));
+    let (int_part, frac_part) = rest
+        .split_once('.')
+        .map_or((rest, None), |(i, f)| (i, Some(f)));
+
+    let mut grouped = String::with_capacity(number.len() + int_part.len() / 3 * separator.len());
+    grouped.push_str(sign);
+    for (i, ch) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(ch);
+    }
+    if let Some(frac) = frac_part {
+        grouped.push(decimal);
+        grouped.push_str(frac);
+    }
+    grouped
+}
+
+/// Render a number of seconds as its two most significant units, e.g.
+/// "1 h", "1 h 30 min", "45 s".
+fn humanize_duration(seconds: f64) -> String {
+    const UNITS: [(u64, &str); 4] = [(86_400, "d"), (3_600, "h"), (60, "min"), (1, "s")];
+
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let mut remaining = seconds.abs().round() as u64;
+    if remaining == 0 {
+        return "0 s".to_string();
+    }
+
+    let mut parts = Vec::with_capacity(2);
+    for (size, name) in UNITS {
+        if remaining >= size {
+            parts.push(format!("{} {name}", remaining / size));
+            remaining %= size;
+        } else if !parts.is_empty() {
+            // Keep the two units adjacent: "1 h 5 s" would read oddly.
+            break;
+        }
+        if parts.len() == 2 {
+            break;
+        }
+    }
+    format!("{sign}{}", parts.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::locale;
+    use chrono::NaiveDate;
+
+    fn fmt(toml_str: &str) -> NumberFormat {
+        toml::from_str(toml_str).unwrap()
+    }
+
+    fn us(raw: &str, format: &NumberFormat) -> String {
+        format_value(raw, format, locale::resolve("en-US"))
+    }
+
+    #[test]
+    fn numeric_formatting() {
+        assert_eq!(
+            us("0.2345", &fmt("precision = 0\nscale = 100\nunit = \"%\"")),
+            "23%"
+        );
+        assert_eq!(
+            us("1234567.891", &fmt("precision = 2\nthousands = true")),
+            "1,234,567.89"
+        );
+        assert_eq!(us("-1234", &fmt("thousands = true")), "-1,234");
+        assert_eq!(us("21.4", &fmt("unit = \"°C\"")), "21.4°C");
+        assert_eq!(us("unavailable", &fmt("unit = \"%\"")), "unavailable");
+    }
+
+    #[test]
+    fn localized_numbers() {
+        let de = locale::resolve("de-DE");
+        assert_eq!(
+            format_value("1234567.891", &fmt("precision = 2\nthousands = true"), de),
+            "1.234.567,89"
+        );
+        assert_eq!(format_value("21.4", &NumberFormat::default(), de), "21,4");
+    }
+
+    #[test]
+    fn durations() {
+        let duration = fmt("duration = true");
+        assert_eq!(us("3600", &duration), "1 h");
+        assert_eq!(us("5400", &duration), "1 h 30 min");
+        assert_eq!(us("3605", &duration), "1 h");
+        assert_eq!(us("45", &duration), "45 s");
+        assert_eq!(us("90061", &duration), "1 d 1 h");
+    }
+
+    #[test]
+    fn countdowns() {
+        let now = NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(7, 30, 0)
+            .unwrap();
+        let de = locale::resolve("de-DE");
+        assert_eq!(countdown("2026-10-16T07:37:30", de, now), "in 7 min");
+        assert_eq!(countdown("2026-10-16T07:30:40", de, now), "now");
+        assert_eq!(countdown("2026-10-16T07:20:00", de, now), "now");
+        assert_eq!(countdown("2026-10-16T09:05:00", de, now), "09:05");
+        assert_eq!(countdown("cancelled", de, now), "cancelled");
+        let unix = now.and_local_timezone(Local).unwrap().timestamp() + 300;
+        assert_eq!(countdown(&unix.to_string(), de, now), "in 5 min");
+    }
+
+    #[test]
+    fn label_placeholders() {
+        let format = fmt("precision = 1\nunit = \"°C\"");
+        let now = NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(7, 30, 0)
+            .unwrap();
+        let mut vars = LabelVars {
+            state: Some("21.43"),
+            format: &format,
+            locale: locale::resolve("de-DE"),
+            now,
+        };
+        assert_eq!(expand_label("{state}", &vars), "21,4°C");
+        assert_eq!(expand_label("{weekday} {time}", &vars), "Fr 07:30");
+        assert!(matches!(
+            expand_label("Office", &vars),
+            Cow::Borrowed("Office")
+        ));
+        vars.state = None;
+        assert_eq!(expand_label("T {state}", &vars), "T --");
+    }
+}
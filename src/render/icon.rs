@@ -1,29 +1,48 @@
 use crate::error::{DeckError, Result};
-use crate::render::canvas::BUTTON_SIZE;
+use crate::render::canvas::scale;
 use image::imageops::FilterType;
 use image::GenericImageView;
 use std::path::Path;
 use tiny_skia::Pixmap;
 
-/// Maximum icon size — leave room for a text label below.
-const ICON_MAX: u32 = 48;
+/// Maximum icon size on a 72 px key — leave room for a text label below.
+pub const ICON_MAX: u32 = 48;
 
-/// Top padding for icon placement.
-const ICON_TOP_PAD: i32 = 4;
+/// Top padding for icon placement on a 72 px key.
+const ICON_TOP_PAD: f32 = 4.0;
 
-/// Load a PNG icon, scale it to fit within the button, and return as a Pixmap.
+/// Load a PNG icon, scale it to fit within a `size` pixel button, and
+/// return as a Pixmap.
 ///
 /// # Errors
 /// Returns `DeckError::Icon` if the image cannot be opened or decoded,
 /// or `DeckError::Render` if the pixmap cannot be created.
-pub fn load_icon(path: &Path) -> Result<Pixmap> {
+pub fn load_icon(path: &Path, size: u32) -> Result<Pixmap> {
+    let max = icon_max(size);
+    load_image_fit(path, max, max)
+}
+
+/// Maximum icon size on a `size` pixel key.
+#[must_use]
+pub fn icon_max(size: u32) -> u32 {
+    (ICON_MAX as f32 * scale(size)).round() as u32
+}
+
+/// Load an image, scaled down (never up) to fit within `max_width` x `max_height`.
+///
+/// # Errors
+/// Returns `DeckError::Icon` if the image cannot be opened or decoded,
+/// or `DeckError::Render` if the pixmap cannot be created.
+pub fn load_image_fit(path: &Path, max_width: u32, max_height: u32) -> Result<Pixmap> {
     let img = image::open(path).map_err(|e| DeckError::Icon {
         path: path.to_path_buf(),
         source: e,
     })?;
 
     let (width, height) = img.dimensions();
-    let scale = (ICON_MAX as f32 / width.max(height) as f32).min(1.0);
+    let scale = (max_width as f32 / width as f32)
+        .min(max_height as f32 / height as f32)
+        .min(1.0);
     let new_w = (width as f32 * scale) as u32;
     let new_h = (height as f32 * scale) as u32;
 
@@ -50,18 +69,20 @@ pub fn load_icon(path: &Path) -> Result<Pixmap> {
     Ok(pixmap)
 }
 
-/// Calculate centered x position for an icon of given width.
+/// Calculate centered x position for an icon of given width on a `size`
+/// pixel key.
 #[must_use]
-pub const fn center_x(icon_width: u32) -> i32 {
-    ((BUTTON_SIZE - icon_width) / 2) as i32
+pub const fn center_x(icon_width: u32, size: u32) -> i32 {
+    (size.saturating_sub(icon_width) / 2) as i32
 }
 
-/// Calculate y position for icon (top area, leaving room for label).
+/// Calculate y position for icon on a `size` pixel key (top area, leaving
+/// room for label).
 #[must_use]
-pub const fn icon_y(has_label: bool) -> i32 {
+pub fn icon_y(has_label: bool, size: u32) -> i32 {
     if has_label {
-        ICON_TOP_PAD
+        (ICON_TOP_PAD * scale(size)).round() as i32
     } else {
-        ((BUTTON_SIZE - ICON_MAX) / 2) as i32
+        (size.saturating_sub(icon_max(size)) / 2) as i32
     }
 }
@@ -0,0 +1,691 @@
+use crate::config::schema::{ButtonConfig, ButtonDefaults, Label, Threshold};
+use crate::error::Result;
+use crate::render::canvas::{self, create_canvas};
+use crate::render::locale::Locale;
+use crate::render::{format, icon, text};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::path::Path;
+use tiny_skia::Pixmap;
+
+// Sizes below are for a 72 px key and scaled with it.
+
+/// Font size of the placeholder glyph drawn for a missing icon.
+const MISSING_ICON_GLYPH_SIZE: f32 = 32.0;
+
+/// Height of the level bar and its inset from the key's edges.
+const METER_HEIGHT: f32 = 5.0;
+const METER_INSET: f32 = 6.0;
+
+/// Unfilled part of the level bar.
+const METER_TRACK: &str = "#ffffff30";
+
+/// Badge diameter, inset and colors.
+const BADGE_SIZE: u32 = 24;
+const BADGE_INSET: f32 = 3.0;
+const BADGE_COLOR: &str = "#e53935";
+const BADGE_TEXT_COLOR: &str = "#ffffff";
+
+/// Height of the sparkline band above the bottom inset.
+const SPARKLINE_HEIGHT: f32 = 22.0;
+
+/// Where a layer sits in a button render. Layers are drawn in stage order,
+/// and in the order they were added within a stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Background,
+    Image,
+    Widget,
+    Text,
+    Badge,
+    Overlay,
+}
+
+/// One step of a button render, drawing onto the canvas.
+pub trait Layer: Send + Sync {
+    fn stage(&self) -> Stage;
+
+    /// # Errors
+    /// Returns `DeckError::Render` if drawing fails.
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()>;
+}
+
+/// A button's inputs and resolved style, shared by the layers rendering it.
+pub struct Frame<'a> {
+    pub button: &'a ButtonConfig,
+    pub defaults: &'a ButtonDefaults,
+    pub entity_states: &'a HashMap<String, String>,
+    pub locale: &'a Locale,
+    /// The time clock placeholders show.
+    pub now: NaiveDateTime,
+    /// Side of the key image in pixels.
+    pub size: u32,
+    pub background: &'a str,
+    pub text_color: &'a str,
+    /// Label font size, scaled to the key size.
+    pub font_size: f32,
+    pub font: &'a str,
+    /// The loaded icon, taken by the image layer.
+    pub icon: Option<Pixmap>,
+    /// An icon is configured but failed to load.
+    pub icon_missing: bool,
+    /// Something was drawn in the icon area; text goes below it.
+    pub icon_drawn: bool,
+}
+
+/// The background of the lowest threshold a numeric `state` is below.
+fn threshold_background<'a>(thresholds: &'a [Threshold], state: &str) -> Option<&'a String> {
+    let value: f64 = state.parse().ok()?;
+    thresholds
+        .iter()
+        .filter(|t| value < t.below)
+        .min_by(|a, b| a.below.total_cmp(&b.below))
+        .map(|t| &t.background)
+}
+
+impl<'a> Frame<'a> {
+    /// Resolve the style of `button` on a `size` pixel key. When its
+    /// `state_entity` is "on", the `on_background` and `on_text_color`
+    /// overrides are used, and a state listed in `state_backgrounds` or
+    /// matching `thresholds` picks the background. The icon is loaded up
+    /// front because a missing icon may swap the background.
+    #[must_use]
+    pub fn new(
+        button: &'a ButtonConfig,
+        defaults: &'a ButtonDefaults,
+        config_dir: &Path,
+        entity_states: &'a HashMap<String, String>,
+        locale: &'a Locale,
+        now: NaiveDateTime,
+        size: u32,
+    ) -> Self {
+        let state = button
+            .state_entity
+            .as_ref()
+            .and_then(|eid| entity_states.get(eid));
+        let entity_on = state.is_some_and(|s| s == "on");
+        let pick = |on: &'a Option<String>, off: &'a Option<String>, default: &'a str| {
+            entity_on
+                .then_some(on.as_deref())
+                .flatten()
+                .or(off.as_deref())
+                .unwrap_or(default)
+        };
+        let state_background = state.and_then(|s| {
+            button
+                .state_backgrounds
+                .get(s)
+                .or_else(|| threshold_background(&button.thresholds, s))
+        });
+        let background = match state_background {
+            Some(color) => color,
+            None => pick(
+                &button.on_background,
+                &button.background,
+                &defaults.background,
+            ),
+        };
+        let text_color = pick(
+            &button.on_text_color,
+            &button.text_color,
+            &defaults.text_color,
+        );
+
+        let icon = button.icon.as_ref().map(|icon_path| {
+            let full_path = crate::config::resolve_path(icon_path, config_dir);
+            if !full_path.exists() {
+                tracing::warn!("icon not found: {}", full_path.display());
+                return None;
+            }
+            icon::load_icon(&full_path, size)
+                .map_err(|e| tracing::warn!("failed to load icon {}: {e}", full_path.display()))
+                .ok()
+        });
+        let icon_missing = matches!(icon, Some(None));
+        let background = match defaults.missing_icon.background.as_deref() {
+            Some(missing) if icon_missing => missing,
+            _ => background,
+        };
+
+        Self {
+            button,
+            defaults,
+            entity_states,
+            locale,
+            now,
+            size,
+            background,
+            text_color,
+            font_size: button.font_size.unwrap_or(defaults.font_size) * canvas::scale(size),
+            font: button.font.as_deref().unwrap_or(&defaults.font),
+            icon: icon.flatten(),
+            icon_missing,
+            icon_drawn: false,
+        }
+    }
+
+    /// The state of the button's `state_entity`, if known.
+    #[must_use]
+    pub fn state(&self) -> Option<&'a str> {
+        self.button
+            .state_entity
+            .as_ref()
+            .and_then(|eid| self.entity_states.get(eid))
+            .map(String::as_str)
+    }
+
+    /// `px` on a 72 px key, in pixels of this key.
+    #[must_use]
+    pub fn scaled(&self, px: f32) -> f32 {
+        px * canvas::scale(self.size)
+    }
+}
+
+/// An ordered set of layers that renders a button.
+pub struct Pipeline {
+    layers: Vec<Box<dyn Layer>>,
+}
+
+impl Pipeline {
+    /// The built-in layers: background, icon, level bar, sparkline, label
+    /// and badge.
+    #[must_use]
+    pub fn standard() -> Self {
+        Self { layers: Vec::new() }
+            .with(Background)
+            .with(Image)
+            .with(Meter)
+            .with(Sparkline)
+            .with(Text)
+            .with(Badge)
+    }
+
+    /// Add a layer at the end of its stage.
+    #[must_use]
+    pub fn with(mut self, layer: impl Layer + 'static) -> Self {
+        let at = self
+            .layers
+            .partition_point(|existing| existing.stage() <= layer.stage());
+        self.layers.insert(at, Box::new(layer));
+        self
+    }
+
+    /// Run every layer over a blank button canvas.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Render` if canvas creation or a layer fails.
+    pub fn render(&self, frame: &mut Frame<'_>) -> Result<Vec<u8>> {
+        let mut canvas = create_canvas(frame.size, "transparent")?;
+        for layer in &self.layers {
+            layer.draw(frame, &mut canvas)?;
+        }
+        Ok(canvas.data().to_vec())
+    }
+}
+
+/// Fills the button with its background color.
+pub struct Background;
+
+impl Layer for Background {
+    fn stage(&self) -> Stage {
+        Stage::Background
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+        *canvas = create_canvas(frame.size, frame.background)?;
+        Ok(())
+    }
+}
+
+/// Draws the icon, or the placeholder glyph where a missing one would be.
+pub struct Image;
+
+impl Layer for Image {
+    fn stage(&self) -> Stage {
+        Stage::Image
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+        let has_label = frame.button.label.is_some();
+        let missing = &frame.defaults.missing_icon;
+        if let Some(icon_pm) = frame.icon.take() {
+            let x = icon::center_x(icon_pm.width(), frame.size);
+            let y = icon::icon_y(has_label, frame.size);
+            canvas::composite(canvas, &icon_pm, x, y);
+            frame.icon_drawn = true;
+        } else if frame.icon_missing && !missing.glyph.is_empty() {
+            let glyph_font = missing.font.as_deref().unwrap_or(frame.font);
+            text::render_text_in_area(
+                canvas,
+                &missing.glyph,
+                &missing.color,
+                frame.scaled(MISSING_ICON_GLYPH_SIZE),
+                glyph_font,
+                icon::icon_y(has_label, frame.size) as f32,
+                icon::icon_max(frame.size) as f32,
+            )?;
+            frame.icon_drawn = true;
+        }
+        Ok(())
+    }
+}
+
+/// Draws the button's `level` as a bar along the bottom, in the text color.
+pub struct Meter;
+
+impl Layer for Meter {
+    fn stage(&self) -> Stage {
+        Stage::Widget
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+        let Some(level) = frame.button.level else {
+            return Ok(());
+        };
+        let (inset, height) = (frame.scaled(METER_INSET), frame.scaled(METER_HEIGHT));
+        let width = frame.size as f32 - 2.0 * inset;
+        let y = frame.size as f32 - inset - height;
+        let filled = width * level.clamp(0.0, 1.0);
+        for (x, w, color) in [
+            (inset, width, METER_TRACK),
+            (inset, filled, frame.text_color),
+        ] {
+            let mut paint = tiny_skia::Paint::default();
+            paint.set_color(canvas::parse_hex_color(color)?);
+            if let Some(rect) = tiny_skia::Rect::from_xywh(x, y, w, height) {
+                canvas.fill_rect(rect, &paint, tiny_skia::Transform::identity(), None);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draws the button's `history` as a line along the bottom, in the text
+/// color, scaled from zero (or the lowest value, if negative) to the highest.
+pub struct Sparkline;
+
+impl Layer for Sparkline {
+    fn stage(&self) -> Stage {
+        Stage::Widget
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+        let values = &frame.button.history;
+        if values.len() < 2 {
+            return Ok(());
+        }
+        let low = values.iter().copied().fold(0.0, f32::min);
+        let high = values.iter().copied().fold(f32::MIN, f32::max);
+        let range = (high - low).max(f32::EPSILON);
+        let inset = frame.scaled(METER_INSET);
+        let width = frame.size as f32 - 2.0 * inset;
+        let bottom = frame.size as f32 - inset;
+        let height = frame.scaled(SPARKLINE_HEIGHT);
+        let step = width / (crate::history::POINTS - 1) as f32;
+        // Newest value at the right edge, so the line grows in from the right.
+        let start = inset + width - step * (values.len() - 1) as f32;
+        let mut path = tiny_skia::PathBuilder::new();
+        for (i, value) in values.iter().enumerate() {
+            let x = start + step * i as f32;
+            let y = bottom - (value - low) / range * height;
+            if i == 0 {
+                path.move_to(x, y);
+            } else {
+                path.line_to(x, y);
+            }
+        }
+        let Some(path) = path.finish() else {
+            return Ok(());
+        };
+        let mut paint = tiny_skia::Paint::default();
+        paint.set_color(canvas::parse_hex_color(frame.text_color)?);
+        paint.anti_alias = true;
+        let stroke = tiny_skia::Stroke {
+            width: frame.scaled(1.5),
+            ..tiny_skia::Stroke::default()
+        };
+        canvas.stroke_path(
+            &path,
+            &paint,
+            &stroke,
+            tiny_skia::Transform::identity(),
+            None,
+        );
+        Ok(())
+    }
+}
+
+/// Draws the count of the button's `badge_entity` in a circle at the
+/// top-right corner, unless it is 0 or not a number. Counts over 99 show
+/// as `99+`.
+pub struct Badge;
+
+impl Layer for Badge {
+    fn stage(&self) -> Stage {
+        Stage::Badge
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+        let count = frame
+            .button
+            .badge_entity
+            .as_ref()
+            .and_then(|entity| frame.entity_states.get(entity))
+            .and_then(|state| state.parse::<f64>().ok())
+            .filter(|&count| count >= 1.0);
+        let Some(count) = count else {
+            return Ok(());
+        };
+        let text = if count > 99.0 {
+            "99+".to_string()
+        } else {
+            format!("{count:.0}")
+        };
+        let badge_size = frame.scaled(BADGE_SIZE as f32).round() as u32;
+        let mut badge = Pixmap::new(badge_size, badge_size)
+            .ok_or_else(|| crate::error::DeckError::Render("badge canvas".into()))?;
+        let radius = badge_size as f32 / 2.0;
+        if let Some(circle) = tiny_skia::PathBuilder::from_circle(radius, radius, radius) {
+            let mut paint = tiny_skia::Paint::default();
+            paint.set_color(canvas::parse_hex_color(BADGE_COLOR)?);
+            paint.anti_alias = true;
+            badge.fill_path(
+                &circle,
+                &paint,
+                tiny_skia::FillRule::Winding,
+                tiny_skia::Transform::identity(),
+                None,
+            );
+        }
+        let size = frame.scaled(if text.len() > 2 { 10.0 } else { 14.0 });
+        text::render_text_in_area(
+            &mut badge,
+            &text,
+            BADGE_TEXT_COLOR,
+            size,
+            frame.font,
+            0.0,
+            badge_size as f32,
+        )?;
+        let inset = frame.scaled(BADGE_INSET) as i32;
+        let x = frame.size.saturating_sub(badge_size) as i32 - inset;
+        canvas.draw_pixmap(
+            x,
+            inset,
+            badge.as_ref(),
+            &tiny_skia::PixmapPaint::default(),
+            tiny_skia::Transform::identity(),
+            None,
+        );
+        Ok(())
+    }
+}
+
+/// Draws the label with placeholders expanded: centered, or below the icon.
+pub struct Text;
+
+impl Layer for Text {
+    fn stage(&self) -> Stage {
+        Stage::Text
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+        let vars = format::LabelVars {
+            state: frame.state(),
+            format: &frame.button.format,
+            locale: frame.locale,
+            now: frame.now,
+        };
+        match frame.button.label {
+            Some(Label::Text(ref label)) => {
+                let label = &*format::expand_label(label, &vars);
+                if frame.icon_drawn {
+                    // Icon present: render text in the bottom portion.
+                    let label_font_size = frame.font_size.min(frame.scaled(12.0));
+                    text::render_text_at_bottom(
+                        canvas,
+                        label,
+                        frame.text_color,
+                        label_font_size,
+                        frame.font,
+                    )?;
+                } else {
+                    // No icon: center text.
+                    text::render_text(
+                        canvas,
+                        label,
+                        frame.text_color,
+                        frame.font_size,
+                        frame.font,
+                    )?;
+                }
+            }
+            Some(Label::Lines(ref lines)) => {
+                let texts: Vec<_> = lines
+                    .iter()
+                    .map(|line| format::expand_label(&line.text, &vars))
+                    .collect();
+                let lines: Vec<text::TextLine<'_>> = lines
+                    .iter()
+                    .zip(&texts)
+                    .flat_map(|(line, expanded)| {
+                        let style = text::TextLine {
+                            text: "",
+                            color: line.color.as_deref().unwrap_or(frame.text_color),
+                            size: line.size.map_or(frame.font_size, |s| frame.scaled(s)),
+                            font: line.font.as_deref().unwrap_or(frame.font),
+                        };
+                        expanded
+                            .split('\n')
+                            .map(move |text| text::TextLine { text, ..style })
+                    })
+                    .collect();
+                let size = frame.size as f32;
+                if frame.icon_drawn {
+                    // Icon present: stack the lines in the band below it.
+                    let top =
+                        (icon::icon_y(true, frame.size) + icon::icon_max(frame.size) as i32) as f32;
+                    text::render_lines_in_area(canvas, &lines, top, size - top)?;
+                } else {
+                    text::render_lines_in_area(canvas, &lines, 0.0, size)?;
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+    use std::sync::LazyLock;
+
+    static DEFAULTS: LazyLock<ButtonDefaults> = LazyLock::new(ButtonDefaults::default);
+
+    fn noon() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, 1)
+            .and_then(|day| day.and_hms_opt(12, 0, 0))
+            .unwrap()
+    }
+
+    /// A frame of `button` in the default style and locale, at noon, on a
+    /// `size` pixel key.
+    fn frame_for<'a>(
+        button: &'a ButtonConfig,
+        states: &'a HashMap<String, String>,
+        size: u32,
+    ) -> Frame<'a> {
+        let locale = crate::render::locale::resolve(crate::render::locale::DEFAULT_LOCALE);
+        Frame::new(
+            button,
+            &DEFAULTS,
+            Path::new("."),
+            states,
+            locale,
+            noon(),
+            size,
+        )
+    }
+
+    struct Marker(Stage, u8);
+
+    impl Layer for Marker {
+        fn stage(&self) -> Stage {
+            self.0
+        }
+
+        fn draw(&self, _frame: &mut Frame<'_>, canvas: &mut Pixmap) -> Result<()> {
+            canvas.data_mut()[0] = self.1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn layers_run_in_stage_order() {
+        let pipeline = Pipeline::standard()
+            .with(Marker(Stage::Overlay, 1))
+            .with(Marker(Stage::Badge, 2))
+            .with(Marker(Stage::Overlay, 3));
+        let stages: Vec<Stage> = pipeline.layers.iter().map(|l| l.stage()).collect();
+        assert_eq!(
+            stages,
+            [
+                Stage::Background,
+                Stage::Image,
+                Stage::Widget,
+                Stage::Widget,
+                Stage::Text,
+                Stage::Badge,
+                Stage::Badge,
+                Stage::Overlay,
+                Stage::Overlay
+            ]
+        );
+
+        let button = ButtonConfig::default();
+        let states = HashMap::new();
+        let mut frame = frame_for(&button, &states, canvas::BUTTON_SIZE);
+        let rgba = pipeline.render(&mut frame).unwrap();
+        // The last overlay added draws last.
+        assert_eq!(rgba[0], 3);
+    }
+
+    #[test]
+    fn on_state_swaps_colors() {
+        let button = ButtonConfig {
+            state_entity: Some("switch.fan".into()),
+            background: Some("#000000".into()),
+            on_background: Some("#ffffff".into()),
+            ..ButtonConfig::default()
+        };
+        let mut states = HashMap::new();
+        let frame = frame_for(&button, &states, canvas::BUTTON_SIZE);
+        assert_eq!(frame.background, "#000000");
+        assert_eq!(frame.text_color, DEFAULTS.text_color);
+        states.insert("switch.fan".into(), "on".into());
+        let frame = frame_for(&button, &states, canvas::BUTTON_SIZE);
+        assert_eq!(frame.background, "#ffffff");
+        assert_eq!(frame.state(), Some("on"));
+    }
+
+    #[test]
+    fn state_backgrounds_win() {
+        let button = ButtonConfig {
+            state_entity: Some("tailscale:exit_node".into()),
+            on_background: Some("#ffffff".into()),
+            state_backgrounds: HashMap::from([("off".into(), "#c62828".into())]),
+            ..ButtonConfig::default()
+        };
+        let states = HashMap::from([("tailscale:exit_node".into(), "off".into())]);
+        let frame = frame_for(&button, &states, canvas::BUTTON_SIZE);
+        assert_eq!(frame.background, "#c62828");
+    }
+
+    #[test]
+    fn badge_shows_nonzero_counts() {
+        let button = ButtonConfig {
+            badge_entity: Some("imap:me@mail.lan".into()),
+            background: Some("#000000".into()),
+            ..ButtonConfig::default()
+        };
+        // Left edge of the badge circle, clear of its digits.
+        let x = canvas::BUTTON_SIZE - BADGE_SIZE - BADGE_INSET as u32 + 2;
+        let pixel =
+            (((BADGE_INSET as u32 + BADGE_SIZE / 2) * canvas::BUTTON_SIZE + x) * 4) as usize;
+        for (count, red) in [("3", true), ("0", false), ("unavailable", false)] {
+            let states = HashMap::from([("imap:me@mail.lan".into(), count.into())]);
+            let mut frame = frame_for(&button, &states, canvas::BUTTON_SIZE);
+            let rgba = Pipeline::standard().render(&mut frame).unwrap();
+            assert_eq!(rgba[pixel] > 200, red, "{count}");
+        }
+    }
+
+    #[test]
+    fn renders_at_the_key_size() {
+        let button = ButtonConfig {
+            badge_entity: Some("imap:me@mail.lan".into()),
+            background: Some("#000000".into()),
+            ..ButtonConfig::default()
+        };
+        let states = HashMap::from([("imap:me@mail.lan".into(), "3".into())]);
+        let mut frame = frame_for(&button, &states, 96);
+        let rgba = Pipeline::standard().render(&mut frame).unwrap();
+        assert_eq!(rgba.len(), 96 * 96 * 4);
+        // The badge grows with the key: 32 px wide, 4 px from the edges.
+        let pixel = ((20 * 96 + 96 - 32 - 4 + 2) * 4) as usize;
+        assert!(rgba[pixel] > 200);
+    }
+
+    #[test]
+    fn scales_with_the_key() {
+        let button = ButtonConfig {
+            label: Some(Label::Text("Lamp".into())),
+            ..ButtonConfig::default()
+        };
+        let states = HashMap::new();
+        let small = frame_for(&button, &states, canvas::BUTTON_SIZE);
+        let mut large = frame_for(&button, &states, 144);
+        assert!((large.font_size - small.font_size * 2.0).abs() < f32::EPSILON);
+        let rgba = Pipeline::standard().render(&mut large).unwrap();
+        assert_eq!(rgba.len(), 144 * 144 * 4);
+    }
+
+    #[test]
+    fn labels_show_the_given_time() {
+        let button = ButtonConfig {
+            label: Some(Label::Text("{time}".into())),
+            ..ButtonConfig::default()
+        };
+        let states = HashMap::new();
+        let at = |hour| {
+            let mut frame = frame_for(&button, &states, canvas::BUTTON_SIZE);
+            frame.now = noon().with_hour(hour).unwrap();
+            Pipeline::standard().render(&mut frame).unwrap()
+        };
+        assert_eq!(at(9), at(9));
+        assert_ne!(at(9), at(17));
+    }
+
+    #[test]
+    fn lowest_threshold_wins() {
+        let thresholds = [
+            Threshold {
+                below: 50.0,
+                background: "#f9a825".into(),
+            },
+            Threshold {
+                below: 20.0,
+                background: "#c62828".into(),
+            },
+        ];
+        assert_eq!(threshold_background(&thresholds, "15").unwrap(), "#c62828");
+        assert_eq!(
+            threshold_background(&thresholds, "35.5").unwrap(),
+            "#f9a825"
+        );
+        assert_eq!(threshold_background(&thresholds, "80"), None);
+        assert_eq!(threshold_background(&thresholds, "unavailable"), None);
+    }
+}
@@ -0,0 +1,161 @@
+use chrono::{Datelike, NaiveDateTime};
+
+/// Conventions for rendering numbers, dates and times.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Locale {
+    /// BCP 47 tag, e.g. "de-DE".
+    pub tag: &'static str,
+    /// Decimal separator.
+    pub decimal: char,
+    /// Thousands separator.
+    pub thousands: &'static str,
+    /// chrono format for `{time}`.
+    pub time: &'static str,
+    /// chrono format for `{date}` (day and month).
+    pub date: &'static str,
+    /// Short weekday names, Monday first.
+    pub weekdays: [&'static str; 7],
+}
+
+/// Locale used when none is configured.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+static LOCALES: &[Locale] = &[
+    Locale {
+        tag: "en-US",
+        decimal: '.',
+        thousands: ",",
+        time: "%-I:%M %p",
+        date: "%-m/%-d",
+        weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    },
+    Locale {
+        tag: "en-GB",
+        decimal: '.',
+        thousands: ",",
+        time: "%H:%M",
+        date: "%d/%m",
+        weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    },
+    Locale {
+        tag: "de-DE",
+        decimal: ',',
+        thousands: ".",
+        time: "%H:%M",
+        date: "%-d.%-m.",
+        weekdays: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    },
+    Locale {
+        tag: "fr-FR",
+        decimal: ',',
+        thousands: " ",
+        time: "%H:%M",
+        date: "%d/%m",
+        weekdays: ["lun", "mar", "mer", "jeu", "ven", "sam", "dim"],
+    },
+    Locale {
+        tag: "es-ES",
+        decimal: ',',
+        thousands: ".",
+        time: "%-H:%M",
+        date: "%-d/%-m",
+        weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    },
+    Locale {
+        tag: "it-IT",
+        decimal: ',',
+        thousands: ".",
+        time: "%H:%M",
+        date: "%d/%m",
+        weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    },
+    Locale {
+        tag: "nl-NL",
+        decimal: ',',
+        thousands: ".",
+        time: "%H:%M",
+        date: "%-d-%-m",
+        weekdays: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+    },
+    Locale {
+        tag: "sv-SE",
+        decimal: ',',
+        thousands: " ",
+        time: "%H:%M",
+        date: "%-d/%-m",
+        weekdays: ["mån", "tis", "ons", "tors", "fre", "lör", "sön"],
+    },
+];
+
+/// Find a supported locale by tag. Matching ignores case and accepts `_` for
+/// `-`; a bare language ("de") picks the first locale for that language.
+#[must_use]
+pub fn lookup(tag: &str) -> Option<&'static Locale> {
+    let tag = tag.replace('_', "-");
+    LOCALES
+        .iter()
+        .find(|l| l.tag.eq_ignore_ascii_case(&tag))
+        .or_else(|| {
+            LOCALES.iter().find(|l| {
+                l.tag
+                    .split('-')
+                    .next()
+                    .is_some_and(|lang| lang.eq_ignore_ascii_case(&tag))
+            })
+        })
+}
+
+/// Like [`lookup`], falling back to [`DEFAULT_LOCALE`].
+#[must_use]
+pub fn resolve(tag: &str) -> &'static Locale {
+    lookup(tag).unwrap_or(&LOCALES[0])
+}
+
+impl Locale {
+    /// Time of day, e.g. "21:05" or "9:05 PM".
+    #[must_use]
+    pub fn format_time(&self, now: NaiveDateTime) -> String {
+        now.format(self.time).to_string()
+    }
+
+    /// Day and month, e.g. "16.10." or "10/16".
+    #[must_use]
+    pub fn format_date(&self, now: NaiveDateTime) -> String {
+        now.format(self.date).to_string()
+    }
+
+    /// Short weekday name.
+    #[must_use]
+    pub fn weekday(&self, now: NaiveDateTime) -> &'static str {
+        self.weekdays[now.weekday().num_days_from_monday() as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn lookup_tags() {
+        assert_eq!(lookup("de_de").map(|l| l.tag), Some("de-DE"));
+        assert_eq!(lookup("en").map(|l| l.tag), Some("en-US"));
+        assert!(lookup("xx-YY").is_none());
+        assert_eq!(resolve("xx-YY").tag, DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn dates_and_times() {
+        let now = NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(21, 5, 0)
+            .unwrap();
+        let us = resolve("en-US");
+        let de = resolve("de-DE");
+        assert_eq!(us.format_time(now), "9:05 PM");
+        assert_eq!(de.format_time(now), "21:05");
+        assert_eq!(us.format_date(now), "10/16");
+        assert_eq!(de.format_date(now), "16.10.");
+        assert_eq!(de.weekday(now), "Fr");
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Rendered RGBA images for every key of a page, as `(key, rgba)` pairs.
+pub type PageImages = Arc<Vec<(u8, Vec<u8>)>>;
+
+/// Bounded cache of fully rendered pages, evicting the least recently used.
+///
+/// Filled with the current page after each render and speculatively with the
+/// pages reachable from it, so a page switch can show something immediately
+/// while the fresh render (with HA states) is still in flight.
+pub struct PageCache {
+    pages: HashMap<String, PageImages>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl PageCache {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pages: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Look up a cached page, marking it as recently used.
+    pub fn get(&mut self, page_id: &str) -> Option<PageImages> {
+        let images = self.pages.get(page_id).cloned()?;
+        self.touch(page_id);
+        Some(images)
+    }
+
+    /// Whether a page is cached.
+    #[must_use]
+    pub fn contains(&self, page_id: &str) -> bool {
+        self.pages.contains_key(page_id)
+    }
+
+    /// Store a rendered page, evicting the least recently used page when
+    /// full.
+    pub fn insert(&mut self, page_id: &str, images: PageImages) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.pages.insert(page_id.to_string(), images).is_some() {
+            self.touch(page_id);
+        } else {
+            self.order.push_back(page_id.to_string());
+        }
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.pages.remove(&old);
+            }
+        }
+    }
+
+    /// Move a cached page to the back of the eviction order.
+    fn touch(&mut self, page_id: &str) {
+        if let Some(index) = self.order.iter().position(|id| id == page_id) {
+            if let Some(id) = self.order.remove(index) {
+                self.order.push_back(id);
+            }
+        }
+    }
+
+    /// Drop one cached page (e.g. when its content changed).
+    pub fn remove(&mut self, page_id: &str) {
+        if self.pages.remove(page_id).is_some() {
+            self.order.retain(|id| id != page_id);
+        }
+    }
+
+    /// Drop all cached pages (e.g. after a config reload).
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = PageCache::new(2);
+        cache.insert("a", Arc::new(vec![]));
+        cache.insert("b", Arc::new(vec![]));
+        cache.insert("a", Arc::new(vec![(0, vec![1])]));
+        cache.insert("c", Arc::new(vec![]));
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+
+        // Looking a page up counts as using it.
+        assert!(cache.get("a").is_some());
+        cache.insert("d", Arc::new(vec![]));
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("c"));
+    }
+
+    #[test]
+    fn zero_capacity_disables() {
+        let mut cache = PageCache::new(0);
+        cache.insert("a", Arc::new(vec![]));
+        assert!(cache.get("a").is_none());
+    }
+}
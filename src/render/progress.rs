@@ -0,0 +1,84 @@
+use crate::config::schema::TrackConfig;
+use crate::error::{DeckError, Result};
+use crate::jobs::{JobState, SPINNER_FRAMES};
+use crate::render::canvas::{parse_hex_color, scale};
+use std::f32::consts::TAU;
+use tiny_skia::{Paint, PathBuilder, PixmapMut, Rect, Transform};
+
+/// Distance of the spinner ring's center from the top-right corner, and its
+/// radius, on a 72 px key.
+const SPINNER_CENTER: (f32, f32) = (13.0, 13.0);
+const SPINNER_RADIUS: f32 = 8.0;
+const SPINNER_DOT: f32 = 2.0;
+
+/// Width of the result frame around a 72 px key.
+const RESULT_BORDER: f32 = 4.0;
+
+/// Draw the job state over a rendered `size`x`size` key: a spinner while
+/// running (in another color while cancelling), a colored frame for the
+/// result.
+///
+/// # Errors
+/// Returns `DeckError::Render` if the image has the wrong size or a color is invalid.
+pub fn overlay(rgba: &mut [u8], size: u32, state: JobState, style: &TrackConfig) -> Result<()> {
+    let mut pm = PixmapMut::from_bytes(rgba, size, size)
+        .ok_or_else(|| DeckError::Render("key image has the wrong size".into()))?;
+
+    match state {
+        JobState::Running { frame } => draw_spinner(&mut pm, frame, &style.running_color),
+        JobState::Cancelling { frame } => draw_spinner(&mut pm, frame, &style.cancelling_color),
+        JobState::Succeeded => draw_border(&mut pm, &style.success_color),
+        JobState::Failed => draw_border(&mut pm, &style.failure_color),
+    }
+}
+
+/// A ring of dots with the one at `frame` highlighted.
+fn draw_spinner(pm: &mut PixmapMut<'_>, frame: u8, color: &str) -> Result<()> {
+    let mut active = Paint::default();
+    active.set_color(parse_hex_color(color)?);
+    active.anti_alias = true;
+    let mut dim = active.clone();
+    dim.set_color(parse_hex_color("#ffffff40")?);
+
+    let s = scale(pm.width());
+    let center = (
+        pm.width() as f32 - SPINNER_CENTER.0 * s,
+        SPINNER_CENTER.1 * s,
+    );
+    for i in 0..SPINNER_FRAMES {
+        let angle = f32::from(i) / f32::from(SPINNER_FRAMES) * TAU;
+        let x = center.0 + SPINNER_RADIUS * s * angle.sin();
+        let y = center.1 - SPINNER_RADIUS * s * angle.cos();
+        let Some(dot) = PathBuilder::from_circle(x, y, SPINNER_DOT * s) else {
+            continue;
+        };
+        let paint = if i == frame { &active } else { &dim };
+        pm.fill_path(
+            &dot,
+            paint,
+            tiny_skia::FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+    Ok(())
+}
+
+fn draw_border(pm: &mut PixmapMut<'_>, color: &str) -> Result<()> {
+    let mut paint = Paint::default();
+    paint.set_color(parse_hex_color(color)?);
+    let size = pm.width() as f32;
+    let border = RESULT_BORDER * scale(pm.width());
+    let edges = [
+        (0.0, 0.0, size, border),
+        (0.0, size - border, size, border),
+        (0.0, 0.0, border, size),
+        (size - border, 0.0, border, size),
+    ];
+    for (x, y, w, h) in edges {
+        if let Some(rect) = Rect::from_xywh(x, y, w, h) {
+            pm.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+    Ok(())
+}
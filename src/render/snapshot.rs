@@ -0,0 +1,199 @@
+use crate::config::schema::{ButtonConfig, ButtonDefaults};
+use crate::error::{DeckError, Result};
+use crate::render::canvas::BUTTON_SIZE;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Where the reference images live, relative to the repository root.
+pub const GOLDEN_DIR: &str = "tests/snapshots";
+
+/// Largest per-channel difference still counted as a match.
+const TOLERANCE: u8 = 8;
+
+/// Share of pixels allowed past the tolerance, for anti-aliasing drift
+/// between font rasterizer versions.
+const MAX_MISMATCH: f64 = 0.01;
+
+/// Known button configs, as `(name, button TOML)`. The entity `sensor.temp`
+/// reads "21.43" and `switch.fan` is "on".
+const CASES: &[(&str, &str)] = &[
+    ("text", r#"label = "Deploy""#),
+    (
+        "large_text",
+        r##"label = "ON"
+background = "#000000"
+text_color = "#FA6831"
+font_size = 56
+font = "jb-extrabold""##,
+    ),
+    (
+        "multiline",
+        r##"label = "Living\nRoom"
+background = "#f39c12""##,
+    ),
+    (
+        "glyph",
+        r##"label = "\uF06C\nPlants"
+text_color = "#4CAF50"
+font_size = 22
+font = "jb-bold""##,
+    ),
+    (
+        "state_on",
+        r##"label = "Fan"
+state_entity = "switch.fan"
+on_background = "#4CAF50"
+on_text_color = "#000000""##,
+    ),
+    (
+        "state_value",
+        r#"label = "{state}"
+state_entity = "sensor.temp"
+format = { precision = 1, unit = "°C" }"#,
+    ),
+    (
+        "lines",
+        r##"label = [{ text = "Office", size = 10 }, { text = "21.4°C", size = 16, color = "#f39c12" }]"##,
+    ),
+    (
+        "missing_icon",
+        r#"label = "Cam"
+icon = "does-not-exist.png""#,
+    ),
+    (
+        "translucent",
+        r##"label = "Dim"
+background = "#ffffff80""##,
+    ),
+];
+
+/// A snapshot that no longer matches its reference image.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub name: &'static str,
+    pub reason: String,
+}
+
+/// Render every case to RGBA with the default style and en-US locale, at
+/// noon on 2024-01-01.
+///
+/// # Errors
+/// Returns `DeckError::Config` if a case does not parse, or
+/// `DeckError::Render` if rendering fails.
+pub fn render_cases() -> Result<Vec<(&'static str, Vec<u8>)>> {
+    let defaults = ButtonDefaults::default();
+    let states = HashMap::from([
+        ("sensor.temp".to_string(), "21.43".to_string()),
+        ("switch.fan".to_string(), "on".to_string()),
+    ]);
+    let locale = crate::render::locale::resolve("en-US");
+    let now = chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+        .and_then(|day| day.and_hms_opt(12, 0, 0))
+        .unwrap_or_default();
+    CASES
+        .iter()
+        .map(|(name, toml)| {
+            let button: ButtonConfig = toml::from_str(&format!("key = 0\n{toml}"))
+                .map_err(|e| DeckError::Config(format!("snapshot case {name}: {e}")))?;
+            let rgba = crate::render::render_button(
+                &button,
+                &defaults,
+                Path::new("."),
+                &states,
+                locale,
+                now,
+                BUTTON_SIZE,
+            )?;
+            Ok((*name, rgba))
+        })
+        .collect()
+}
+
+/// Render every case and write it to `dir` as `<name>.png`, replacing the
+/// reference images.
+///
+/// # Errors
+/// Returns `DeckError::Io` if `dir` cannot be created, or
+/// `DeckError::Render` if rendering or writing an image fails.
+pub fn write_goldens(dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    render_cases()?
+        .into_iter()
+        .map(|(name, rgba)| {
+            let path = dir.join(format!("{name}.png"));
+            image::RgbaImage::from_raw(BUTTON_SIZE, BUTTON_SIZE, rgba)
+                .ok_or_else(|| DeckError::Render(format!("snapshot {name}: bad image size")))?
+                .save(&path)
+                .map_err(|e| DeckError::Render(format!("{}: {e}", path.display())))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Render every case and compare it with its reference image in `dir`.
+///
+/// # Errors
+/// Returns `DeckError::Config` or `DeckError::Render` if a case cannot be
+/// rendered.
+pub fn check_goldens(dir: &Path) -> Result<Vec<Mismatch>> {
+    let mut mismatches = Vec::new();
+    for (name, rgba) in render_cases()? {
+        let path = dir.join(format!("{name}.png"));
+        let reason = match image::open(&path) {
+            Ok(golden) => compare(&rgba, golden.to_rgba8().as_raw()),
+            Err(e) => Some(format!("cannot read {}: {e}", path.display())),
+        };
+        if let Some(reason) = reason {
+            mismatches.push(Mismatch { name, reason });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Why `actual` does not match `expected`, if it doesn't.
+fn compare(actual: &[u8], expected: &[u8]) -> Option<String> {
+    if actual.len() != expected.len() {
+        return Some(format!(
+            "size differs: {} bytes, reference has {}",
+            actual.len(),
+            expected.len()
+        ));
+    }
+    let pixels = actual.len() / 4;
+    let differing = actual
+        .chunks_exact(4)
+        .zip(expected.chunks_exact(4))
+        .filter(|(a, e)| a.iter().zip(*e).any(|(a, e)| a.abs_diff(*e) > TOLERANCE))
+        .count();
+    (differing as f64 > pixels as f64 * MAX_MISMATCH)
+        .then(|| format!("{differing} of {pixels} pixels differ"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_allows_small_drift() {
+        let expected = vec![100; 40];
+        let mut actual = expected.clone();
+        actual[0] = 108;
+        assert_eq!(compare(&actual, &expected), None);
+        actual[0] = 109;
+        assert_eq!(
+            compare(&actual, &expected).unwrap(),
+            "1 of 10 pixels differ"
+        );
+        assert!(compare(&actual[4..], &expected).is_some());
+    }
+
+    #[test]
+    fn renders_match_goldens() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_DIR);
+        let mismatches = check_goldens(&dir).unwrap();
+        assert!(
+            mismatches.is_empty(),
+            "snapshots changed (run `deckd render-test` to update if intended): {mismatches:?}"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::config::schema::{SplashConfig, ToastConfig};
+use crate::error::Result;
+use crate::render::canvas::{composite, create_canvas_sized, scale};
+use crate::render::{icon, text};
+use std::path::Path;
+use tiny_skia::Pixmap;
+
+/// Key grid of the connected deck.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl Grid {
+    /// Number of keys on the grid.
+    #[must_use]
+    pub const fn keys(self) -> u8 {
+        (self.columns * self.rows) as u8
+    }
+}
+
+/// Render the connect splash on keys of `size` pixels: the logo if it loads,
+/// otherwise the text.
+///
+/// # Errors
+/// Returns `DeckError::Render` if canvas creation or text rendering fails.
+pub fn render_splash(
+    splash: &SplashConfig,
+    font: &str,
+    config_dir: &Path,
+    grid: Grid,
+    size: u32,
+) -> Result<Vec<(u8, Vec<u8>)>> {
+    let (width, height) = (grid.columns * size, grid.rows * size);
+    let mut pm = create_canvas_sized(width, height, &splash.background)?;
+
+    let logo = splash.image.as_ref().and_then(|image| {
+        let path = crate::config::resolve_path(image, config_dir);
+        icon::load_image_fit(&path, width, height)
+            .map_err(|e| tracing::warn!("failed to load splash image {}: {e}", path.display()))
+            .ok()
+    });
+    match (logo, splash.text.as_deref()) {
+        (Some(logo), _) => {
+            let x = (width - logo.width()) / 2;
+            let y = (height - logo.height()) / 2;
+            composite(&mut pm, &logo, x as i32, y as i32);
+        }
+        (None, Some(label)) => draw_text(&mut pm, splash, font, label, size)?,
+        (None, None) => {}
+    }
+
+    Ok(slice_keys(&pm, grid, size))
+}
+
+/// Render the shutdown frame on keys of `size` pixels: the goodbye text, or
+/// all keys blank.
+///
+/// # Errors
+/// Returns `DeckError::Render` if canvas creation or text rendering fails.
+pub fn render_goodbye(
+    splash: Option<&SplashConfig>,
+    font: &str,
+    grid: Grid,
+    size: u32,
+) -> Result<Vec<(u8, Vec<u8>)>> {
+    let (width, height) = (grid.columns * size, grid.rows * size);
+    let background = splash.map_or("#000000", |s| s.background.as_str());
+    let mut pm = create_canvas_sized(width, height, background)?;
+    if let Some((splash, goodbye)) = splash.and_then(|s| Some((s, s.goodbye.as_deref()?))) {
+        draw_text(&mut pm, splash, font, goodbye, size)?;
+    }
+    Ok(slice_keys(&pm, grid, size))
+}
+
+/// Render a notification banner across the top row of keys of `size`
+/// pixels.
+///
+/// # Errors
+/// Returns `DeckError::Render` if canvas creation or text rendering fails.
+pub fn render_banner(
+    toast: &ToastConfig,
+    font: &str,
+    message: &str,
+    columns: u32,
+    size: u32,
+) -> Result<Vec<(u8, Vec<u8>)>> {
+    let mut pm = create_canvas_sized(columns * size, size, &toast.background)?;
+    let height = pm.height() as f32;
+    text::render_text_in_area(
+        &mut pm,
+        message,
+        &toast.text_color,
+        toast.font_size * scale(size),
+        font,
+        0.0,
+        height,
+    )?;
+    Ok(slice_keys(&pm, Grid { columns, rows: 1 }, size))
+}
+
+fn draw_text(
+    pm: &mut Pixmap,
+    splash: &SplashConfig,
+    font: &str,
+    label: &str,
+    size: u32,
+) -> Result<()> {
+    let height = pm.height() as f32;
+    text::render_text_in_area(
+        pm,
+        label,
+        &splash.text_color,
+        splash.font_size * scale(size),
+        font,
+        0.0,
+        height,
+    )
+}
+
+/// Cut a deck-sized frame into per-key RGBA images of `size` pixels,
+/// numbered row by row.
+fn slice_keys(pm: &Pixmap, grid: Grid, size: u32) -> Vec<(u8, Vec<u8>)> {
+    let key = size as usize;
+    let stride = pm.width() as usize * 4;
+    let data = pm.data();
+
+    (0..grid.rows * grid.columns)
+        .map(|index| {
+            let x = (index % grid.columns) as usize * key;
+            let y = (index / grid.columns) as usize * key;
+            let mut rgba = Vec::with_capacity(key * key * 4);
+            for row in y..y + key {
+                let start = row * stride + x * 4;
+                rgba.extend_from_slice(&data[start..start + key * 4]);
+            }
+            (index as u8, rgba)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::canvas::BUTTON_SIZE;
+    use tiny_skia::Color;
+
+    #[test]
+    fn slices_row_by_row() {
+        let grid = Grid {
+            columns: 2,
+            rows: 2,
+        };
+        let mut pm = create_canvas_sized(2 * BUTTON_SIZE, 2 * BUTTON_SIZE, "#000000").unwrap();
+        // Mark the top-left pixel of key 3 (second row, second column).
+        let mark = (BUTTON_SIZE * 2 * BUTTON_SIZE + BUTTON_SIZE) as usize;
+        pm.pixels_mut()[mark] = Color::WHITE.premultiply().to_color_u8();
+
+        let keys = slice_keys(&pm, grid, BUTTON_SIZE);
+        assert_eq!(keys.len(), 4);
+        assert!(keys
+            .iter()
+            .all(|(_, rgba)| rgba.len() == (BUTTON_SIZE * BUTTON_SIZE * 4) as usize));
+        assert_eq!(keys[3].1[0], 255);
+        assert_eq!(keys[1].1[0], 0);
+        assert_eq!(keys[2].1[0], 0);
+    }
+}
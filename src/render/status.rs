@@ -0,0 +1,143 @@
+use crate::config::schema::ButtonDefaults;
+use crate::error::Result;
+use crate::render::canvas::{create_canvas, scale};
+use crate::render::text::{self, TextLine};
+use crate::status::{Issue, StatusLog};
+
+/// Background of a status key while there are unacknowledged issues.
+const ERROR_BACKGROUND: &str = "#c0392b";
+
+/// Characters per line of a wrapped issue message.
+const WRAP_WIDTH: usize = 9;
+
+/// Render the `size`x`size` summary tile: "OK", or the issue count and
+/// newest issue label.
+///
+/// # Errors
+/// Returns `DeckError::Render` if canvas creation or text rendering fails.
+pub fn render_status_tile(
+    log: &StatusLog,
+    defaults: &ButtonDefaults,
+    size: u32,
+) -> Result<Vec<u8>> {
+    let s = scale(size);
+    let Some(last) = log.last() else {
+        let mut pm = create_canvas(size, &defaults.background)?;
+        text::render_text(
+            &mut pm,
+            "OK",
+            &defaults.text_color,
+            18.0 * s,
+            &defaults.font,
+        )?;
+        return Ok(pm.data().to_vec());
+    };
+
+    let mut pm = create_canvas(size, ERROR_BACKGROUND)?;
+    let count = format!("{} err", log.count());
+    let source = last.label();
+    let mut lines = vec![
+        line(&count, 16.0 * s, defaults),
+        line(&source, 12.0 * s, defaults),
+    ];
+    let message = wrap(&last.message, WRAP_WIDTH, 1);
+    lines.extend(message.iter().map(|m| line(m, 10.0 * s, defaults)));
+    text::render_lines_in_area(&mut pm, &lines, 0.0, size as f32)?;
+    Ok(pm.data().to_vec())
+}
+
+/// Render one `size`x`size` entry of the status page; `None` renders an
+/// empty key.
+///
+/// # Errors
+/// Returns `DeckError::Render` if canvas creation or text rendering fails.
+pub fn render_issue(
+    issue: Option<&Issue>,
+    defaults: &ButtonDefaults,
+    size: u32,
+) -> Result<Vec<u8>> {
+    let Some(issue) = issue else {
+        return Ok(create_canvas(size, &defaults.background)?.data().to_vec());
+    };
+
+    let s = scale(size);
+    let mut pm = create_canvas(size, ERROR_BACKGROUND)?;
+    let source = issue.label();
+    let message = wrap(&issue.message, WRAP_WIDTH, 3);
+    let mut lines = vec![line(&source, 12.0 * s, defaults)];
+    lines.extend(message.iter().map(|m| line(m, 10.0 * s, defaults)));
+    text::render_lines_in_area(&mut pm, &lines, 0.0, size as f32)?;
+    Ok(pm.data().to_vec())
+}
+
+fn line<'a>(text: &'a str, size: f32, defaults: &'a ButtonDefaults) -> TextLine<'a> {
+    TextLine {
+        text,
+        color: "#ffffff",
+        size,
+        font: &defaults.font,
+    }
+}
+
+/// Break `text` into at most `max_lines` lines of `width` characters,
+/// preferring word boundaries and ending with "…" if truncated.
+pub(super) fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word = word;
+        loop {
+            let used = current.chars().count();
+            let sep = usize::from(used > 0);
+            let len = word.chars().count();
+            if used + sep + len <= width {
+                if sep == 1 {
+                    current.push(' ');
+                }
+                current.push_str(word);
+                break;
+            }
+            if used > 0 {
+                lines.push(std::mem::take(&mut current));
+                continue;
+            }
+            // A single word longer than a line: hard-split it.
+            let split = word
+                .char_indices()
+                .nth(width)
+                .map_or(word.len(), |(i, _)| i);
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+            if word.is_empty() {
+                break;
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            if last.chars().count() >= width {
+                last.pop();
+            }
+            last.push('…');
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_and_truncates() {
+        assert_eq!(wrap("HTTP 500 from n8n", 9, 3), ["HTTP 500", "from n8n"]);
+        assert_eq!(wrap("connection refused", 9, 1), ["connecti…"]);
+        assert_eq!(wrap("abcdefghijkl", 9, 3), ["abcdefghi", "jkl"]);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::{DeckError, Result};
-use crate::render::canvas::{parse_hex_color, BUTTON_SIZE};
+use crate::render::canvas::{parse_hex_color, scale};
 use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
 use tiny_skia::Pixmap;
 
@@ -7,13 +7,17 @@ use tiny_skia::Pixmap;
 const FONT_INTER: &[u8] = include_bytes!("../../assets/fonts/Inter-Regular.ttf");
 const FONT_ROBOTO_SLAB: &[u8] = include_bytes!("../../assets/fonts/RobotoSlab-Bold.ttf");
 const FONT_JB_THIN: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-Thin.ttf");
-const FONT_JB_EXTRALIGHT: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-ExtraLight.ttf");
+const FONT_JB_EXTRALIGHT: &[u8] =
+    include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-ExtraLight.ttf");
 const FONT_JB_LIGHT: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-Light.ttf");
-const FONT_JB_REGULAR: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-Regular.ttf");
+const FONT_JB_REGULAR: &[u8] =
+    include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-Regular.ttf");
 const FONT_JB_MEDIUM: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-Medium.ttf");
-const FONT_JB_SEMIBOLD: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-SemiBold.ttf");
+const FONT_JB_SEMIBOLD: &[u8] =
+    include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-SemiBold.ttf");
 const FONT_JB_BOLD: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-Bold.ttf");
-const FONT_JB_EXTRABOLD: &[u8] = include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-ExtraBold.ttf");
+const FONT_JB_EXTRABOLD: &[u8] =
+    include_bytes!("../../assets/fonts/JetBrainsMonoNerdFont-ExtraBold.ttf");
 
 /// Get font bytes by name. Falls back to Inter.
 ///
@@ -38,11 +42,12 @@ fn font_data(name: &str) -> &'static [u8] {
     }
 }
 
-/// RGB color components for blending.
+/// RGBA color components for blending.
 struct Rgb {
     red: u8,
     green: u8,
     blue: u8,
+    alpha: u8,
 }
 
 impl Rgb {
@@ -52,6 +57,7 @@ impl Rgb {
             red: (color.red() * 255.0) as u8,
             green: (color.green() * 255.0) as u8,
             blue: (color.blue() * 255.0) as u8,
+            alpha: (color.alpha() * 255.0) as u8,
         })
     }
 }
@@ -106,7 +112,12 @@ fn rasterize_glyphs(
                 let y = py as i32 + bounds.min.y as i32;
                 if x >= 0 && x < cw && y >= 0 && y < ch {
                     let idx = (y * cw + x) as usize * 4;
-                    blend_pixel(canvas.data, idx, color, (coverage * 255.0) as u8);
+                    blend_pixel(
+                        canvas.data,
+                        idx,
+                        color,
+                        (coverage * f32::from(color.alpha)) as u8,
+                    );
                 }
             });
         }
@@ -121,18 +132,79 @@ fn rasterize_glyphs(
 /// # Errors
 /// Returns `DeckError::Font` if the embedded font fails to load,
 /// or `DeckError::Render` if the color is invalid.
-pub fn render_text(pixmap: &mut Pixmap, text: &str, color_hex: &str, font_size: f32, font_name: &str) -> Result<()> {
-    let font =
-        FontRef::try_from_slice(font_data(font_name)).map_err(|e| DeckError::Font(e.to_string()))?;
-    let color = Rgb::from_hex(color_hex)?;
+pub fn render_text(
+    pixmap: &mut Pixmap,
+    text: &str,
+    color_hex: &str,
+    font_size: f32,
+    font_name: &str,
+) -> Result<()> {
+    let height = pixmap.height() as f32;
+    render_text_in_area(pixmap, text, color_hex, font_size, font_name, 0.0, height)
+}
 
-    let scale = PxScale::from(font_size);
-    let scaled_font = font.as_scaled(scale);
+/// Rasterize text horizontally centered and vertically centered within the
+/// band starting at `area_top` with height `area_height`.
+///
+/// # Errors
+/// Returns `DeckError::Font` if the embedded font fails to load,
+/// or `DeckError::Render` if the color is invalid.
+pub fn render_text_in_area(
+    pixmap: &mut Pixmap,
+    text: &str,
+    color_hex: &str,
+    font_size: f32,
+    font_name: &str,
+    area_top: f32,
+    area_height: f32,
+) -> Result<()> {
+    let lines: Vec<TextLine<'_>> = text
+        .split('\n')
+        .map(|line| TextLine {
+            text: line,
+            color: color_hex,
+            size: font_size,
+            font: font_name,
+        })
+        .collect();
+    render_lines_in_area(pixmap, &lines, area_top, area_height)
+}
+
+/// A single line of text with its own style.
+#[derive(Debug, Clone, Copy)]
+pub struct TextLine<'a> {
+    pub text: &'a str,
+    pub color: &'a str,
+    pub size: f32,
+    pub font: &'a str,
+}
+
+/// Stack lines vertically, each horizontally centered, with the block
+/// vertically centered in the band starting at `area_top`. Each line is as
+/// tall as its own font, so mixed sizes pack tightly.
+///
+/// # Errors
+/// Returns `DeckError::Font` if an embedded font fails to load,
+/// or `DeckError::Render` if a color is invalid.
+pub fn render_lines_in_area(
+    pixmap: &mut Pixmap,
+    lines: &[TextLine<'_>],
+    area_top: f32,
+    area_height: f32,
+) -> Result<()> {
+    let mut prepared = Vec::with_capacity(lines.len());
+    for line in lines {
+        let font = FontRef::try_from_slice(font_data(line.font))
+            .map_err(|e| DeckError::Font(e.to_string()))?;
+        let color = Rgb::from_hex(line.color)?;
+        prepared.push((line.text, font, PxScale::from(line.size), color));
+    }
 
-    let lines: Vec<&str> = text.split('\n').collect();
-    let line_height = scaled_font.height();
-    let total_height = line_height * lines.len() as f32;
-    let start_y = ((BUTTON_SIZE as f32 - total_height) / 2.0).max(2.0);
+    let total_height: f32 = prepared
+        .iter()
+        .map(|(_, font, scale, _)| font.as_scaled(*scale).height())
+        .sum();
+    let mut line_top = area_top + ((area_height - total_height) / 2.0).max(2.0);
 
     let width = pixmap.width() as i32;
     let height = pixmap.height() as i32;
@@ -142,20 +214,23 @@ pub fn render_text(pixmap: &mut Pixmap, text: &str, color_hex: &str, font_size:
         height,
     };
 
-    for (line_idx, line) in lines.iter().enumerate() {
-        let visual_width = measure_line_visual(&scaled_font, scale, line);
-        let x_offset = ((BUTTON_SIZE as f32 - visual_width) / 2.0).max(1.0);
-        let y_baseline = line_height.mul_add(line_idx as f32 + 0.8, start_y);
+    for (text, font, scale, color) in &prepared {
+        let scaled_font = font.as_scaled(*scale);
+        let line_height = scaled_font.height();
+        let visual_width = measure_line_visual(&scaled_font, *scale, text);
+        let x_offset = ((width as f32 - visual_width) / 2.0).max(1.0);
+        let y_baseline = line_height.mul_add(0.8, line_top);
 
         rasterize_glyphs(
             &mut canvas,
-            line,
+            text,
             &scaled_font,
-            scale,
+            *scale,
             x_offset,
             y_baseline,
-            &color,
+            color,
         );
+        line_top += line_height;
     }
 
     Ok(())
@@ -173,19 +248,19 @@ pub fn render_text_at_bottom(
     font_size: f32,
     font_name: &str,
 ) -> Result<()> {
-    let font =
-        FontRef::try_from_slice(font_data(font_name)).map_err(|e| DeckError::Font(e.to_string()))?;
+    let font = FontRef::try_from_slice(font_data(font_name))
+        .map_err(|e| DeckError::Font(e.to_string()))?;
     let color = Rgb::from_hex(color_hex)?;
 
     let scale = PxScale::from(font_size);
     let scaled_font = font.as_scaled(scale);
 
-    let y_baseline = BUTTON_SIZE as f32 - 4.0;
-    let visual_width = measure_line_visual(&scaled_font, scale, text);
-    let x_offset = ((BUTTON_SIZE as f32 - visual_width) / 2.0).max(1.0);
-
     let width = pixmap.width() as i32;
     let height = pixmap.height() as i32;
+    let y_baseline = (height as f32 - 4.0 * self::scale(pixmap.width())).round();
+    let visual_width = measure_line_visual(&scaled_font, scale, text);
+    let x_offset = ((width as f32 - visual_width) / 2.0).max(1.0);
+
     let mut canvas = Canvas {
         data: pixmap.data_mut(),
         width,
@@ -225,9 +300,9 @@ fn measure_line_visual(
         if let Some(prev_id) = prev {
             cursor_x += font.kern(prev_id, glyph_id);
         }
-        if let Some(outlined) = font.outline_glyph(
-            glyph_id.with_scale_and_position(scale, ab_glyph::point(cursor_x, 0.0)),
-        ) {
+        if let Some(outlined) = font
+            .outline_glyph(glyph_id.with_scale_and_position(scale, ab_glyph::point(cursor_x, 0.0)))
+        {
             let bounds = outlined.px_bounds();
             min_x = min_x.min(bounds.min.x);
             max_x = max_x.max(bounds.max.x);
@@ -237,5 +312,9 @@ fn measure_line_visual(
         prev = Some(glyph_id);
     }
 
-    if has_bounds { max_x - min_x } else { cursor_x }
+    if has_bounds {
+        max_x - min_x
+    } else {
+        cursor_x
+    }
 }
@@ -0,0 +1,129 @@
+use crate::config::schema::Transition;
+use crate::render::splash::Grid;
+
+/// Intermediate frames of a page transition, before the final page.
+pub const FRAMES: u32 = 4;
+
+/// The in-between frames from one page render to the next, each a full set
+/// of key images of `size` pixels. Keys missing from either render are
+/// drawn black.
+#[must_use]
+pub fn frames(
+    transition: Transition,
+    from: &[(u8, Vec<u8>)],
+    to: &[(u8, Vec<u8>)],
+    grid: Grid,
+    size: u32,
+) -> Vec<Vec<(u8, Vec<u8>)>> {
+    if transition == Transition::None {
+        return Vec::new();
+    }
+    let blank = vec![0; (size * size * 4) as usize];
+    let keys = (0..grid.columns * grid.rows).filter_map(|key| u8::try_from(key).ok());
+    (1..=FRAMES)
+        .map(|i| {
+            keys.clone()
+                .map(|key| {
+                    let rgba = if transition == Transition::Slide {
+                        slide(from, to, key, grid, size, &blank, i)
+                    } else {
+                        fade(image(from, key, &blank), image(to, key, &blank), i)
+                    };
+                    (key, rgba)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The image of `key`, or `blank` if there is none of its size.
+fn image<'a>(images: &'a [(u8, Vec<u8>)], key: u8, blank: &'a [u8]) -> &'a [u8] {
+    images
+        .iter()
+        .find(|(k, rgba)| *k == key && rgba.len() == blank.len())
+        .map_or(blank, |(_, rgba)| rgba)
+}
+
+/// Blend `from` into `to` for frame `i` of `FRAMES + 1`.
+fn fade(from: &[u8], to: &[u8], i: u32) -> Vec<u8> {
+    let (a, b) = (FRAMES + 1 - i, i);
+    from.iter()
+        .zip(to)
+        .map(|(&x, &y)| ((u32::from(x) * a + u32::from(y) * b) / (FRAMES + 1)) as u8)
+        .collect()
+}
+
+/// Key `key` of frame `i` with both pages side by side, shifted left.
+fn slide(
+    from: &[(u8, Vec<u8>)],
+    to: &[(u8, Vec<u8>)],
+    key: u8,
+    grid: Grid,
+    size: u32,
+    blank: &[u8],
+    i: u32,
+) -> Vec<u8> {
+    let width = grid.columns * size;
+    let offset = width * i / (FRAMES + 1);
+    let (row, column) = (u32::from(key) / grid.columns, u32::from(key) % grid.columns);
+    let stride = (size * 4) as usize;
+
+    let mut rgba = vec![0; blank.len()];
+    for x in 0..size {
+        let global = column * size + x + offset;
+        let (images, global) = if global < width {
+            (from, global)
+        } else {
+            (to, global - width)
+        };
+        let source_key = u8::try_from(row * grid.columns + global / size).unwrap_or(0);
+        let source = image(images, source_key, blank);
+        let (src_x, dst_x) = (((global % size) * 4) as usize, (x * 4) as usize);
+        for y in 0..size as usize {
+            let (src, dst) = (y * stride + src_x, y * stride + dst_x);
+            rgba[dst..dst + 4].copy_from_slice(&source[src..src + 4]);
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::canvas::BUTTON_SIZE;
+
+    const GRID: Grid = Grid {
+        columns: 2,
+        rows: 1,
+    };
+
+    fn solid(key: u8, value: u8) -> (u8, Vec<u8>) {
+        (key, vec![value; (BUTTON_SIZE * BUTTON_SIZE * 4) as usize])
+    }
+
+    #[test]
+    fn fade_blends_towards_the_new_page() {
+        let from = [solid(0, 0), solid(1, 0)];
+        let to = [solid(0, 250), solid(1, 250)];
+        let frames = frames(Transition::Fade, &from, &to, GRID, BUTTON_SIZE);
+        assert_eq!(frames.len(), FRAMES as usize);
+        let firsts: Vec<u8> = frames.iter().map(|f| f[0].1[0]).collect();
+        assert_eq!(firsts, [50, 100, 150, 200]);
+        assert!(super::frames(Transition::None, &from, &to, GRID, BUTTON_SIZE).is_empty());
+    }
+
+    #[test]
+    fn slide_shifts_the_pages_left() {
+        let from = [solid(0, 10), solid(1, 20)];
+        let to = [solid(0, 30), solid(1, 40)];
+        let frames = frames(Transition::Slide, &from, &to, GRID, BUTTON_SIZE);
+        // Frame 1 of 5 shifts by 28 px: key 0 starts in the old key 0 and
+        // ends in the old key 1; key 1 runs into the new key 0.
+        let key0 = &frames[0][0].1;
+        let key1 = &frames[0][1].1;
+        assert_eq!(key0[0], 10);
+        assert_eq!(key0[71 * 4], 20);
+        assert_eq!(key1[0], 20);
+        assert_eq!(key1[71 * 4], 30);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// File name used under `$STATE_DIRECTORY` when `deckd.scenes_file` is unset.
+pub const DEFAULT_FILE: &str = "scenes.json";
+
+/// Attributes kept in a snapshot, the ones a restore sets again.
+const RESTORED_ATTRIBUTES: [&str; 6] = [
+    "brightness",
+    "color_mode",
+    "color_temp_kelvin",
+    "rgb_color",
+    "current_position",
+    "percentage",
+];
+
+/// The saved state of one entity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    pub state: String,
+    #[serde(default)]
+    pub attributes: Map<String, Value>,
+}
+
+/// Saved entity states by entity id.
+pub type Scene = BTreeMap<String, EntitySnapshot>;
+
+impl EntitySnapshot {
+    /// The restorable part of an HA state object.
+    #[must_use]
+    pub fn from_state(state: &Value) -> Option<Self> {
+        let attributes = state["attributes"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(name, value)| {
+                RESTORED_ATTRIBUTES.contains(&name.as_str()) && !value.is_null()
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        Some(Self {
+            state: state["state"].as_str()?.to_string(),
+            attributes,
+        })
+    }
+
+    /// The service call that brings `entity_id` back to this state, or
+    /// `None` if its domain cannot be restored.
+    #[must_use]
+    pub fn restore(&self, entity_id: &str) -> Option<(&'static str, Value)> {
+        let domain = entity_id.split_once('.')?.0;
+        let attribute = |name: &str| self.attributes.get(name).cloned();
+        let mut data = json!({ "entity_id": entity_id });
+        let service = match (domain, self.state.as_str()) {
+            ("light", "on") => {
+                if let Some(brightness) = attribute("brightness") {
+                    data["brightness"] = brightness;
+                }
+                let temperature = attribute("color_temp_kelvin")
+                    .filter(|_| self.attributes.get("color_mode") == Some(&json!("color_temp")));
+                if let Some(kelvin) = temperature {
+                    data["color_temp_kelvin"] = kelvin;
+                } else if let Some(rgb) = attribute("rgb_color") {
+                    data["rgb_color"] = rgb;
+                }
+                "light.turn_on"
+            }
+            ("light", "off") => "light.turn_off",
+            ("cover", _) => {
+                data["position"] = attribute("current_position")?;
+                "cover.set_cover_position"
+            }
+            ("fan", "on") if self.attributes.contains_key("percentage") => {
+                data["percentage"] = attribute("percentage")?;
+                "fan.set_percentage"
+            }
+            (_, "on") => "homeassistant.turn_on",
+            (_, "off") => "homeassistant.turn_off",
+            _ => return None,
+        };
+        Some((service, data))
+    }
+}
+
+/// Fetch the current states of `entities` from Home Assistant.
+///
+/// # Errors
+/// Returns the errors of [`crate::action::ha::entity`] for the first entity
+/// that could not be fetched.
+pub async fn snapshot(entities: &[String]) -> Result<Scene> {
+    let states = futures::future::try_join_all(entities.iter().map(|entity| async move {
+        let state = crate::action::ha::entity(entity).await?;
+        Ok::<_, crate::error::DeckError>((entity.clone(), state))
+    }))
+    .await?;
+    Ok(states
+        .into_iter()
+        .filter_map(|(entity, state)| Some((entity, EntitySnapshot::from_state(&state)?)))
+        .collect())
+}
+
+/// Restore every entity of `scene`, skipping those that cannot be restored.
+///
+/// # Errors
+/// Returns the error of the first service call that failed; the others are
+/// still made.
+pub async fn recall(scene: &Scene) -> Result<()> {
+    let calls = scene.iter().filter_map(|(entity, snapshot)| {
+        let restore = snapshot.restore(entity);
+        if restore.is_none() {
+            warn!("scene: cannot restore {entity} ({})", snapshot.state);
+        }
+        restore
+    });
+    let results = futures::future::join_all(calls.map(|(service, data)| async move {
+        crate::action::ha::call_service_raw(service, &data).await
+    }))
+    .await;
+    results.into_iter().collect()
+}
+
+/// Snapshots by name, optionally persisted as JSON.
+#[derive(Debug, Default)]
+pub struct SceneStore {
+    scenes: BTreeMap<String, Scene>,
+    path: Option<PathBuf>,
+}
+
+impl SceneStore {
+    /// Load snapshots from `path`, starting empty if it does not exist yet
+    /// or cannot be read. Without a path, snapshots are kept in memory only.
+    #[must_use]
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let scenes = path
+            .as_deref()
+            .and_then(|path| match std::fs::read(path) {
+                Ok(data) => serde_json::from_slice(&data)
+                    .map_err(|e| warn!("ignoring unreadable scenes file {}: {e}", path.display()))
+                    .ok(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    warn!("cannot read scenes file {}: {e}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self { scenes, path }
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Scene> {
+        self.scenes.get(name)
+    }
+
+    /// Store `scene` as `name` and write the file.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Io` if the file cannot be written; the snapshot
+    /// is kept in memory either way.
+    pub fn save(&mut self, name: String, scene: Scene) -> Result<()> {
+        self.scenes.insert(name, scene);
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        crate::usage::write_atomic(
+            path,
+            &serde_json::to_vec_pretty(&self.scenes).map_err(std::io::Error::from)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_lights_by_color_mode() {
+        let state = json!({
+            "state": "on",
+            "attributes": {
+                "friendly_name": "Desk",
+                "brightness": 128,
+                "color_mode": "color_temp",
+                "color_temp_kelvin": 3000,
+                "rgb_color": [255, 180, 107],
+                "supported_color_modes": ["color_temp", "hs"]
+            }
+        });
+        let snapshot = EntitySnapshot::from_state(&state).unwrap();
+        assert!(!snapshot.attributes.contains_key("friendly_name"));
+        let (service, data) = snapshot.restore("light.desk").unwrap();
+        assert_eq!(service, "light.turn_on");
+        assert_eq!(
+            data,
+            json!({ "entity_id": "light.desk", "brightness": 128, "color_temp_kelvin": 3000 })
+        );
+
+        let off = EntitySnapshot::from_state(&json!({ "state": "off" })).unwrap();
+        assert_eq!(
+            off.restore("switch.fan").unwrap().0,
+            "homeassistant.turn_off"
+        );
+        let unknown = EntitySnapshot::from_state(&json!({ "state": "42" })).unwrap();
+        assert_eq!(unknown.restore("sensor.power"), None);
+    }
+
+    #[test]
+    fn snapshots_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("deckd-scenes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DEFAULT_FILE);
+
+        let snapshot = EntitySnapshot::from_state(&json!({ "state": "on" })).unwrap();
+        let mut store = SceneStore::load(Some(path.clone()));
+        store
+            .save(
+                "evening".into(),
+                Scene::from([("light.desk".into(), snapshot.clone())]),
+            )
+            .unwrap();
+
+        let store = SceneStore::load(Some(path));
+        assert_eq!(store.get("evening").unwrap()["light.desk"], snapshot);
+        assert!(store.get("morning").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use chrono::Timelike;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// A local wall-clock time of day with minute resolution, written "HH:MM".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    /// Build from hours (0-23) and minutes (0-59).
+    #[must_use]
+    pub fn from_hm(hours: u8, minutes: u8) -> Option<Self> {
+        (hours < 24 && minutes < 60).then(|| Self(u16::from(hours) * 60 + u16::from(minutes)))
+    }
+
+    /// The current local time of day.
+    #[must_use]
+    pub fn now() -> Self {
+        let now = chrono::Local::now();
+        Self((now.hour() * 60 + now.minute()) as u16)
+    }
+
+    /// Minutes since midnight.
+    #[must_use]
+    pub const fn minutes(self) -> u16 {
+        self.0
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid time '{s}', expected HH:MM");
+        let (h, m) = s.trim().split_once(':').ok_or_else(err)?;
+        let hours: u8 = h.parse().map_err(|_| err())?;
+        let minutes: u8 = m.parse().map_err(|_| err())?;
+        Self::from_hm(hours, minutes).ok_or_else(err)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Pick the entry of a daily schedule that is in effect at `now`: the one with
+/// the latest start time at or before `now`, wrapping around to the last entry
+/// of the previous day before the first start.
+///
+/// `entries` must be sorted by start time (e.g. iterated from a `BTreeMap`).
+pub fn active_entry<'a, T>(
+    entries: impl IntoIterator<Item = (&'a TimeOfDay, &'a T)>,
+    now: TimeOfDay,
+) -> Option<&'a T> {
+    let mut last = None;
+    let mut current = None;
+    for (start, value) in entries {
+        if *start <= now {
+            current = Some(value);
+        }
+        last = Some(value);
+    }
+    current.or(last)
+}
+
+/// Whether `now` falls within `[start, end)`, wrapping past midnight when
+/// `end` is earlier than `start` (e.g. 22:00-06:00).
+#[must_use]
+pub fn in_range(start: TimeOfDay, end: TimeOfDay, now: TimeOfDay) -> bool {
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn t(s: &str) -> TimeOfDay {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_time_of_day() {
+        assert_eq!(t("07:30").minutes(), 450);
+        assert_eq!(t("23:59").to_string(), "23:59");
+        assert!("24:00".parse::<TimeOfDay>().is_err());
+        assert!("7".parse::<TimeOfDay>().is_err());
+    }
+
+    #[test]
+    fn schedule_wraps_past_midnight() {
+        let schedule: BTreeMap<TimeOfDay, &str> =
+            [(t("07:00"), "day"), (t("21:30"), "night")].into();
+        assert_eq!(active_entry(&schedule, t("12:00")), Some(&"day"));
+        assert_eq!(active_entry(&schedule, t("22:00")), Some(&"night"));
+        assert_eq!(active_entry(&schedule, t("03:00")), Some(&"night"));
+        assert_eq!(active_entry(&schedule, t("07:00")), Some(&"day"));
+    }
+
+    #[test]
+    fn range_wraps_past_midnight() {
+        assert!(in_range(t("22:00"), t("06:00"), t("23:00")));
+        assert!(in_range(t("22:00"), t("06:00"), t("05:59")));
+        assert!(!in_range(t("22:00"), t("06:00"), t("06:00")));
+        assert!(in_range(t("09:00"), t("17:00"), t("12:00")));
+        assert!(!in_range(t("09:00"), t("17:00"), t("18:00")));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::error::{DeckError, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Prefix of config values read from a file when used, e.g.
+/// `"@file:/run/secrets/ha_auth"`.
+pub const FILE_PREFIX: &str = "@file:";
+
+/// Resolve a config value: `@file:<path>` is replaced by the file's
+/// contents, anything else is returned as-is.
+///
+/// # Errors
+/// Returns `DeckError::Secret` if the referenced file cannot be read.
+pub fn resolve(value: &str) -> Result<Cow<'_, str>> {
+    match value.strip_prefix(FILE_PREFIX) {
+        Some(path) => read_file(Path::new(path.trim())).map(Cow::Owned),
+        None => Ok(Cow::Borrowed(value)),
+    }
+}
+
+/// A secret given as the environment variable `var`, else in the file named
+/// by `<var>_FILE`, else as the systemd credential `credential`.
+///
+/// # Errors
+/// Returns a human-readable message if the secret is not configured or its
+/// file cannot be read or is empty.
+pub fn from_env(var: &str, credential: &str) -> std::result::Result<String, String> {
+    if let Ok(value) = std::env::var(var) {
+        if !value.is_empty() {
+            return Ok(value);
+        }
+    }
+    let file = std::env::var_os(format!("{var}_FILE"))
+        .map(PathBuf::from)
+        .or_else(|| {
+            let credential =
+                Path::new(&std::env::var_os("CREDENTIALS_DIRECTORY")?).join(credential);
+            credential.exists().then_some(credential)
+        });
+    match file {
+        Some(path) => read_file(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|value| {
+                if value.is_empty() {
+                    Err(format!("{} is empty", path.display()))
+                } else {
+                    Ok(value)
+                }
+            }),
+        None => Err(format!("{var} not set")),
+    }
+}
+
+/// Read a secret file, dropping the trailing newline editors and `echo` add.
+///
+/// # Errors
+/// Returns `DeckError::Secret` if the file cannot be read.
+pub fn read_file(path: &Path) -> Result<String> {
+    let content = std::fs::read_to_string(path).map_err(|source| DeckError::Secret {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Whether a `presented` token is the `expected` one, taking the same time
+/// wherever they differ (and whatever their lengths), so the token cannot be
+/// guessed byte by byte from response times.
+#[must_use]
+pub fn tokens_match(presented: &str, expected: &str) -> bool {
+    let digest = |token: &str| ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+    let (presented, expected) = (digest(presented), digest(expected));
+    let diff = presented
+        .as_ref()
+        .iter()
+        .zip(expected.as_ref())
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+    std::hint::black_box(diff) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_file_references() {
+        let path = std::env::temp_dir().join(format!("deckd-secret-{}", std::process::id()));
+        std::fs::write(&path, "Bearer abc123\n").unwrap();
+
+        let value = format!("{FILE_PREFIX}{}", path.display());
+        assert_eq!(resolve(&value).unwrap(), "Bearer abc123");
+        assert_eq!(resolve("Bearer plain").unwrap(), "Bearer plain");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(resolve(&value), Err(DeckError::Secret { .. })));
+    }
+
+    #[test]
+    fn matches_tokens() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cres", "s3cret"));
+        assert!(!tokens_match("", "s3cret"));
+    }
+}
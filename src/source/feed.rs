@@ -0,0 +1,346 @@
+//! News headlines: the latest items of an RSS, Atom or JSON feed, shown one
+//! after the other.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a fetched feed is shown before it is fetched again.
+const REFRESH: Duration = Duration::from_secs(600);
+
+/// How long each headline is shown.
+const ROTATE: Duration = Duration::from_secs(10);
+
+/// Headlines shown of each feed, newest first.
+const ITEMS: usize = 5;
+
+/// Characters per line and lines of a wrapped headline, sized for the
+/// default font on a 72px key.
+const LINE_WIDTH: usize = 11;
+const LINES: usize = 4;
+
+/// A headline and where it links to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Item {
+    title: String,
+    link: String,
+}
+
+/// Items of a feed and when they were fetched.
+type Fetched = (Instant, Vec<Item>);
+
+/// Items of each feed by URL, with when they were fetched. Sources are read
+/// without any context, so feeds are cached here between renders.
+static FEEDS: LazyLock<Mutex<HashMap<String, Fetched>>> = LazyLock::new(Mutex::default);
+
+/// Replace the XML entities and character references in `text`.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';').filter(|&end| end <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            name => name
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| name.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The text content of an element: CDATA sections kept verbatim, tags
+/// dropped, entities replaced and whitespace collapsed.
+fn text(inner: &str) -> String {
+    let mut out = String::new();
+    let mut rest = inner;
+    while !rest.is_empty() {
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let (content, after) = cdata.split_once("]]>").unwrap_or((cdata, ""));
+            out.push_str(content);
+            rest = after;
+        } else if let Some(tag) = rest.strip_prefix('<') {
+            rest = tag.split_once('>').map_or("", |(_, after)| after);
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            out.push_str(&unescape(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The attributes and content of each `<name>` element in `xml`, in order.
+pub(super) fn elements<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // `<item` must not match `<items>`.
+        if !rest.starts_with([' ', '\t', '\r', '\n', '>', '/']) {
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let attributes = &rest[..end];
+        rest = &rest[end + 1..];
+        if let Some(attributes) = attributes.strip_suffix('/') {
+            found.push((attributes, ""));
+            continue;
+        }
+        let (inner, after) = rest.split_once(&close).unwrap_or((rest, ""));
+        found.push((attributes, inner));
+        rest = after;
+    }
+    found
+}
+
+/// The value of attribute `name` in the attributes of a start tag.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {name}=");
+    let start = attributes.find(&pattern)? + pattern.len();
+    let rest = &attributes[start..];
+    let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let value = rest[1..].split(quote).next()?;
+    Some(unescape(value))
+}
+
+/// The items of an RSS or Atom document.
+fn parse_xml(xml: &str) -> Vec<Item> {
+    let mut entries = elements(xml, "item");
+    if entries.is_empty() {
+        entries = elements(xml, "entry");
+    }
+    entries
+        .into_iter()
+        .filter_map(|(_, entry)| {
+            let title = text(elements(entry, "title").first()?.1);
+            let links = elements(entry, "link");
+            // RSS has the URL as content, Atom in `href`, where the
+            // `alternate` link (the default) is the article.
+            let link = links
+                .iter()
+                .find(|(attributes, _)| {
+                    attribute(attributes, "rel").is_none_or(|rel| rel == "alternate")
+                })
+                .or(links.first())
+                .map(|(attributes, inner)| {
+                    attribute(attributes, "href").unwrap_or_else(|| text(inner))
+                })
+                .unwrap_or_default();
+            Some(Item { title, link })
+        })
+        .filter(|item| !item.title.is_empty())
+        .collect()
+}
+
+/// The items of a JSON Feed document.
+fn parse_json(feed: &Value) -> Vec<Item> {
+    feed["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            Some(Item {
+                title: item["title"].as_str()?.trim().to_string(),
+                link: item["url"]
+                    .as_str()
+                    .or(item["external_url"].as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+        })
+        .filter(|item| !item.title.is_empty())
+        .collect()
+}
+
+/// The items of a feed document of any supported format.
+fn parse(body: &str) -> std::result::Result<Vec<Item>, String> {
+    let items = if body.trim_start().starts_with('{') {
+        let feed: Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+        parse_json(&feed)
+    } else {
+        parse_xml(body)
+    };
+    if items.is_empty() {
+        return Err("no items in feed".into());
+    }
+    Ok(items)
+}
+
+/// `title` word-wrapped to the key, with an ellipsis if it does not fit.
+fn wrap(title: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut truncated = false;
+    for word in title.split_whitespace() {
+        let full = lines.len() == LINES;
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= LINE_WIDTH => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ if full => {
+                truncated = true;
+                break;
+            }
+            _ => lines.push(word.chars().take(LINE_WIDTH).collect()),
+        }
+    }
+    if truncated {
+        if let Some(last) = lines.last_mut() {
+            while last.chars().count() >= LINE_WIDTH {
+                last.pop();
+            }
+            last.push('…');
+        }
+    }
+    lines.join("\n")
+}
+
+/// The latest items of the feed at `url`, fetched at most every
+/// [`REFRESH`]. A feed that cannot be fetched keeps showing what it had.
+async fn items(url: &str) -> std::result::Result<Vec<Item>, String> {
+    let cached = FEEDS.lock().unwrap().get(url).cloned();
+    if let Some((fetched, items)) = &cached {
+        if fetched.elapsed() < REFRESH {
+            return Ok(items.clone());
+        }
+    }
+    let fetch = async {
+        let client =
+            crate::action::http::build_client(false, None, None).map_err(|e| e.to_string())?;
+        let body = client
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        parse(&body)
+    };
+    let items = match (fetch.await, cached) {
+        (Ok(mut items), _) => {
+            items.truncate(ITEMS);
+            items
+        }
+        (Err(e), Some((_, items))) => {
+            tracing::debug!("feed {url}: {e}, showing cached items");
+            items
+        }
+        (Err(e), None) => return Err(e),
+    };
+    FEEDS
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), (Instant::now(), items.clone()));
+    Ok(items)
+}
+
+/// The state of a `feed:<url>` entity: one of the feed's latest headlines,
+/// wrapped to fit the key, changing every [`ROTATE`].
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(url: &str) -> std::result::Result<String, String> {
+    let items = items(url).await?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let shown = (now.as_secs() / ROTATE.as_secs()) as usize % items.len();
+    Ok(wrap(&items[shown].title))
+}
+
+/// The link of the headline `state` of the `feed:<url>` entity `entity`,
+/// for the `{link}` placeholder of the action of a key showing it.
+#[must_use]
+pub fn link(entity: &str, state: &str) -> Option<String> {
+    let url = entity.strip_prefix("feed:")?;
+    let feeds = FEEDS.lock().unwrap();
+    feeds
+        .get(url)?
+        .1
+        .iter()
+        .find(|item| wrap(&item.title) == state)
+        .map(|item| item.link.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rss_atom_and_json_feeds() {
+        let rss = r#"<?xml version="1.0"?><rss><channel><title>News</title>
+            <item><title><![CDATA[Rust 2.0 & friends]]></title>
+              <link>https://example.com/a?x=1&amp;y=2</link></item>
+            <item><title>Caf&#233; opens</title><link>https://example.com/b</link></item>
+            </channel></rss>"#;
+        assert_eq!(
+            parse(rss).unwrap(),
+            vec![
+                Item {
+                    title: "Rust 2.0 & friends".into(),
+                    link: "https://example.com/a?x=1&y=2".into(),
+                },
+                Item {
+                    title: "Café opens".into(),
+                    link: "https://example.com/b".into(),
+                },
+            ]
+        );
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title>
+            <entry><title type="html">Release &lt;b&gt;notes&lt;/b&gt;</title>
+              <link rel="replies" href="https://example.com/c#comments"/>
+              <link href="https://example.com/c"/></entry></feed>"#;
+        let items = parse(atom).unwrap();
+        assert_eq!(items[0].link, "https://example.com/c");
+
+        let json = r#"{"version": "https://jsonfeed.org/version/1.1",
+            "items": [{"id": "1", "title": "Hello", "url": "https://example.com/d"}]}"#;
+        assert_eq!(parse(json).unwrap()[0].link, "https://example.com/d");
+        assert!(parse("<html></html>").is_err());
+    }
+
+    #[test]
+    fn wraps_headlines_to_the_key() {
+        assert_eq!(
+            wrap("Storm warning for the coast"),
+            "Storm\nwarning for\nthe coast"
+        );
+        assert_eq!(
+            wrap("One two three four five six seven eight nine ten"),
+            "One two\nthree four\nfive six\nseven eigh…"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Unread mail: the `UNSEEN` count of an IMAP folder, over TLS.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_rustls::rustls;
+
+/// Port of IMAP over TLS.
+const PORT: u16 = 993;
+
+/// How long a count is shown before the folder is checked again. Every
+/// check is a login, which providers throttle when it happens too often.
+const REFRESH: Duration = Duration::from_secs(120);
+
+/// The longest wait between checks of a folder that keeps failing.
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// The last check of each entity address. Sources are read on every render,
+/// so counts, and failures, are cached here between them.
+static COUNTS: LazyLock<Mutex<HashMap<String, Check>>> = LazyLock::new(Mutex::default);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Check {
+    at: Instant,
+    /// Until the next check: [`REFRESH`], doubled with each failure in a
+    /// row up to [`MAX_BACKOFF`].
+    wait: Duration,
+    failed: bool,
+    /// The last count, or why there is none yet.
+    count: Result<u32, String>,
+}
+
+impl Check {
+    /// The check after `last` with the result `read`, keeping the last count
+    /// when `read` failed.
+    fn next(last: Option<Self>, read: Result<u32, String>) -> Self {
+        let (wait, failed, count) = match (read, last) {
+            (Ok(count), _) => (REFRESH, false, Ok(count)),
+            (Err(e), last) => {
+                let wait = last
+                    .as_ref()
+                    .filter(|last| last.failed)
+                    .map_or(REFRESH, |last| (last.wait * 2).min(MAX_BACKOFF));
+                (wait, true, last.map_or(Err(e), |last| last.count))
+            }
+        };
+        Self {
+            at: Instant::now(),
+            wait,
+            failed,
+            count,
+        }
+    }
+}
+
+/// `value` as an IMAP quoted string.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The count in a `* STATUS <folder> (UNSEEN <n>)` line.
+fn unseen(line: &str) -> Option<u32> {
+    let (_, rest) = line.split_once("UNSEEN ")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Send tagged `command` and read up to its completion, returning the
+/// untagged lines before it.
+async fn command<S>(
+    stream: &mut BufReader<S>,
+    tag: &str,
+    command: &str,
+) -> std::result::Result<Vec<String>, String>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    stream
+        .get_mut()
+        .write_all(format!("{tag} {command}\r\n").as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut untagged = Vec::new();
+    loop {
+        let mut line = String::new();
+        if stream
+            .read_line(&mut line)
+            .await
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Err("connection closed".into());
+        }
+        let Some(status) = line.strip_prefix(tag).map(str::trim) else {
+            untagged.push(line);
+            continue;
+        };
+        return if status.starts_with("OK") {
+            Ok(untagged)
+        } else {
+            Err(status.to_string())
+        };
+    }
+}
+
+/// The number of unread messages in `folder` of `user` on `host`.
+async fn unread(host: &str, user: &str, folder: &str) -> std::result::Result<u32, String> {
+    let password = crate::secret::from_env("IMAP_PASSWORD", "imap_password")?;
+    let (name, addr) = match host.split_once(':') {
+        Some((name, _)) => (name, host.to_string()),
+        None => (host, format!("{host}:{PORT}")),
+    };
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let tls = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = rustls::pki_types::ServerName::try_from(name.to_string())
+        .map_err(|e| format!("{name}: {e}"))?;
+    let tcp = tokio::net::TcpStream::connect(&addr)
+        .await
+        .map_err(|e| format!("{addr}: {e}"))?;
+    let tls = tokio_rustls::TlsConnector::from(Arc::new(tls))
+        .connect(server_name, tcp)
+        .await
+        .map_err(|e| format!("{addr}: {e}"))?;
+    let mut stream = BufReader::new(tls);
+
+    let mut greeting = String::new();
+    stream
+        .read_line(&mut greeting)
+        .await
+        .map_err(|e| e.to_string())?;
+    let login = format!("LOGIN {} {}", quote(user), quote(&password));
+    command(&mut stream, "a1", &login).await?;
+    let status = format!("STATUS {} (UNSEEN)", quote(folder));
+    let lines = command(&mut stream, "a2", &status).await?;
+    let _ = command(&mut stream, "a3", "LOGOUT").await;
+    lines
+        .iter()
+        .find_map(|line| unseen(line))
+        .ok_or_else(|| "no UNSEEN in STATUS reply".into())
+}
+
+/// The state of an `imap:<user>@<host>[/<folder>]` entity: the number of
+/// unread messages in the folder (default `INBOX`), checked at most every
+/// [`REFRESH`]. A folder that cannot be checked keeps its last count, or
+/// error, and is checked less and less often while it fails. The password
+/// is `IMAP_PASSWORD`, the file named by `IMAP_PASSWORD_FILE`, or the
+/// `imap_password` systemd credential.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let (user, rest) = address
+        .rsplit_once('@')
+        .ok_or_else(|| format!("expected <user>@<host>[/<folder>], got '{address}'"))?;
+    let (host, folder) = rest.split_once('/').unwrap_or((rest, "INBOX"));
+
+    let last = COUNTS.lock().unwrap().get(address).cloned();
+    if let Some(ref last) = last {
+        if last.at.elapsed() < last.wait {
+            return last
+                .count
+                .as_ref()
+                .map(u32::to_string)
+                .map_err(Clone::clone);
+        }
+    }
+    let read = unread(host, user, folder).await;
+    if let Err(ref e) = read {
+        tracing::debug!("imap:{address}: {e}");
+    }
+    let check = Check::next(last, read);
+    let state = check
+        .count
+        .as_ref()
+        .map(u32::to_string)
+        .map_err(Clone::clone);
+    COUNTS.lock().unwrap().insert(address.to_string(), check);
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_status_replies() {
+        assert_eq!(unseen("* STATUS INBOX (UNSEEN 12)\r\n"), Some(12));
+        assert_eq!(unseen("* STATUS \"Lists/Rust\" (UNSEEN 0)\r\n"), Some(0));
+        assert_eq!(unseen("* OK still here\r\n"), None);
+        assert_eq!(quote(r#"pa"ss\"#), r#""pa\"ss\\""#);
+    }
+
+    #[tokio::test]
+    async fn backs_off_from_failing_folders() {
+        let failed = Check::next(None, Err("login failed".into()));
+        assert_eq!(
+            (failed.wait, failed.count.clone()),
+            (REFRESH, Err("login failed".into()))
+        );
+        let again = Check::next(Some(failed.clone()), Err("timed out".into()));
+        assert_eq!(again.wait, REFRESH * 2);
+        assert_eq!(again.count, Err("login failed".into()));
+        let counted = Check::next(Some(again), Ok(3));
+        assert_eq!((counted.wait, counted.failed), (REFRESH, false));
+        let kept = Check::next(Some(counted), Err("timed out".into()));
+        assert_eq!((kept.wait, kept.count), (REFRESH, Ok(3)));
+
+        // A remembered failure is answered without logging in again.
+        let address = "me@mail.invalid/Backoff";
+        COUNTS.lock().unwrap().insert(address.into(), failed);
+        assert_eq!(read_state(address).await, Err("login failed".into()));
+    }
+}
@@ -0,0 +1,116 @@
+//! State sources besides Home Assistant. A `state_entity` of the form
+//! `<source>:<address>` is read from that source instead of HA, whose entity
+//! ids never contain a colon.
+
+pub mod feed;
+pub mod imap;
+pub mod net;
+pub mod nut;
+pub mod price;
+pub mod rest;
+
+use crate::action::vpn::VpnKind;
+use std::time::Duration;
+
+/// How long a source may take to answer before the render goes on without it.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The sources built into this binary.
+const SOURCES: &[&str] = &[
+    "tailscale",
+    "wireguard",
+    "proxmox",
+    "cups",
+    "nut",
+    "imap",
+    "feed",
+    "rest",
+    "price",
+    "net",
+    "speedtest",
+    #[cfg(feature = "bluetooth")]
+    "bluetooth",
+    #[cfg(feature = "kubernetes")]
+    "k8s",
+    #[cfg(feature = "industrial")]
+    "modbus",
+];
+
+/// Whether `entity` is read from a source other than HA.
+#[must_use]
+pub fn is_sourced(entity: &str) -> bool {
+    entity.contains(':')
+}
+
+/// Check that the source of `entity` exists in this build.
+///
+/// # Errors
+/// Returns a message naming the unknown source.
+pub fn check(entity: &str) -> std::result::Result<(), String> {
+    match entity.split_once(':') {
+        Some((source, _)) if !SOURCES.contains(&source) => {
+            let available = if SOURCES.is_empty() {
+                "none".to_string()
+            } else {
+                SOURCES.join(", ")
+            };
+            Err(format!(
+                "unknown state source '{source}' in '{entity}' (available: {available})"
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read the state of a sourced `entity`.
+///
+/// # Errors
+/// Returns a short description of the problem for the status tile.
+pub async fn fetch(entity: &str) -> std::result::Result<String, String> {
+    let read = async {
+        match entity.split_once(':') {
+            Some(("tailscale", address)) => {
+                crate::action::vpn::read_state(VpnKind::Tailscale, address).await
+            }
+            Some(("wireguard", address)) => {
+                crate::action::vpn::read_state(VpnKind::Wireguard, address).await
+            }
+            Some(("net", address)) => net::read_state(address).await,
+            Some(("speedtest", address)) => net::read_speedtest(address).await,
+            Some(("imap", address)) => imap::read_state(address).await,
+            Some(("feed", url)) => feed::read_state(url).await,
+            Some(("rest", address)) => rest::read_state(address).await,
+            Some(("price", address)) => price::read_state(address).await,
+            Some(("nut", address)) => nut::read_state(address).await,
+            Some(("cups", address)) => crate::action::ipp::read_state(address).await,
+            Some(("proxmox", address)) => crate::action::proxmox::read_state(address).await,
+            #[cfg(feature = "bluetooth")]
+            Some(("bluetooth", address)) => crate::action::bluetooth::read_state(address).await,
+            #[cfg(feature = "kubernetes")]
+            Some(("k8s", address)) => crate::action::k8s::read_state(address).await,
+            #[cfg(feature = "industrial")]
+            Some(("modbus", address)) => crate::action::modbus::read_state(address).await,
+            _ => Err("unknown state source".into()),
+        }
+    };
+    tokio::time::timeout(TIMEOUT, read)
+        .await
+        .unwrap_or_else(|_| Err("timeout".into()))
+        .map_err(|e| format!("{entity}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_known_sources_pass() {
+        assert!(check("light.desk").is_ok());
+        assert!(check("tailscale:exit_node").is_ok());
+        assert!(check("snmp:router/1.3.6").is_err());
+        assert_eq!(
+            check("modbus:plc/1/40").is_ok(),
+            cfg!(feature = "industrial")
+        );
+    }
+}
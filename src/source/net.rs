@@ -0,0 +1,169 @@
+//! Network throughput: interface counters sampled from `/proc/net/dev`, and
+//! the results of a scheduled speedtest.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Counters older than this are sampled again instead of giving an average
+/// over minutes.
+const MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Gap between two samples when no recent one exists.
+const FIRST_SAMPLE: Duration = Duration::from_millis(500);
+
+/// Reads closer together than this (e.g. `down` and `up` keys of one
+/// render) share the rates of the first.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Download and upload rate in Mbit/s.
+type Rates = (f64, f64);
+
+/// Received and sent bytes of an interface at some instant.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    rx: u64,
+    tx: u64,
+}
+
+/// Last sample and the rates up to it, per interface. Sources are read
+/// without any context, so the counters to diff against live here.
+static LAST: LazyLock<Mutex<HashMap<String, (Sample, Rates)>>> = LazyLock::new(Mutex::default);
+
+/// Received and sent bytes of `iface` in the contents of `/proc/net/dev`.
+fn counters(dev: &str, iface: &str) -> Option<(u64, u64)> {
+    let fields = dev.lines().find_map(|line| {
+        let (name, fields) = line.split_once(':')?;
+        (name.trim() == iface).then_some(fields)
+    })?;
+    let fields: Vec<u64> = fields
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    // Receive: bytes packets errs drop fifo frame compressed multicast.
+    Some((*fields.first()?, *fields.get(8)?))
+}
+
+fn sample(iface: &str) -> std::result::Result<Sample, String> {
+    let dev = std::fs::read_to_string("/proc/net/dev").map_err(|e| e.to_string())?;
+    let (rx, tx) = counters(&dev, iface).ok_or_else(|| format!("no interface '{iface}'"))?;
+    Ok(Sample {
+        at: Instant::now(),
+        rx,
+        tx,
+    })
+}
+
+/// The rates between two samples.
+fn rates(before: Sample, after: Sample) -> Rates {
+    let seconds = after.at.duration_since(before.at).as_secs_f64().max(1e-3);
+    let mbits = |from: u64, to: u64| to.saturating_sub(from) as f64 * 8.0 / seconds / 1e6;
+    (mbits(before.rx, after.rx), mbits(before.tx, after.tx))
+}
+
+/// The state of a `net:<interface>[/down|/up]` entity: the throughput in
+/// Mbit/s since the last read, or both as `↓<down> ↑<up>`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let (iface, direction) = address.split_once('/').unwrap_or((address, ""));
+    let previous = LAST.lock().unwrap().get(iface).copied();
+    let (down, up) = match previous {
+        Some((last, rates)) if last.at.elapsed() < MIN_INTERVAL => rates,
+        _ => {
+            let before = match previous {
+                Some((last, _)) if last.at.elapsed() < MAX_AGE => last,
+                _ => {
+                    let first = sample(iface)?;
+                    tokio::time::sleep(FIRST_SAMPLE).await;
+                    first
+                }
+            };
+            let after = sample(iface)?;
+            let rates = rates(before, after);
+            LAST.lock()
+                .unwrap()
+                .insert(iface.to_string(), (after, rates));
+            rates
+        }
+    };
+    match direction {
+        "down" => Ok(format!("{down:.2}")),
+        "up" => Ok(format!("{up:.2}")),
+        "" => Ok(format!("↓{down:.1} ↑{up:.1}")),
+        _ => Err(format!("unknown direction '{direction}' (down, up)")),
+    }
+}
+
+/// A field of an Ookla `speedtest --format=json` result: `down` and `up`
+/// in Mbit/s, `ping` in ms.
+fn speedtest_field(result: &Value, field: &str) -> std::result::Result<String, String> {
+    let value = match field {
+        "down" => result["download"]["bandwidth"]
+            .as_f64()
+            .map(|b| b * 8.0 / 1e6),
+        "up" => result["upload"]["bandwidth"]
+            .as_f64()
+            .map(|b| b * 8.0 / 1e6),
+        "ping" => result["ping"]["latency"].as_f64(),
+        _ => return Err(format!("unknown field '{field}' (down, up, ping)")),
+    };
+    value
+        .map(|v| format!("{v:.1}"))
+        .ok_or_else(|| format!("no {field} in result"))
+}
+
+/// The state of a `speedtest:<file>#<down|up|ping>` entity, read from the
+/// JSON a scheduled `speedtest --format=json` wrote to `file`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_speedtest(address: &str) -> std::result::Result<String, String> {
+    let (path, field) = address
+        .rsplit_once('#')
+        .ok_or_else(|| format!("expected <file>#<down|up|ping>, got '{address}'"))?;
+    let json = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("{path}: {e}"))?;
+    let result: Value = serde_json::from_slice(&json).map_err(|e| format!("{path}: {e}"))?;
+    speedtest_field(&result, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_counters_and_rates() {
+        let dev = "Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:    1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0
+  eth0: 5000000    4000    0    0    0     0          0         0   250000    2000    0    0    0     0       0          0
+";
+        assert_eq!(counters(dev, "eth0"), Some((5_000_000, 250_000)));
+        assert_eq!(counters(dev, "wlan0"), None);
+
+        let before = Sample {
+            at: Instant::now(),
+            rx: 0,
+            tx: 0,
+        };
+        let after = Sample {
+            at: before.at + Duration::from_secs(2),
+            rx: 5_000_000,
+            tx: 250_000,
+        };
+        assert_eq!(rates(before, after), (20.0, 1.0));
+
+        let result =
+            json!({ "download": { "bandwidth": 11_750_000 }, "ping": { "latency": 12.34 } });
+        assert_eq!(speedtest_field(&result, "down"), Ok("94.0".into()));
+        assert_eq!(speedtest_field(&result, "ping"), Ok("12.3".into()));
+        assert!(speedtest_field(&result, "up").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Network UPS Tools: variables of a UPS read from `upsd`, and a watcher
+//! that shows a page while the UPS runs on battery.
+
+use crate::config::schema::AppConfig;
+use crate::event::DeckEvent;
+use crate::ids::PageId;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Port `upsd` listens on.
+const PORT: u16 = 3493;
+
+/// How often the UPS of `deckd.ups` is polled.
+const POLL: Duration = Duration::from_secs(10);
+
+/// Derived variable: `online`, `battery` or `low_battery`.
+const POWER: &str = "power";
+
+/// Where the power comes from, by the flags of `ups.status` (e.g. `OB LB`).
+fn power(status: &str) -> &'static str {
+    let flags: Vec<&str> = status.split_whitespace().collect();
+    if flags.contains(&"LB") {
+        "low_battery"
+    } else if flags.contains(&"OB") {
+        "battery"
+    } else {
+        "online"
+    }
+}
+
+/// The value of a `VAR <ups> <name> "<value>"` reply.
+fn parse_reply(line: &str) -> std::result::Result<String, String> {
+    if let Some(error) = line.strip_prefix("ERR ") {
+        return Err(error.trim().to_lowercase().replace('-', " "));
+    }
+    let value = line
+        .split_once('"')
+        .and_then(|(_, rest)| rest.rsplit_once('"'))
+        .map(|(value, _)| value)
+        .ok_or_else(|| format!("unexpected reply '{}'", line.trim()))?;
+    Ok(value.replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+/// Ask `upsd` at `host` for the variable `name` of `ups`.
+async fn get_var(host: &str, ups: &str, name: &str) -> std::result::Result<String, String> {
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:{PORT}")
+    };
+    let mut stream = tokio::net::TcpStream::connect(&addr)
+        .await
+        .map_err(|e| format!("{addr}: {e}"))?;
+    let (read, mut write) = stream.split();
+    write
+        .write_all(format!("GET VAR {ups} {name}\n").as_bytes())
+        .await
+        .map_err(|e| format!("{addr}: {e}"))?;
+    let mut line = String::new();
+    BufReader::new(read)
+        .read_line(&mut line)
+        .await
+        .map_err(|e| format!("{addr}: {e}"))?;
+    // Without LOGOUT, upsd logs every poll as a lost connection.
+    let _ = write.write_all(b"LOGOUT\n").await;
+    parse_reply(&line)
+}
+
+/// The state of a `nut:<ups>@<host>/<variable>` entity, e.g.
+/// `nut:ups@nas.lan/battery.charge`. The variable `power` is `online`,
+/// `battery` or `low_battery`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let (ups, rest) = address
+        .split_once('@')
+        .ok_or_else(|| format!("expected <ups>@<host>/<variable>, got '{address}'"))?;
+    let (host, name) = rest.rsplit_once('/').unwrap_or((rest, POWER));
+    if name == POWER {
+        return get_var(host, ups, "ups.status")
+            .await
+            .map(|status| power(&status).to_string());
+    }
+    get_var(host, ups, name).await
+}
+
+/// Poll `deckd.ups` and show its page while the UPS runs on battery. The
+/// page is left again when power returns, unless the user navigated away.
+pub async fn watch(
+    config: Arc<ArcSwap<AppConfig>>,
+    current_page: Arc<ArcSwap<PageId>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut on_battery = false;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let Some(ups) = config.load().deckd.ups.clone() else {
+            on_battery = false;
+            continue;
+        };
+        let Ok(state) = super::fetch(&format!("nut:{}/{POWER}", ups.ups)).await else {
+            continue;
+        };
+        let now = state != "online";
+        if now == on_battery {
+            continue;
+        }
+        on_battery = now;
+        if now {
+            info!("UPS {} on battery, showing page '{}'", ups.ups, ups.page);
+            let _ = tx.send(DeckEvent::NavigateTo(ups.page));
+        } else if **current_page.load() == ups.page {
+            info!("UPS {} back on line power", ups.ups);
+            let _ = tx.send(DeckEvent::NavigateBack);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_replies_and_status() {
+        assert_eq!(
+            parse_reply("VAR ups battery.charge \"87\"\n"),
+            Ok("87".into())
+        );
+        assert_eq!(parse_reply("ERR UNKNOWN-UPS\n"), Err("unknown ups".into()));
+        assert_eq!(power("OL CHRG"), "online");
+        assert_eq!(power("OB DISCHRG"), "battery");
+        assert_eq!(power("OB LB"), "low_battery");
+    }
+}
@@ -0,0 +1,281 @@
+//! Electricity prices: the current spot or tariff price from aWATTar,
+//! Tibber or the ENTSO-E transparency platform.
+
+use chrono::{DateTime, Duration as TimeDelta, NaiveDateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Prices are fetched again after this even while the known ones last, as
+/// tomorrow's are published during the day.
+const REFRESH: Duration = Duration::from_secs(3_600);
+
+const TIBBER_URL: &str = "https://api.tibber.com/v1-beta/gql";
+const ENTSOE_URL: &str = "https://web-api.tp.entsoe.eu/api";
+
+/// A price in ct/kWh from `start` until `end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    price: f64,
+}
+
+/// Prices of a provider and when they were fetched.
+type Fetched = (Instant, Vec<Slot>);
+
+/// Known prices by entity address.
+static PRICES: LazyLock<Mutex<HashMap<String, Fetched>>> = LazyLock::new(Mutex::default);
+
+/// The price of the slot containing `now`.
+fn current(slots: &[Slot], now: DateTime<Utc>) -> Option<f64> {
+    slots
+        .iter()
+        .find(|slot| slot.start <= now && now < slot.end)
+        .map(|slot| slot.price)
+}
+
+/// Slots of an aWATTar `marketdata` response, priced in EUR/MWh.
+fn parse_awattar(body: &Value) -> Vec<Slot> {
+    body["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            Some(Slot {
+                start: DateTime::from_timestamp_millis(entry["start_timestamp"].as_i64()?)?,
+                end: DateTime::from_timestamp_millis(entry["end_timestamp"].as_i64()?)?,
+                price: entry["marketprice"].as_f64()? / 10.0,
+            })
+        })
+        .collect()
+}
+
+/// Slots of the first home of a Tibber `priceInfo` response, priced in
+/// currency per kWh including taxes. Each lasts until the next one starts.
+fn parse_tibber(body: &Value) -> Vec<Slot> {
+    let prices = &body["data"]["viewer"]["homes"][0]["currentSubscription"]["priceInfo"];
+    let starts: Vec<(DateTime<Utc>, f64)> = ["today", "tomorrow"]
+        .iter()
+        .flat_map(|day| prices[day].as_array().into_iter().flatten())
+        .filter_map(|entry| {
+            let start = DateTime::parse_from_rfc3339(entry["startsAt"].as_str()?).ok()?;
+            Some((start.to_utc(), entry["total"].as_f64()? * 100.0))
+        })
+        .collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, price))| Slot {
+            start,
+            end: starts
+                .get(i + 1)
+                .map_or(start + TimeDelta::hours(1), |next| next.0),
+            price,
+        })
+        .collect()
+}
+
+/// A time of an ENTSO-E document, e.g. `2026-10-15T22:00Z`.
+fn entsoe_time(text: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(text.trim(), "%Y-%m-%dT%H:%MZ")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Slots of an ENTSO-E day-ahead prices document, priced in EUR/MWh. A
+/// point lasts until the next one, as positions with an unchanged price may
+/// be left out.
+fn parse_entsoe(xml: &str) -> Vec<Slot> {
+    let field = |within: &str, name: &str| {
+        super::feed::elements(within, name)
+            .first()
+            .map(|(_, inner)| inner.trim().to_string())
+    };
+    let mut slots = Vec::new();
+    for (_, period) in super::feed::elements(xml, "Period") {
+        let interval = field(period, "timeInterval").unwrap_or_default();
+        let (Some(start), Some(end)) = (
+            field(&interval, "start").as_deref().and_then(entsoe_time),
+            field(&interval, "end").as_deref().and_then(entsoe_time),
+        ) else {
+            continue;
+        };
+        let minutes = match field(period, "resolution").as_deref() {
+            Some("PT15M") => 15,
+            Some("PT30M") => 30,
+            _ => 60,
+        };
+        let points: Vec<(i64, f64)> = super::feed::elements(period, "Point")
+            .into_iter()
+            .filter_map(|(_, point)| {
+                let position = field(point, "position")?.parse().ok()?;
+                let price: f64 = field(point, "price.amount")?.parse().ok()?;
+                Some((position, price / 10.0))
+            })
+            .collect();
+        let at = |position: i64| start + TimeDelta::minutes((position - 1) * minutes);
+        for (i, &(position, price)) in points.iter().enumerate() {
+            slots.push(Slot {
+                start: at(position),
+                end: points.get(i + 1).map_or(end, |next| at(next.0)),
+                price,
+            });
+        }
+    }
+    slots
+}
+
+async fn fetch_awattar(country: &str) -> std::result::Result<Vec<Slot>, String> {
+    let client = crate::action::http::build_client(false, None, None).map_err(|e| e.to_string())?;
+    let body: Value = client
+        .get(format!("https://api.awattar.{country}/v1/marketdata"))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(parse_awattar(&body))
+}
+
+async fn fetch_tibber() -> std::result::Result<Vec<Slot>, String> {
+    let token = crate::secret::from_env("TIBBER_TOKEN", "tibber_token")?;
+    let query = "{ viewer { homes { currentSubscription { priceInfo { \
+                 today { total startsAt } tomorrow { total startsAt } } } } } }";
+    let client = crate::action::http::build_client(false, None, None).map_err(|e| e.to_string())?;
+    let body: Value = client
+        .post(TIBBER_URL)
+        .bearer_auth(token)
+        .json(&json!({ "query": query }))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(error) = body["errors"][0]["message"].as_str() {
+        return Err(error.to_string());
+    }
+    Ok(parse_tibber(&body))
+}
+
+async fn fetch_entsoe(area: &str) -> std::result::Result<Vec<Slot>, String> {
+    let token = crate::secret::from_env("ENTSOE_TOKEN", "entsoe_token")?;
+    let today = Utc::now().date_naive();
+    let period = |days: i64| {
+        (today + TimeDelta::days(days))
+            .format("%Y%m%d0000")
+            .to_string()
+    };
+    let client = crate::action::http::build_client(false, None, None).map_err(|e| e.to_string())?;
+    let xml = client
+        .get(ENTSOE_URL)
+        .query(&[
+            ("securityToken", token.as_str()),
+            ("documentType", "A44"),
+            ("in_Domain", area),
+            ("out_Domain", area),
+            ("periodStart", &period(-1)),
+            ("periodEnd", &period(2)),
+        ])
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    // Errors come as an acknowledgement document with a reason.
+    if let Some((_, reason)) = super::feed::elements(&xml, "text").first() {
+        return Err(reason.trim().to_string());
+    }
+    Ok(parse_entsoe(&xml))
+}
+
+/// The state of a `price:<provider>[/<area>]` entity: the current
+/// electricity price in ct/kWh. `awattar` (or `awattar/at`) is the net spot
+/// price, `tibber` the price of the first home of `TIBBER_TOKEN` including
+/// taxes, and `entsoe/<EIC area code>` the day-ahead price with
+/// `ENTSOE_TOKEN`.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let now = Utc::now();
+    let cached = PRICES.lock().unwrap().get(address).cloned();
+    if let Some((fetched, slots)) = cached {
+        if fetched.elapsed() < REFRESH {
+            if let Some(price) = current(&slots, now) {
+                return Ok(format!("{price:.2}"));
+            }
+        }
+    }
+    let (provider, area) = address.split_once('/').unwrap_or((address, ""));
+    let slots = match (provider, area) {
+        ("awattar", "") => fetch_awattar("de").await?,
+        ("awattar", country @ ("de" | "at")) => fetch_awattar(country).await?,
+        ("tibber", "") => fetch_tibber().await?,
+        ("entsoe", area) if !area.is_empty() => fetch_entsoe(area).await?,
+        _ => {
+            return Err(format!(
+                "expected awattar[/de|/at], tibber or entsoe/<area>, got '{address}'"
+            ))
+        }
+    };
+    let price = current(&slots, now).ok_or("no price for the current time")?;
+    PRICES
+        .lock()
+        .unwrap()
+        .insert(address.to_string(), (Instant::now(), slots));
+    Ok(format!("{price:.2}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The price at `time` as a state.
+    fn price(slots: &[Slot], time: &str) -> Option<String> {
+        let time = DateTime::parse_from_rfc3339(time).unwrap().to_utc();
+        current(slots, time).map(|price| format!("{price:.2}"))
+    }
+
+    #[test]
+    fn reads_spot_and_tariff_prices() {
+        let awattar = json!({ "data": [
+            { "start_timestamp": 1_792_101_600_000_i64, "end_timestamp": 1_792_105_200_000_i64,
+              "marketprice": 87.4, "unit": "Eur/MWh" }
+        ] });
+        let slots = parse_awattar(&awattar);
+        assert_eq!(price(&slots, "2026-10-15T22:30:00Z"), Some("8.74".into()));
+        assert_eq!(price(&slots, "2026-10-15T23:00:00Z"), None);
+
+        let tibber = json!({ "data": { "viewer": { "homes": [{ "currentSubscription": {
+            "priceInfo": { "today": [
+                { "total": 0.2513, "startsAt": "2026-10-16T00:00:00.000+02:00" },
+                { "total": 0.3021, "startsAt": "2026-10-16T01:00:00.000+02:00" }
+            ], "tomorrow": [] } } }] } } });
+        let slots = parse_tibber(&tibber);
+        assert_eq!(price(&slots, "2026-10-15T22:59:00Z"), Some("25.13".into()));
+        assert_eq!(price(&slots, "2026-10-15T23:59:00Z"), Some("30.21".into()));
+        assert_eq!(price(&slots, "2026-10-16T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn reads_entsoe_documents_with_gaps() {
+        let xml = "<Publication_MarketDocument><TimeSeries><Period>
+            <timeInterval><start>2026-10-15T22:00Z</start><end>2026-10-16T01:00Z</end></timeInterval>
+            <resolution>PT60M</resolution>
+            <Point><position>1</position><price.amount>80.5</price.amount></Point>
+            <Point><position>3</position><price.amount>-2</price.amount></Point>
+            </Period></TimeSeries></Publication_MarketDocument>";
+        let slots = parse_entsoe(xml);
+        assert_eq!(price(&slots, "2026-10-15T23:30:00Z"), Some("8.05".into()));
+        assert_eq!(price(&slots, "2026-10-16T00:30:00Z"), Some("-0.20".into()));
+        assert_eq!(price(&slots, "2026-10-16T01:00:00Z"), None);
+    }
+}
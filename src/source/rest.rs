@@ -0,0 +1,103 @@
+//! Values picked from any JSON API, e.g. the next departure of a transit
+//! stop.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a response is used before the URL is requested again.
+const REFRESH: Duration = Duration::from_secs(60);
+
+/// A response and when it was received.
+type Fetched = (Instant, Value);
+
+/// Responses by URL. Keys of one API share a request, and a countdown
+/// label re-renders from the cached time between requests.
+static RESPONSES: LazyLock<Mutex<HashMap<String, Fetched>>> = LazyLock::new(Mutex::default);
+
+/// The value at `path` in `json`: fields and array indexes separated by
+/// dots or in brackets, optionally starting with `$`, e.g.
+/// `$.departures[0].when` or `departures.0.when`.
+fn select<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    path.split(['.', '['])
+        .map(|segment| segment.trim_end_matches(']'))
+        .filter(|segment| !segment.is_empty())
+        .try_fold(json, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+/// `value` as a state: strings as they are, numbers and booleans printed.
+fn state(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// The JSON at `url`, requested at most every [`REFRESH`].
+async fn response(url: &str) -> std::result::Result<Value, String> {
+    if let Some((fetched, json)) = RESPONSES.lock().unwrap().get(url) {
+        if fetched.elapsed() < REFRESH {
+            return Ok(json.clone());
+        }
+    }
+    let client = crate::action::http::build_client(false, None, None).map_err(|e| e.to_string())?;
+    let json: Value = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    RESPONSES
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), (Instant::now(), json.clone()));
+    Ok(json)
+}
+
+/// The state of a `rest:<url>#<path>` entity: the string, number or
+/// boolean at `path` in the JSON the URL returns.
+///
+/// # Errors
+/// Returns a short description of the problem.
+pub async fn read_state(address: &str) -> std::result::Result<String, String> {
+    let (url, path) = address
+        .rsplit_once('#')
+        .ok_or_else(|| format!("expected <url>#<path>, got '{address}'"))?;
+    let json = response(url).await?;
+    select(&json, path)
+        .and_then(state)
+        .ok_or_else(|| format!("no value at '{path}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn selects_by_path() {
+        let json = json!({
+            "departures": [
+                { "line": { "name": "U2" }, "when": "2026-10-16T07:42:00+02:00", "delay": 60 },
+                { "line": { "name": "U8" }, "when": null }
+            ]
+        });
+        let pick = |path| select(&json, path).and_then(state);
+        assert_eq!(pick("$.departures[0].line.name"), Some("U2".into()));
+        assert_eq!(pick("departures.0.delay"), Some("60".into()));
+        assert_eq!(pick("$.departures[1].when"), None);
+        assert_eq!(pick("$.departures[2].when"), None);
+        assert_eq!(pick("$.departures"), None);
+    }
+}
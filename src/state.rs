@@ -1,5 +1,186 @@
+use crate::config::schema::AppConfig;
+use crate::event::DeckEvent;
+use arc_swap::ArcSwap;
 use std::collections::HashMap;
-use tracing::warn;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Timeout of state requests, short so rendering is never held up long.
+const STATE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often the presence entity is polled.
+const PRESENCE_POLL: Duration = Duration::from_secs(5);
+
+/// How long a Home Assistant URL that could not be reached goes to the back
+/// of the list before it is tried first again.
+const HA_URL_RETRY: Duration = Duration::from_secs(60);
+
+/// Home Assistant URLs that could not be reached, and when they failed.
+static HA_DOWN: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(Mutex::default);
+
+/// The Home Assistant token: `HA_TOKEN`, else the file named by
+/// `HA_TOKEN_FILE`, else the `ha_token` systemd credential, else the
+/// Supervisor's token when running as an add-on.
+///
+/// # Errors
+/// Returns a human-readable message if no token is configured or its file
+/// cannot be read.
+pub fn ha_token() -> std::result::Result<String, String> {
+    crate::secret::from_env("HA_TOKEN", "ha_token")
+        .or_else(|e| crate::addon::supervisor_token().ok_or(e))
+}
+
+/// The Home Assistant base URLs in order of preference: `HA_URL` (several
+/// separated by commas, e.g. the LAN address, then Nabu Casa), else the
+/// Supervisor's proxy when running as an add-on, else
+/// `http://homeassistant.local:8123`.
+#[must_use]
+pub fn ha_urls() -> Vec<String> {
+    let urls = std::env::var("HA_URL").map(|list| parse_urls(&list));
+    match urls {
+        Ok(urls) if !urls.is_empty() => urls,
+        _ if crate::addon::active() => vec![crate::addon::CORE_URL.into()],
+        _ => vec!["http://homeassistant.local:8123".into()],
+    }
+}
+
+fn parse_urls(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// [`ha_urls`] in the order to try them: those not found unreachable within
+/// [`HA_URL_RETRY`] first.
+fn ha_urls_in_turn() -> Vec<String> {
+    let down = HA_DOWN.lock().unwrap();
+    in_turn(ha_urls(), &down, Instant::now())
+}
+
+fn in_turn(urls: Vec<String>, down: &HashMap<String, Instant>, now: Instant) -> Vec<String> {
+    let (up, down): (Vec<String>, Vec<String>) = urls.into_iter().partition(|url| {
+        down.get(url)
+            .is_none_or(|&since| now.duration_since(since) >= HA_URL_RETRY)
+    });
+    up.into_iter().chain(down).collect()
+}
+
+/// Send the request `build` makes for a base URL to Home Assistant, trying
+/// its URLs in turn until one can be reached. URLs that cannot be reached,
+/// or whose gateway fails (a broken reverse proxy), are passed over for a
+/// while; any other answer is returned as it is, with the base URL that gave
+/// it. A request that may already have reached Home Assistant (after a
+/// timeout or gateway error) is only sent again if its method is
+/// idempotent, so a service call never runs twice.
+///
+/// # Errors
+/// Returns the error of the last URL tried.
+pub(crate) async fn send_to_ha(
+    build: impl Fn(&str) -> reqwest::RequestBuilder,
+) -> reqwest::Result<(String, reqwest::Response)> {
+    let urls = ha_urls_in_turn();
+    for (i, url) in urls.iter().enumerate() {
+        let (client, request) = build(url).build_split();
+        let request = request?;
+        let repeatable = request.method().is_idempotent();
+        let next = urls.get(i + 1);
+        match client.execute(request).await {
+            Ok(resp) if gateway_failed(resp.status()) => {
+                let problem = format!("HTTP {}", resp.status());
+                mark_down(url, &problem, next.filter(|_| repeatable));
+                if !repeatable || next.is_none() {
+                    crate::health::ha_answered(url);
+                    return Ok((url.clone(), resp));
+                }
+            }
+            Ok(resp) => {
+                if HA_DOWN.lock().unwrap().remove(url).is_some() {
+                    info!("Home Assistant reachable again at {url}");
+                }
+                crate::health::ha_answered(url);
+                return Ok((url.clone(), resp));
+            }
+            Err(e) if e.is_connect() || e.is_timeout() => {
+                // A failed connect never reached Home Assistant.
+                let fall_back = e.is_connect() || repeatable;
+                mark_down(url, &e.to_string(), next.filter(|_| fall_back));
+                if !fall_back || next.is_none() {
+                    return Err(e);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("ha_urls is never empty")
+}
+
+/// Whether `status` comes from a gateway in front of Home Assistant that
+/// could not reach it, rather than from Home Assistant.
+fn gateway_failed(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 502..=504)
+}
+
+/// Pass over `url` for a while, warning the first time it fails.
+fn mark_down(url: &str, problem: &str, next: Option<&String>) {
+    let first = HA_DOWN
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), Instant::now())
+        .is_none();
+    if let (true, Some(next)) = (first, next) {
+        warn!("Home Assistant at {url} unreachable ({problem}), trying {next}");
+    }
+}
+
+/// HTTP client for Home Assistant. `HA_INSECURE_TLS=1` accepts any
+/// certificate; `HA_CA_CERT` names an extra trusted CA (PEM).
+pub(crate) fn ha_client(timeout: Duration) -> std::result::Result<reqwest::Client, String> {
+    let insecure =
+        std::env::var("HA_INSECURE_TLS").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    let ca_cert = std::env::var_os("HA_CA_CERT").map(PathBuf::from);
+    crate::action::http::build_client(insecure, ca_cert.as_deref(), Some(timeout))
+        .map_err(|e| e.to_string())
+}
+
+/// Fetch entity states for rendering: HA entities from Home Assistant and
+/// `<source>:<address>` entities from their [`crate::source`].
+pub async fn fetch_states(entities: &[String]) -> HashMap<String, String> {
+    fetch_states_checked(entities).await.0
+}
+
+/// Like [`fetch_states`], but also returns the first problem encountered.
+pub async fn fetch_states_checked(
+    entities: &[String],
+) -> (HashMap<String, String>, Option<String>) {
+    let (sourced, ha): (Vec<&String>, Vec<&String>) =
+        entities.iter().partition(|e| crate::source::is_sourced(e));
+    let ha: Vec<String> = ha.into_iter().cloned().collect();
+    let sourced = futures::future::join_all(
+        sourced
+            .into_iter()
+            .map(|entity| async move { (entity.clone(), crate::source::fetch(entity).await) }),
+    );
+    let ((mut states, mut problem), sourced) = tokio::join!(fetch_ha_states_checked(&ha), sourced);
+    for (entity, result) in sourced {
+        match result {
+            Ok(state) => {
+                states.insert(entity, state);
+            }
+            Err(e) => {
+                warn!("state source {e}");
+                problem.get_or_insert(e);
+            }
+        }
+    }
+    crate::template::record_states(&states);
+    (states, problem)
+}
 
 /// Fetch entity states from Home Assistant for the given entity IDs.
 ///
@@ -7,56 +188,209 @@ use tracing::warn;
 /// Returns a map of entity_id → state string (e.g. "on", "off", "unavailable").
 /// Silently returns an empty map on any error so rendering is never blocked.
 pub async fn fetch_ha_states(entities: &[String]) -> HashMap<String, String> {
+    fetch_ha_states_checked(entities).await.0
+}
+
+/// Like [`fetch_ha_states`], but also returns the first problem encountered
+/// (missing token, HTTP or connection error) for the status tile.
+pub async fn fetch_ha_states_checked(
+    entities: &[String],
+) -> (HashMap<String, String>, Option<String>) {
     if entities.is_empty() {
-        return HashMap::new();
+        return (HashMap::new(), None);
     }
 
-    let token = match std::env::var("HA_TOKEN") {
-        Ok(t) if !t.is_empty() => t,
-        _ => return HashMap::new(),
+    let token = match ha_token() {
+        Ok(t) => t,
+        Err(e) => {
+            crate::health::ha_fetched(Some(&e));
+            return (HashMap::new(), Some(e));
+        }
     };
 
-    let ha_url = std::env::var("HA_URL")
-        .unwrap_or_else(|_| "http://homeassistant.local:8123".into());
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(3))
-        .build()
-        .unwrap_or_default();
+    let client = match ha_client(STATE_TIMEOUT) {
+        Ok(c) => c,
+        Err(e) => {
+            crate::health::ha_fetched(Some(&e));
+            return (HashMap::new(), Some(e));
+        }
+    };
 
     // Fire all requests in parallel.
     let futures: Vec<_> = entities
         .iter()
         .map(|entity_id| {
-            let url = format!("{ha_url}/api/states/{entity_id}");
-            let req = client
-                .get(&url)
-                .header("Authorization", format!("Bearer {token}"))
-                .send();
+            let (client, token) = (&client, &token);
+            let req = send_to_ha(move |ha_url| {
+                client
+                    .get(format!("{ha_url}/api/states/{entity_id}"))
+                    .header("Authorization", format!("Bearer {token}"))
+            });
             let eid = entity_id.clone();
             async move {
-                match req.await {
+                match req.await.map(|(_, resp)| resp) {
                     Ok(resp) if resp.status().is_success() => {
                         if let Ok(json) = resp.json::<serde_json::Value>().await {
+                            if let Some(attributes) = json.get("attributes") {
+                                crate::template::record_attributes(&eid, attributes.clone());
+                            }
                             if let Some(state) = json.get("state").and_then(|s| s.as_str()) {
-                                return Some((eid, state.to_string()));
+                                return Ok((eid, state.to_string()));
                             }
                         }
-                        None
+                        Err(format!("{eid}: malformed response"))
                     }
                     Ok(resp) => {
                         warn!("HA state fetch {eid}: HTTP {}", resp.status());
-                        None
+                        Err(format!("{eid}: HTTP {}", resp.status().as_u16()))
                     }
                     Err(e) => {
                         warn!("HA state fetch {eid}: {e}");
-                        None
+                        Err(if e.is_timeout() {
+                            "timeout".to_string()
+                        } else if e.is_connect() {
+                            "unreachable".to_string()
+                        } else {
+                            format!("{eid}: {e}")
+                        })
                     }
                 }
             }
         })
         .collect();
 
-    let results = futures::future::join_all(futures).await;
-    results.into_iter().flatten().collect()
+    let mut states = HashMap::new();
+    let mut problem = None;
+    for result in futures::future::join_all(futures).await {
+        match result {
+            Ok((eid, state)) => {
+                states.insert(eid, state);
+            }
+            Err(e) => {
+                problem.get_or_insert(e);
+            }
+        }
+    }
+    crate::health::ha_fetched(problem.as_deref());
+    (states, problem)
+}
+
+/// Check that Home Assistant is reachable, at one of its URLs, and accepts
+/// the token. Returns the base URL that answered, or a description of the
+/// problem.
+///
+/// # Errors
+/// Returns a human-readable message if the token is missing, HA cannot be
+/// reached, or it rejects the request.
+pub async fn check_ha_connection() -> std::result::Result<String, String> {
+    let token = ha_token()?;
+
+    let client = ha_client(STATE_TIMEOUT)?;
+    let (ha_url, resp) = send_to_ha(|ha_url| {
+        client
+            .get(format!("{ha_url}/api/"))
+            .header("Authorization", format!("Bearer {token}"))
+    })
+    .await
+    .map_err(|e| format!("{}: {e}", ha_urls().join(", ")))?;
+
+    match resp.status().as_u16() {
+        200..=299 => Ok(ha_url),
+        401 | 403 => Err(format!("{ha_url}: token rejected (HTTP {})", resp.status())),
+        _ => Err(format!("{ha_url}: HTTP {}", resp.status())),
+    }
+}
+
+/// Interpret a presence/occupancy entity state.
+///
+/// Returns `None` for states that say nothing about occupancy
+/// (e.g. "unavailable"), so a flaky sensor never blanks the display.
+fn is_occupied(state: &str) -> Option<bool> {
+    match state {
+        "on" | "home" | "detected" | "occupied" => Some(true),
+        "off" | "not_home" | "away" | "clear" => Some(false),
+        _ => None,
+    }
+}
+
+/// Poll `deckd.presence_entity` and emit `DisplaySleep`/`DisplayWake` when the
+/// room becomes empty or occupied. Only transitions are reported, so manual
+/// sleep/wake actions are not overridden on every poll.
+pub async fn watch_presence(
+    config: Arc<ArcSwap<AppConfig>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(PRESENCE_POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut occupied: Option<bool> = None;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let Some(entity) = config.load().deckd.presence_entity.clone() else {
+            occupied = None;
+            continue;
+        };
+
+        let states = fetch_ha_states(std::slice::from_ref(&entity)).await;
+        let Some(now) = states.get(&entity).and_then(|s| is_occupied(s)) else {
+            continue;
+        };
+
+        if occupied != Some(now) {
+            // The first reading only establishes a baseline when occupied,
+            // but an empty room at startup should still blank the display.
+            if now {
+                if occupied.is_some() {
+                    info!("presence detected ({entity}), waking display");
+                    let _ = tx.send(DeckEvent::DisplayWake);
+                }
+            } else {
+                info!("no presence ({entity}), sleeping display");
+                let _ = tx.send(DeckEvent::DisplaySleep);
+            }
+            occupied = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presence_states() {
+        assert_eq!(is_occupied("on"), Some(true));
+        assert_eq!(is_occupied("home"), Some(true));
+        assert_eq!(is_occupied("not_home"), Some(false));
+        assert_eq!(is_occupied("off"), Some(false));
+        assert_eq!(is_occupied("unavailable"), None);
+    }
+
+    #[test]
+    fn tries_unreachable_urls_last() {
+        let urls = parse_urls("http://[fd00::10]:8123/, https://example.ui.nabu.casa,");
+        assert_eq!(
+            urls,
+            ["http://[fd00::10]:8123", "https://example.ui.nabu.casa"]
+        );
+        let now = Instant::now();
+        let mut down = HashMap::new();
+        assert_eq!(in_turn(urls.clone(), &down, now), urls);
+        down.insert(urls[0].clone(), now);
+        assert_eq!(
+            in_turn(urls.clone(), &down, now + Duration::from_secs(5)),
+            [urls[1].clone(), urls[0].clone()]
+        );
+        assert_eq!(in_turn(urls.clone(), &down, now + HA_URL_RETRY), urls);
+
+        assert!(gateway_failed(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(gateway_failed(reqwest::StatusCode::GATEWAY_TIMEOUT));
+        assert!(!gateway_failed(reqwest::StatusCode::UNAUTHORIZED));
+        assert!(!gateway_failed(reqwest::StatusCode::INTERNAL_SERVER_ERROR));
+    }
 }
@@ -0,0 +1,140 @@
+use crate::error::{DeckError, ErrorKind};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Number of distinct recent issues kept for the status page.
+pub const RECENT_ISSUES: usize = 15;
+
+/// Where a problem was detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSource {
+    Action,
+    HomeAssistant,
+    Device,
+}
+
+impl fmt::Display for IssueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Action => "action",
+            Self::HomeAssistant => "HA",
+            Self::Device => "device",
+        })
+    }
+}
+
+/// A problem worth surfacing on the deck itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub source: IssueSource,
+    pub message: String,
+    /// The kind of error, when the issue came from a `DeckError`.
+    pub kind: Option<ErrorKind>,
+}
+
+impl Issue {
+    #[must_use]
+    pub fn new(source: IssueSource, message: impl Into<String>) -> Self {
+        Self {
+            source,
+            message: message.into(),
+            kind: None,
+        }
+    }
+
+    /// An issue for `error`, with its short user-facing message.
+    #[must_use]
+    pub fn from_error(source: IssueSource, error: &DeckError) -> Self {
+        Self {
+            source,
+            message: error.user_message(),
+            kind: Some(error.kind()),
+        }
+    }
+
+    /// What the deck shows as the issue's heading: the error kind if known,
+    /// else where it came from.
+    #[must_use]
+    pub fn label(&self) -> String {
+        self.kind
+            .map_or_else(|| self.source.to_string(), |kind| kind.to_string())
+    }
+}
+
+/// Unacknowledged issues since the last clear.
+#[derive(Debug, Default)]
+pub struct StatusLog {
+    /// Most recent first. Repeats of the newest issue are not added again.
+    recent: VecDeque<Issue>,
+    count: u64,
+}
+
+impl StatusLog {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an issue. Returns `false` if it repeats the newest one, in
+    /// which case only the count changes.
+    pub fn record(&mut self, issue: Issue) -> bool {
+        self.count += 1;
+        if self.recent.front() == Some(&issue) {
+            return false;
+        }
+        self.recent.push_front(issue);
+        self.recent.truncate(RECENT_ISSUES);
+        true
+    }
+
+    /// Acknowledge all issues.
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.count = 0;
+    }
+
+    /// Issues recorded since the last clear, including repeats.
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The most recent issue.
+    #[must_use]
+    pub fn last(&self) -> Option<&Issue> {
+        self.recent.front()
+    }
+
+    /// The `index`-th most recent distinct issue.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Issue> {
+        self.recent.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_only_count() {
+        let mut log = StatusLog::new();
+        let ha = Issue::new(IssueSource::HomeAssistant, "connection refused");
+        assert!(log.record(ha.clone()));
+        assert!(!log.record(ha.clone()));
+        assert!(log.record(Issue::new(IssueSource::Action, "key 3: HTTP 500")));
+        assert!(log.record(ha));
+
+        assert_eq!(log.count(), 4);
+        assert_eq!(
+            log.last().map(|i| i.source),
+            Some(IssueSource::HomeAssistant)
+        );
+        assert_eq!(log.get(1).map(|i| i.source), Some(IssueSource::Action));
+        assert!(log.get(3).is_none());
+
+        log.clear();
+        assert_eq!(log.count(), 0);
+        assert!(log.last().is_none());
+    }
+}
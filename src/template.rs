@@ -0,0 +1,476 @@
+//! Templates (Jinja syntax, as in Home Assistant) in labels, action fields
+//! and `visible_if` conditions, with entity states and attributes, action
+//! variables and the time.
+//!
+//! Text with `{{ … }}` or `{% … %}` is a template; other text keeps the
+//! `{state}`-style placeholders. Templates see:
+//!
+//! - `state`: the raw state of the button's `state_entity`, `value` the
+//!   same formatted by the button's `format`
+//! - `states('light.desk')`, `state_attr('light.desk', 'brightness')`,
+//!   `is_state('person.anna', 'home')`
+//! - action variables by name, e.g. `hook.name`
+//! - `time`, `date`, `weekday` as in the locale, and `now` with `year`,
+//!   `month`, `day`, `hour`, `minute`, `second` and `weekday` (1 is Monday)
+//! - the filters `number(precision)`, `duration` and `countdown`, formatting
+//!   like the button `format` options
+//!
+//! Programs embedding deckd add their own with [`register_filter`] and
+//! [`register_function`].
+
+use crate::config::schema::{ButtonConfig, NumberFormat};
+use crate::render::format;
+use crate::render::locale::{self, Locale};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use minijinja::functions::Function;
+use minijinja::value::{FunctionArgs, FunctionResult};
+use minijinja::{Environment, Error, Output, State, Value};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, RwLock};
+
+/// State of entities not fetched yet, as Home Assistant's `states()` says.
+const UNKNOWN: &str = "unknown";
+
+/// Functions taking an entity ID as their first argument.
+const ENTITY_FUNCTIONS: [&str; 3] = ["states(", "state_attr(", "is_state("];
+
+/// How the render time is handed to filters.
+const NOW_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// Last known entity states and HA attributes, as fetched for rendering.
+static STATES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(Mutex::default);
+static ATTRIBUTES: LazyLock<Mutex<HashMap<String, serde_json::Value>>> =
+    LazyLock::new(Mutex::default);
+
+/// Environment for text: values are printed as they are.
+static TEXT: LazyLock<RwLock<Environment<'static>>> =
+    LazyLock::new(|| RwLock::new(environment(false)));
+
+/// Environment for shell commands: printed values are quoted.
+static SHELL: LazyLock<RwLock<Environment<'static>>> =
+    LazyLock::new(|| RwLock::new(environment(true)));
+
+/// Values a template is rendered with.
+pub struct Context<'a> {
+    /// Raw state of the button's `state_entity`, if known.
+    pub state: Option<&'a str>,
+    /// Formatting of `value`.
+    pub format: Option<&'a NumberFormat>,
+    /// Action variables by name; dotted names become nested objects.
+    pub vars: Option<&'a HashMap<String, String>>,
+    pub locale: &'a Locale,
+    pub now: NaiveDateTime,
+}
+
+impl<'a> Context<'a> {
+    /// A context with only the time, for `locale`.
+    #[must_use]
+    pub fn new(locale: &'a Locale) -> Self {
+        Self {
+            state: None,
+            format: None,
+            vars: None,
+            locale,
+            now: chrono::Local::now().naive_local(),
+        }
+    }
+
+    fn value(&self) -> serde_json::Value {
+        let mut root = serde_json::Map::new();
+        for (name, value) in self.vars.into_iter().flatten() {
+            let mut parts: Vec<&str> = name.split('.').collect();
+            let last = parts.pop().unwrap_or_default();
+            let mut object = &mut root;
+            for part in parts {
+                let entry = object
+                    .entry(part)
+                    .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+                if !entry.is_object() {
+                    *entry = serde_json::Value::Object(serde_json::Map::new());
+                }
+                object = entry.as_object_mut().expect("just made an object");
+            }
+            object.insert(last.into(), value.clone().into());
+        }
+        if let Some(state) = self.state {
+            root.insert("state".into(), state.into());
+            let value = match self.format {
+                Some(f) if f.countdown => format::countdown(state, self.locale, self.now),
+                Some(f) => format::format_value(state, f, self.locale),
+                None => state.to_string(),
+            };
+            root.insert("value".into(), value.into());
+        }
+        let now = self.now;
+        root.insert("time".into(), self.locale.format_time(now).into());
+        root.insert("date".into(), self.locale.format_date(now).into());
+        root.insert("weekday".into(), self.locale.weekday(now).into());
+        root.insert(
+            "now".into(),
+            serde_json::json!({
+                "year": now.year(),
+                "month": now.month(),
+                "day": now.day(),
+                "hour": now.hour(),
+                "minute": now.minute(),
+                "second": now.second(),
+                "weekday": now.weekday().number_from_monday(),
+            }),
+        );
+        root.insert("_locale".into(), self.locale.tag.into());
+        root.insert("_now".into(), now.format(NOW_FORMAT).to_string().into());
+        serde_json::Value::Object(root)
+    }
+}
+
+/// Whether `text` is a template rather than plain text with placeholders.
+#[must_use]
+pub fn is_template(text: &str) -> bool {
+    text.contains("{{") || text.contains("{%")
+}
+
+/// Check the syntax of the template `text`, or of the condition `text` if
+/// `condition` is set.
+///
+/// # Errors
+/// Returns the engine's description of the syntax error.
+pub fn check(text: &str, condition: bool) -> std::result::Result<(), String> {
+    let env = TEXT.read().unwrap();
+    let checked = if condition {
+        env.compile_expression(text).map(drop)
+    } else {
+        env.template_from_str(text).map(drop)
+    };
+    checked.map_err(|e| describe(&e))
+}
+
+/// Render the template `text`.
+///
+/// # Errors
+/// Returns the engine's description of the error.
+pub fn render(text: &str, ctx: &Context<'_>) -> std::result::Result<String, String> {
+    TEXT.read()
+        .unwrap()
+        .render_str(text, ctx.value())
+        .map_err(|e| describe(&e))
+}
+
+/// Render the template `text` for a shell command: every printed value is
+/// quoted as a single shell word.
+///
+/// # Errors
+/// Returns the engine's description of the error.
+pub fn render_shell(text: &str, ctx: &Context<'_>) -> std::result::Result<String, String> {
+    SHELL
+        .read()
+        .unwrap()
+        .render_str(text, ctx.value())
+        .map_err(|e| describe(&e))
+}
+
+/// Whether the condition `expr` (e.g. `is_state('person.anna', 'home')`)
+/// holds.
+///
+/// # Errors
+/// Returns the engine's description of the error.
+pub fn holds(expr: &str, ctx: &Context<'_>) -> std::result::Result<bool, String> {
+    TEXT.read()
+        .unwrap()
+        .compile_expression(expr)
+        .and_then(|expr| expr.eval(ctx.value()))
+        .map(|value| value.is_true())
+        .map_err(|e| describe(&e))
+}
+
+/// Add a filter to every template, e.g. `wind_dir_to_arrow` for
+/// `{{ state | wind_dir_to_arrow }}`. A filter of the same name, built-in
+/// or not, is replaced.
+pub fn register_filter<F, Rv, Args>(name: &str, filter: F)
+where
+    F: Function<Rv, Args> + Clone,
+    Rv: FunctionResult,
+    Args: for<'a> FunctionArgs<'a>,
+{
+    for env in [&TEXT, &SHELL] {
+        env.write()
+            .unwrap()
+            .add_filter(name.to_string(), filter.clone());
+    }
+}
+
+/// Add a function to every template, like `states()`. A function of the
+/// same name, built-in or not, is replaced.
+pub fn register_function<F, Rv, Args>(name: &str, function: F)
+where
+    F: Function<Rv, Args> + Clone,
+    Rv: FunctionResult,
+    Args: for<'a> FunctionArgs<'a>,
+{
+    for env in [&TEXT, &SHELL] {
+        env.write()
+            .unwrap()
+            .add_function(name.to_string(), function.clone());
+    }
+}
+
+/// Whether `button` is shown: its `visible_if` holds, or it has none. A
+/// condition that fails shows the button.
+#[must_use]
+pub fn is_visible(button: &ButtonConfig, locale: &Locale) -> bool {
+    let Some(ref condition) = button.visible_if else {
+        return true;
+    };
+    let known = STATES.lock().unwrap();
+    let state = button
+        .state_entity
+        .as_ref()
+        .and_then(|e| known.get(e))
+        .cloned();
+    drop(known);
+    let ctx = Context {
+        state: state.as_deref(),
+        format: Some(&button.format),
+        ..Context::new(locale)
+    };
+    holds(condition, &ctx).unwrap_or_else(|e| {
+        tracing::warn!("key {} visible_if: {e}", button.key);
+        true
+    })
+}
+
+/// Entities a button's templates read: those named in its label and
+/// `visible_if`.
+#[must_use]
+pub fn button_entities(button: &ButtonConfig) -> Vec<String> {
+    button
+        .label
+        .iter()
+        .flat_map(|l| l.texts())
+        .chain(button.visible_if.as_deref())
+        .flat_map(entities)
+        .collect()
+}
+
+/// Whether a button's templates must be re-rendered as states and time
+/// change.
+#[must_use]
+pub fn is_dynamic(button: &ButtonConfig) -> bool {
+    button.visible_if.is_some()
+        || button
+            .label
+            .as_ref()
+            .is_some_and(|l| l.texts().into_iter().any(is_template))
+}
+
+/// Entities named in `text` through `states()`, `state_attr()` or
+/// `is_state()`, to fetch before rendering it.
+#[must_use]
+pub fn entities(text: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for function in ENTITY_FUNCTIONS {
+        for (at, _) in text.match_indices(function) {
+            // Skip longer names ending the same, e.g. `is_state(` in `states(`.
+            let before = text[..at].chars().next_back();
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                continue;
+            }
+            let rest = text[at + function.len()..].trim_start();
+            let Some(quote) = rest.chars().next().filter(|c| matches!(c, '\'' | '"')) else {
+                continue;
+            };
+            if let Some((entity, _)) = rest[1..].split_once(quote) {
+                found.push(entity.to_string());
+            }
+        }
+    }
+    found
+}
+
+/// Remember fetched entity states for `states()` and `is_state()`.
+pub fn record_states(states: &HashMap<String, String>) {
+    let mut known = STATES.lock().unwrap();
+    for (entity, state) in states {
+        known.insert(entity.clone(), state.clone());
+    }
+}
+
+/// Remember the attributes of an HA entity for `state_attr()`.
+pub fn record_attributes(entity: &str, attributes: serde_json::Value) {
+    ATTRIBUTES
+        .lock()
+        .unwrap()
+        .insert(entity.to_string(), attributes);
+}
+
+/// The engine's message, with the cause it wraps.
+fn describe(e: &Error) -> String {
+    match e.detail() {
+        Some(detail) => format!("template: {} ({detail})", e.kind()),
+        None => format!("template: {e}"),
+    }
+}
+
+fn environment(shell: bool) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.add_function("states", |entity: &str| {
+        STATES
+            .lock()
+            .unwrap()
+            .get(entity)
+            .cloned()
+            .unwrap_or_else(|| UNKNOWN.into())
+    });
+    env.add_function("state_attr", |entity: &str, name: &str| {
+        ATTRIBUTES
+            .lock()
+            .unwrap()
+            .get(entity)
+            .and_then(|attributes| attributes.get(name))
+            .map_or(Value::from(()), Value::from_serialize)
+    });
+    env.add_function("is_state", |entity: &str, state: &str| {
+        STATES.lock().unwrap().get(entity).map(String::as_str) == Some(state)
+    });
+    env.add_filter(
+        "number",
+        |state: &State, value: Value, precision: Option<u8>| {
+            let format = NumberFormat {
+                precision,
+                ..NumberFormat::default()
+            };
+            format::format_value(&value.to_string(), &format, locale_of(state))
+        },
+    );
+    env.add_filter("duration", |state: &State, value: Value| {
+        let format = NumberFormat {
+            duration: true,
+            ..NumberFormat::default()
+        };
+        format::format_value(&value.to_string(), &format, locale_of(state))
+    });
+    env.add_filter("countdown", |state: &State, value: Value| {
+        format::countdown(&value.to_string(), locale_of(state), now_of(state))
+    });
+    env.set_formatter(
+        move |out: &mut Output<'_>, _: &State<'_, '_>, value: &Value| {
+            let text = if value.is_undefined() || value.is_none() {
+                String::new()
+            } else {
+                value.to_string()
+            };
+            if shell && !value.is_safe() {
+                out.write_str(&shell_quote(&text))?;
+            } else {
+                out.write_str(&text)?;
+            }
+            Ok(())
+        },
+    );
+    env
+}
+
+/// The locale a template is rendered in.
+fn locale_of(state: &State<'_, '_>) -> &'static Locale {
+    let tag = state.lookup("_locale");
+    locale::resolve(tag.as_ref().and_then(Value::as_str).unwrap_or_default())
+}
+
+/// The time a template is rendered for.
+fn now_of(state: &State<'_, '_>) -> NaiveDateTime {
+    state
+        .lookup("_now")
+        .as_ref()
+        .and_then(Value::as_str)
+        .and_then(|now| NaiveDateTime::parse_from_str(now, NOW_FORMAT).ok())
+        .unwrap_or_else(|| chrono::Local::now().naive_local())
+}
+
+/// `text` as one single-quoted shell word.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn renders_states_vars_and_time() {
+        record_states(&HashMap::from([
+            ("sensor.test_power".to_string(), "1234.5".to_string()),
+            ("person.test_anna".to_string(), "home".to_string()),
+        ]));
+        record_attributes(
+            "sensor.test_power",
+            serde_json::json!({ "unit_of_measurement": "W" }),
+        );
+        let vars = HashMap::from([("hook.name".to_string(), "it's me".to_string())]);
+        let format = NumberFormat {
+            precision: Some(0),
+            ..NumberFormat::default()
+        };
+        let ctx = Context {
+            state: Some("21.43"),
+            format: Some(&format),
+            vars: Some(&vars),
+            now: NaiveDate::from_ymd_opt(2026, 10, 16)
+                .unwrap()
+                .and_hms_opt(7, 30, 0)
+                .unwrap(),
+            ..Context::new(locale::resolve("de-DE"))
+        };
+
+        let text = "{{ states('sensor.test_power') | number(1) }} \
+                    {{ state_attr('sensor.test_power', 'unit_of_measurement') }}";
+        assert_eq!(render(text, &ctx).unwrap(), "1234,5 W");
+        assert_eq!(
+            render("{{ value }}°, {{ weekday }} {{ time }}", &ctx).unwrap(),
+            "21°, Fr 07:30"
+        );
+        assert_eq!(
+            render("{% if now.hour < 12 %}Morning{% endif %}", &ctx).unwrap(),
+            "Morning"
+        );
+        assert_eq!(
+            render_shell("notify {{ hook.name }}", &ctx).unwrap(),
+            r"notify 'it'\''s me'"
+        );
+        assert!(holds("is_state('person.test_anna', 'home')", &ctx).unwrap());
+        assert!(!holds("states('person.test_bob') == 'home'", &ctx).unwrap());
+        assert!(render("{{ states(", &ctx).is_err());
+    }
+
+    #[test]
+    fn uses_registered_filters_and_functions() {
+        register_filter("wind_dir_to_arrow", |degrees: f64| {
+            let arrows = ["↓", "↙", "←", "↖", "↑", "↗", "→", "↘"];
+            arrows[((degrees / 45.0).round() as usize) % 8]
+        });
+        register_function("test_greeting", |name: &str| format!("hi {name}"));
+        let ctx = Context {
+            state: Some("270"),
+            ..Context::new(locale::resolve("en-US"))
+        };
+        assert_eq!(
+            render("{{ state | float | wind_dir_to_arrow }}", &ctx).unwrap(),
+            "→"
+        );
+        assert_eq!(
+            render_shell("echo {{ test_greeting('you') }}", &ctx).unwrap(),
+            "echo 'hi you'"
+        );
+        assert!(check("{{ 1 | wind_dir_to_arrow }}", false).is_ok());
+    }
+
+    #[test]
+    fn finds_entities() {
+        let text = "{{ states('sensor.a') }} {{ state_attr(\"light.b\", 'x') }} \
+                    {% if is_state('person.c', 'home') %}{% endif %}";
+        let mut found = entities(text);
+        found.sort();
+        assert_eq!(found, ["light.b", "person.c", "sensor.a"]);
+        assert!(is_template(text));
+        assert!(!is_template("{state} W"));
+    }
+}
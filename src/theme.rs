@@ -0,0 +1,95 @@
+use crate::config::schema::{AppConfig, ThemeSelection};
+use crate::event::DeckEvent;
+use crate::schedule::{self, TimeOfDay};
+use crate::state::fetch_ha_states;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// How often the theme entity and schedule are re-evaluated.
+const THEME_POLL: Duration = Duration::from_secs(15);
+
+/// Resolve the active theme: a mapped entity state wins, then the schedule,
+/// then the configured default.
+#[must_use]
+pub fn select_theme(
+    selection: &ThemeSelection,
+    entity_state: Option<&str>,
+    now: TimeOfDay,
+) -> Option<String> {
+    entity_state
+        .and_then(|state| selection.states.get(state))
+        .or_else(|| schedule::active_entry(&selection.schedule, now))
+        .or(selection.default.as_ref())
+        .cloned()
+}
+
+/// Re-evaluate `[deckd.theme]` periodically and emit `ThemeChanged` when the
+/// active theme differs from the last one reported. The first evaluation is
+/// always reported so the event loop starts with the right theme.
+pub async fn watch_theme(
+    config: Arc<ArcSwap<AppConfig>>,
+    tx: broadcast::Sender<DeckEvent>,
+    cancel: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(THEME_POLL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut active: Option<Option<String>> = None;
+
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let cfg = config.load_full();
+        let selection = &cfg.deckd.theme;
+        let entity_state = match selection.entity {
+            Some(ref entity) => fetch_ha_states(std::slice::from_ref(entity))
+                .await
+                .remove(entity),
+            None => None,
+        };
+        let theme = select_theme(selection, entity_state.as_deref(), TimeOfDay::now());
+
+        if active.as_ref() != Some(&theme) {
+            info!("theme: {}", theme.as_deref().unwrap_or("default"));
+            let _ = tx.send(DeckEvent::ThemeChanged(theme.clone()));
+            active = Some(theme);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_precedence() {
+        let selection: ThemeSelection = toml::from_str(
+            r#"
+default = "day"
+entity = "sun.sun"
+states = { below_horizon = "night" }
+schedule = { "07:00" = "day", "22:00" = "dim" }
+"#,
+        )
+        .unwrap();
+        let noon: TimeOfDay = "12:00".parse().unwrap();
+        let late: TimeOfDay = "23:00".parse().unwrap();
+
+        assert_eq!(
+            select_theme(&selection, Some("below_horizon"), noon).as_deref(),
+            Some("night")
+        );
+        assert_eq!(
+            select_theme(&selection, Some("above_horizon"), late).as_deref(),
+            Some("dim")
+        );
+        assert_eq!(select_theme(&selection, None, noon).as_deref(), Some("day"));
+        assert_eq!(select_theme(&ThemeSelection::default(), None, noon), None);
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+/// Most banners waiting behind the one on screen; older ones are dropped.
+pub const MAX_PENDING: usize = 10;
+
+/// A notification banner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    /// Distinguishes banners so a stale auto-dismiss timer is ignored.
+    pub id: u64,
+    pub message: String,
+}
+
+/// The banner on screen and those waiting for it to go away.
+#[derive(Debug, Default)]
+pub struct ToastQueue {
+    current: Option<Toast>,
+    pending: VecDeque<Toast>,
+    next_id: u64,
+}
+
+impl ToastQueue {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a message. Returns it if nothing was showing, i.e. it should be
+    /// drawn now.
+    pub fn push(&mut self, message: String) -> Option<&Toast> {
+        self.next_id += 1;
+        let toast = Toast {
+            id: self.next_id,
+            message,
+        };
+        if self.current.is_some() {
+            if self.pending.len() == MAX_PENDING {
+                self.pending.pop_front();
+            }
+            self.pending.push_back(toast);
+            return None;
+        }
+        self.current = Some(toast);
+        self.current.as_ref()
+    }
+
+    /// The banner on screen.
+    #[must_use]
+    pub fn current(&self) -> Option<&Toast> {
+        self.current.as_ref()
+    }
+
+    /// Take down the banner `id` if it is still showing. Returns `false` if it
+    /// was not; otherwise the next banner, if any, becomes current.
+    pub fn dismiss(&mut self, id: u64) -> bool {
+        if self.current.as_ref().is_none_or(|t| t.id != id) {
+            return false;
+        }
+        self.current = self.pending.pop_front();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_one_at_a_time() {
+        let mut queue = ToastQueue::new();
+        let first = queue.push("doorbell".into()).unwrap().id;
+        assert!(queue.push("build failed".into()).is_none());
+
+        // A timer for a banner that is no longer showing does nothing.
+        assert!(!queue.dismiss(first + 1));
+        assert!(queue.dismiss(first));
+        assert_eq!(queue.current().unwrap().message, "build failed");
+
+        let second = queue.current().unwrap().id;
+        assert!(queue.dismiss(second));
+        assert!(queue.current().is_none());
+    }
+
+    #[test]
+    fn drops_oldest_pending() {
+        let mut queue = ToastQueue::new();
+        queue.push("on screen".into());
+        for i in 0..=MAX_PENDING {
+            queue.push(format!("pending {i}"));
+        }
+        let id = queue.current().unwrap().id;
+        queue.dismiss(id);
+        assert_eq!(queue.current().unwrap().message, "pending 1");
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error::Result;
+use crate::ids::KeyIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// File name used under `$STATE_DIRECTORY` when `deckd.usage_file` is unset.
+pub const DEFAULT_FILE: &str = "usage.json";
+
+/// How often a button was pressed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonUsage {
+    pub presses: u64,
+    /// Unix time of the last press.
+    pub last_pressed: Option<u64>,
+}
+
+/// Press counts per page and key, optionally persisted as JSON.
+#[derive(Debug, Default)]
+pub struct UsageStats {
+    buttons: BTreeMap<String, ButtonUsage>,
+    path: Option<PathBuf>,
+    dirty: bool,
+}
+
+impl UsageStats {
+    /// Load counts from `path`, starting empty if it does not exist yet or
+    /// cannot be read. Without a path, counts are kept in memory only.
+    #[must_use]
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let buttons = path
+            .as_deref()
+            .and_then(|path| match std::fs::read(path) {
+                Ok(data) => serde_json::from_slice(&data)
+                    .map_err(|e| warn!("ignoring unreadable usage file {}: {e}", path.display()))
+                    .ok(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    warn!("cannot read usage file {}: {e}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            buttons,
+            path,
+            dirty: false,
+        }
+    }
+
+    /// Count a press of `key` on `page` at unix time `now`.
+    pub fn record(&mut self, page: &str, key: KeyIndex, now: u64) {
+        let usage = self.buttons.entry(usage_key(page, key)).or_default();
+        usage.presses += 1;
+        usage.last_pressed = Some(now);
+        self.dirty = true;
+    }
+
+    #[must_use]
+    pub fn get(&self, page: &str, key: KeyIndex) -> Option<&ButtonUsage> {
+        self.buttons.get(&usage_key(page, key))
+    }
+
+    /// Write the counts if they changed since the last save.
+    ///
+    /// # Errors
+    /// Returns `DeckError::Io` if the file cannot be written.
+    pub fn save(&mut self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        write_atomic(
+            path,
+            &serde_json::to_vec_pretty(&self.buttons).map_err(std::io::Error::from)?,
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Where usage counts are stored: `configured` (relative to the config dir),
+/// else `$STATE_DIRECTORY/usage.json` under systemd, else nowhere.
+#[must_use]
+pub fn usage_path(configured: Option<&str>, config_dir: &Path) -> Option<PathBuf> {
+    state_path(configured, config_dir, DEFAULT_FILE)
+}
+
+/// Where a state file is kept: `configured` (relative to the config dir),
+/// else `name` in systemd's `$STATE_DIRECTORY`, else nowhere.
+#[must_use]
+pub fn state_path(configured: Option<&str>, config_dir: &Path, name: &str) -> Option<PathBuf> {
+    if let Some(path) = configured {
+        return Some(crate::config::resolve_path(path, config_dir));
+    }
+    let dirs = std::env::var_os("STATE_DIRECTORY")?;
+    let first = std::env::split_paths(&dirs).next()?;
+    Some(first.join(name))
+}
+
+fn usage_key(page: &str, key: KeyIndex) -> String {
+    format!("{page}/{key}")
+}
+
+/// Replace `path` without leaving a half-written file behind on a crash.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::key;
+
+    #[test]
+    fn counts_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("deckd-usage-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DEFAULT_FILE);
+
+        let mut stats = UsageStats::load(Some(path.clone()));
+        stats.record("home", key(3), 100);
+        stats.record("home", key(3), 200);
+        stats.record("lights", key(0), 150);
+        stats.save().unwrap();
+
+        let stats = UsageStats::load(Some(path));
+        assert_eq!(
+            stats.get("home", key(3)),
+            Some(&ButtonUsage {
+                presses: 2,
+                last_pressed: Some(200),
+            })
+        );
+        assert_eq!(stats.get("lights", key(0)).unwrap().presses, 1);
+        assert!(stats.get("home", key(4)).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}